@@ -1,51 +1,233 @@
-use blame_finder::{Repository, cleanup_old_repos, find_oldest_todo};
+use blame_finder::{
+    Clock, DEFAULT_CLEANUP_CONCURRENCY, Repository, ScanOptions, ScanResult, SystemClock, TodoItem,
+    cleanup_old_repos, find_all_todos_with_options, scan,
+};
 use std::env;
+use std::error::Error;
+use std::io::{self, Write};
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Get repository URL from command-line arguments
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: {} <repository-url>", args[0]);
-        std::process::exit(1);
-    }
+const USAGE: &str = "Usage: find_todos [--all [--untracked]] [--discard] [--format text|json] <repository-url | --local <path>>";
+
+/// Command-line options for the example
+struct Args {
+    /// Remote URL or local path to scan
+    target: String,
+    /// Whether `target` is a local checkout rather than a URL
+    local: bool,
+    /// Print every TODO rather than just the oldest
+    all: bool,
+    /// With `all`, also list TODOs in files git doesn't track yet
+    untracked: bool,
+    /// Delete the clone straight after scanning rather than keeping it for next time
+    discard: bool,
+    /// Print the result as JSON, the oldest `TodoItem` (or `null`) or with `all` an
+    /// array of them, and send everything else to stderr
+    json: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Option<Args> {
+    let mut target = None;
+    let mut local = false;
+    let mut all = false;
+    let mut untracked = false;
+    let mut discard = false;
+    let mut json = false;
 
-    let repo_url = &args[1];
-    println!("Searching for TODOs in {}", repo_url);
-
-    // Find the oldest TODO
-    let repository = Repository::new(repo_url).await.unwrap();
-    match find_oldest_todo(&repository).await {
-        Ok(Some(todo)) => {
-            println!("\nFound oldest TODO!");
-            println!("File: {}", todo.file_path);
-            println!("Line: {}", todo.line_number);
-            println!("Text: {}", todo.todo_text);
-            println!("\nContext:");
-            println!("{}", todo.context_code);
-
-            if let Some(blame) = todo.blame_info {
-                println!("\nAuthor: {} <{}>", blame.author, blame.author_email);
-                println!("Date: {}", blame.date.format("%Y-%m-%d %H:%M:%S"));
-                println!("Commit: {}", blame.commit_hash);
-                println!("Message: {}", blame.summary);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--all" => all = true,
+            "--untracked" => untracked = true,
+            "--discard" => discard = true,
+            "--format" => {
+                json = match args.next()?.as_str() {
+                    "json" => true,
+                    "text" => false,
+                    _ => return None,
+                };
             }
+            "--local" => {
+                local = true;
+                target = Some(args.next()?);
+            }
+            _ if arg.starts_with("--") => return None,
+            _ if target.is_none() => target = Some(arg),
+            _ => return None,
         }
-        Ok(None) => {
-            println!("No TODOs found in the repository.");
-        }
+    }
+
+    Some(Args {
+        target: target?,
+        local,
+        all,
+        untracked,
+        discard,
+        json,
+    })
+}
+
+/// Print a progress message, keeping `out` to the JSON alone in JSON mode
+fn note(args: &Args, out: &mut impl Write, err: &mut impl Write, message: &str) -> io::Result<()> {
+    if args.json {
+        writeln!(err, "{}", message)
+    } else {
+        writeln!(out, "{}", message)
+    }
+}
+
+fn print_json<T: serde::Serialize>(out: &mut impl Write, value: &T) -> Result<(), Box<dyn Error>> {
+    writeln!(out, "{}", serde_json::to_string_pretty(value)?)?;
+    Ok(())
+}
+
+fn print_todo(out: &mut impl Write, todo: &TodoItem) -> io::Result<()> {
+    writeln!(out, "File: {}", todo.file_path)?;
+    writeln!(out, "Line: {}", todo.line_number)?;
+    writeln!(out, "Text: {}", todo.todo_text)?;
+    writeln!(out, "\nContext:")?;
+    writeln!(out, "{}", todo.context_code)?;
+
+    if let Some(blame) = &todo.blame_info {
+        writeln!(out, "\nAuthor: {} <{}>", blame.author, blame.author_email)?;
+        writeln!(out, "Date: {}", blame.date.format("%Y-%m-%d %H:%M:%S"))?;
+        writeln!(out, "Commit: {}", blame.commit_hash)?;
+        writeln!(out, "Message: {}", blame.summary)?;
+    }
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let code = run(
+        env::args().skip(1),
+        &SystemClock,
+        &mut io::stdout(),
+        &mut io::stderr(),
+    )
+    .await;
+    std::process::exit(code);
+}
+
+/// Run the example with command-line `args`, aging TODOs by `clock` and writing
+/// results to `out` and errors to `err`, returning the exit code. Separate from
+/// `main` so tests can run it in-process
+pub async fn run(
+    args: impl Iterator<Item = String>,
+    clock: &dyn Clock,
+    out: &mut impl Write,
+    err: &mut impl Write,
+) -> i32 {
+    let Some(args) = parse_args(args) else {
+        let _ = writeln!(err, "{}", USAGE);
+        return 1;
+    };
+
+    match find_and_print(&args, clock, out, err).await {
+        Ok(code) => code,
         Err(e) => {
-            eprintln!("Error: {}", e);
-            std::process::exit(1);
+            let _ = writeln!(err, "Error: {}", e);
+            1
         }
     }
+}
 
-    // Clean up old repos (older than 7 days)
-    if let Ok(count) = cleanup_old_repos(7, None).await {
-        if count > 0 {
-            println!("\nCleaned up {} old repositories.", count);
+async fn find_and_print(
+    args: &Args,
+    clock: &dyn Clock,
+    out: &mut impl Write,
+    err: &mut impl Write,
+) -> Result<i32, Box<dyn Error>> {
+    note(
+        args,
+        out,
+        err,
+        &format!("Searching for TODOs in {}", args.target),
+    )?;
+
+    let repository = if args.local {
+        Repository::from_local_path(&args.target)?
+    } else {
+        Repository::new(&args.target).await?
+    };
+
+    if args.all {
+        // Print every TODO, oldest first
+        let options = ScanOptions {
+            include_untracked: args.untracked,
+            ..ScanOptions::default()
+        };
+        match find_all_todos_with_options(&repository, &options, clock).await {
+            Ok(todos) if args.json => print_json(out, &todos)?,
+            Ok(todos) if todos.is_empty() => writeln!(out, "No TODOs found in the repository.")?,
+            Ok(todos) => {
+                for todo in &todos {
+                    let age = if todo.untracked {
+                        format!("{:>11}", "untracked")
+                    } else {
+                        let days = todo.age_in_days(clock).unwrap_or(0);
+                        format!("{:>6} days", days)
+                    };
+                    writeln!(
+                        out,
+                        "{}  {}:{}  {}",
+                        age, todo.file_path, todo.line_number, todo.todo_text
+                    )?;
+                }
+            }
+            Err(e) => {
+                writeln!(err, "Error: {}", e)?;
+                return Ok(1);
+            }
+        }
+    } else {
+        // Find the oldest TODO
+        match scan(&repository, clock).await {
+            Ok(result) if args.json => {
+                let oldest = match result {
+                    ScanResult::Found(todo) => Some(todo),
+                    ScanResult::NoTodos | ScanResult::AllBlameFailed { .. } => None,
+                };
+                print_json(out, &oldest)?;
+            }
+            Ok(ScanResult::Found(todo)) => {
+                writeln!(out, "\nFound oldest TODO!")?;
+                print_todo(out, &todo)?;
+            }
+            Ok(ScanResult::NoTodos) => {
+                writeln!(out, "No TODOs found in the repository.")?;
+            }
+            Ok(ScanResult::AllBlameFailed { attempted }) => {
+                writeln!(
+                    out,
+                    "Found {} TODOs, but couldn't git blame any of them.",
+                    attempted
+                )?;
+            }
+            Err(e) => {
+                writeln!(err, "Error: {}", e)?;
+                return Ok(1);
+            }
         }
     }
 
-    Ok(())
+    if args.discard
+        && !args.local
+        && let Err(e) = repository.cleanup_self().await
+    {
+        writeln!(err, "Failed to remove the clone: {}", e)?;
+    }
+
+    // Local checkouts aren't managed clones, so there's nothing of ours to clean up
+    if !args.local
+        && let Ok(count) = cleanup_old_repos(7, None, DEFAULT_CLEANUP_CONCURRENCY).await
+        && count > 0
+    {
+        note(
+            args,
+            out,
+            err,
+            &format!("\nCleaned up {} old repositories.", count),
+        )?;
+    }
+
+    Ok(0)
 }
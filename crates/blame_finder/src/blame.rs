@@ -1,10 +1,20 @@
 use chrono::{DateTime, Utc};
-use log::debug;
+use log::{debug, error};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
 use tokio::process::Command;
+use tokio::sync::{Semaphore, mpsc};
+use tokio::task::JoinHandle;
 
+use crate::blame_cache::BlameCache;
+use crate::clock::Clock;
 use crate::error::BlameError;
+use crate::generated::GeneratedFiles;
+use crate::options::ScanOptions;
 use crate::repo::Repository;
 use crate::todo::TodoItem;
 
@@ -24,117 +34,553 @@ pub struct BlameInfo {
 
     /// Commit message summary
     pub summary: String,
+
+    /// The rest of the commit message after its subject, if the scan asked for it
+    /// with [`ScanOptions::commit_body_limit`] and the message has one
+    #[serde(default)]
+    pub body: Option<String>,
+
+    /// Whether the commit sits at the shallow clone boundary, meaning the line
+    /// may actually be older than reported
+    #[serde(default)]
+    pub boundary: bool,
+
+    /// Whether this was recovered from the file's last commit rather than a
+    /// line-accurate blame, e.g. because the file vanished before it could be blamed
+    #[serde(default)]
+    pub approximate: bool,
+
+    /// How many commits have touched the file since this one, if the scan counted
+    /// them for [`ScanOptions::neglect_days_per_commit`]
+    #[serde(default)]
+    pub later_commits: Option<u32>,
 }
 
 /// Find the oldest TODO among the provided list
 pub async fn find_oldest_todo(
     repo: &Repository,
     todos: Vec<TodoItem>,
+    options: &ScanOptions,
+    clock: &dyn Clock,
+) -> Result<TodoItem, BlameError> {
+    let in_range = blame_matching_todos(repo, todos, options, clock).await?;
+    let mut oldest = oldest_blamed(repo, in_range, options)?;
+    attach_commit_body(repo, &mut oldest, options).await;
+    Ok(oldest)
+}
+
+/// The blamed TODO with the oldest commit date, after any test file penalty or
+/// neglect and passing over generated files if asked
+pub(crate) fn oldest_blamed(
+    repo: &Repository,
+    todos: Vec<TodoItem>,
+    options: &ScanOptions,
 ) -> Result<TodoItem, BlameError> {
+    pick_oldest_in(repo, &todos, options)?
+        .cloned()
+        .ok_or(BlameError::NoTodosInDateRange)
+}
+
+/// The oldest of `todos` as [`ScanOptions::rank_oldest`] sees it
+///
+/// TODOs of the same age are told apart by path and line rather than by the order
+/// blame happened to finish in. Without a test file penalty the pick always heads
+/// the oldest-first list of every TODO
+pub fn pick_oldest<'a>(todos: &'a [TodoItem], options: &ScanOptions) -> Option<&'a TodoItem> {
+    todos.iter().max_by(|a, b| options.rank_oldest(a, b))
+}
+
+/// Like [`pick_oldest`], passing over TODOs in `repo`'s generated files when
+/// [`ScanOptions::exclude_generated`] is on. `None` if every TODO is generated
+pub fn pick_oldest_in<'a>(
+    repo: &Repository,
+    todos: &'a [TodoItem],
+    options: &ScanOptions,
+) -> Result<Option<&'a TodoItem>, BlameError> {
+    if !options.exclude_generated {
+        return Ok(pick_oldest(todos, options));
+    }
+
+    let generated = GeneratedFiles::load(repo.path(), &options.generated_globs)?;
+    Ok(todos
+        .iter()
+        .filter(|todo| !generated.is_generated(&todo.file_path))
+        .max_by(|a, b| options.rank_oldest(a, b)))
+}
+
+/// Blame the provided TODOs as `options` asks, keeping only those blamed inside
+/// the requested date range and on authors we credit
+pub async fn blame_matching_todos(
+    repo: &Repository,
+    todos: Vec<TodoItem>,
+    options: &ScanOptions,
+    clock: &dyn Clock,
+) -> Result<Vec<TodoItem>, BlameError> {
+    let todos_with_blame = if options.quick {
+        quick_blame_todos(repo, todos, clock).await?
+    } else {
+        blame_todos(repo, todos, clock).await?
+    };
+
+    let mut matching = keep_matching(todos_with_blame, options);
+    count_later_commits(repo, &mut matching, options).await;
+    Ok(matching)
+}
+
+/// Keep only the blamed TODOs inside the requested date range and on authors we credit
+pub(crate) fn keep_matching(todos: Vec<TodoItem>, options: &ScanOptions) -> Vec<TodoItem> {
+    todos
+        .into_iter()
+        .filter(|t| {
+            let blame_info = t.blame_info.as_ref().unwrap();
+            options.in_date_range(&blame_info.date) && !options.excludes_author(blame_info)
+        })
+        .collect()
+}
+
+/// Most `git blame`s one scan runs at once. Each is its own process, and a repository
+/// can hold thousands of TODOs
+pub const DEFAULT_BLAME_CONCURRENCY: usize = 16;
+
+/// Git blame every TODO in parallel, keeping those that could be blamed
+///
+/// Fails with `AllBlameFailed` if none of them could be blamed. Author times past
+/// `clock`'s time are rejected, see [`commit_date`]
+pub async fn blame_todos(
+    repo: &Repository,
+    todos: Vec<TodoItem>,
+    clock: &dyn Clock,
+) -> Result<Vec<TodoItem>, BlameError> {
+    blame_todos_with(repo, todos, Arc::new(GitBlame { now: clock.now() })).await
+}
+
+/// [`blame_todos`], blaming each line with `runner`
+pub(crate) async fn blame_todos_with(
+    repo: &Repository,
+    todos: Vec<TodoItem>,
+    runner: Arc<dyn BlameRunner>,
+) -> Result<Vec<TodoItem>, BlameError> {
     if todos.is_empty() {
         return Err(BlameError::InternalError("No TODOs provided".to_string()));
     }
 
-    // Process blame information for each TODO in parallel using Tokio
-    debug!("Getting blame info for todos in parallel");
+    let mut blames = BlameTasks::start(repo, runner).await?;
+    for todo in todos {
+        blames.spawn(todo).await;
+    }
+    blames.finish().await
+}
 
-    use tokio::task;
+/// Git blame TODOs as they arrive on `todos` until the sender is dropped. Pairs with
+/// [`stream_todos`](crate::todo::stream_todos) so blaming overlaps the search
+///
+/// Fails with `AllBlameFailed` if none of them could be blamed, or with an
+/// `InternalError` if none arrived at all.
+pub async fn blame_todo_stream(
+    repo: &Repository,
+    mut todos: mpsc::Receiver<TodoItem>,
+    clock: &dyn Clock,
+) -> Result<Vec<TodoItem>, BlameError> {
+    let mut blames = BlameTasks::start(repo, Arc::new(GitBlame { now: clock.now() })).await?;
+    while let Some(todo) = todos.recv().await {
+        blames.spawn(todo).await;
+    }
+    blames.finish().await
+}
 
-    // Create a vector to hold all the task handles
-    let mut blame_tasks = Vec::with_capacity(todos.len());
+/// One scan's blames, each in its own task but at most [`DEFAULT_BLAME_CONCURRENCY`]
+/// running at once
+struct BlameTasks {
+    repo: Repository,
+    runner: Arc<dyn BlameRunner>,
+    cache: Option<Arc<BlameCache>>,
+    permits: Arc<Semaphore>,
+    tasks: Vec<JoinHandle<Option<TodoItem>>>,
+}
 
-    // Spawn a task for each TODO item
-    for todo in todos {
-        let repo_clone = repo.clone();
+impl BlameTasks {
+    async fn start(repo: &Repository, runner: Arc<dyn BlameRunner>) -> Result<Self, BlameError> {
+        // Every blame would fail the same way, so say why once instead
+        repo.ensure_git_repository().await?;
+        debug!("Getting blame info for todos in parallel");
+
+        Ok(BlameTasks {
+            repo: repo.clone(),
+            runner,
+            // Reuse blame from earlier scans at the same HEAD, if caching is on
+            cache: open_blame_cache(repo).await.map(Arc::new),
+            permits: Arc::new(Semaphore::new(DEFAULT_BLAME_CONCURRENCY)),
+            tasks: Vec::new(),
+        })
+    }
 
-        // Spawn a Tokio task for each TODO
-        let task_handle = task::spawn(async move {
-            let mut todo_clone = todo;
-            match get_blame_info(&repo_clone, &todo_clone).await {
+    /// Blame `todo` once a running blame finishes, if there are already too many
+    async fn spawn(&mut self, mut todo: TodoItem) {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("blame semaphore is never closed");
+        let repo = self.repo.clone();
+        let runner = self.runner.clone();
+        let cache = self.cache.clone();
+
+        self.tasks.push(tokio::task::spawn(async move {
+            let _permit = permit;
+            match get_blame_info(&repo, &todo, runner.as_ref(), cache.as_deref()).await {
                 Ok(blame_info) => {
-                    todo_clone.blame_info = Some(blame_info);
-                    Some(todo_clone)
+                    todo.blame_info = Some(blame_info);
+                    Some(todo)
                 }
                 Err(e) => {
-                    eprintln!(
-                        "Error getting blame info for {}: {}",
-                        todo_clone.file_path, e
-                    );
+                    eprintln!("Error getting blame info for {}: {}", todo.file_path, e);
                     None
                 }
             }
-        });
-
-        blame_tasks.push(task_handle);
+        }));
     }
 
-    let mut todos_with_blame = Vec::new();
-    for task in blame_tasks {
-        match task.await {
-            Ok(Some(todo)) => todos_with_blame.push(todo),
-            Ok(None) => {} // Skip TODOs that failed to get blame info
-            Err(e) => eprintln!("Task join error: {}", e),
+    /// Wait for every blame, keeping the TODOs that were blamed
+    async fn finish(self) -> Result<Vec<TodoItem>, BlameError> {
+        let attempted = self.tasks.len();
+        if attempted == 0 {
+            return Err(BlameError::InternalError("No TODOs provided".to_string()));
+        }
+
+        let todos_with_blame = collect_blame_results(self.tasks).await?;
+
+        debug!("Finished getting all blame info's in parallel");
+
+        if let Some(cache) = self.cache {
+            save_blame_cache(&cache, &todos_with_blame).await;
+        }
+
+        if todos_with_blame.is_empty() {
+            return Err(BlameError::AllBlameFailed { attempted });
         }
+
+        Ok(todos_with_blame)
     }
+}
+
+/// Commits fetched by quick blame's first deepen, doubling every round after
+const QUICK_DEEPEN_FIRST_STEP: u32 = 64;
+
+/// Most times quick blame deepens a shallow clone, reaching about 4000 commits back
+const QUICK_DEEPEN_ROUNDS: u32 = 6;
+
+/// Date every TODO by the oldest commit `git log -L` finds for its line, rather
+/// than blaming the whole file
+///
+/// A line older than a shallow clone's history lands on its boundary commit, so
+/// while any TODO does the clone is deepened, a little more each round, and those
+/// TODOs are dated again. Whatever is still on the boundary after
+/// [`QUICK_DEEPEN_ROUNDS`] is marked as such. Every result is marked approximate.
+pub async fn quick_blame_todos(
+    repo: &Repository,
+    todos: Vec<TodoItem>,
+    clock: &dyn Clock,
+) -> Result<Vec<TodoItem>, BlameError> {
+    if todos.is_empty() {
+        return Err(BlameError::InternalError("No TODOs provided".to_string()));
+    }
+
+    repo.ensure_git_repository().await?;
+    let now = clock.now();
+
+    let attempted = todos.len();
+    let mut todos_with_blame = Vec::with_capacity(attempted);
+    let mut pending = todos;
+    let mut step = QUICK_DEEPEN_FIRST_STEP;
+
+    for round in 0..=QUICK_DEEPEN_ROUNDS {
+        let shallow_roots = shallow_boundaries(repo.path()).await;
+        let (on_boundary, dated): (Vec<_>, Vec<_>) = date_todo_lines(repo, pending, now)
+            .await
+            .into_iter()
+            .partition(|todo| {
+                let blame_info = todo.blame_info.as_ref().unwrap();
+                shallow_roots.contains(&blame_info.commit_hash)
+            });
+        todos_with_blame.extend(dated);
+        pending = on_boundary;
+        if pending.is_empty() {
+            break;
+        }
 
-    debug!("Finished getting all blame info's in parallel");
+        let deepened = if round < QUICK_DEEPEN_ROUNDS {
+            debug!(
+                "{} TODOs on the shallow boundary, deepening by {}",
+                pending.len(),
+                step
+            );
+            repo.deepen(step).await
+        } else {
+            Err(BlameError::GitError("out of deepen rounds".to_string()))
+        };
+        if let Err(e) = deepened {
+            debug!(
+                "Leaving {} TODOs on the shallow boundary: {}",
+                pending.len(),
+                e
+            );
+            for todo in &mut pending {
+                todo.blame_info.as_mut().unwrap().boundary = true;
+            }
+            todos_with_blame.append(&mut pending);
+            break;
+        }
+        step = step.saturating_mul(2);
+    }
 
     if todos_with_blame.is_empty() {
-        return Err(BlameError::InternalError(
-            "Failed to get blame info for any TODOs".to_string(),
-        ));
+        return Err(BlameError::AllBlameFailed { attempted });
     }
 
-    // Find the oldest TODO by commit date
-    let oldest_todo = todos_with_blame
+    Ok(todos_with_blame)
+}
+
+/// Date each TODO's line with [`get_line_introduction_info`], at most
+/// [`DEFAULT_BLAME_CONCURRENCY`] at once, dropping those that couldn't be dated
+async fn date_todo_lines(
+    repo: &Repository,
+    todos: Vec<TodoItem>,
+    now: DateTime<Utc>,
+) -> Vec<TodoItem> {
+    let permits = Arc::new(Semaphore::new(DEFAULT_BLAME_CONCURRENCY));
+    let tasks: Vec<_> = todos
         .into_iter()
-        .min_by_key(|t| t.blame_info.as_ref().unwrap().date)
-        .unwrap();
+        .map(|mut todo| {
+            let repo_clone = repo.clone();
+            let permits = permits.clone();
+            tokio::task::spawn(async move {
+                let _permit = permits.acquire_owned().await.ok()?;
+                match get_line_introduction_info(
+                    &repo_clone,
+                    &todo.file_path,
+                    todo.line_number,
+                    now,
+                )
+                .await
+                {
+                    Ok(blame_info) => {
+                        todo.blame_info = Some(blame_info);
+                        Some(todo)
+                    }
+                    Err(e) => {
+                        eprintln!(
+                            "Error dating {}:{}: {}",
+                            todo.file_path, todo.line_number, e
+                        );
+                        None
+                    }
+                }
+            })
+        })
+        .collect();
 
-    Ok(oldest_todo)
+    let mut dated = Vec::new();
+    for task in tasks {
+        match task.await {
+            Ok(Some(todo)) => dated.push(todo),
+            Ok(None) => {}
+            Err(e) => error!("Quick blame task failed: {}", e),
+        }
+    }
+    dated
 }
 
-async fn __get_git_depth(repo: &Repository, branch: &str) -> Result<i64, BlameError> {
-    let output = tokio::process::Command::new("git")
+/// The oldest commit in `git log -L`'s history of one line, following it through
+/// edits and moves
+async fn get_line_introduction_info(
+    repo: &Repository,
+    file_path: &str,
+    line_number: u32,
+    now: DateTime<Utc>,
+) -> Result<BlameInfo, BlameError> {
+    let output = Command::new("git")
         .current_dir(repo.path())
+        .arg("log")
+        .arg(format!("-L{},{}:{}", line_number, line_number, file_path))
+        .arg("--no-patch")
+        .arg("--reverse") // Oldest first
+        .arg(LAST_COMMIT_FORMAT)
+        .arg("HEAD")
+        .output()
+        .await
+        .map_err(|e| BlameError::GitError(format!("Failed to execute git log: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(BlameError::GitError(format!(
+            "Git log failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    parse_last_commit_output(&String::from_utf8_lossy(&output.stdout), now)
+}
+
+/// Commits at the edge of a shallow clone, where every older line appears to be added
+async fn shallow_boundaries(repo_path: &Path) -> HashSet<String> {
+    tokio::fs::read_to_string(repo_path.join(".git").join("shallow"))
+        .await
+        .map(|shallow| shallow.lines().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// If more than this fraction of blame tasks panic, the scan is treated as failed
+/// rather than reporting an oldest TODO from a suspiciously small set of survivors
+const MAX_PANICKED_TASK_RATIO: f64 = 0.5;
+
+/// Await every blame task, keeping the TODOs that were blamed successfully
+///
+/// Each task runs independently, so a panic in one doesn't affect the others,
+/// but too many panics fail the whole scan with `BlameTasksPanicked`.
+async fn collect_blame_results(
+    tasks: Vec<JoinHandle<Option<TodoItem>>>,
+) -> Result<Vec<TodoItem>, BlameError> {
+    let total = tasks.len();
+    let mut panicked = 0;
+    let mut todos_with_blame = Vec::new();
+
+    for task in tasks {
+        match task.await {
+            Ok(Some(todo)) => todos_with_blame.push(todo),
+            Ok(None) => {} // Skip TODOs that failed to get blame info
+            Err(e) => {
+                panicked += 1;
+                error!("Blame task failed: {}", e);
+            }
+        }
+    }
+
+    if panicked > 0 && panicked as f64 / total as f64 > MAX_PANICKED_TASK_RATIO {
+        return Err(BlameError::BlameTasksPanicked { panicked, total });
+    }
+
+    Ok(todos_with_blame)
+}
+
+/// How many commits `git rev-list --count` finds for `args`, e.g. a revision range
+/// followed by `--` and a path
+pub(crate) async fn count_commits(repo_path: &Path, args: &[&str]) -> Result<u32, BlameError> {
+    let output = Command::new("git")
+        .current_dir(repo_path)
         .arg("rev-list")
         .arg("--count")
-        .arg(branch)
+        .args(args)
         .output()
         .await
         .map_err(|e| BlameError::GitError(format!("Failed to execute git rev-list: {}", e)))?;
 
     if !output.status.success() {
         return Err(BlameError::GitError(format!(
-            "Git command failed: {}",
+            "Git rev-list failed: {}",
             String::from_utf8_lossy(&output.stderr)
         )));
     }
 
-    let count_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    count_str
-        .parse::<i64>()
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
         .map_err(|e| BlameError::GitError(format!("Failed to parse git output as number: {}", e)))
 }
 
 pub async fn get_git_depth(repo: &Repository) -> Result<i64, BlameError> {
-    let result = __get_git_depth(repo, "main").await;
+    let result = count_commits(repo.path(), &["main"]).await;
     if result.is_err() {
-        return __get_git_depth(repo, "master").await;
+        return count_commits(repo.path(), &["master"]).await.map(i64::from);
+    }
+
+    result.map(i64::from)
+}
+
+/// Load the repository's blame cache, carrying on without one if it can't be read
+async fn open_blame_cache(repo: &Repository) -> Option<BlameCache> {
+    let cache_dir = repo.blame_cache_dir()?;
+    match BlameCache::open(repo, cache_dir).await {
+        Ok(cache) => Some(cache),
+        Err(e) => {
+            error!("Failed to open blame cache, blaming everything: {}", e);
+            None
+        }
+    }
+}
+
+/// Remember this scan's blame results for next time
+///
+/// Approximate results are left out, a later scan may be able to blame the line properly.
+async fn save_blame_cache(cache: &BlameCache, todos: &[TodoItem]) {
+    let mut updated = cache.clone();
+    for todo in todos {
+        if let Some(blame_info) = &todo.blame_info
+            && !blame_info.approximate
+        {
+            updated.insert(&todo.file_path, todo.line_number, blame_info.clone());
+        }
+    }
+
+    if updated.len() == cache.len() {
+        return;
+    }
+    if let Err(e) = updated.save().await {
+        error!("Failed to save blame cache: {}", e);
+    }
+}
+
+/// Blames one line of a file, so tests can see how often blame actually runs
+pub(crate) trait BlameRunner: Send + Sync {
+    fn blame<'a>(
+        &'a self,
+        repo: &'a Repository,
+        todo: &'a TodoItem,
+    ) -> Pin<Box<dyn Future<Output = Result<BlameInfo, BlameError>> + Send + 'a>>;
+}
+
+/// Blames with `git blame` itself, rejecting author times past `now`
+pub(crate) struct GitBlame {
+    pub(crate) now: DateTime<Utc>,
+}
+
+impl BlameRunner for GitBlame {
+    fn blame<'a>(
+        &'a self,
+        repo: &'a Repository,
+        todo: &'a TodoItem,
+    ) -> Pin<Box<dyn Future<Output = Result<BlameInfo, BlameError>> + Send + 'a>> {
+        Box::pin(git_blame(repo, todo, self.now))
+    }
+}
+
+/// Cached blame for `todo` if there is any, otherwise whatever `runner` finds
+async fn get_blame_info(
+    repo: &Repository,
+    todo: &TodoItem,
+    runner: &dyn BlameRunner,
+    cache: Option<&BlameCache>,
+) -> Result<BlameInfo, BlameError> {
+    if let Some(blame_info) = cache.and_then(|c| c.get(&todo.file_path, todo.line_number)) {
+        debug!("Using cached blame info for todo: {}", todo.file_path);
+        return Ok(blame_info.clone());
     }
 
-    result
+    runner.blame(repo, todo).await
 }
 
 // Optimized git blame command
-async fn get_blame_info(repo: &Repository, todo: &TodoItem) -> Result<BlameInfo, BlameError> {
+async fn git_blame(
+    repo: &Repository,
+    todo: &TodoItem,
+    now: DateTime<Utc>,
+) -> Result<BlameInfo, BlameError> {
     debug!("Starting blame info for todo: {}", todo.file_path);
 
     // Using Tokio's Command for async process execution
     let output = tokio::process::Command::new("git")
         .current_dir(repo.path())
         .arg("blame")
-        .arg("-p") // porcelain format for easier parsing
+        .arg("--line-porcelain") // full commit info repeated for every line, so each block stands alone
         .arg("--no-progress") // reduce output
         .arg("-L")
         .arg(format!("{},{}", todo.line_number, todo.line_number))
@@ -147,92 +593,1127 @@ async fn get_blame_info(repo: &Repository, todo: &TodoItem) -> Result<BlameInfo,
     debug!("finished blame info for todo: {}", todo.file_path);
 
     if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        // The file disappeared from under us, fall back to its last known commit
+        if is_missing_path_error(&stderr) {
+            debug!(
+                "{} no longer exists, falling back to git log",
+                todo.file_path
+            );
+            return get_last_commit_info(repo, &todo.file_path, now).await;
+        }
+
         return Err(BlameError::GitError(format!(
             "Git blame failed: {}",
-            String::from_utf8_lossy(&output.stderr)
+            stderr
         )));
     }
 
     // Parse the blame output
     let blame_output = String::from_utf8_lossy(&output.stdout);
-    parse_blame_output(&blame_output, repo.path()).await
+    parse_line_porcelain(&blame_output, todo.line_number, now)
 }
-/// Parse git blame output in porcelain format
-async fn parse_blame_output(blame_output: &str, repo_path: &Path) -> Result<BlameInfo, BlameError> {
-    let lines: Vec<&str> = blame_output.lines().collect();
 
-    if lines.is_empty() {
-        return Err(BlameError::ParseError("Empty blame output".to_string()));
+/// Earliest author time we believe, 1972-01-01T00:00:00Z. Well before git's 2005
+/// release on purpose: histories converted from CVS, RCS or SCCS keep their original
+/// dates and hold the oldest TODOs of all. Anything before version control itself
+/// is a zeroed or crafted timestamp
+const MIN_PLAUSIBLE_AUTHOR_TIME: i64 = 2 * 365 * 24 * 60 * 60;
+
+/// How far past our own clock an author time may be, allowing for skewed clocks
+const MAX_AUTHOR_TIME_SKEW: chrono::TimeDelta = chrono::TimeDelta::days(1);
+
+/// The date of an author time, rejecting ones too far in the past or past `now` to
+/// be real, so a crafted commit can't pass itself off as the oldest TODO
+fn commit_date(author_time: i64, now: DateTime<Utc>) -> Result<DateTime<Utc>, BlameError> {
+    if author_time < MIN_PLAUSIBLE_AUTHOR_TIME {
+        return Err(BlameError::ParseError(format!(
+            "Implausibly old author time: {}",
+            author_time
+        )));
     }
 
-    // First line has the commit hash
-    let first_line_parts: Vec<&str> = lines[0].split_whitespace().collect();
-    if first_line_parts.is_empty() {
-        return Err(BlameError::ParseError(
-            "Invalid blame output format".to_string(),
-        ));
+    let date = DateTime::<Utc>::from_timestamp(author_time, 0)
+        .ok_or_else(|| BlameError::ParseError("Invalid timestamp".to_string()))?;
+    if date > now + MAX_AUTHOR_TIME_SKEW {
+        return Err(BlameError::ParseError(format!(
+            "Author time in the future: {}",
+            date
+        )));
+    }
+
+    Ok(date)
+}
+
+/// One line's block of `git blame --line-porcelain` output
+#[derive(Debug, Default)]
+struct PorcelainBlock {
+    commit_hash: String,
+    final_line: u32,
+    author: String,
+    author_email: String,
+    author_time: Option<i64>,
+    summary: String,
+    boundary: bool,
+}
+
+impl PorcelainBlock {
+    /// Start a block from its `<sha> <orig-line> <final-line> [<group-size>]` header
+    fn from_header(header: &str) -> Result<Self, BlameError> {
+        let mut parts = header.split_whitespace();
+        let commit_hash = parts
+            .next()
+            .filter(|hash| hash.len() >= 40 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or_else(|| BlameError::ParseError(format!("Invalid blame header: {}", header)))?;
+        let final_line = parts
+            .nth(1)
+            .and_then(|line| line.parse().ok())
+            .ok_or_else(|| BlameError::ParseError(format!("Invalid blame header: {}", header)))?;
+
+        Ok(PorcelainBlock {
+            commit_hash: commit_hash.to_string(),
+            final_line,
+            ..Default::default()
+        })
+    }
+
+    fn into_blame_info(self, now: DateTime<Utc>) -> Result<BlameInfo, BlameError> {
+        let author_time = self
+            .author_time
+            .ok_or_else(|| BlameError::ParseError("Missing author time".to_string()))?;
+        let date = commit_date(author_time, now)?;
+
+        Ok(BlameInfo {
+            commit_hash: self.commit_hash,
+            author: self.author,
+            author_email: self.author_email,
+            date,
+            summary: self.summary,
+            body: None,
+            boundary: self.boundary,
+            approximate: false,
+            later_commits: None,
+        })
     }
+}
 
-    let commit_hash = first_line_parts[0].to_string();
+/// Parse `git blame --line-porcelain` output, returning the blame for `line_number`
+/// with any author time past `now` rejected
+///
+/// Every block carries its commit's full details and ends with the line's content
+/// (prefixed by a tab), so `previous` references and repeated commits can't leak
+/// fields from one line into another.
+fn parse_line_porcelain(
+    blame_output: &str,
+    line_number: u32,
+    now: DateTime<Utc>,
+) -> Result<BlameInfo, BlameError> {
+    let mut current: Option<PorcelainBlock> = None;
+    let mut first_block = None;
 
-    // Parse the rest of the porcelain output
-    let mut author = String::new();
-    let mut author_email = String::new();
-    let mut author_time = 0;
+    for line in blame_output.lines() {
+        // The content line closes its block, and may itself look like anything
+        if line.starts_with('\t') {
+            let Some(block) = current.take() else {
+                return Err(BlameError::ParseError(
+                    "Blame content line without a header".to_string(),
+                ));
+            };
+            if block.final_line == line_number {
+                return block.into_blame_info(now);
+            }
+            first_block.get_or_insert(block);
+            continue;
+        }
 
-    for line in &lines[1..] {
-        if let Some(stripped) = line.strip_prefix("author ") {
-            author = stripped.to_string();
-        } else if let Some(stripped) = line.strip_prefix("author-mail ") {
-            author_email = stripped.to_string();
+        let Some(block) = current.as_mut() else {
+            current = Some(PorcelainBlock::from_header(line)?);
+            continue;
+        };
+        if line == "boundary" {
+            block.boundary = true;
+        } else if let Some(author) = line.strip_prefix("author ") {
+            block.author = author.to_string();
+        } else if let Some(email) = line.strip_prefix("author-mail ") {
             // Clean up email format: <email> -> email
-            author_email = author_email
+            block.author_email = email
                 .trim_start_matches('<')
                 .trim_end_matches('>')
                 .to_string();
-        } else if let Some(stripped) = line.strip_prefix("author-time ") {
-            author_time = stripped
-                .parse::<i64>()
-                .map_err(|_| BlameError::ParseError("Invalid author time".to_string()))?;
+        } else if let Some(time) = line.strip_prefix("author-time ") {
+            block.author_time = Some(
+                time.parse::<i64>()
+                    .map_err(|_| BlameError::ParseError("Invalid author time".to_string()))?,
+            );
+        } else if let Some(summary) = line.strip_prefix("summary ") {
+            block.summary = summary.to_string();
         }
     }
 
-    // Get the commit message summary
-    let summary = get_commit_summary(&commit_hash, repo_path).await?;
+    // Only one line is ever requested, so a lone block is the one we asked for
+    first_block
+        .or(current)
+        .ok_or_else(|| BlameError::ParseError("Empty blame output".to_string()))?
+        .into_blame_info(now)
+}
 
-    // Convert timestamp to DateTime
-    let date = chrono::DateTime::<Utc>::from_timestamp(author_time, 0)
-        .ok_or_else(|| BlameError::ParseError("Invalid timestamp".to_string()))?;
+/// Whether git blame failed because the path no longer exists in the
+/// working tree or in HEAD
+fn is_missing_path_error(stderr: &str) -> bool {
+    stderr.contains("no such path") || stderr.contains("No such file or directory")
+}
 
-    Ok(BlameInfo {
-        commit_hash,
-        author,
-        author_email,
-        date,
-        summary,
-    })
+/// Format used to recover approximate blame info from `git log`
+const LAST_COMMIT_FORMAT: &str = "--format=%H%n%an%n%ae%n%at%n%s";
+
+/// Approximate blame info from the last commit that touched `file_path` on HEAD
+async fn get_last_commit_info(
+    repo: &Repository,
+    file_path: &str,
+    now: DateTime<Utc>,
+) -> Result<BlameInfo, BlameError> {
+    let output = Command::new("git")
+        .current_dir(repo.path())
+        .arg("log")
+        .arg("-1")
+        .arg(LAST_COMMIT_FORMAT)
+        .arg("HEAD")
+        .arg("--")
+        .arg(file_path)
+        .output()
+        .await
+        .map_err(|e| BlameError::GitError(format!("Failed to execute git log: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(BlameError::GitError(format!(
+            "Git log failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    parse_last_commit_output(&String::from_utf8_lossy(&output.stdout), now)
+}
+
+/// Fill in how many commits have touched each TODO's file since its blamed commit,
+/// if `options` ranks by neglect
+///
+/// One `git rev-list` per file and commit, run in parallel. A count that can't be
+/// read is left out, ranking that TODO by age alone.
+pub async fn count_later_commits(repo: &Repository, todos: &mut [TodoItem], options: &ScanOptions) {
+    if !options.ranks_by_neglect() {
+        return;
+    }
+
+    let mut pairs: Vec<(String, String)> = todos
+        .iter()
+        .filter_map(|todo| {
+            let blame_info = todo.blame_info.as_ref()?;
+            Some((blame_info.commit_hash.clone(), todo.file_path.clone()))
+        })
+        .collect();
+    pairs.sort();
+    pairs.dedup();
+
+    // One `git rev-list` each, held to as many at once as blame
+    let permits = Arc::new(Semaphore::new(DEFAULT_BLAME_CONCURRENCY));
+    let mut tasks = Vec::with_capacity(pairs.len());
+    for (commit_hash, file_path) in pairs {
+        let permit = permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("commit count semaphore is never closed");
+        let repo_clone = repo.clone();
+        tasks.push(tokio::task::spawn(async move {
+            let _permit = permit;
+            let count = get_later_commit_count(&repo_clone, &commit_hash, &file_path).await;
+            ((commit_hash, file_path), count)
+        }));
+    }
+
+    let mut counts = HashMap::new();
+    for task in tasks {
+        match task.await {
+            Ok((pair, Ok(count))) => {
+                counts.insert(pair, count);
+            }
+            Ok(((commit_hash, file_path), Err(e))) => error!(
+                "Failed to count commits to {} since {}: {}",
+                file_path, commit_hash, e
+            ),
+            Err(e) => error!("Commit count task failed: {}", e),
+        }
+    }
+
+    for todo in todos {
+        if let Some(blame_info) = todo.blame_info.as_mut() {
+            let pair = (blame_info.commit_hash.clone(), todo.file_path.clone());
+            blame_info.later_commits = counts.get(&pair).copied();
+        }
+    }
+}
+
+/// How many commits on HEAD have touched `file_path` since `commit_hash`
+async fn get_later_commit_count(
+    repo: &Repository,
+    commit_hash: &str,
+    file_path: &str,
+) -> Result<u32, BlameError> {
+    let range = format!("{}..HEAD", commit_hash);
+    count_commits(repo.path(), &[&range, "--", file_path]).await
+}
+
+/// Format used to read a commit's subject and body, split by the first blank line
+const COMMIT_MESSAGE_FORMAT: &str = "--format=%s%n%n%b";
+
+/// Fill in the [`body`](BlameInfo::body) of the TODO's commit, if `options` asks for it
+///
+/// Only done for the TODO being reported, so scans don't pay a `git log` per TODO.
+/// If the message can't be read the body is just left out.
+pub async fn attach_commit_body(repo: &Repository, todo: &mut TodoItem, options: &ScanOptions) {
+    let Some(limit) = options.commit_body_limit else {
+        return;
+    };
+    let Some(blame_info) = todo.blame_info.as_mut() else {
+        return;
+    };
+
+    match get_commit_body(repo, &blame_info.commit_hash, limit).await {
+        Ok(body) => blame_info.body = body,
+        Err(e) => error!(
+            "Failed to read the message of commit {}: {}",
+            blame_info.commit_hash, e
+        ),
+    }
 }
 
-/// Get the summary (first line) of a commit message
-async fn get_commit_summary(commit_hash: &str, repo_path: &Path) -> Result<String, BlameError> {
+/// The body of a commit's message, cut to at most `limit` characters, or `None` if
+/// the message is only a subject
+async fn get_commit_body(
+    repo: &Repository,
+    commit_hash: &str,
+    limit: usize,
+) -> Result<Option<String>, BlameError> {
     let output = Command::new("git")
-        .current_dir(repo_path)
-        .arg("show")
-        .arg("-s")
-        .arg("--format=%s") // Just the subject line
+        .current_dir(repo.path())
+        .arg("log")
+        .arg("-1")
+        .arg(COMMIT_MESSAGE_FORMAT)
         .arg(commit_hash)
+        .arg("--")
         .output()
         .await
-        .map_err(|e| BlameError::GitError(format!("Failed to get commit message: {}", e)))?;
+        .map_err(|e| BlameError::GitError(format!("Failed to execute git log: {}", e)))?;
 
     if !output.status.success() {
         return Err(BlameError::GitError(format!(
-            "Failed to get commit message: {}",
+            "Git log failed: {}",
             String::from_utf8_lossy(&output.stderr)
         )));
     }
 
-    let summary = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let message = String::from_utf8_lossy(&output.stdout);
+    Ok(parse_commit_body(&message, limit))
+}
+
+/// Marks a commit body that was cut short
+const BODY_ELLIPSIS: char = '…';
+
+/// The body of `message` in `COMMIT_MESSAGE_FORMAT`, cut to at most `limit` characters
+fn parse_commit_body(message: &str, limit: usize) -> Option<String> {
+    let (_subject, body) = message.split_once("\n\n")?;
+    let body = body.trim();
+    if body.is_empty() {
+        return None;
+    }
+
+    if body.chars().count() <= limit {
+        return Some(body.to_string());
+    }
+    let mut cut: String = body.chars().take(limit).collect();
+    cut.truncate(cut.trim_end().len());
+    cut.push(BODY_ELLIPSIS);
+    Some(cut)
+}
+
+/// Parse the output of `git log -1` in `LAST_COMMIT_FORMAT` into approximate blame info
+fn parse_last_commit_output(output: &str, now: DateTime<Utc>) -> Result<BlameInfo, BlameError> {
+    let mut lines = output.lines();
+    let mut next_field = |name: &str| {
+        lines
+            .next()
+            .map(|line| line.to_string())
+            .ok_or_else(|| BlameError::ParseError(format!("Missing {} in git log output", name)))
+    };
+
+    let commit_hash = next_field("commit hash")?;
+    if commit_hash.is_empty() {
+        return Err(BlameError::ParseError(
+            "No commits found for path".to_string(),
+        ));
+    }
+    let author = next_field("author")?;
+    let author_email = next_field("author email")?;
+    let author_time = next_field("author time")?
+        .parse::<i64>()
+        .map_err(|_| BlameError::ParseError("Invalid author time".to_string()))?;
+    let summary = next_field("summary").unwrap_or_default();
+
+    let date = commit_date(author_time, now)?;
+
+    Ok(BlameInfo {
+        commit_hash,
+        author,
+        author_email,
+        date,
+        summary,
+        body: None,
+        boundary: false,
+        approximate: true,
+        later_commits: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SystemClock;
+    use crate::test_utils::{commit_file, commit_file_at, git, init_repo, todo_at};
+    use chrono::{Datelike, TimeZone};
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+    /// Counts the lines it blames, passing each on to `git blame`
+    #[derive(Default)]
+    struct CountingBlame(AtomicUsize);
+
+    impl CountingBlame {
+        fn calls(&self) -> usize {
+            self.0.load(AtomicOrdering::SeqCst)
+        }
+    }
+
+    impl BlameRunner for CountingBlame {
+        fn blame<'a>(
+            &'a self,
+            repo: &'a Repository,
+            todo: &'a TodoItem,
+        ) -> Pin<Box<dyn Future<Output = Result<BlameInfo, BlameError>> + Send + 'a>> {
+            self.0.fetch_add(1, AtomicOrdering::SeqCst);
+            Box::pin(git_blame(repo, todo, Utc::now()))
+        }
+    }
+
+    fn todo_in(repo: &Repository, file_path: &str, line_number: u32) -> TodoItem {
+        TodoItem {
+            source_repo_url: repo.url().to_string(),
+            ..todo_at(file_path, line_number)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blame_falls_back_when_file_removed() {
+        let dir = init_repo();
+        commit_file(dir.path(), "gone.rs", "// TODO: vanish\n", "Add gone.rs");
+        let head = git(dir.path(), &["rev-parse", "HEAD"]);
+
+        // Simulate the working tree changing between find_todos and blame
+        std::fs::remove_file(dir.path().join("gone.rs")).unwrap();
+
+        let repo = Repository::from_local_path(dir.path()).unwrap();
+        let blame = git_blame(&repo, &todo_in(&repo, "gone.rs", 1), Utc::now())
+            .await
+            .unwrap();
+
+        assert!(blame.approximate);
+        assert_eq!(blame.commit_hash, head);
+        assert_eq!(blame.author, "Test Author");
+        assert_eq!(blame.summary, "Add gone.rs");
+    }
+
+    #[tokio::test]
+    async fn test_blame_does_not_fall_back_for_other_errors() {
+        let dir = init_repo();
+        commit_file(
+            dir.path(),
+            "short.rs",
+            "// TODO: one line\n",
+            "Add short.rs",
+        );
+
+        let repo = Repository::from_local_path(dir.path()).unwrap();
+        // Line out of range is a real blame failure, not a missing path
+        let result = git_blame(&repo, &todo_in(&repo, "short.rs", 50), Utc::now()).await;
+
+        assert!(matches!(result, Err(BlameError::GitError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_blames_more_todos_than_run_at_once() {
+        let dir = init_repo();
+        let count = DEFAULT_BLAME_CONCURRENCY * 2 + 1;
+        commit_file(
+            dir.path(),
+            "many.rs",
+            &"// TODO: again\n".repeat(count),
+            "Add many.rs",
+        );
+
+        let repo = Repository::from_local_path(dir.path()).unwrap();
+        let todos = (1..=count)
+            .map(|line| todo_in(&repo, "many.rs", line as u32))
+            .collect();
+        let blamed = blame_todos(&repo, todos, &SystemClock).await.unwrap();
+
+        assert_eq!(blamed.len(), count);
+    }
+
+    #[tokio::test]
+    async fn test_rescan_at_same_head_uses_blame_cache() {
+        let dir = init_repo();
+        let cache_dir = tempfile::tempdir().unwrap();
+        commit_file(dir.path(), "a.rs", "// TODO: one\n", "Add a.rs");
+        commit_file(dir.path(), "b.rs", "// TODO: two\n", "Add b.rs");
+
+        let repo = Repository::from_local_path(dir.path())
+            .unwrap()
+            .with_blame_cache_dir(cache_dir.path());
+        let todos = vec![todo_in(&repo, "a.rs", 1), todo_in(&repo, "b.rs", 1)];
+
+        let runner = Arc::new(CountingBlame::default());
+        let first = blame_todos_with(&repo, todos.clone(), runner.clone())
+            .await
+            .unwrap();
+        assert_eq!(runner.calls(), 2);
+
+        let second = blame_todos_with(&repo, todos.clone(), runner.clone())
+            .await
+            .unwrap();
+        assert_eq!(runner.calls(), 2);
+        assert_eq!(first.len(), second.len());
+        for (a, b) in first.iter().zip(&second) {
+            assert_eq!(a.blame_info, b.blame_info);
+        }
+
+        // Moving HEAD throws the cache away
+        commit_file(dir.path(), "c.rs", "// TODO: three\n", "Add c.rs");
+        blame_todos_with(&repo, todos, runner.clone())
+            .await
+            .unwrap();
+        assert_eq!(runner.calls(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_collect_blame_results_survives_a_panic() {
+        let tasks = vec![
+            tokio::spawn(async { Some(todo_at("a.rs", 1)) }),
+            tokio::spawn(async { panic!("blame task exploded") }),
+            tokio::spawn(async { None }),
+            tokio::spawn(async { Some(todo_at("b.rs", 1)) }),
+        ];
+
+        let todos = collect_blame_results(tasks).await.unwrap();
+
+        assert_eq!(todos.len(), 2);
+        assert_eq!(todos[0].file_path, "a.rs");
+        assert_eq!(todos[1].file_path, "b.rs");
+    }
+
+    #[tokio::test]
+    async fn test_collect_blame_results_fails_on_panic_storm() {
+        let tasks: Vec<JoinHandle<Option<TodoItem>>> = vec![
+            tokio::spawn(async { panic!("one") }),
+            tokio::spawn(async { panic!("two") }),
+            tokio::spawn(async { Some(todo_at("a.rs", 1)) }),
+        ];
+
+        let result = collect_blame_results(tasks).await;
+
+        assert!(matches!(
+            result,
+            Err(BlameError::BlameTasksPanicked {
+                panicked: 2,
+                total: 3
+            })
+        ));
+    }
+
+    /// Three TODOs committed in 2015, 2018 and 2022
+    fn dated_fixture() -> (tempfile::TempDir, Repository, Vec<TodoItem>) {
+        let dir = init_repo();
+        commit_file_at(
+            dir.path(),
+            "a.rs",
+            "// TODO: a\n",
+            "Add a",
+            "2015-01-01T12:00:00Z",
+        );
+        commit_file_at(
+            dir.path(),
+            "b.rs",
+            "// TODO: b\n",
+            "Add b",
+            "2018-06-01T12:00:00Z",
+        );
+        commit_file_at(
+            dir.path(),
+            "c.rs",
+            "// TODO: c\n",
+            "Add c",
+            "2022-01-01T12:00:00Z",
+        );
+
+        let repo = Repository::from_local_path(dir.path()).unwrap();
+        let todos = ["a.rs", "b.rs", "c.rs"]
+            .iter()
+            .map(|file| todo_in(&repo, file, 1))
+            .collect();
+        (dir, repo, todos)
+    }
+
+    #[tokio::test]
+    async fn test_quick_mode_dates_todo_lines_without_blame() {
+        let (dir, repo, _) = dated_fixture();
+        // A TODO added to an old file long after the file itself
+        commit_file_at(
+            dir.path(),
+            "a.rs",
+            "// TODO: a\n// TODO: late\n",
+            "Add late TODO",
+            "2023-01-01T12:00:00Z",
+        );
+        let todos = vec![todo_in(&repo, "a.rs", 2), todo_in(&repo, "c.rs", 1)];
+        let options = ScanOptions {
+            quick: true,
+            ..Default::default()
+        };
+
+        let oldest = find_oldest_todo(&repo, todos, &options, &SystemClock)
+            .await
+            .unwrap();
+
+        // The late TODO is dated by its own line, not by when a.rs was added,
+        // and approximately, as `git blame` never ran
+        let blame = oldest.blame_info.unwrap();
+        assert_eq!(oldest.file_path, "c.rs");
+        assert_eq!(
+            blame.date,
+            Utc.with_ymd_and_hms(2022, 1, 1, 12, 0, 0).unwrap()
+        );
+        assert_eq!(blame.summary, "Add c");
+        assert!(blame.approximate);
+        assert!(!blame.boundary);
+    }
+
+    #[tokio::test]
+    async fn test_quick_mode_deepens_a_shallow_clone_past_old_lines() {
+        let (upstream, _, _) = dated_fixture();
+        commit_file_at(
+            upstream.path(),
+            "c.rs",
+            "// TODO: c\n// TODO: newest\n",
+            "Add newest",
+            "2024-01-01T12:00:00Z",
+        );
+        let repos_dir = tempfile::tempdir().unwrap();
+        let clone_url = format!("file://{}", upstream.path().display());
+        let repo = Repository::builder("https://github.com/o/r")
+            .repos_dir(repos_dir.path())
+            .url_rewrite(crate::UrlRewrite::new(".*", &clone_url).unwrap())
+            .quick()
+            .build()
+            .await
+            .unwrap();
+        repo.prepare().await.unwrap();
+        assert_eq!(repo.commit_count().await.unwrap(), 1);
+
+        let todos = vec![
+            todo_in(&repo, "a.rs", 1),
+            todo_in(&repo, "b.rs", 1),
+            todo_in(&repo, "c.rs", 2),
+        ];
+        let blamed = quick_blame_todos(&repo, todos, &SystemClock).await.unwrap();
+
+        // Rather than every line landing on the one commit the clone started with
+        let dates: HashMap<_, _> = blamed
+            .iter()
+            .map(|todo| {
+                let blame = todo.blame_info.as_ref().unwrap();
+                assert!(!blame.boundary);
+                (todo.file_path.as_str(), blame.date.year())
+            })
+            .collect();
+        assert_eq!(
+            dates,
+            HashMap::from([("a.rs", 2015), ("b.rs", 2018), ("c.rs", 2024)])
+        );
+        assert!(repo.commit_count().await.unwrap() > 1);
+    }
+
+    fn date(year: i32) -> chrono::DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_date_range_since() {
+        let (_dir, repo, todos) = dated_fixture();
+        let options = ScanOptions {
+            since: Some(date(2016)),
+            ..Default::default()
+        };
+
+        let oldest = find_oldest_todo(&repo, todos, &options, &SystemClock)
+            .await
+            .unwrap();
+
+        assert_eq!(oldest.file_path, "b.rs");
+    }
+
+    #[tokio::test]
+    async fn test_date_range_until() {
+        let (_dir, repo, todos) = dated_fixture();
+        let options = ScanOptions {
+            until: Some(date(2017)),
+            ..Default::default()
+        };
+
+        let oldest = find_oldest_todo(&repo, todos, &options, &SystemClock)
+            .await
+            .unwrap();
+
+        assert_eq!(oldest.file_path, "a.rs");
+    }
+
+    #[tokio::test]
+    async fn test_date_range_with_no_todos() {
+        let (_dir, repo, todos) = dated_fixture();
+        let options = ScanOptions {
+            since: Some(date(2019)),
+            until: Some(date(2020)),
+            ..Default::default()
+        };
+
+        let result = find_oldest_todo(&repo, todos, &options, &SystemClock).await;
+
+        assert!(matches!(result, Err(BlameError::NoTodosInDateRange)));
+    }
+
+    #[tokio::test]
+    async fn test_test_file_penalty_ranks_source_todos_first() {
+        let dir = init_repo();
+        let date = "2015-01-01T12:00:00Z";
+        commit_file_at(
+            dir.path(),
+            "src/parser.rs",
+            "// TODO: a\n",
+            "Add parser",
+            date,
+        );
+        commit_file_at(
+            dir.path(),
+            "tests/parser.rs",
+            "// TODO: b\n",
+            "Add tests",
+            date,
+        );
+        commit_file_at(
+            dir.path(),
+            "src/lexer_test.go",
+            "// TODO: c\n",
+            "Add ancient test",
+            "2005-01-01T12:00:00Z",
+        );
+        let repo = Repository::from_local_path(dir.path()).unwrap();
+        let same_age = vec![
+            todo_in(&repo, "tests/parser.rs", 1),
+            todo_in(&repo, "src/parser.rs", 1),
+        ];
+
+        // Equal real age, the test file TODO wins only the path tie-break
+        let oldest = find_oldest_todo(
+            &repo,
+            same_age.clone(),
+            &ScanOptions::default(),
+            &SystemClock,
+        )
+        .await
+        .unwrap();
+        assert_eq!(oldest.file_path, "tests/parser.rs");
+
+        let options = ScanOptions {
+            test_file_penalty_days: 365,
+            ..Default::default()
+        };
+        let oldest = find_oldest_todo(&repo, same_age.clone(), &options, &SystemClock)
+            .await
+            .unwrap();
+        assert_eq!(oldest.file_path, "src/parser.rs");
+
+        // A test TODO a decade older still beats the penalty
+        let mut todos = same_age;
+        todos.push(todo_in(&repo, "src/lexer_test.go", 1));
+        let oldest = find_oldest_todo(&repo, todos, &options, &SystemClock)
+            .await
+            .unwrap();
+        assert_eq!(oldest.file_path, "src/lexer_test.go");
+    }
+
+    #[tokio::test]
+    async fn test_neglect_ranks_todos_in_busy_files_first() {
+        let dir = init_repo();
+        let date = "2015-01-01T12:00:00Z";
+        commit_file_at(
+            dir.path(),
+            "quiet.rs",
+            "// TODO: quiet\n",
+            "Add quiet",
+            date,
+        );
+        commit_file_at(dir.path(), "busy.rs", "// TODO: busy\n", "Add busy", date);
+        // Everyone edits around the TODO in busy.rs and nobody touches quiet.rs again
+        let mut contents = "// TODO: busy\n".to_string();
+        for edit in 1..=5 {
+            contents.push_str(&format!("fn edit_{}() {{}}\n", edit));
+            commit_file_at(
+                dir.path(),
+                "busy.rs",
+                &contents,
+                &format!("Edit {}", edit),
+                &format!("2016-0{}-01T12:00:00Z", edit),
+            );
+        }
+        let repo = Repository::from_local_path(dir.path()).unwrap();
+        let todos = vec![todo_in(&repo, "quiet.rs", 1), todo_in(&repo, "busy.rs", 1)];
+
+        // Equal age, so the path tie-break picks quiet.rs
+        let oldest = find_oldest_todo(&repo, todos.clone(), &ScanOptions::default(), &SystemClock)
+            .await
+            .unwrap();
+        assert_eq!(oldest.file_path, "quiet.rs");
+        assert_eq!(oldest.blame_info.unwrap().later_commits, None);
+
+        let options = ScanOptions {
+            neglect_days_per_commit: 30,
+            ..Default::default()
+        };
+        let oldest = find_oldest_todo(&repo, todos, &options, &SystemClock)
+            .await
+            .unwrap();
+        assert_eq!(oldest.file_path, "busy.rs");
+        assert_eq!(oldest.blame_info.as_ref().unwrap().later_commits, Some(5));
+        assert_eq!(
+            options.ranking_date(&oldest),
+            oldest.commit_date() - chrono::TimeDelta::days(150)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_same_age_todos_pick_the_same_oldest_whatever_the_order() {
+        let dir = init_repo();
+        let date = "2015-01-01T12:00:00Z";
+        commit_file_at(dir.path(), "a.rs", "// TODO: a\n", "Add a", date);
+        commit_file_at(dir.path(), "b.rs", "// TODO: b\n", "Add b", date);
+        let repo = Repository::from_local_path(dir.path()).unwrap();
+
+        let in_order = vec![todo_in(&repo, "a.rs", 1), todo_in(&repo, "b.rs", 1)];
+        let reversed: Vec<TodoItem> = in_order.iter().rev().cloned().collect();
+
+        let first = find_oldest_todo(
+            &repo,
+            in_order.clone(),
+            &ScanOptions::default(),
+            &SystemClock,
+        )
+        .await
+        .unwrap();
+        for _ in 0..3 {
+            let again = find_oldest_todo(
+                &repo,
+                reversed.clone(),
+                &ScanOptions::default(),
+                &SystemClock,
+            )
+            .await
+            .unwrap();
+            assert_eq!(again.file_path, first.file_path);
+        }
+
+        // And it's the one listing every TODO puts first
+        let mut all = blame_todos(&repo, in_order, &SystemClock).await.unwrap();
+        all.sort_by(|a, b| b.cmp(a));
+        assert_eq!(all[0].file_path, first.file_path);
+    }
+
+    #[tokio::test]
+    async fn test_excluded_authors_are_skipped() {
+        let (dir, repo, mut todos) = dated_fixture();
+        // The oldest TODO of all, but written by a bot
+        std::fs::write(dir.path().join("bot.rs"), "// TODO: bump\n").unwrap();
+        git(dir.path(), &["add", "bot.rs"]);
+        let output = std::process::Command::new("git")
+            .current_dir(dir.path())
+            .args(["commit", "-q", "-m", "Bump deps"])
+            .env("GIT_AUTHOR_NAME", "dependabot[bot]")
+            .env(
+                "GIT_AUTHOR_EMAIL",
+                "49699333+dependabot[bot]@users.noreply.github.com",
+            )
+            .env("GIT_AUTHOR_DATE", "2010-01-01T12:00:00Z")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        todos.push(todo_in(&repo, "bot.rs", 1));
+
+        let oldest = find_oldest_todo(&repo, todos.clone(), &ScanOptions::default(), &SystemClock)
+            .await
+            .unwrap();
+        assert_eq!(oldest.file_path, "bot.rs");
+
+        let options = ScanOptions {
+            exclude_authors: vec!["*[bot]".to_string()],
+            ..Default::default()
+        };
+        let oldest = find_oldest_todo(&repo, todos, &options, &SystemClock)
+            .await
+            .unwrap();
+        assert_eq!(oldest.file_path, "a.rs");
+    }
+
+    #[tokio::test]
+    async fn test_generated_files_are_passed_over() {
+        let (dir, repo, mut todos) = dated_fixture();
+        // The oldest TODO of all, but stamped into a generated file
+        commit_file_at(
+            dir.path(),
+            "schema.gen.rs",
+            "// TODO: regenerate\n",
+            "Regenerate schema",
+            "2010-01-01T12:00:00Z",
+        );
+        commit_file(
+            dir.path(),
+            ".gitattributes",
+            "*.gen.rs linguist-generated=true\n",
+            "Mark generated files",
+        );
+        todos.push(todo_in(&repo, "schema.gen.rs", 1));
+
+        let oldest = find_oldest_todo(&repo, todos.clone(), &ScanOptions::default(), &SystemClock)
+            .await
+            .unwrap();
+        assert_eq!(oldest.file_path, "schema.gen.rs");
+
+        let options = ScanOptions {
+            exclude_generated: true,
+            ..Default::default()
+        };
+        let oldest = find_oldest_todo(&repo, todos.clone(), &options, &SystemClock)
+            .await
+            .unwrap();
+        assert_eq!(oldest.file_path, "a.rs");
+
+        // Extra globs count as generated too
+        let options = ScanOptions {
+            exclude_generated: true,
+            generated_globs: vec!["a.rs".to_string()],
+            ..Default::default()
+        };
+        let oldest = find_oldest_todo(&repo, todos, &options, &SystemClock)
+            .await
+            .unwrap();
+        assert_eq!(oldest.file_path, "b.rs");
+    }
+
+    #[tokio::test]
+    async fn test_commit_body_is_read_and_truncated() {
+        let dir = init_repo();
+        commit_file(
+            dir.path(),
+            "why.rs",
+            "// TODO: explain\n",
+            "Work around the flaky parser\n\n\
+             The upstream parser drops the last token.\n\n\
+             Remove this once 2.0 ships.",
+        );
+        let repo = Repository::from_local_path(dir.path()).unwrap();
+        let todos = vec![todo_in(&repo, "why.rs", 1)];
+
+        // Not asked for, not read
+        let oldest = find_oldest_todo(&repo, todos.clone(), &ScanOptions::default(), &SystemClock)
+            .await
+            .unwrap();
+        let blame_info = oldest.blame_info.unwrap();
+        assert_eq!(blame_info.summary, "Work around the flaky parser");
+        assert_eq!(blame_info.body, None);
+
+        let options = ScanOptions {
+            commit_body_limit: Some(1000),
+            ..Default::default()
+        };
+        let oldest = find_oldest_todo(&repo, todos.clone(), &options, &SystemClock)
+            .await
+            .unwrap();
+        let blame_info = oldest.blame_info.unwrap();
+        assert_eq!(blame_info.summary, "Work around the flaky parser");
+        assert_eq!(
+            blame_info.body.as_deref(),
+            Some("The upstream parser drops the last token.\n\nRemove this once 2.0 ships.")
+        );
+
+        let options = ScanOptions {
+            commit_body_limit: Some(20),
+            ..Default::default()
+        };
+        let oldest = find_oldest_todo(&repo, todos, &options, &SystemClock)
+            .await
+            .unwrap();
+        let blame_info = oldest.blame_info.unwrap();
+        assert_eq!(blame_info.summary, "Work around the flaky parser");
+        assert_eq!(blame_info.body.as_deref(), Some("The upstream parser…"));
+    }
+
+    #[test]
+    fn test_parse_commit_body() {
+        assert_eq!(parse_commit_body("Subject only\n\n\n", 100), None);
+        assert_eq!(parse_commit_body("", 100), None);
+        assert_eq!(
+            parse_commit_body("Subject\n\nÜberall ünicode", 7).as_deref(),
+            Some("Überall…")
+        );
+    }
+
+    const OLD_SHA: &str = "1111111111111111111111111111111111111111";
+    const NEW_SHA: &str = "2222222222222222222222222222222222222222";
+
+    /// A `--line-porcelain` block for one line
+    fn porcelain_block(sha: &str, line: u32, author: &str, time: i64, extra: &str) -> String {
+        format!(
+            "{sha} {line} {line} 1\n\
+             author {author}\n\
+             author-mail <{author}@example.com>\n\
+             author-time {time}\n\
+             author-tz +0000\n\
+             committer {author}\n\
+             committer-mail <{author}@example.com>\n\
+             committer-time {time}\n\
+             committer-tz +0000\n\
+             summary Commit by {author}\n\
+             {extra}\
+             filename src/main.rs\n\
+             \t// line {line}\n"
+        )
+    }
+
+    #[test]
+    fn test_parse_line_porcelain_picks_requested_line() {
+        let output = [
+            porcelain_block(OLD_SHA, 3, "alice", 1_000_000_000, "boundary\n"),
+            porcelain_block(
+                NEW_SHA,
+                4,
+                "bob",
+                1_600_000_000,
+                &format!("previous {} src/main.rs\n", OLD_SHA),
+            ),
+            // The same commit again, repeated in full as `--line-porcelain` does
+            porcelain_block(OLD_SHA, 5, "alice", 1_000_000_000, ""),
+        ]
+        .concat();
+
+        let blame = parse_line_porcelain(&output, 4, Utc::now()).unwrap();
+        assert_eq!(blame.commit_hash, NEW_SHA);
+        assert_eq!(blame.author, "bob");
+        assert_eq!(blame.author_email, "bob@example.com");
+        assert_eq!(blame.date.timestamp(), 1_600_000_000);
+        assert_eq!(blame.summary, "Commit by bob");
+        assert!(!blame.boundary);
+
+        let blame = parse_line_porcelain(&output, 5, Utc::now()).unwrap();
+        assert_eq!(blame.commit_hash, OLD_SHA);
+        assert_eq!(blame.summary, "Commit by alice");
+        assert!(!blame.boundary);
+
+        let blame = parse_line_porcelain(&output, 3, Utc::now()).unwrap();
+        assert!(blame.boundary);
+    }
+
+    #[test]
+    fn test_parse_line_porcelain_ignores_lookalike_content() {
+        // Code that looks like porcelain fields must not be read as them
+        let output = porcelain_block(NEW_SHA, 7, "bob", 1_600_000_000, "")
+            .replace("\t// line 7", "\tauthor-time 1");
 
-    Ok(summary)
+        let blame = parse_line_porcelain(&output, 7, Utc::now()).unwrap();
+        assert_eq!(blame.date.timestamp(), 1_600_000_000);
+    }
+
+    #[test]
+    fn test_parse_line_porcelain_rejects_garbage() {
+        assert!(matches!(
+            parse_line_porcelain("", 1, Utc::now()),
+            Err(BlameError::ParseError(_))
+        ));
+        assert!(matches!(
+            parse_line_porcelain("fatal: not a blame\n", 1, Utc::now()),
+            Err(BlameError::ParseError(_))
+        ));
+        let no_time = format!("{} 1 1 1\nauthor bob\n\t// TODO\n", NEW_SHA);
+        assert!(matches!(
+            parse_line_porcelain(&no_time, 1, Utc::now()),
+            Err(BlameError::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_implausible_author_times_are_rejected() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let too_late = (now + chrono::TimeDelta::days(2)).timestamp();
+        // 1900-01-01 and just before the cutoff
+        for time in [too_late, -2_208_988_800, MIN_PLAUSIBLE_AUTHOR_TIME - 1] {
+            let output = porcelain_block(NEW_SHA, 1, "mallory", time, "");
+            assert!(matches!(
+                parse_line_porcelain(&output, 1, now),
+                Err(BlameError::ParseError(_))
+            ));
+
+            let log_output = format!("{}\nmallory\nm@example.com\n{}\nBackdate\n", NEW_SHA, time);
+            assert!(matches!(
+                parse_last_commit_output(&log_output, now),
+                Err(BlameError::ParseError(_))
+            ));
+        }
+
+        // A little clock skew is fine, and so is history converted from before git
+        assert_eq!(
+            MIN_PLAUSIBLE_AUTHOR_TIME,
+            Utc.with_ymd_and_hms(1972, 1, 1, 0, 0, 0)
+                .unwrap()
+                .timestamp()
+        );
+        let skewed = (now + chrono::TimeDelta::hours(1)).timestamp();
+        let pre_git = 315_532_800; // 1980-01-01
+        for time in [skewed, pre_git] {
+            let output = porcelain_block(NEW_SHA, 1, "alice", time, "");
+            assert_eq!(
+                parse_line_porcelain(&output, 1, now)
+                    .unwrap()
+                    .date
+                    .timestamp(),
+                time
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_missing_path_error() {
+        assert!(is_missing_path_error(
+            "fatal: no such path 'src/old.rs' in HEAD"
+        ));
+        assert!(is_missing_path_error(
+            "fatal: Cannot lstat 'src/old.rs': No such file or directory"
+        ));
+        assert!(!is_missing_path_error(
+            "fatal: file src/a.rs has only 3 lines"
+        ));
+    }
 }
@@ -0,0 +1,219 @@
+use log::debug;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+use crate::blame::BlameInfo;
+use crate::error::BlameError;
+use crate::repo::Repository;
+
+/// Blame results for one repository, remembered across scans on disk
+///
+/// Entries are only valid for the HEAD they were blamed at, so the whole
+/// cache is thrown away as soon as HEAD moves.
+#[derive(Debug, Clone)]
+pub struct BlameCache {
+    /// File the cache is persisted to
+    path: PathBuf,
+
+    /// HEAD commit the entries were blamed at
+    head: String,
+
+    /// Blame info keyed by `file_path:line_number`
+    entries: HashMap<String, BlameInfo>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    head: String,
+    entries: HashMap<String, BlameInfo>,
+}
+
+impl BlameCache {
+    /// Load the cache for this repository from `cache_dir`, starting empty if
+    /// there isn't one yet or it was written at a different HEAD
+    pub async fn open(repo: &Repository, cache_dir: &Path) -> Result<Self, BlameError> {
+        let head = repo.head_sha().await?;
+        let path = cache_dir.join(cache_file_name(repo));
+
+        let entries = match tokio::fs::read(&path).await {
+            Ok(bytes) => match serde_json::from_slice::<CacheFile>(&bytes) {
+                Ok(file) if file.head == head => file.entries,
+                Ok(_) => {
+                    debug!(
+                        "HEAD moved since {} was written, ignoring it",
+                        path.display()
+                    );
+                    HashMap::new()
+                }
+                Err(e) => {
+                    debug!("Ignoring unreadable blame cache {}: {}", path.display(), e);
+                    HashMap::new()
+                }
+            },
+            Err(_) => HashMap::new(),
+        };
+
+        Ok(BlameCache {
+            path,
+            head,
+            entries,
+        })
+    }
+
+    /// Cached blame for a line, if it was blamed at the current HEAD
+    pub fn get(&self, file_path: &str, line_number: u32) -> Option<&BlameInfo> {
+        self.entries.get(&entry_key(file_path, line_number))
+    }
+
+    /// Remember the blame for a line
+    pub fn insert(&mut self, file_path: &str, line_number: u32, blame_info: BlameInfo) {
+        self.entries
+            .insert(entry_key(file_path, line_number), blame_info);
+    }
+
+    /// Number of cached lines
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether nothing is cached yet
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the cache back to disk
+    pub async fn save(&self) -> Result<(), BlameError> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(|e| {
+                BlameError::DirectoryError(format!(
+                    "Failed to create blame cache dir {}: {}",
+                    parent.display(),
+                    e
+                ))
+            })?;
+        }
+
+        let file = CacheFile {
+            head: self.head.clone(),
+            entries: self.entries.clone(),
+        };
+        let bytes = serde_json::to_vec(&file)
+            .map_err(|e| BlameError::ParseError(format!("Failed to encode blame cache: {}", e)))?;
+
+        // Write to a temp file of our own then move it into place, so a concurrent
+        // scan never reads a half-written file nor writes over ours
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || write_atomically(&path, &bytes))
+            .await
+            .map_err(|e| BlameError::InternalError(format!("Blame cache writer failed: {}", e)))?
+    }
+}
+
+fn entry_key(file_path: &str, line_number: u32) -> String {
+    format!("{}:{}", file_path, line_number)
+}
+
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), BlameError> {
+    let write_error = |e: &dyn std::fmt::Display| {
+        BlameError::FileError(format!(
+            "Failed to write blame cache {}: {}",
+            path.display(),
+            e
+        ))
+    };
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut file = NamedTempFile::new_in(dir).map_err(|e| write_error(&e))?;
+    file.write_all(bytes).map_err(|e| write_error(&e))?;
+    file.persist(path).map_err(|e| write_error(&e))?;
+    Ok(())
+}
+
+/// One file per clone, so branches of the same repo don't share a cache. Named
+/// by a sha256 of the clone's path, which unlike `DefaultHasher` stays the same
+/// across Rust versions
+fn cache_file_name(repo: &Repository) -> String {
+    let digest = Sha256::digest(repo.path().as_os_str().as_encoded_bytes());
+    let hex: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}.json", repo.name(), hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{commit_file, init_repo};
+    use chrono::Utc;
+    use tempfile::tempdir;
+
+    fn blame(commit_hash: &str) -> BlameInfo {
+        BlameInfo {
+            commit_hash: commit_hash.to_string(),
+            author: "Test".to_string(),
+            author_email: "test@example.com".to_string(),
+            date: Utc::now(),
+            summary: "add".to_string(),
+            body: None,
+            boundary: false,
+            approximate: false,
+            later_commits: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_round_trips_at_same_head() {
+        let repo_dir = init_repo();
+        let cache_dir = tempdir().unwrap();
+        commit_file(repo_dir.path(), "a.rs", "// TODO: one\n", "add a");
+        let repo = Repository::from_local_path(repo_dir.path()).unwrap();
+
+        let mut cache = BlameCache::open(&repo, cache_dir.path()).await.unwrap();
+        assert!(cache.is_empty());
+        cache.insert("a.rs", 1, blame("abc"));
+        cache.save().await.unwrap();
+
+        let reopened = BlameCache::open(&repo, cache_dir.path()).await.unwrap();
+        assert_eq!(reopened.get("a.rs", 1).unwrap().commit_hash, "abc");
+        assert!(reopened.get("a.rs", 2).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cache_invalidated_when_head_moves() {
+        let repo_dir = init_repo();
+        let cache_dir = tempdir().unwrap();
+        commit_file(repo_dir.path(), "a.rs", "// TODO: one\n", "add a");
+        let repo = Repository::from_local_path(repo_dir.path()).unwrap();
+
+        let mut cache = BlameCache::open(&repo, cache_dir.path()).await.unwrap();
+        cache.insert("a.rs", 1, blame("abc"));
+        cache.save().await.unwrap();
+
+        commit_file(repo_dir.path(), "b.rs", "// TODO: two\n", "add b");
+
+        let reopened = BlameCache::open(&repo, cache_dir.path()).await.unwrap();
+        assert!(reopened.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_saves_leave_a_whole_file() {
+        let repo_dir = init_repo();
+        let cache_dir = tempdir().unwrap();
+        commit_file(repo_dir.path(), "a.rs", "// TODO: one\n", "add a");
+        let repo = Repository::from_local_path(repo_dir.path()).unwrap();
+
+        let mut first = BlameCache::open(&repo, cache_dir.path()).await.unwrap();
+        let mut second = first.clone();
+        first.insert("a.rs", 1, blame("abc"));
+        second.insert("a.rs", 1, blame("def"));
+        let (a, b) = tokio::join!(first.save(), second.save());
+        a.unwrap();
+        b.unwrap();
+
+        // One of them won, and no temp files were left behind
+        let reopened = BlameCache::open(&repo, cache_dir.path()).await.unwrap();
+        assert_eq!(reopened.len(), 1);
+        assert_eq!(std::fs::read_dir(cache_dir.path()).unwrap().count(), 1);
+    }
+}
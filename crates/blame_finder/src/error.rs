@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -5,9 +6,21 @@ pub enum BlameError {
     #[error("Invalid repository URL: {0}")]
     InvalidUrl(String),
 
+    #[error("Host not allowed: {0}")]
+    HostNotAllowed(String),
+
     #[error("Git operation failed: {0}")]
     GitError(String),
 
+    #[error("Not a git repository (or inside one): {}", .0.display())]
+    NotAGitRepository(PathBuf),
+
+    #[error("This repository has no commits yet: {0}")]
+    EmptyRepository(String),
+
+    #[error("Pull request #{number} not found in {url}")]
+    PullRequestNotFound { url: String, number: u32 },
+
     #[error("Ripgrep search failed: {0}")]
     SearchError(String),
 
@@ -17,12 +30,33 @@ pub enum BlameError {
     #[error("Failed to parse output: {0}")]
     ParseError(String),
 
+    #[error("Failed to parse ripgrep line {raw_line:?}: {reason}")]
+    RipgrepLineError { raw_line: String, reason: String },
+
+    #[error("Path resolves outside the repository: {0}")]
+    PathOutsideRepo(String),
+
+    #[error("Invalid pattern: {0}")]
+    InvalidPattern(String),
+
+    #[error("Invalid option: {0}")]
+    InvalidOption(String),
+
     #[error("Failed to create or access directory: {0}")]
     DirectoryError(String),
 
     #[error("Failed to access or read file: {0}")]
     FileError(String),
 
+    #[error("{panicked} of {total} blame tasks panicked")]
+    BlameTasksPanicked { panicked: usize, total: usize },
+
+    #[error("Failed to get blame info for any of {attempted} TODOs")]
+    AllBlameFailed { attempted: usize },
+
+    #[error("No TODOs found in the given date range")]
+    NoTodosInDateRange,
+
     #[error("Internal error: {0}")]
     InternalError(String),
 }
@@ -0,0 +1,141 @@
+use log::warn;
+use std::path::Path;
+
+use crate::error::BlameError;
+use crate::glob::Glob;
+
+/// Name of the file at the repository root giving paths their git attributes
+pub const GITATTRIBUTES_FILE: &str = ".gitattributes";
+
+/// The attribute GitHub's linguist reads to tell generated files apart
+const GENERATED_ATTRIBUTE: &str = "linguist-generated";
+
+/// Files a generator writes, whose TODOs blame credits to whichever commit last
+/// regenerated them rather than to the person who meant them
+///
+/// A file is generated when the repository's `.gitattributes` marks it
+/// `linguist-generated`, or it matches one of the extra globs passed in.
+#[derive(Debug, Default)]
+pub struct GeneratedFiles {
+    // `.gitattributes` patterns in file order, each setting or unsetting the
+    // attribute. The last one matching a path decides, as in git
+    attributes: Vec<(Glob, bool)>,
+    globs: Vec<Glob>,
+}
+
+impl GeneratedFiles {
+    pub fn parse(gitattributes: &str, globs: &[String]) -> Self {
+        let mut generated = GeneratedFiles::default();
+
+        for line in gitattributes.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let Some(pattern) = fields.next() else {
+                continue;
+            };
+            let Some(set) = fields.filter_map(generated_attribute).next_back() else {
+                continue;
+            };
+
+            // Someone else's broken pattern shouldn't fail the scan
+            match Glob::new(pattern) {
+                Ok(glob) => generated.attributes.push((glob, set)),
+                Err(e) => warn!(
+                    "Skipping {} pattern {:?}: {}",
+                    GITATTRIBUTES_FILE, pattern, e
+                ),
+            }
+        }
+
+        for pattern in globs {
+            match Glob::new(pattern) {
+                Ok(glob) => generated.globs.push(glob),
+                Err(e) => warn!("Skipping generated file glob {:?}: {}", pattern, e),
+            }
+        }
+
+        generated
+    }
+
+    /// Load the `.gitattributes` at the root of `repo_path`, if there is one, along
+    /// with the extra `globs`
+    pub fn load(repo_path: &Path, globs: &[String]) -> Result<Self, BlameError> {
+        let path = repo_path.join(GITATTRIBUTES_FILE);
+        let contents = if path.exists() {
+            std::fs::read_to_string(&path).map_err(|e| {
+                BlameError::FileError(format!("Failed to read {}: {}", GITATTRIBUTES_FILE, e))
+            })?
+        } else {
+            String::new()
+        };
+
+        Ok(Self::parse(&contents, globs))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty() && self.globs.is_empty()
+    }
+
+    /// Whether the repo-relative `file_path` is a generated file
+    pub fn is_generated(&self, file_path: &str) -> bool {
+        let marked = self
+            .attributes
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.is_match(file_path))
+            .is_some_and(|(_, set)| *set);
+        marked || self.globs.iter().any(|glob| glob.is_match(file_path))
+    }
+}
+
+/// Whether one attribute of a `.gitattributes` line sets `linguist-generated`, or
+/// `None` when it's about some other attribute
+fn generated_attribute(attribute: &str) -> Option<bool> {
+    if let Some(name) = attribute
+        .strip_prefix('-')
+        .or_else(|| attribute.strip_prefix('!'))
+    {
+        return (name == GENERATED_ATTRIBUTE).then_some(false);
+    }
+    match attribute.split_once('=') {
+        Some((name, value)) if name == GENERATED_ATTRIBUTE => Some(!matches!(value, "false" | "0")),
+        Some(_) => None,
+        None => (attribute == GENERATED_ATTRIBUTE).then_some(true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_gitattributes() {
+        let generated = GeneratedFiles::parse(
+            "# Generated by protoc\n\
+             *.pb.go linguist-generated=true\n\
+             api/** text linguist-generated\n\
+             api/handwritten.go -linguist-generated\n\
+             docs/** linguist-documentation\n\
+             vendor/** linguist-generated=false\n",
+            &["*.min.js".to_string()],
+        );
+
+        assert!(generated.is_generated("proto/user.pb.go"));
+        assert!(generated.is_generated("api/client.go"));
+        assert!(!generated.is_generated("api/handwritten.go"));
+        assert!(!generated.is_generated("docs/guide.md"));
+        assert!(!generated.is_generated("vendor/lib.go"));
+        assert!(generated.is_generated("static/app.min.js"));
+        assert!(!generated.is_generated("src/main.go"));
+    }
+
+    #[test]
+    fn test_missing_gitattributes_is_empty() {
+        let dir = tempdir().unwrap();
+        assert!(GeneratedFiles::load(dir.path(), &[]).unwrap().is_empty());
+    }
+}
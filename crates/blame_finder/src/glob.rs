@@ -0,0 +1,152 @@
+use regex::Regex;
+
+use crate::error::BlameError;
+
+/// A gitignore-style path glob
+///
+/// Supports `*` (anything but `/`), `**` (anything, including `/`), `?` and
+/// `[...]` character classes. A pattern without a `/` matches against the
+/// file name in any directory, and a trailing `/` matches everything under
+/// that directory.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pattern: String,
+    regex: Regex,
+    match_file_name: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Self, BlameError> {
+        let trimmed = pattern.trim();
+        if trimmed.is_empty() {
+            return Err(BlameError::InvalidPattern("Empty glob pattern".to_string()));
+        }
+
+        // `dir/` is shorthand for everything under `dir`
+        let expanded = match trimmed.strip_suffix('/') {
+            Some(dir) => format!("{}/**", dir),
+            None => trimmed.to_string(),
+        };
+        let expanded = expanded.trim_start_matches('/');
+
+        let regex = Regex::new(&glob_to_regex(expanded)?).map_err(|e| {
+            BlameError::InvalidPattern(format!("Invalid glob {:?}: {}", pattern, e))
+        })?;
+
+        Ok(Glob {
+            pattern: pattern.to_string(),
+            regex,
+            match_file_name: !expanded.contains('/'),
+        })
+    }
+
+    /// The pattern as originally written
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether the repo-relative `path` matches this glob
+    pub fn is_match(&self, path: &str) -> bool {
+        let path = path.trim_start_matches("./");
+        if self.regex.is_match(path) {
+            return true;
+        }
+
+        self.match_file_name
+            && path
+                .rsplit('/')
+                .next()
+                .is_some_and(|file_name| self.regex.is_match(file_name))
+    }
+}
+
+/// Translate a glob into an anchored regex
+fn glob_to_regex(glob: &str) -> Result<String, BlameError> {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` also matches zero directories
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                let mut class = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        closed = true;
+                        break;
+                    }
+                    class.push(c);
+                }
+                if !closed || class.is_empty() {
+                    return Err(BlameError::InvalidPattern(format!(
+                        "Unclosed character class in glob {:?}",
+                        glob
+                    )));
+                }
+                let class = match class.strip_prefix('!') {
+                    Some(negated) => format!("^{}", negated),
+                    None => class,
+                };
+                regex.push('[');
+                regex.push_str(&class.replace('\\', "\\\\"));
+                regex.push(']');
+            }
+            other => regex.push_str(&regex::escape(&other.to_string())),
+        }
+    }
+
+    regex.push('$');
+    Ok(regex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matching() {
+        let glob = Glob::new("*.min.js").unwrap();
+        assert!(glob.is_match("static/app.min.js"));
+        assert!(!glob.is_match("static/app.js"));
+
+        let glob = Glob::new("src/**").unwrap();
+        assert!(glob.is_match("src/parser/mod.rs"));
+        assert!(!glob.is_match("tests/src/mod.rs"));
+
+        let glob = Glob::new("vendor/").unwrap();
+        assert!(glob.is_match("vendor/lib/a.c"));
+
+        let glob = Glob::new("**/generated/*.rs").unwrap();
+        assert!(glob.is_match("generated/a.rs"));
+        assert!(glob.is_match("crates/x/generated/a.rs"));
+        assert!(!glob.is_match("crates/x/generated/deep/a.rs"));
+
+        let glob = Glob::new("file[0-9].txt").unwrap();
+        assert!(glob.is_match("file1.txt"));
+        assert!(!glob.is_match("filex.txt"));
+    }
+
+    #[test]
+    fn test_invalid_glob() {
+        assert!(matches!(
+            Glob::new("src/[abc"),
+            Err(BlameError::InvalidPattern(_))
+        ));
+        assert!(matches!(
+            Glob::new("  "),
+            Err(BlameError::InvalidPattern(_))
+        ));
+    }
+}
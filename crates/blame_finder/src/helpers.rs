@@ -27,3 +27,102 @@ pub fn extract_path_segments(url: &str, host: &str) -> String {
     // Fallback
     url.to_string()
 }
+
+/// How many leading bytes to inspect when guessing whether a file is binary (same as git)
+const BINARY_SNIFF_LEN: usize = 8000;
+
+/// Treat content as binary if a null byte appears near the start, like git and ripgrep do
+pub fn looks_binary(content: &[u8]) -> bool {
+    content[..content.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+/// Byte order mark some (mostly Windows) editors put at the start of UTF-8 files
+pub const UTF8_BOM: &str = "\u{FEFF}";
+
+/// Drop a leading UTF-8 BOM, which would otherwise show up as a stray character
+pub fn strip_bom(content: &[u8]) -> &[u8] {
+    content.strip_prefix(UTF8_BOM.as_bytes()).unwrap_or(content)
+}
+
+/// Drop terminal escape sequences and control characters, keeping tabs and newlines
+///
+/// A crafted repo could otherwise smuggle ANSI sequences into our logs, or stray control
+/// characters into the page. Whole `ESC [ ...` (CSI) and `ESC ] ...` (OSC) sequences go,
+/// so no `[31m` debris is left behind
+pub fn strip_control_chars(text: &str) -> String {
+    let mut clean = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\t' | '\n' => clean.push(c),
+            '\u{1b}' => match chars.peek() {
+                // CSI runs until a final byte in `@`..=`~`
+                Some('[') => {
+                    chars.next();
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC runs until BEL or the `ESC \` string terminator
+                Some(']') => {
+                    chars.next();
+                    while let Some(c) = chars.next() {
+                        if c == '\u{7}' {
+                            break;
+                        }
+                        if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            c if c.is_control() => {}
+            c => clean.push(c),
+        }
+    }
+    clean
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_looks_binary() {
+        assert!(!looks_binary(b"// TODO: plain text"));
+        assert!(looks_binary(b"\x7fELF\0\0TODO"));
+
+        // Null bytes past the sniffed prefix are ignored
+        let mut late_null = vec![b'a'; BINARY_SNIFF_LEN];
+        late_null.push(0);
+        assert!(!looks_binary(&late_null));
+    }
+
+    #[test]
+    fn test_strip_bom() {
+        assert_eq!(strip_bom(b"\xEF\xBB\xBF// TODO"), b"// TODO");
+        assert_eq!(strip_bom(b"// TODO"), b"// TODO");
+    }
+
+    #[test]
+    fn test_strip_control_chars() {
+        assert_eq!(strip_control_chars("// TODO: plain"), "// TODO: plain");
+        assert_eq!(
+            strip_control_chars("\u{1b}[31;1m// TODO\u{1b}[0m: red"),
+            "// TODO: red"
+        );
+        assert_eq!(
+            strip_control_chars("\u{1b}]0;pwned\u{7}// TODO\u{1b}]8;;x\u{1b}\\ link"),
+            "// TODO link"
+        );
+        assert_eq!(
+            strip_control_chars("a\tb\nc\rd\u{8}e\u{7f}f\u{9b}g"),
+            "a\tb\ncdefg"
+        );
+    }
+}
@@ -0,0 +1,23 @@
+use std::sync::LazyLock;
+
+use crate::keyed_permits::{KeyedPermit, KeyedPermits};
+
+/// Default number of clones or fetches allowed against one host at once
+pub const DEFAULT_MAX_CLONES_PER_HOST: usize = 2;
+
+/// One semaphore per git host being cloned from, shared by every `Repository`
+/// in the process
+static HOST_PERMITS: LazyLock<KeyedPermits<String>> = LazyLock::new(KeyedPermits::default);
+
+/// A turn at cloning from a host, released on drop
+///
+/// Many simultaneous clones from e.g. github.com risk being rate limited or banned,
+/// so each host only gets a few at a time however many scans are running.
+pub type HostPermit = KeyedPermit<String>;
+
+/// Wait until fewer than `limit` clones are running against `host`, then take a turn
+///
+/// The limit of whichever clone found the host idle applies until it's idle again.
+pub async fn acquire_host(host: &str, limit: usize) -> HostPermit {
+    HOST_PERMITS.acquire(host.to_string(), limit).await
+}
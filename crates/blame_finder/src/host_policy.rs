@@ -0,0 +1,167 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use url::{Host, Url};
+
+use crate::error::BlameError;
+
+/// Which git hosts may be cloned from
+///
+/// Private, loopback and link-local addresses are always rejected so a public
+/// instance can't be pointed at internal services. When `allowed` is non-empty
+/// only those hosts (and their subdomains) are accepted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostPolicy {
+    pub allowed: Vec<String>,
+    pub denied: Vec<String>,
+}
+
+impl HostPolicy {
+    pub fn new(allowed: Vec<String>, denied: Vec<String>) -> Self {
+        let normalize = |hosts: Vec<String>| {
+            hosts
+                .into_iter()
+                .map(|host| host.trim().trim_end_matches('.').to_lowercase())
+                .filter(|host| !host.is_empty())
+                .collect()
+        };
+
+        HostPolicy {
+            allowed: normalize(allowed),
+            denied: normalize(denied),
+        }
+    }
+
+    /// Whether an allowlist is configured, replacing the default host heuristics
+    pub fn has_allowlist(&self) -> bool {
+        !self.allowed.is_empty()
+    }
+
+    /// Check the scheme and host of a repository URL against the policy
+    pub fn check(&self, url: &Url) -> Result<(), BlameError> {
+        let rejected = |reason: &str| BlameError::HostNotAllowed(format!("{}: {}", reason, url));
+
+        if !matches!(url.scheme(), "https" | "http") {
+            return Err(rejected("only http(s) URLs can be scanned"));
+        }
+
+        let host = match url.host() {
+            Some(Host::Domain(domain)) => domain.trim_end_matches('.').to_lowercase(),
+            Some(Host::Ipv4(ip)) => ip.to_string(),
+            Some(Host::Ipv6(ip)) => ip.to_string(),
+            None => return Err(rejected("missing host")),
+        };
+
+        let internal_ip = host.parse::<IpAddr>().is_ok_and(is_internal_ip);
+        if internal_ip || host == "localhost" || host.ends_with(".localhost") {
+            return Err(rejected("private address"));
+        }
+
+        if self.denied.iter().any(|denied| host_matches(&host, denied)) {
+            return Err(rejected("host is denied"));
+        }
+        if self.has_allowlist()
+            && !self
+                .allowed
+                .iter()
+                .any(|allowed| host_matches(&host, allowed))
+        {
+            return Err(rejected("host is not on the allowlist"));
+        }
+
+        Ok(())
+    }
+}
+
+/// `host` is `pattern` or one of its subdomains
+fn host_matches(host: &str, pattern: &str) -> bool {
+    host == pattern
+        || host
+            .strip_suffix(pattern)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+fn is_internal_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_internal_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_internal_v4(mapped),
+            None => is_internal_v6(ip),
+        },
+    }
+}
+
+fn is_internal_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        // Carrier-grade NAT, 100.64.0.0/10
+        || (a == 100 && (64..128).contains(&b))
+}
+
+fn is_internal_v6(ip: Ipv6Addr) -> bool {
+    ip.is_loopback() || ip.is_unspecified() || ip.is_unique_local() || ip.is_unicast_link_local()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(policy: &HostPolicy, url: &str) -> Result<(), BlameError> {
+        policy.check(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_rejects_internal_addresses() {
+        let policy = HostPolicy::default();
+
+        for url in [
+            "https://localhost/o/r.git",
+            "https://api.localhost/o/r.git",
+            "https://127.0.0.1/o/r.git",
+            "http://169.254.169.254/latest/meta-data",
+            "https://10.0.0.5/o/r.git",
+            "https://192.168.1.1/o/r.git",
+            "https://[::1]/o/r.git",
+            "https://[::ffff:127.0.0.1]/o/r.git",
+        ] {
+            assert!(
+                matches!(check(&policy, url), Err(BlameError::HostNotAllowed(_))),
+                "{} should be rejected",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejects_non_http_schemes() {
+        let policy = HostPolicy::default();
+
+        assert!(check(&policy, "git://github.com/o/r.git").is_err());
+        assert!(check(&policy, "file:///etc/passwd").is_err());
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let policy = HostPolicy::new(
+            vec!["github.com".to_string(), "Git.Example.com".to_string()],
+            vec![],
+        );
+
+        assert!(check(&policy, "https://github.com/o/r").is_ok());
+        assert!(check(&policy, "https://git.example.com/o/r.git").is_ok());
+        assert!(check(&policy, "https://eu.git.example.com/o/r.git").is_ok());
+        assert!(check(&policy, "https://gitlab.com/o/r").is_err());
+        assert!(check(&policy, "https://notgithub.com/o/r").is_err());
+    }
+
+    #[test]
+    fn test_denylist() {
+        let policy = HostPolicy::new(vec![], vec!["gitlab.com".to_string()]);
+
+        assert!(check(&policy, "https://github.com/o/r").is_ok());
+        assert!(check(&policy, "https://gitlab.com/o/r").is_err());
+        assert!(check(&policy, "https://eu.gitlab.com/o/r").is_err());
+    }
+}
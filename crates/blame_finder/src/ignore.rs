@@ -0,0 +1,132 @@
+use log::warn;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::error::BlameError;
+use crate::glob::Glob;
+use crate::todo::TodoItem;
+
+/// Name of the file at the repository root listing TODOs to skip
+pub const TODOIGNORE_FILE: &str = ".todoignore";
+
+/// Parsed `.todoignore` file
+///
+/// Each line is either a `path:line` entry ignoring a single TODO, or a glob
+/// ignoring every TODO in matching files. Blank lines and lines starting with
+/// `#` are skipped.
+#[derive(Debug, Default)]
+pub struct TodoIgnore {
+    globs: Vec<Glob>,
+    lines: HashSet<(String, u32)>,
+}
+
+impl TodoIgnore {
+    pub fn parse(contents: &str) -> Self {
+        let mut ignore = TodoIgnore::default();
+
+        for line in contents.lines() {
+            let entry = line.trim();
+            if entry.is_empty() || entry.starts_with('#') {
+                continue;
+            }
+
+            // `path:line` ignores one specific TODO
+            if let Some((path, line_number)) = entry.rsplit_once(':')
+                && let Ok(line_number) = line_number.trim().parse::<u32>()
+            {
+                ignore.lines.insert((
+                    path.trim().trim_start_matches("./").to_string(),
+                    line_number,
+                ));
+                continue;
+            }
+
+            // A broken pattern in someone else's repo shouldn't fail the scan
+            match Glob::new(entry) {
+                Ok(glob) => ignore.globs.push(glob),
+                Err(e) => warn!("Skipping {} entry {:?}: {}", TODOIGNORE_FILE, entry, e),
+            }
+        }
+
+        ignore
+    }
+
+    /// Load the `.todoignore` at the root of `repo_path`, if there is one
+    pub fn load(repo_path: &Path) -> Result<Self, BlameError> {
+        let path = repo_path.join(TODOIGNORE_FILE);
+        if !path.exists() {
+            return Ok(TodoIgnore::default());
+        }
+
+        let contents = std::fs::read_to_string(&path).map_err(|e| {
+            BlameError::FileError(format!("Failed to read {}: {}", TODOIGNORE_FILE, e))
+        })?;
+
+        Ok(Self::parse(&contents))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.globs.is_empty() && self.lines.is_empty()
+    }
+
+    pub fn is_ignored(&self, todo: &TodoItem) -> bool {
+        let file_path = todo.file_path.trim_start_matches("./");
+        self.lines
+            .contains(&(file_path.to_string(), todo.line_number))
+            || self.globs.iter().any(|glob| glob.is_match(file_path))
+    }
+
+    /// Drop every TODO matched by this ignore file
+    pub fn filter(&self, todos: Vec<TodoItem>) -> Vec<TodoItem> {
+        if self.is_empty() {
+            return todos;
+        }
+
+        todos
+            .into_iter()
+            .filter(|todo| !self.is_ignored(todo))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::todo_at;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_todoignore_format() {
+        let ignore = TodoIgnore::parse(
+            "# intentional, see #42\n\
+             src/main.rs:10\n\
+             \n\
+             vendor/\n\
+             *.min.js\n",
+        );
+
+        assert!(ignore.is_ignored(&todo_at("src/main.rs", 10)));
+        assert!(!ignore.is_ignored(&todo_at("src/main.rs", 11)));
+        assert!(ignore.is_ignored(&todo_at("vendor/lib/a.c", 3)));
+        assert!(ignore.is_ignored(&todo_at("static/app.min.js", 1)));
+        assert!(!ignore.is_ignored(&todo_at("static/app.js", 1)));
+    }
+
+    #[test]
+    fn test_todoignore_removes_matching_todos() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join(TODOIGNORE_FILE), "src/lib.rs:3\n").unwrap();
+
+        let ignore = TodoIgnore::load(dir.path()).unwrap();
+        let todos = ignore.filter(vec![todo_at("src/lib.rs", 3), todo_at("src/lib.rs", 7)]);
+
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].line_number, 7);
+    }
+
+    #[test]
+    fn test_missing_todoignore_is_empty() {
+        let dir = tempdir().unwrap();
+        assert!(TodoIgnore::load(dir.path()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// One semaphore per key in use, e.g. per git host or per clone directory, each
+/// forgotten once nobody holds or waits on it
+pub struct KeyedPermits<K> {
+    semaphores: Mutex<HashMap<K, Arc<Semaphore>>>,
+}
+
+impl<K> Default for KeyedPermits<K> {
+    fn default() -> Self {
+        KeyedPermits {
+            semaphores: Mutex::default(),
+        }
+    }
+}
+
+/// A turn at whatever a [`KeyedPermits`] key guards, released on drop
+pub struct KeyedPermit<K: Eq + Hash + 'static> {
+    permits: &'static KeyedPermits<K>,
+    key: K,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl<K: Eq + Hash + Clone> KeyedPermits<K> {
+    /// Wait until fewer than `limit` turns at `key` are taken, then take one
+    ///
+    /// The limit of whoever found the key idle applies until it's idle again.
+    pub async fn acquire(&'static self, key: K, limit: usize) -> KeyedPermit<K> {
+        let semaphore = self
+            .lock()
+            .entry(key.clone())
+            .or_insert_with(|| Arc::new(Semaphore::new(limit.max(1))))
+            .clone();
+
+        KeyedPermit {
+            permits: self,
+            key,
+            permit: Some(
+                semaphore
+                    .acquire_owned()
+                    .await
+                    .expect("keyed semaphores are never closed"),
+            ),
+        }
+    }
+
+    /// Whether anyone holds or waits on `key`
+    #[cfg(test)]
+    pub fn is_tracked(&self, key: &K) -> bool {
+        self.lock().contains_key(key)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<K, Arc<Semaphore>>> {
+        self.semaphores
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<K: Eq + Hash + 'static> Drop for KeyedPermit<K> {
+    fn drop(&mut self) {
+        let mut semaphores = self
+            .permits
+            .semaphores
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        drop(self.permit.take());
+
+        // Forget the key once nobody holds or waits on it, waiters clone its
+        // semaphore under the map's lock so none can sneak in before the removal
+        if semaphores
+            .get(&self.key)
+            .is_some_and(|semaphore| Arc::strong_count(semaphore) == 1)
+        {
+            semaphores.remove(&self.key);
+        }
+    }
+}
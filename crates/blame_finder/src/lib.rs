@@ -2,58 +2,276 @@ use std::path::Path;
 use std::sync::Arc;
 use std::{collections::HashSet, path::PathBuf, time::SystemTime};
 
+use chrono::{DateTime, Utc};
 use log::debug;
-use tokio::sync::Mutex;
+use serde::Serialize;
+use tokio::sync::{Mutex, mpsc};
+use tokio::task::JoinSet;
 
 pub mod blame;
+mod blame_cache;
+pub mod clock;
 mod error;
+mod generated;
+mod glob;
 mod helpers;
+mod host_limit;
+mod host_policy;
+pub mod ignore;
+mod keyed_permits;
+mod options;
+mod path_lock;
+mod rate_limit;
 mod repo;
+mod scan_result;
+mod submodules;
+mod test_paths;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+pub mod timings;
 pub mod todo;
+mod url_rewrite;
 
 pub use blame::BlameInfo;
+pub use blame_cache::BlameCache;
+pub use clock::{Clock, FixedClock, SystemClock};
 pub use error::BlameError;
-pub use repo::Repository;
+pub use generated::{GITATTRIBUTES_FILE, GeneratedFiles};
+pub use glob::Glob;
+pub use host_limit::DEFAULT_MAX_CLONES_PER_HOST;
+pub use host_policy::HostPolicy;
+pub use options::ScanOptions;
+pub use rate_limit::DEFAULT_RATE_LIMIT_BACKOFF;
+pub use repo::{DEFAULT_CLONE_DEPTH, DEFAULT_CONTEXT_LINES, Repository, RepositoryBuilder};
+pub use scan_result::ScanResult;
+pub use test_paths::{TEST_PATH_PATTERNS, is_test_path};
+pub use timings::ScanTimings;
 pub use todo::TodoItem;
+pub use url_rewrite::UrlRewrite;
+
+/// How many found TODOs may wait for blame during a scan before the search pauses
+const TODO_STREAM_CAPACITY: usize = 256;
 
 /// Main entry point for finding the oldest TODO in a git repository
 /// similar to one found in server/src/todo_entrypoint
-/// minus the app state updates
-pub async fn find_oldest_todo(repo: &Repository) -> Result<Option<TodoItem>, BlameError> {
+/// minus the app state updates. Ages and discovery times are as of `clock`
+pub async fn scan(repo: &Repository, clock: &dyn Clock) -> Result<ScanResult, BlameError> {
+    scan_with_options(repo, &ScanOptions::default(), clock).await
+}
+
+/// Scan for the oldest TODO, restricted by the given scan options
+pub async fn scan_with_options(
+    repo: &Repository,
+    options: &ScanOptions,
+    clock: &dyn Clock,
+) -> Result<ScanResult, BlameError> {
     // Clone or fetch the repository
     debug!("getting repo");
     repo.prepare().await?;
     debug!("done preparing");
 
-    // Find all TODO comments
-    let todos = todo::find_todos(repo).await?;
+    let blamed = if options.quick {
+        // Quick blame deepens the clone for all of them at once, so it needs every TODO up front
+        let todos = todo::find_todos(repo, clock).await?;
+        if todos.is_empty() {
+            return Ok(ScanResult::NoTodos);
+        }
+        blame::quick_blame_todos(repo, todos, clock).await
+    } else {
+        // Blame each TODO as soon as ripgrep finds it, rather than after the whole search
+        let (sender, receiver) = mpsc::channel(TODO_STREAM_CAPACITY);
+        let (found, blamed) = tokio::join!(
+            todo::stream_todos(repo, clock, sender),
+            blame::blame_todo_stream(repo, receiver, clock)
+        );
+        if found? == 0 {
+            return Ok(ScanResult::NoTodos);
+        }
+        blamed
+    };
 
-    if todos.is_empty() {
-        return Ok(None);
-    }
+    let mut todos = match blamed {
+        Ok(todos) => blame::keep_matching(todos, options),
+        Err(BlameError::AllBlameFailed { attempted }) => {
+            return Ok(ScanResult::AllBlameFailed { attempted });
+        }
+        Err(e) => return Err(e),
+    };
+    blame::count_later_commits(repo, &mut todos, options).await;
 
     // Find the oldest TODO by analyzing git blame for each
-    let oldest = blame::find_oldest_todo(repo, todos).await?;
+    let mut oldest = blame::oldest_blamed(repo, todos, options)?;
+    blame::attach_commit_body(repo, &mut oldest, options).await;
+    Ok(ScanResult::Found(oldest))
+}
+
+/// Find every TODO that could be git blamed, oldest first
+pub async fn find_all_todos(
+    repo: &Repository,
+    clock: &dyn Clock,
+) -> Result<Vec<TodoItem>, BlameError> {
+    find_all_todos_with_options(repo, &ScanOptions::default(), clock).await
+}
+
+/// Find every TODO the scan options let through, oldest first
+///
+/// With [`ScanOptions::include_untracked`], TODOs in untracked files follow the
+/// blamed ones by path and line, marked [`untracked`](TodoItem::untracked).
+pub async fn find_all_todos_with_options(
+    repo: &Repository,
+    options: &ScanOptions,
+    clock: &dyn Clock,
+) -> Result<Vec<TodoItem>, BlameError> {
+    repo.prepare().await?;
+
+    let todos = todo::find_todos(repo, clock).await?;
+    let (mut untracked, todos): (Vec<_>, Vec<_>) = if options.include_untracked {
+        let untracked_files = repo.untracked_files().await?;
+        todos
+            .into_iter()
+            .partition(|todo| untracked_files.contains(&todo.file_path))
+    } else {
+        (Vec::new(), todos)
+    };
 
-    Ok(Some(oldest))
+    let mut todos = if todos.is_empty() {
+        Vec::new()
+    } else {
+        blame::blame_matching_todos(repo, todos, options, clock).await?
+    };
+    // Oldest first, ties broken by path and line
+    todos.sort_by(|a, b| b.cmp(a));
+
+    for todo in &mut untracked {
+        todo.untracked = true;
+    }
+    untracked.sort_by(|a, b| (&a.file_path, a.line_number).cmp(&(&b.file_path, b.line_number)));
+    todos.extend(untracked);
+
+    if let Some(budget) = options.context_budget {
+        todo::apply_context_budget(&mut todos, budget);
+    }
+
+    Ok(todos)
+}
+
+/// Find the oldest TODO, returning `None` if the repository has no TODOs
+#[deprecated(note = "use `scan`, which tells apart why no TODO was found")]
+pub async fn find_oldest_todo(
+    repo: &Repository,
+    clock: &dyn Clock,
+) -> Result<Option<TodoItem>, BlameError> {
+    match scan(repo, clock).await? {
+        ScanResult::Found(todo) => Ok(Some(todo)),
+        ScanResult::NoTodos => Ok(None),
+        ScanResult::AllBlameFailed { attempted } => Err(BlameError::AllBlameFailed { attempted }),
+    }
 }
 
-/// Clean up old repositories that haven't been accessed recently
+/// Most repositories [`find_oldest_across`] prepares and scans at once
+pub const DEFAULT_MULTI_REPO_CONCURRENCY: usize = 4;
+
+/// Find the oldest TODO across several repositories, e.g. every repo in an org,
+/// along with the repository it came from
+///
+/// Repositories are prepared and scanned [`DEFAULT_MULTI_REPO_CONCURRENCY`] at a
+/// time. One that fails is reported and skipped, only if every repository fails
+/// is the first error returned. `None` if none of them had a blameable TODO.
+pub async fn find_oldest_across(
+    repos: Vec<Repository>,
+    clock: Arc<dyn Clock>,
+) -> Result<Option<(Repository, TodoItem)>, BlameError> {
+    let total = repos.len();
+    let mut scans = JoinSet::new();
+    let mut oldest: Option<(Repository, TodoItem)> = None;
+    let mut first_error = None;
+    let mut failed = 0;
+
+    let mut record =
+        |result: Result<(Repository, Result<ScanResult, BlameError>), tokio::task::JoinError>| {
+            let (repo, scanned) = match result {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    failed += 1;
+                    first_error.get_or_insert(BlameError::InternalError(format!(
+                        "Repository scan task failed: {}",
+                        e
+                    )));
+                    return;
+                }
+            };
+            match scanned {
+                Ok(ScanResult::Found(todo)) => {
+                    // Older TODOs compare greater
+                    if oldest.as_ref().is_none_or(|(_, current)| todo > *current) {
+                        oldest = Some((repo, todo));
+                    }
+                }
+                Ok(ScanResult::NoTodos | ScanResult::AllBlameFailed { .. }) => {}
+                Err(e) => {
+                    eprintln!("Error scanning {}: {}", repo.url(), e);
+                    failed += 1;
+                    first_error.get_or_insert(e);
+                }
+            }
+        };
+
+    for repo in repos {
+        // Wait for a slot before starting another scan
+        if scans.len() >= DEFAULT_MULTI_REPO_CONCURRENCY
+            && let Some(result) = scans.join_next().await
+        {
+            record(result);
+        }
+        let clock = clock.clone();
+        scans.spawn(async move {
+            let scanned = scan(&repo, clock.as_ref()).await;
+            (repo, scanned)
+        });
+    }
+
+    while let Some(result) = scans.join_next().await {
+        record(result);
+    }
+
+    match first_error {
+        Some(e) if failed == total => Err(e),
+        _ => Ok(oldest),
+    }
+}
+
+/// Default number of repositories deleted in parallel by [`cleanup_old_repos`]
+pub const DEFAULT_CLEANUP_CONCURRENCY: usize = 4;
+
+/// Clean up old repositories that haven't been accessed recently, deleting
+/// at most `max_concurrent` directories at a time
 pub async fn cleanup_old_repos(
     max_age_days: u64,
     active_repos: Option<Arc<Mutex<HashSet<PathBuf>>>>,
+    max_concurrent: usize,
 ) -> Result<usize, BlameError> {
     let repos_dir = Repository::get_repos_dir()?;
+    cleanup_repos_in(&repos_dir, max_age_days, active_repos, max_concurrent).await
+}
+
+async fn cleanup_repos_in(
+    repos_dir: &Path,
+    max_age_days: u64,
+    active_repos: Option<Arc<Mutex<HashSet<PathBuf>>>>,
+    max_concurrent: usize,
+) -> Result<usize, BlameError> {
     let max_age = std::time::Duration::from_secs(max_age_days * 24 * 60 * 60);
     let now = SystemTime::now();
+    let max_concurrent = max_concurrent.max(1);
     let mut deleted_count = 0;
+    let mut deletions = JoinSet::new();
 
-    let entries = match std::fs::read_dir(&repos_dir) {
+    let mut entries = match tokio::fs::read_dir(repos_dir).await {
         Ok(entries) => entries,
         Err(_) => return Ok(0),
     };
 
-    for entry in entries.flatten() {
+    while let Ok(Some(entry)) = entries.next_entry().await {
         let path = entry.path();
 
         // Skip if repository is active
@@ -66,33 +284,397 @@ pub async fn cleanup_old_repos(
         }
 
         // Skip if not a directory
-        if !path.is_dir() {
+        let Ok(metadata) = entry.metadata().await else {
+            continue;
+        };
+        if !metadata.is_dir() {
             continue;
         }
 
         // Delete if older than max_age
-        if should_delete_repo(&path, &now, &max_age) {
-            if let Err(e) = std::fs::remove_dir_all(&path) {
-                eprintln!("Error removing old repository at {}: {}", path.display(), e);
-            } else {
-                deleted_count += 1;
+        if should_delete_repo(&metadata, &now, &max_age) {
+            // Wait for a slot before starting another deletion
+            if deletions.len() >= max_concurrent
+                && let Some(result) = deletions.join_next().await
+            {
+                deleted_count += count_deletion(result);
             }
+            deletions.spawn(async move {
+                let result = tokio::fs::remove_dir_all(&path).await;
+                (path, result)
+            });
         }
     }
 
+    while let Some(result) = deletions.join_next().await {
+        deleted_count += count_deletion(result);
+    }
+
     Ok(deleted_count)
 }
 
+/// A clone kept in the repositories directory, as [`list_cached_repos`] finds it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CachedRepoInfo {
+    pub path: PathBuf,
+
+    /// Bytes taken by every file under the directory, `.git` included
+    pub size_bytes: u64,
+
+    /// When the clone was last prepared, which is what cleanup goes by
+    pub modified: Option<DateTime<Utc>>,
+
+    /// Whether git recognises the directory as a repository of its own, e.g. not a
+    /// clone that failed half way
+    pub is_git_repo: bool,
+}
+
+/// Every clone in the repositories directory, by path, for seeing what the cache
+/// holds and how much room it takes
+pub async fn list_cached_repos() -> Result<Vec<CachedRepoInfo>, BlameError> {
+    let repos_dir = Repository::get_repos_dir()?;
+    list_repos_in(&repos_dir).await
+}
+
+async fn list_repos_in(repos_dir: &Path) -> Result<Vec<CachedRepoInfo>, BlameError> {
+    let mut entries = match tokio::fs::read_dir(repos_dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut repos = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let Ok(metadata) = entry.metadata().await else {
+            continue;
+        };
+        if !metadata.is_dir() {
+            continue;
+        }
+
+        let path = entry.path();
+        let size_path = path.clone();
+        let size_bytes = tokio::task::spawn_blocking(move || dir_size(&size_path))
+            .await
+            .map_err(|e| BlameError::InternalError(format!("Sizing task failed: {}", e)))?;
+        repos.push(CachedRepoInfo {
+            is_git_repo: is_own_git_repo(&path).await,
+            modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+            size_bytes,
+            path,
+        });
+    }
+
+    repos.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(repos)
+}
+
+/// Total size of the files under `path`. Symlinks aren't followed, and anything
+/// that can't be read counts as empty
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+            Ok(file_type) if file_type.is_file() => {
+                entry.metadata().map(|metadata| metadata.len()).unwrap_or(0)
+            }
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Whether `path` is a git repository itself, rather than merely inside one
+async fn is_own_git_repo(path: &Path) -> bool {
+    // Stops git looking for a repository above `path`
+    let ceiling = path.parent().unwrap_or(path);
+    tokio::process::Command::new("git")
+        .current_dir(path)
+        .env("GIT_CEILING_DIRECTORIES", ceiling)
+        .args(["rev-parse", "--git-dir"])
+        .output()
+        .await
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Report the outcome of a single deletion task, returning 1 if the directory was removed
+fn count_deletion(result: Result<(PathBuf, std::io::Result<()>), tokio::task::JoinError>) -> usize {
+    match result {
+        Ok((_, Ok(()))) => 1,
+        Ok((path, Err(e))) => {
+            eprintln!("Error removing old repository at {}: {}", path.display(), e);
+            0
+        }
+        Err(e) => {
+            eprintln!("Repository cleanup task failed: {}", e);
+            0
+        }
+    }
+}
+
 /// Helper function to determine if a repository should be deleted based on its age
-fn should_delete_repo(path: &Path, now: &SystemTime, max_age: &std::time::Duration) -> bool {
-    match std::fs::metadata(path) {
-        Ok(metadata) => match metadata.modified() {
-            Ok(modified) => match now.duration_since(modified) {
-                Ok(age) => age > *max_age,
-                Err(_) => false,
-            },
+fn should_delete_repo(
+    metadata: &std::fs::Metadata,
+    now: &SystemTime,
+    max_age: &std::time::Duration,
+) -> bool {
+    match metadata.modified() {
+        Ok(modified) => match now.duration_since(modified) {
+            Ok(age) => age > *max_age,
             Err(_) => false,
         },
         Err(_) => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{commit_file, commit_file_at, init_repo};
+    use filetime::FileTime;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    fn make_repo_dir(root: &Path, name: &str, age_days: u64) -> PathBuf {
+        let path = root.join(name);
+        std::fs::create_dir_all(path.join("src")).unwrap();
+        std::fs::write(path.join("src").join("main.rs"), "// TODO: clean me up\n").unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(age_days * 24 * 60 * 60);
+        filetime::set_file_mtime(&path, FileTime::from_system_time(modified)).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_removes_old_repos_concurrently() {
+        let dir = tempdir().unwrap();
+        let old: Vec<PathBuf> = (0..6)
+            .map(|i| make_repo_dir(dir.path(), &format!("old-{}", i), 30))
+            .collect();
+        let fresh = make_repo_dir(dir.path(), "fresh", 0);
+        let active = make_repo_dir(dir.path(), "active", 30);
+        let active_repos = Arc::new(Mutex::new(HashSet::from([active.clone()])));
+
+        let deleted = cleanup_repos_in(dir.path(), 7, Some(active_repos), 2)
+            .await
+            .unwrap();
+
+        assert_eq!(deleted, old.len());
+        assert!(old.iter().all(|path| !path.exists()));
+        assert!(fresh.exists());
+        assert!(active.exists());
+    }
+
+    #[tokio::test]
+    async fn test_list_cached_repos_reports_size_and_age() {
+        let dir = tempdir().unwrap();
+        let plain = dir.path().join("half-cloned");
+        std::fs::create_dir_all(plain.join("src")).unwrap();
+        std::fs::write(plain.join("README"), vec![b'x'; 1000]).unwrap();
+        std::fs::write(plain.join("src").join("main.rs"), vec![b'y'; 24]).unwrap();
+        let cloned = init_repo();
+        commit_file(cloned.path(), "lib.rs", "// TODO: cached\n", "Add lib.rs");
+        let clone_path = dir.path().join("cloned");
+        std::fs::rename(cloned.path(), &clone_path).unwrap();
+        // Stray files beside the clones aren't repositories
+        std::fs::write(dir.path().join("notes.txt"), "hi").unwrap();
+
+        let plain_modified = DateTime::parse_from_rfc3339("2024-03-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        filetime::set_file_mtime(&plain, FileTime::from_system_time(plain_modified.into()))
+            .unwrap();
+
+        let repos = list_repos_in(dir.path()).await.unwrap();
+        assert_eq!(repos.len(), 2);
+        let (clone, half) = (&repos[0], &repos[1]);
+
+        assert_eq!(half.path, plain);
+        assert_eq!(half.size_bytes, 1024);
+        assert_eq!(half.modified, Some(plain_modified));
+        assert!(!half.is_git_repo);
+
+        assert_eq!(clone.path, clone_path);
+        assert!(clone.is_git_repo);
+        // The history counts too, not just the checked out file
+        assert!(clone.size_bytes > "// TODO: cached\n".len() as u64);
+        assert!(clone.modified.is_some());
+
+        let missing = list_repos_in(&dir.path().join("missing")).await.unwrap();
+        assert!(missing.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_missing_repos_dir() {
+        let dir = tempdir().unwrap();
+
+        let deleted = cleanup_repos_in(&dir.path().join("missing"), 7, None, 2)
+            .await
+            .unwrap();
+
+        assert_eq!(deleted, 0);
+    }
+
+    #[tokio::test]
+    async fn test_scan_found() {
+        let fixture = init_repo();
+        commit_file(fixture.path(), "lib.rs", "// TODO: find me\n", "Add lib.rs");
+        let repo = Repository::from_local_path(fixture.path()).unwrap();
+
+        let result = scan(&repo, &SystemClock).await.unwrap();
+
+        let todo = result.into_todo().expect("expected a TODO");
+        assert_eq!(todo.file_path, "lib.rs");
+        assert!(todo.blame_info.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_find_oldest_across_picks_the_older_repo() {
+        let newer = init_repo();
+        commit_file_at(
+            newer.path(),
+            "a.rs",
+            "// TODO: newer\n",
+            "Add a.rs",
+            "2022-01-01T12:00:00Z",
+        );
+        let older = init_repo();
+        commit_file_at(
+            older.path(),
+            "b.rs",
+            "// TODO: older\n",
+            "Add b.rs",
+            "2015-01-01T12:00:00Z",
+        );
+        let empty = init_repo();
+        commit_file(empty.path(), "c.rs", "fn main() {}\n", "Add c.rs");
+        let repos = [&newer, &older, &empty]
+            .iter()
+            .map(|dir| Repository::from_local_path(dir.path()).unwrap())
+            .collect();
+
+        let (repo, todo) = find_oldest_across(repos, Arc::new(SystemClock))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(repo.path(), older.path().canonicalize().unwrap());
+        assert_eq!(todo.file_path, "b.rs");
+    }
+
+    #[tokio::test]
+    async fn test_find_oldest_across_skips_failed_repos() {
+        let good = init_repo();
+        commit_file(good.path(), "a.rs", "// TODO: found\n", "Add a.rs");
+        let broken = init_repo();
+        commit_file(broken.path(), "b.rs", "// TODO: lost\n", "Add b.rs");
+        let good_repo = Repository::from_local_path(good.path()).unwrap();
+        let broken_repo = Repository::from_local_path(broken.path()).unwrap();
+        std::fs::remove_dir_all(broken.path().join(".git")).unwrap();
+
+        let (repo, _) =
+            find_oldest_across(vec![broken_repo.clone(), good_repo], Arc::new(SystemClock))
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(repo.path(), good.path().canonicalize().unwrap());
+
+        // Nothing to fall back on when every repository fails
+        assert!(matches!(
+            find_oldest_across(vec![broken_repo], Arc::new(SystemClock)).await,
+            Err(BlameError::NotAGitRepository(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_scan_no_todos() {
+        let fixture = init_repo();
+        commit_file(fixture.path(), "lib.rs", "fn main() {}\n", "Add lib.rs");
+        let repo = Repository::from_local_path(fixture.path()).unwrap();
+
+        let result = scan(&repo, &SystemClock).await.unwrap();
+
+        assert!(matches!(result, ScanResult::NoTodos));
+    }
+
+    #[tokio::test]
+    async fn test_scan_all_blame_failed() {
+        // TODOs that were never committed have no history to blame
+        let fixture = init_repo();
+        commit_file(fixture.path(), "lib.rs", "fn main() {}\n", "Add lib.rs");
+        std::fs::write(fixture.path().join("a.rs"), "// TODO: untracked\n").unwrap();
+        std::fs::write(fixture.path().join("b.rs"), "// TODO: untracked\n").unwrap();
+        let repo = Repository::from_local_path(fixture.path()).unwrap();
+
+        let result = scan(&repo, &SystemClock).await.unwrap();
+
+        assert!(matches!(
+            result,
+            ScanResult::AllBlameFailed { attempted: 2 }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_find_all_todos_oldest_first() {
+        let fixture = init_repo();
+        commit_file_at(
+            fixture.path(),
+            "new.rs",
+            "// TODO: new\n",
+            "Add new",
+            "2022-01-01T00:00:00Z",
+        );
+        commit_file_at(
+            fixture.path(),
+            "old.rs",
+            "// TODO: old\n",
+            "Add old",
+            "2015-01-01T00:00:00Z",
+        );
+        let repo = Repository::from_local_path(fixture.path()).unwrap();
+
+        let todos = find_all_todos(&repo, &SystemClock).await.unwrap();
+
+        let files: Vec<&str> = todos.iter().map(|t| t.file_path.as_str()).collect();
+        assert_eq!(files, vec!["old.rs", "new.rs"]);
+    }
+
+    #[tokio::test]
+    async fn test_untracked_todos_listed_but_never_oldest() {
+        let fixture = init_repo();
+        commit_file_at(
+            fixture.path(),
+            "lib.rs",
+            "// TODO: tracked\n",
+            "Add lib.rs",
+            "2015-01-01T00:00:00Z",
+        );
+        std::fs::write(fixture.path().join("draft.rs"), "// TODO: untracked\n").unwrap();
+        let repo = Repository::from_local_path(fixture.path()).unwrap();
+        let options = ScanOptions {
+            include_untracked: true,
+            ..ScanOptions::default()
+        };
+
+        let todos = find_all_todos_with_options(&repo, &options, &SystemClock)
+            .await
+            .unwrap();
+        assert_eq!(todos.len(), 2);
+        assert_eq!(todos[0].file_path, "lib.rs");
+        assert!(!todos[0].untracked);
+        assert_eq!(todos[1].file_path, "draft.rs");
+        assert!(todos[1].untracked);
+        assert!(todos[1].blame_info.is_none());
+
+        // Left out by default
+        let todos = find_all_todos(&repo, &SystemClock).await.unwrap();
+        assert_eq!(todos.len(), 1);
+
+        let oldest = scan_with_options(&repo, &options, &SystemClock)
+            .await
+            .unwrap();
+        assert_eq!(oldest.into_todo().unwrap().file_path, "lib.rs");
+    }
+}
@@ -0,0 +1,171 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use std::cmp::Ordering;
+
+use crate::blame::BlameInfo;
+use crate::test_paths::is_test_path;
+use crate::todo::TodoItem;
+
+/// Options controlling which TODOs are considered when picking the oldest
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Only consider TODOs blamed on or after this date
+    pub since: Option<DateTime<Utc>>,
+
+    /// Only consider TODOs blamed on or before this date
+    pub until: Option<DateTime<Utc>>,
+
+    /// Date each TODO by the oldest commit in its line's `git log -L` history
+    /// instead of blaming every file, deepening a shallow clone only as far as
+    /// the TODOs need. Much faster on huge repos, but approximate.
+    /// Pair with [`RepositoryBuilder::quick`](crate::RepositoryBuilder::quick)
+    pub quick: bool,
+
+    /// Authors never credited with the oldest TODO, e.g. bots. Each pattern is
+    /// matched case-insensitively against the author's name and email, and may
+    /// use `*` as a wildcard: `*[bot]` skips `dependabot[bot]`
+    pub exclude_authors: Vec<String>,
+
+    /// List TODOs in files git doesn't track yet, unblamed and marked
+    /// [`untracked`](crate::TodoItem::untracked), after every blamed TODO. Only
+    /// [`find_all_todos_with_options`](crate::find_all_todos_with_options) lists them,
+    /// as having no age they can never be the oldest
+    pub include_untracked: bool,
+
+    /// Most bytes of context, summed over every TODO, that
+    /// [`find_all_todos_with_options`](crate::find_all_todos_with_options) returns.
+    /// The oldest TODOs keep theirs; see [`apply_context_budget`](crate::todo::apply_context_budget)
+    pub context_budget: Option<usize>,
+
+    /// Also read the oldest TODO's commit message body into
+    /// [`BlameInfo::body`](crate::BlameInfo::body), cut to at most this many characters
+    pub commit_body_limit: Option<usize>,
+
+    /// When picking the oldest TODO, treat those in test code (see
+    /// [`TEST_PATH_PATTERNS`](crate::TEST_PATH_PATTERNS)) as this many days younger
+    /// than they are, so a truly ancient one can still win but test code doesn't
+    /// trivially beat production code. 0 ranks them like any other
+    pub test_file_penalty_days: u32,
+
+    /// Pass over TODOs in generated files when picking the oldest, as blame credits
+    /// them to whichever commit last regenerated them. A file is generated when the
+    /// repo's `.gitattributes` marks it `linguist-generated` or it matches one of
+    /// `generated_globs`. Its TODOs are still counted and listed
+    pub exclude_generated: bool,
+
+    /// Extra globs, e.g. `*.pb.go`, counting as generated with `exclude_generated`
+    pub generated_globs: Vec<String>,
+
+    /// Rank by neglect rather than age alone: when picking the oldest, each commit
+    /// to a TODO's file since the TODO was added counts as this many extra days of
+    /// age, as one in a file everyone edits around has been passed over more often.
+    /// Costs a `git rev-list` per TODO, see
+    /// [`BlameInfo::later_commits`](crate::BlameInfo::later_commits). 0 ranks by age alone
+    pub neglect_days_per_commit: u32,
+}
+
+impl ScanOptions {
+    /// Whether a blame date falls inside the `since`/`until` window
+    pub fn in_date_range(&self, date: &DateTime<Utc>) -> bool {
+        self.since.is_none_or(|since| *date >= since)
+            && self.until.is_none_or(|until| *date <= until)
+    }
+
+    pub fn has_date_range(&self) -> bool {
+        self.since.is_some() || self.until.is_some()
+    }
+
+    /// The date `todo` competes with when picking the oldest, its commit date moved
+    /// later by `test_file_penalty_days` if it's in test code, and earlier by
+    /// `neglect_days_per_commit` for each later commit to its file
+    pub fn ranking_date(&self, todo: &TodoItem) -> DateTime<Utc> {
+        let date = todo.commit_date();
+        let mut days = i64::from(self.neglect_days_per_commit)
+            * i64::from(
+                todo.blame_info
+                    .as_ref()
+                    .and_then(|blame_info| blame_info.later_commits)
+                    .unwrap_or_default(),
+            );
+        if self.test_file_penalty_days > 0 && is_test_path(&todo.file_path) {
+            days -= i64::from(self.test_file_penalty_days);
+        }
+        TimeDelta::try_days(days)
+            .and_then(|delta| date.checked_sub_signed(delta))
+            .unwrap_or(date)
+    }
+
+    /// Whether ranking weighs later commits to a TODO's file, so the scan has to
+    /// count them after blaming
+    pub fn ranks_by_neglect(&self) -> bool {
+        self.neglect_days_per_commit > 0
+    }
+
+    /// Rank two TODOs for the oldest, `Greater` being the older after any test file
+    /// penalty or neglect. Ties fall back to `TodoItem`'s `Ord`, i.e. path and line
+    pub fn rank_oldest(&self, a: &TodoItem, b: &TodoItem) -> Ordering {
+        self.ranking_date(b)
+            .cmp(&self.ranking_date(a))
+            .then_with(|| a.cmp(b))
+    }
+
+    /// Whether a blamed author matches one of the `exclude_authors` patterns
+    pub fn excludes_author(&self, blame_info: &BlameInfo) -> bool {
+        self.exclude_authors.iter().any(|pattern| {
+            wildcard_match(pattern, &blame_info.author)
+                || wildcard_match(pattern, &blame_info.author_email)
+        })
+    }
+}
+
+/// Case-insensitive match where `*` stands for any run of characters and
+/// everything else, brackets included, is literal
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.trim().to_lowercase();
+    let text = text.to_lowercase();
+    if pattern.is_empty() {
+        return false;
+    }
+
+    let mut parts = pattern.split('*');
+    // `split` always yields at least one part
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No `*` at all, so the whole text must match
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*[bot]", "dependabot[bot]"));
+        assert!(wildcard_match("*[bot]", "GitHub-Actions[bot]"));
+        assert!(!wildcard_match("*[bot]", "robot"));
+        assert!(wildcard_match("renovate*", "renovate-bot"));
+        assert!(wildcard_match(
+            "*@users.noreply.github.com",
+            "1+x@users.noreply.github.com"
+        ));
+        assert!(wildcard_match("a*b*c", "abc"));
+        assert!(!wildcard_match("a*b*c", "acb"));
+        assert!(!wildcard_match("ab*ba", "aba"));
+        assert!(wildcard_match("Alice", "alice"));
+        assert!(!wildcard_match("Alice", "alice2"));
+        assert!(!wildcard_match("", "alice"));
+    }
+}
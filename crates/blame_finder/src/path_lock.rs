@@ -0,0 +1,53 @@
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::keyed_permits::{KeyedPermit, KeyedPermits};
+
+/// One lock per clone directory that's being worked on, shared by every
+/// `Repository` in the process
+static PATH_LOCKS: LazyLock<KeyedPermits<PathBuf>> = LazyLock::new(KeyedPermits::default);
+
+/// Exclusive use of a clone directory, released on drop
+///
+/// Two scans of the same URL share a directory, so without this their clones or
+/// fetches would interleave and corrupt the working tree.
+pub type PathGuard = KeyedPermit<PathBuf>;
+
+/// Wait until nothing else holds `path`, then hold it
+pub async fn lock_path(path: &Path) -> PathGuard {
+    PATH_LOCKS.acquire(path.to_path_buf(), 1).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn is_tracked(path: &Path) -> bool {
+        PATH_LOCKS.is_tracked(&path.to_path_buf())
+    }
+
+    #[tokio::test]
+    async fn test_same_path_waits_and_other_paths_do_not() {
+        let path = PathBuf::from("/tmp/path-lock-test/a");
+        let other = PathBuf::from("/tmp/path-lock-test/b");
+
+        let held = lock_path(&path).await;
+        let waiting = tokio::spawn({
+            let path = path.clone();
+            async move { lock_path(&path).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        // A different directory isn't held up
+        drop(lock_path(&other).await);
+        assert!(!is_tracked(&other));
+
+        drop(held);
+        let second = waiting.await.unwrap();
+        assert!(is_tracked(&path));
+        drop(second);
+        assert!(!is_tracked(&path));
+    }
+}
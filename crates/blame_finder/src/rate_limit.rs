@@ -0,0 +1,175 @@
+use log::warn;
+use std::time::Duration;
+
+use crate::error::BlameError;
+
+/// How long to wait before retrying a rate limited clone or fetch, when the
+/// host doesn't say
+pub const DEFAULT_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Longest we'll wait on a rate limit, however long the host asks for
+const MAX_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Phrases git relays from a host that is rate limiting us
+const RATE_LIMIT_MARKERS: &[&str] = &["rate limit", "too many requests", "returned error: 429"];
+
+/// Phrases that mean the credentials were wrong, which waiting won't fix
+const AUTH_FAILURE_MARKERS: &[&str] = &[
+    "authentication failed",
+    "could not read username",
+    "invalid username or password",
+    "returned error: 401",
+];
+
+/// How long to back off if `stderr` says the host rate limited us, or `None`
+/// for any other failure. A `retry after N` hint from the host wins over `default`
+pub fn rate_limit_backoff(stderr: &str, default: Duration) -> Option<Duration> {
+    let stderr = stderr.to_lowercase();
+    let rate_limited = RATE_LIMIT_MARKERS
+        .iter()
+        .any(|marker| stderr.contains(marker));
+    let auth_failed = AUTH_FAILURE_MARKERS
+        .iter()
+        .any(|marker| stderr.contains(marker));
+    if !rate_limited || auth_failed {
+        return None;
+    }
+
+    let backoff = retry_after_hint(&stderr).unwrap_or(default);
+    Some(backoff.min(MAX_RATE_LIMIT_BACKOFF))
+}
+
+/// Seconds from a `Retry-After: 60` or `retry after 60 seconds` style hint
+fn retry_after_hint(stderr: &str) -> Option<Duration> {
+    ["retry-after", "retry after"].iter().find_map(|hint| {
+        let (_, rest) = stderr.split_once(hint)?;
+        let digits: String = rest
+            .trim_start_matches([':', ' '])
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .collect();
+        digits.parse().ok().map(Duration::from_secs)
+    })
+}
+
+/// Run a git operation, retrying it once after backing off if the host rate limited it
+///
+/// `on_rate_limit` hears how long we're about to wait, so callers can tell the user.
+pub(crate) async fn retry_if_rate_limited<T, F, Fut>(
+    default_backoff: Duration,
+    on_rate_limit: &(dyn Fn(Duration) + Send + Sync),
+    mut attempt: F,
+) -> Result<T, BlameError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, BlameError>>,
+{
+    match attempt().await {
+        Err(BlameError::GitError(message)) => {
+            let Some(backoff) = rate_limit_backoff(&message, default_backoff) else {
+                return Err(BlameError::GitError(message));
+            };
+            warn!(
+                "Rate limited by the git host, retrying in {}s: {}",
+                backoff.as_secs(),
+                message.trim()
+            );
+            on_rate_limit(backoff);
+            tokio::time::sleep(backoff).await;
+            attempt().await
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const DEFAULT: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn test_rate_limit_backoff() {
+        assert_eq!(
+            rate_limit_backoff(
+                "error: RPC failed; HTTP 429 curl 22 The requested URL returned error: 429",
+                DEFAULT
+            ),
+            Some(DEFAULT)
+        );
+        assert_eq!(
+            rate_limit_backoff("remote: API rate limit exceeded. Retry-After: 42", DEFAULT),
+            Some(Duration::from_secs(42))
+        );
+        assert_eq!(
+            rate_limit_backoff(
+                "remote: Too many requests, retry after 100000 seconds",
+                DEFAULT
+            ),
+            Some(MAX_RATE_LIMIT_BACKOFF)
+        );
+        // Bad credentials won't get better by waiting
+        assert_eq!(
+            rate_limit_backoff(
+                "fatal: Authentication failed for 'https://github.com/o/r'",
+                DEFAULT
+            ),
+            None
+        );
+        assert_eq!(
+            rate_limit_backoff(
+                "fatal: Remote branch main not found in upstream origin",
+                DEFAULT
+            ),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retries_once_after_rate_limit() {
+        let calls = AtomicUsize::new(0);
+        let waits = Mutex::new(Vec::new());
+        let backoff = Duration::from_millis(10);
+
+        // Rate limited on the first call, fine on the retry
+        let result = retry_if_rate_limited(
+            backoff,
+            &|wait| waits.lock().unwrap().push(wait),
+            || async {
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(BlameError::GitError(
+                        "The requested URL returned error: 429".to_string(),
+                    )),
+                    _ => Ok("cloned"),
+                }
+            },
+        )
+        .await;
+
+        assert_eq!(result.unwrap(), "cloned");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(*waits.lock().unwrap(), vec![backoff]);
+    }
+
+    #[tokio::test]
+    async fn test_other_failures_are_not_retried() {
+        let calls = AtomicUsize::new(0);
+
+        let result: Result<(), _> = retry_if_rate_limited(
+            Duration::from_millis(10),
+            &|_| panic!("not rate limited"),
+            || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(BlameError::GitError(
+                    "fatal: Authentication failed".to_string(),
+                ))
+            },
+        )
+        .await;
+
+        assert!(matches!(result, Err(BlameError::GitError(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,11 +1,22 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
 use log::debug;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tokio::process::Command;
 use url::Url;
 
+use crate::blame::count_commits;
 use crate::error::BlameError;
+use crate::glob::Glob;
+use crate::host_limit::{DEFAULT_MAX_CLONES_PER_HOST, HostPermit, acquire_host};
+use crate::host_policy::HostPolicy;
+use crate::path_lock::lock_path;
+use crate::rate_limit::{DEFAULT_RATE_LIMIT_BACKOFF, retry_if_rate_limited};
+use crate::url_rewrite::UrlRewrite;
 
 /// Repository represents a Git repository that has been cloned locally
 #[derive(Debug, Hash, Eq, PartialEq, Clone, Deserialize, Serialize)]
@@ -13,37 +24,668 @@ pub struct Repository {
     /// URL of the remote repository
     url: String,
 
+    /// Where the clone and fetch actually go when a [`UrlRewrite`] changed `url`
+    #[serde(default)]
+    clone_url: Option<String>,
+
     /// Local path where the repository is cloned
     path: PathBuf,
 
+    /// Directory holding managed clones, `None` for local checkouts we don't own
+    repos_dir: Option<PathBuf>,
+
     /// Name of the repository (extracted from URL)
     name: String,
+
+    /// How many extra commits to fetch after the initial shallow clone.
+    /// `None` skips the deepen step entirely and relies on the clone depth.
+    deepen_depth: Option<u32>,
+
+    /// Most commits a fresh shallow clone may hold and still be deepened
+    history_limit: Option<u32>,
+
+    /// Whether this is an existing local checkout rather than a managed clone
+    local: bool,
+
+    /// Access token for private repositories, never serialized
+    #[serde(skip)]
+    token: Option<AccessToken>,
+
+    /// Branch to clone, falls back to main then master when not set
+    branch: Option<String>,
+
+    /// GitHub pull request whose head is scanned instead of a branch
+    #[serde(default)]
+    pull_request: Option<u32>,
+
+    /// Depth of the initial shallow clone
+    clone_depth: u32,
+
+    /// Only scan this directory (relative to the repository root)
+    subpath: Option<String>,
+
+    /// Only consider TODOs in files matching this glob, checked when the repository is built
+    path_filter: Option<String>,
+
+    /// Regex ripgrep searches for instead of the TODO keywords, checked when it's set
+    custom_pattern: Option<String>,
+
+    /// Only search files with these extensions, without the dot. Empty searches every file
+    extensions: Vec<String>,
+
+    /// Clone once and never update, so repeated scans see the same tree
+    freeze_after_clone: bool,
+
+    /// Directory to remember blame results in across scans, `None` disables the cache
+    blame_cache_dir: Option<PathBuf>,
+
+    /// How long to wait before retrying a rate limited clone or fetch, unless the host says
+    rate_limit_backoff: Duration,
+
+    /// Most clones or fetches from the clone URL's host running at once, across all repositories
+    max_clones_per_host: usize,
+
+    /// HTTP user-agent sent to the git host, git's own when not set
+    user_agent: Option<String>,
+
+    /// Name and email git runs as, so nothing falls back to the machine's identity
+    git_identity: Option<(String, String)>,
+
+    /// Lines of code shown above each TODO
+    context_before: u32,
+
+    /// Lines of code shown below each TODO
+    context_after: u32,
+}
+
+/// Default number of commits fetched by the post-clone deepen step
+pub const DEFAULT_DEEPEN_DEPTH: u32 = 10000;
+
+/// Default depth of the initial shallow clone
+pub const DEFAULT_CLONE_DEPTH: u32 = 1000;
+
+/// File in a clone's `.git` saying it was left shallow, see [`Repository::skipped_deepen`]
+const SKIPPED_DEEPEN_MARKER: &str = "oldest-todo-finder-skipped-deepen";
+
+/// Default lines of context shown on each side of a TODO
+pub const DEFAULT_CONTEXT_LINES: u32 = 2;
+
+/// Succeeds only inside a git repository
+const GIT_DIR_ARGS: [&str; 2] = ["rev-parse", "--git-dir"];
+
+/// Token used to authenticate clones, kept out of Debug output
+#[derive(Clone, Hash, Eq, PartialEq)]
+struct AccessToken(String);
+
+impl std::fmt::Debug for AccessToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AccessToken(***)")
+    }
+}
+
+/// Builder for a [`Repository`] with non-default clone/scan options
+#[derive(Debug, Clone)]
+pub struct RepositoryBuilder {
+    url: String,
+    token: Option<String>,
+    branch: Option<String>,
+    pull_request: Option<u32>,
+    depth: u32,
+    deepen: Option<u32>,
+    history_limit: Option<u32>,
+    subpath: Option<String>,
+    path_filter: Option<String>,
+    custom_pattern: Option<String>,
+    extensions: Vec<String>,
+    repos_dir: Option<PathBuf>,
+    freeze_after_clone: bool,
+    host_policy: HostPolicy,
+    url_rewrite: Option<UrlRewrite>,
+    blame_cache_dir: Option<PathBuf>,
+    quick: bool,
+    rate_limit_backoff: Duration,
+    max_clones_per_host: usize,
+    user_agent: Option<String>,
+    git_identity: Option<(String, String)>,
+    context_before: u32,
+    context_after: u32,
+}
+
+impl RepositoryBuilder {
+    pub fn new(repo_url: &str) -> Self {
+        RepositoryBuilder {
+            url: repo_url.to_string(),
+            token: None,
+            branch: None,
+            pull_request: None,
+            depth: DEFAULT_CLONE_DEPTH,
+            deepen: Some(DEFAULT_DEEPEN_DEPTH),
+            history_limit: None,
+            subpath: None,
+            path_filter: None,
+            custom_pattern: None,
+            extensions: Vec::new(),
+            repos_dir: None,
+            freeze_after_clone: false,
+            host_policy: HostPolicy::default(),
+            url_rewrite: None,
+            blame_cache_dir: None,
+            quick: false,
+            rate_limit_backoff: DEFAULT_RATE_LIMIT_BACKOFF,
+            max_clones_per_host: DEFAULT_MAX_CLONES_PER_HOST,
+            user_agent: None,
+            git_identity: None,
+            context_before: DEFAULT_CONTEXT_LINES,
+            context_after: DEFAULT_CONTEXT_LINES,
+        }
+    }
+
+    /// Access token for cloning private repositories
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Clone this branch instead of trying main then master
+    pub fn branch(mut self, branch: impl Into<String>) -> Self {
+        self.branch = Some(branch.into());
+        self
+    }
+
+    /// Scan the head of this GitHub pull request instead of a branch. A
+    /// `https://github.com/owner/repo/pull/123` URL picks its pull request by itself
+    pub fn pull_request(mut self, number: u32) -> Self {
+        self.pull_request = Some(number);
+        self
+    }
+
+    /// Depth of the initial shallow clone
+    pub fn depth(mut self, depth: u32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Extra history to fetch after cloning, `None` skips the deepen step
+    pub fn deepen(mut self, deepen: Option<u32>) -> Self {
+        self.deepen = deepen;
+        self
+    }
+
+    /// Skip the deepen step when the fresh shallow clone already holds more than
+    /// `limit` commits, as blaming through that much history is slow. A shallow
+    /// clone can't count past its [`depth`](Self::depth), so keep `limit` below it.
+    /// See [`Repository::skipped_deepen`]
+    pub fn history_limit(mut self, limit: u32) -> Self {
+        self.history_limit = Some(limit);
+        self
+    }
+
+    /// Only scan this directory, relative to the repository root
+    pub fn subpath(mut self, subpath: impl Into<String>) -> Self {
+        self.subpath = Some(subpath.into());
+        self
+    }
+
+    /// Only consider TODOs in files matching this [`Glob`], e.g. `src/parser/**`.
+    /// Unlike `subpath` it can pick out files across directories
+    pub fn path_filter(mut self, glob: impl Into<String>) -> Self {
+        self.path_filter = Some(glob.into());
+        self
+    }
+
+    /// Search for this regex instead of the TODO keywords, e.g. `TODO\(@me\)` or a
+    /// ticket number pattern. Uses ripgrep's regex syntax, and is checked by `build`
+    pub fn custom_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.custom_pattern = Some(pattern.into());
+        self
+    }
+
+    /// Search for any of these words instead of the TODO keywords, e.g. `FIXME`. They're
+    /// matched literally, use [`custom_pattern`](Self::custom_pattern) for a regex
+    pub fn keywords<S: AsRef<str>>(mut self, keywords: impl IntoIterator<Item = S>) -> Self {
+        let escaped: Vec<_> = keywords
+            .into_iter()
+            .map(|keyword| regex::escape(keyword.as_ref()))
+            .collect();
+        self.custom_pattern = Some(escaped.join("|"));
+        self
+    }
+
+    /// Only search files with these extensions, e.g. `rs` or `.py`, ignoring case
+    pub fn extensions<S: Into<String>>(mut self, extensions: impl IntoIterator<Item = S>) -> Self {
+        self.extensions = extensions.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Clone into this directory instead of the default repos dir
+    pub fn repos_dir(mut self, repos_dir: impl Into<PathBuf>) -> Self {
+        self.repos_dir = Some(repos_dir.into());
+        self
+    }
+
+    /// Never update an existing clone, pinning its working tree (e.g. for benchmarking)
+    pub fn freeze_after_clone(mut self, freeze: bool) -> Self {
+        self.freeze_after_clone = freeze;
+        self
+    }
+
+    /// Restrict which hosts may be cloned from
+    pub fn host_policy(mut self, host_policy: HostPolicy) -> Self {
+        self.host_policy = host_policy;
+        self
+    }
+
+    /// Clone through this rewrite, e.g. an internal mirror, while permalinks keep
+    /// the original URL. The host policy is checked against the original URL
+    pub fn url_rewrite(mut self, url_rewrite: UrlRewrite) -> Self {
+        self.url_rewrite = Some(url_rewrite);
+        self
+    }
+
+    /// Shallowest possible clone for quick scans: depth 1 and no deepen step.
+    /// Use with [`ScanOptions::quick`](crate::ScanOptions::quick), which fetches more
+    /// history only when a TODO turns out older than the clone
+    pub fn quick(mut self) -> Self {
+        self.depth = 1;
+        self.deepen = None;
+        self.quick = true;
+        self
+    }
+
+    /// Remember blame results in this directory, so rescans at the same HEAD skip `git blame`
+    pub fn blame_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.blame_cache_dir = Some(dir.into());
+        self
+    }
+
+    /// How long to wait before retrying a clone or fetch the host rate limited,
+    /// when the host gives no retry hint of its own
+    pub fn rate_limit_backoff(mut self, backoff: Duration) -> Self {
+        self.rate_limit_backoff = backoff;
+        self
+    }
+
+    /// How many clones or fetches may run against the clone URL's host at once, counting
+    /// every repository in the process. Separate from any limit on whole scans
+    pub fn max_clones_per_host(mut self, limit: usize) -> Self {
+        self.max_clones_per_host = limit;
+        self
+    }
+
+    /// HTTP user-agent for clones and fetches, so hosts can tell who's crawling them
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Name and email git runs as while cloning and fetching
+    pub fn git_identity(mut self, name: impl Into<String>, email: impl Into<String>) -> Self {
+        self.git_identity = Some((name.into(), email.into()));
+        self
+    }
+
+    /// Lines of code to keep above each TODO, 0 starts the context at the TODO itself
+    pub fn context_before(mut self, lines: u32) -> Self {
+        self.context_before = lines;
+        self
+    }
+
+    /// Lines of code to keep below each TODO, 0 ends the context at the TODO itself
+    pub fn context_after(mut self, lines: u32) -> Self {
+        self.context_after = lines;
+        self
+    }
+
+    /// Validate the options and create the repository
+    pub async fn build(self) -> Result<Repository, BlameError> {
+        let (repo_url, url_pull_request) = split_pull_request_url(&self.url);
+        let url = Repository::validate_url(&repo_url, &self.host_policy)?;
+        let pull_request = match (self.pull_request, url_pull_request) {
+            (Some(chosen), Some(linked)) if chosen != linked => {
+                return Err(BlameError::InvalidUrl(format!(
+                    "Asked for pull request #{} of a link to #{}",
+                    chosen, linked
+                )));
+            }
+            (chosen, linked) => chosen.or(linked),
+        };
+        if pull_request.is_some() && self.branch.is_some() {
+            return Err(BlameError::InvalidUrl(
+                "Scan either a branch or a pull request, not both".to_string(),
+            ));
+        }
+
+        let name = Repository::extract_repo_name(&url)?;
+
+        let clone_url = self
+            .url_rewrite
+            .map(|rewrite| rewrite.apply(&url))
+            .filter(|clone_url| *clone_url != url);
+        if let Some(clone_url) = &clone_url {
+            debug!("Cloning {} from {}", url, clone_url);
+        }
+
+        if self.depth == 0 {
+            return Err(BlameError::InvalidOption(
+                "Clone depth must be at least 1".to_string(),
+            ));
+        }
+
+        let subpath = self
+            .subpath
+            .map(|subpath| validate_subpath(&subpath))
+            .transpose()?;
+
+        if let Some(path_filter) = &self.path_filter {
+            Glob::new(path_filter)?;
+        }
+        if let Some(pattern) = &self.custom_pattern {
+            validate_search_pattern(pattern)?;
+        }
+        let extensions = self
+            .extensions
+            .iter()
+            .map(|extension| validate_extension(extension))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(branch) = &self.branch {
+            validate_branch(branch)?;
+        }
+
+        let repos_dir = match self.repos_dir {
+            Some(repos_dir) => {
+                ensure_dir(&repos_dir)?;
+                repos_dir
+            }
+            None => Repository::get_repos_dir()?,
+        };
+
+        // Different branches get their own clone, as clones are single-branch.
+        // Quick clones have no history, so they never share a full clone's directory
+        let mut path_key = match (&self.branch, pull_request) {
+            (Some(branch), _) => format!("{}@{}", name, branch),
+            (None, Some(number)) => format!("{}@pull/{}", name, number),
+            (None, None) => name.clone(),
+        };
+        if self.quick {
+            path_key.push_str("@quick");
+        }
+        let path = Repository::create_repo_path(&repos_dir, &name, &path_key);
+
+        Ok(Repository {
+            url,
+            clone_url,
+            path,
+            repos_dir: Some(repos_dir),
+            name,
+            deepen_depth: self.deepen,
+            history_limit: self.history_limit,
+            local: false,
+            token: self.token.map(AccessToken),
+            branch: self.branch,
+            pull_request,
+            clone_depth: self.depth,
+            subpath,
+            path_filter: self.path_filter,
+            custom_pattern: self.custom_pattern,
+            extensions,
+            freeze_after_clone: self.freeze_after_clone,
+            blame_cache_dir: self.blame_cache_dir,
+            rate_limit_backoff: self.rate_limit_backoff,
+            max_clones_per_host: self.max_clones_per_host,
+            user_agent: self.user_agent,
+            git_identity: self.git_identity,
+            context_before: self.context_before,
+            context_after: self.context_after,
+        })
+    }
+}
+
+/// Split a GitHub pull request link such as `https://github.com/o/r/pull/123/files`
+/// into the repository's URL and the pull request's number. Other URLs come back whole
+fn split_pull_request_url(input: &str) -> (String, Option<u32>) {
+    let input = input.trim();
+    let Ok(url) = Url::parse(input) else {
+        return (input.to_string(), None);
+    };
+    if !matches!(url.host_str(), Some("github.com" | "www.github.com")) {
+        return (input.to_string(), None);
+    }
+
+    let segments: Vec<&str> = url.path().trim_start_matches('/').split('/').collect();
+    match segments.as_slice() {
+        [owner, repo, "pull", number, ..] => match number.parse() {
+            Ok(number) => (
+                format!("{}://{}/{}/{}", url.scheme(), "github.com", owner, repo),
+                Some(number),
+            ),
+            Err(_) => (input.to_string(), None),
+        },
+        _ => (input.to_string(), None),
+    }
+}
+
+/// Branch names from `git ls-remote --heads` output, one `<sha>\trefs/heads/<name>` per line
+fn parse_remote_heads(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .filter_map(|(_, reference)| reference.trim().strip_prefix("refs/heads/"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Make sure a custom search pattern is a regex ripgrep will accept, so a typo is
+/// reported up front rather than as a failed search
+fn validate_search_pattern(pattern: &str) -> Result<(), BlameError> {
+    if pattern.is_empty() {
+        return Err(BlameError::InvalidPattern(
+            "Empty search pattern".to_string(),
+        ));
+    }
+
+    regex::Regex::new(pattern).map_err(|e| {
+        BlameError::InvalidPattern(format!("Invalid search regex {:?}: {}", pattern, e))
+    })?;
+    Ok(())
+}
+
+/// A file extension without its leading dot, made only of characters that can't
+/// turn it into a wider glob
+fn validate_extension(extension: &str) -> Result<String, BlameError> {
+    let trimmed = extension.trim().trim_start_matches('.');
+    let plain = trimmed
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+'));
+
+    if trimmed.is_empty() || !plain {
+        return Err(BlameError::InvalidPattern(format!(
+            "Invalid file extension: {:?}",
+            extension
+        )));
+    }
+
+    Ok(trimmed.to_string())
+}
+
+/// Reject branch names git would refuse, or read as an option
+fn validate_branch(branch: &str) -> Result<(), BlameError> {
+    let invalid = branch.is_empty()
+        || branch.starts_with(['-', '/'])
+        || branch.ends_with(['/', '.'])
+        || branch.contains("..")
+        || branch.contains("@{")
+        || branch
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || "~^:?*[\\".contains(c));
+
+    if invalid {
+        return Err(BlameError::InvalidUrl(format!(
+            "Invalid branch: {}",
+            branch
+        )));
+    }
+    Ok(())
+}
+
+/// Make sure a subpath stays inside the repository
+fn validate_subpath(subpath: &str) -> Result<String, BlameError> {
+    let trimmed = subpath.trim().trim_matches('/');
+    let escapes = Path::new(trimmed)
+        .components()
+        .any(|c| !matches!(c, std::path::Component::Normal(_)));
+
+    if escapes {
+        return Err(BlameError::PathOutsideRepo(subpath.to_string()));
+    }
+    if trimmed.is_empty() {
+        return Err(BlameError::InvalidOption(format!(
+            "Invalid subpath: {:?}",
+            subpath
+        )));
+    }
+
+    Ok(trimmed.to_string())
+}
+
+/// Turn the shorthands people type into full URLs: `owner/repo` means GitHub and
+/// `host/owner/repo` means https. Anything else comes back as given for strict validation
+fn expand_url_shorthand(input: &str) -> String {
+    let input = input.trim();
+    if input.contains("://") {
+        return input.to_string();
+    }
+
+    let trimmed = input.trim_end_matches('/');
+    let segments: Vec<&str> = trimmed.split('/').collect();
+    let plain_segment = |segment: &&str| {
+        !segment.is_empty()
+            && !segment.starts_with('.')
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    if !segments.iter().all(plain_segment) {
+        return input.to_string();
+    }
+
+    match segments.as_slice() {
+        // GitHub owners can't contain dots, so `example.com/repo` stays ambiguous
+        [owner, _] if !owner.contains('.') => format!("https://github.com/{}", trimmed),
+        [host, _, _, ..] if host.contains('.') => format!("https://{}", trimmed),
+        _ => input.to_string(),
+    }
+}
+
+/// Create a directory (and parents) if it doesn't already exist
+fn ensure_dir(dir: &Path) -> Result<(), BlameError> {
+    if !dir.exists() {
+        fs::create_dir_all(dir).map_err(|e| {
+            BlameError::DirectoryError(format!("Failed to create repositories directory: {}", e))
+        })?;
+    }
+
+    Ok(())
 }
 
 impl Repository {
     pub async fn new(repo_url: &str) -> Result<Self, BlameError> {
-        let url = Self::validate_url(repo_url)?;
+        Self::builder(repo_url).build().await
+    }
+
+    /// Start building a repository with non-default options
+    pub fn builder(repo_url: &str) -> RepositoryBuilder {
+        RepositoryBuilder::new(repo_url)
+    }
+
+    /// Create a repository with a custom deepen depth, pass `None` to skip
+    /// deepening history after the clone (faster, but blame may stop at the
+    /// shallow boundary)
+    pub async fn new_with_deepen(
+        repo_url: &str,
+        deepen_depth: Option<u32>,
+    ) -> Result<Self, BlameError> {
+        Self::builder(repo_url).deepen(deepen_depth).build().await
+    }
+
+    /// Use an existing local checkout instead of cloning, `prepare` leaves it untouched
+    pub fn from_local_path(local_path: impl AsRef<Path>) -> Result<Self, BlameError> {
+        let path = local_path.as_ref().canonicalize().map_err(|e| {
+            BlameError::DirectoryError(format!(
+                "Failed to access {}: {}",
+                local_path.as_ref().display(),
+                e
+            ))
+        })?;
 
-        let name = Self::extract_repo_name(&url)?;
+        if !path.is_dir() {
+            return Err(BlameError::DirectoryError(format!(
+                "Not a directory: {}",
+                path.display()
+            )));
+        }
+
+        let is_git_repository = std::process::Command::new("git")
+            .current_dir(&path)
+            .args(GIT_DIR_ARGS)
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if !is_git_repository {
+            return Err(BlameError::NotAGitRepository(path));
+        }
 
-        let path = Self::create_repo_path(&name)?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "local".to_string());
 
-        Ok(Repository { url, path, name })
+        Ok(Repository {
+            url: format!("file://{}", path.display()),
+            clone_url: None,
+            path,
+            repos_dir: None,
+            name,
+            deepen_depth: None,
+            history_limit: None,
+            local: true,
+            token: None,
+            branch: None,
+            pull_request: None,
+            clone_depth: DEFAULT_CLONE_DEPTH,
+            subpath: None,
+            path_filter: None,
+            custom_pattern: None,
+            extensions: Vec::new(),
+            freeze_after_clone: false,
+            blame_cache_dir: None,
+            rate_limit_backoff: DEFAULT_RATE_LIMIT_BACKOFF,
+            max_clones_per_host: DEFAULT_MAX_CLONES_PER_HOST,
+            user_agent: None,
+            git_identity: None,
+            context_before: DEFAULT_CONTEXT_LINES,
+            context_after: DEFAULT_CONTEXT_LINES,
+        })
     }
 
     /// Validate and normalize the repository URL
-    fn validate_url(repo_url: &str) -> Result<String, BlameError> {
+    fn validate_url(repo_url: &str, host_policy: &HostPolicy) -> Result<String, BlameError> {
+        let repo_url = &expand_url_shorthand(repo_url);
         let url = match Url::parse(repo_url) {
             Ok(url) => url,
             Err(_) => return Err(BlameError::InvalidUrl(repo_url.to_string())),
         };
 
-        // Ensure it's a GitHub, GitLab, or other common Git host
         let host = url
             .host_str()
             .ok_or_else(|| BlameError::InvalidUrl(format!("Missing host in URL: {}", repo_url)))?;
 
-        if !["github.com", "gitlab.com", "bitbucket.org"].contains(&host) && !host.contains("git") {
+        // Never clone from internal addresses, and respect any configured allow/deny lists
+        host_policy.check(&url)?;
+
+        // Without an allowlist, ensure it's a GitHub, GitLab, or other common Git host
+        if !host_policy.has_allowlist()
+            && !["github.com", "gitlab.com", "bitbucket.org"].contains(&host)
+            && !host.contains("git")
+        {
             // Not a recognized Git host, but we'll still try if it ends with .git
             if !repo_url.ends_with(".git") {
                 return Err(BlameError::InvalidUrl(format!(
@@ -89,20 +731,16 @@ impl Repository {
     }
 
     /// Create a path for the local repository clone
-    fn create_repo_path(repo_name: &str) -> Result<PathBuf, BlameError> {
-        let repos_dir = Self::get_repos_dir()?;
-
-        // Create a more unique folder name by adding a hash of the name
+    fn create_repo_path(repos_dir: &Path, repo_name: &str, key: &str) -> PathBuf {
+        // Create a more unique folder name by adding a hash of the key
         use std::collections::hash_map::DefaultHasher;
         use std::hash::{Hash, Hasher};
 
         let mut hasher = DefaultHasher::new();
-        repo_name.hash(&mut hasher);
+        key.hash(&mut hasher);
         let hash = hasher.finish();
 
-        let repo_dir = repos_dir.join(format!("{}-{:x}", repo_name, hash));
-
-        Ok(repo_dir)
+        repos_dir.join(format!("{}-{:x}", repo_name, hash))
     }
 
     /// Get the base directory for all repository clones
@@ -115,18 +753,45 @@ impl Repository {
         let repos_dir = home_dir.join(".oldest-todo-finder").join("repos");
 
         // Make sure the directory exists
-        if !repos_dir.exists() {
-            fs::create_dir_all(&repos_dir).map_err(|e| {
-                BlameError::DirectoryError(format!(
-                    "Failed to create repositories directory: {}",
-                    e
-                ))
-            })?;
-        }
+        ensure_dir(&repos_dir)?;
 
         Ok(repos_dir)
     }
 
+    /// Delete just this repository's clone, leaving every other clone alone
+    ///
+    /// Refuses local checkouts and anything that doesn't resolve to a directory
+    /// strictly inside the repositories directory the clone was made under.
+    pub async fn cleanup_self(&self) -> Result<(), BlameError> {
+        let Some(repos_dir) = &self.repos_dir else {
+            return Err(BlameError::DirectoryError(format!(
+                "Refusing to delete local checkout {}",
+                self.path.display()
+            )));
+        };
+
+        // Nothing to do if it was never cloned (or is already gone)
+        let Ok(path) = self.path.canonicalize() else {
+            return Ok(());
+        };
+        let repos_dir = repos_dir.canonicalize().map_err(|e| {
+            BlameError::DirectoryError(format!("Failed to access {}: {}", repos_dir.display(), e))
+        })?;
+
+        if path == repos_dir || !path.starts_with(&repos_dir) {
+            return Err(BlameError::DirectoryError(format!(
+                "Refusing to delete {} outside {}",
+                path.display(),
+                repos_dir.display()
+            )));
+        }
+
+        debug!("Removing clone of {} at {}", self.url, path.display());
+        tokio::fs::remove_dir_all(&path).await.map_err(|e| {
+            BlameError::DirectoryError(format!("Failed to remove {}: {}", path.display(), e))
+        })
+    }
+
     /// Get the path to the local repository
     pub fn path(&self) -> &Path {
         &self.path
@@ -142,148 +807,1485 @@ impl Repository {
         &self.url
     }
 
+    /// Get the URL cloned and fetched from, which differs from [`url`](Self::url)
+    /// only when a [`UrlRewrite`] applies
+    pub fn clone_url(&self) -> &str {
+        self.clone_url.as_deref().unwrap_or(&self.url)
+    }
+
+    /// Get the branch being scanned, if one was chosen explicitly
+    pub fn branch(&self) -> Option<&str> {
+        self.branch.as_deref()
+    }
+
+    /// Get the number of the pull request being scanned, if any
+    pub fn pull_request(&self) -> Option<u32> {
+        self.pull_request
+    }
+
+    /// Where a pull request's head is kept in our clone, fetched from `refs/pull/N/head`
+    fn pull_request_ref(number: u32) -> String {
+        format!("refs/remotes/origin/pull/{}", number)
+    }
+
+    /// Get the directory the scan is restricted to, if any
+    pub fn subpath(&self) -> Option<&str> {
+        self.subpath.as_deref()
+    }
+
+    /// Get the glob TODOs' files must match, if any
+    pub fn path_filter(&self) -> Option<&str> {
+        self.path_filter.as_deref()
+    }
+
+    /// Get the regex searched for instead of the TODO keywords, if any
+    pub fn custom_pattern(&self) -> Option<&str> {
+        self.custom_pattern.as_deref()
+    }
+
+    /// Get the file extensions the search is restricted to, empty when every file is searched
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    /// The compiled [`path_filter`](Self::path_filter)
+    pub(crate) fn path_glob(&self) -> Result<Option<Glob>, BlameError> {
+        self.path_filter.as_deref().map(Glob::new).transpose()
+    }
+
+    /// Get how many lines of context are kept above and below each TODO
+    pub fn context_lines(&self) -> (u32, u32) {
+        (self.context_before, self.context_after)
+    }
+
+    /// Get the directory blame results are cached in, if any
+    pub fn blame_cache_dir(&self) -> Option<&Path> {
+        self.blame_cache_dir.as_deref()
+    }
+
+    /// Remember blame results in this directory, e.g. for a local checkout
+    pub fn with_blame_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.blame_cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Search a local checkout for this regex instead of the TODO keywords, see
+    /// [`RepositoryBuilder::custom_pattern`]
+    pub fn with_custom_pattern(mut self, pattern: impl Into<String>) -> Result<Self, BlameError> {
+        let pattern = pattern.into();
+        validate_search_pattern(&pattern)?;
+        self.custom_pattern = Some(pattern);
+        Ok(self)
+    }
+
+    /// A git command carrying any per-repository config, such as auth
+    fn git_command(&self) -> Command {
+        let mut command = Command::new("git");
+        if let Some(token) = &self.token {
+            // Through the environment rather than `-c`, so the token never shows up in
+            // the process list
+            let credentials = BASE64.encode(format!("x-access-token:{}", token.0));
+            command
+                .env("GIT_CONFIG_COUNT", "1")
+                .env("GIT_CONFIG_KEY_0", "http.extraHeader")
+                .env(
+                    "GIT_CONFIG_VALUE_0",
+                    format!("Authorization: Basic {}", credentials),
+                );
+        }
+        if let Some(user_agent) = &self.user_agent {
+            command
+                .arg("-c")
+                .arg(format!("http.userAgent={}", user_agent));
+        }
+        if let Some((name, email)) = &self.git_identity {
+            command
+                .arg("-c")
+                .arg(format!("user.name={}", name))
+                .arg("-c")
+                .arg(format!("user.email={}", email));
+        }
+        command
+    }
+
     /// Clone or update the repository
-    pub async fn prepare(&self) -> Result<(), BlameError> {
-        if self.path.exists() {
-            debug!("path exists");
-            // Repository already exists, just fetch latest changes
-            self.update().await
+    ///
+    /// Returns true when the checkout is still on the commit it was already on, so
+    /// anything worked out from that commit last time still holds: a clone matching
+    /// the remote (any local edits are still thrown away), a frozen clone or a local
+    /// checkout. False when it was freshly cloned or moved to a new commit.
+    pub async fn prepare(&self) -> Result<bool, BlameError> {
+        self.prepare_with_rate_limit_hook(&|_| {}).await
+    }
+
+    /// Like [`prepare`](Self::prepare), calling `on_rate_limit` with how long
+    /// we'll wait whenever the host rate limits the clone or fetch
+    ///
+    /// Concurrent prepares of the same clone directory take turns, so the second of
+    /// two scans of a URL finds the first's finished clone rather than racing it.
+    pub async fn prepare_with_rate_limit_hook(
+        &self,
+        on_rate_limit: &(dyn Fn(Duration) + Send + Sync),
+    ) -> Result<bool, BlameError> {
+        let _guard = lock_path(&self.path).await;
+
+        if self.local {
+            debug!("local checkout, nothing to prepare");
+            // It may have been deleted or `git init`ed elsewhere since we were built
+            self.ensure_git_repository().await?;
+            Ok(true)
+        } else if self.path.exists() && self.freeze_after_clone {
+            debug!("path exists and clone is frozen, skipping update");
+            Ok(true)
+        } else if self.path.exists() {
+            // Asking the remote for its HEAD counts against the host like the fetch it may save
+            let _host_permit = self.acquire_host_permit().await;
+            if self.is_up_to_date().await {
+                debug!("path exists and matches the remote, skipping fetch");
+                // Nothing to fetch, but the tree must still be exactly that commit
+                self.reset_to("HEAD").await?;
+                self.touch()?;
+                Ok(true)
+            } else {
+                debug!("path exists");
+                // Repository already exists, just fetch latest changes
+                self.update(on_rate_limit).await.map(|_| false)
+            }
         } else {
             debug!("path doesn't exist, cloning");
             // Repository doesn't exist yet, clone it
-            self.clone().await
+            let _host_permit = self.acquire_host_permit().await;
+            self.clone(on_rate_limit).await.map(|_| false)
         }
     }
 
-    /// Clone the repository
-    async fn clone(&self) -> Result<(), BlameError> {
-        // Ensure parent directory exists
-        if let Some(parent) = self.path.parent() {
-            fs::create_dir_all(parent).map_err(|e| {
-                BlameError::DirectoryError(format!("Failed to create parent directory: {}", e))
-            })?;
-        }
+    /// Wait for a turn to clone or fetch from the clone URL's host, see
+    /// [`RepositoryBuilder::max_clones_per_host`]. `None` when there's no host, e.g. a
+    /// mirror on local disk
+    async fn acquire_host_permit(&self) -> Option<HostPermit> {
+        let url = Url::parse(self.clone_url()).ok()?;
+        let host = url.host_str().filter(|host| !host.is_empty())?;
+        Some(acquire_host(&host.to_lowercase(), self.max_clones_per_host).await)
+    }
 
-        // Try main branch first, fall back to master if needed
-        let result = self.clone_branch("main").await;
-        if result.is_err() {
-            self.clone_branch("master").await?;
+    /// How many commits HEAD's history holds, as far back as the clone reaches
+    pub async fn commit_count(&self) -> Result<u32, BlameError> {
+        count_commits(&self.path, &["HEAD"]).await
+    }
+
+    /// Whether the history has more commits than the
+    /// [`history_limit`](RepositoryBuilder::history_limit). Always `false` without a limit
+    async fn exceeds_history_limit(&self) -> Result<bool, BlameError> {
+        match self.history_limit {
+            Some(limit) => Ok(self.commit_count().await? > limit),
+            None => Ok(false),
         }
+    }
+
+    /// Whether the clone was left shallow because its history was past the
+    /// [`history_limit`](RepositoryBuilder::history_limit), so line blame would be slow.
+    /// Decided once when cloning, so a reused clone is never counted again
+    pub fn skipped_deepen(&self) -> bool {
+        self.skipped_deepen_marker().exists()
+    }
 
-        // Deepen history after successful clone
-        self.deepen_history(10000).await?;
+    fn skipped_deepen_marker(&self) -> PathBuf {
+        self.path.join(".git").join(SKIPPED_DEEPEN_MARKER)
+    }
 
-        Ok(())
+    /// The full sha of the commit currently checked out
+    pub async fn head_sha(&self) -> Result<String, BlameError> {
+        self.rev_parse("HEAD").await
     }
 
-    async fn clone_branch(&self, branch: &str) -> Result<(), BlameError> {
-        // Clone the repository with optimizations
+    /// The sha of the tree currently checked out
+    ///
+    /// Unlike [`head_sha`](Self::head_sha) this only changes when the content
+    /// does, so e.g. an empty commit or a rebase onto identical content keeps it.
+    pub async fn tree_hash(&self) -> Result<String, BlameError> {
+        self.rev_parse("HEAD^{tree}").await
+    }
+
+    /// Paths, relative to the repository root, of files git doesn't track and isn't ignoring
+    pub async fn untracked_files(&self) -> Result<HashSet<String>, BlameError> {
+        Ok(self
+            .ls_files(&["--others", "--exclude-standard"])
+            .await?
+            .into_iter()
+            .collect())
+    }
+
+    /// Paths, relative to the repository root, of every file git tracks. Read from
+    /// the index, so it's cheap even for a big checkout
+    pub async fn tracked_files(&self) -> Result<Vec<String>, BlameError> {
+        self.ls_files(&[]).await
+    }
+
+    async fn ls_files(&self, args: &[&str]) -> Result<Vec<String>, BlameError> {
         let output = Command::new("git")
-            .arg("clone")
-            .arg("--single-branch")
-            .arg("--branch")
-            .arg(branch)
-            .arg("--filter=blob:none")
-            .arg("--depth=1000")
-            .arg("-c")
-            .arg("core.compression=0")
-            .arg("-c")
-            .arg("http.postBuffer=524288000")
-            .arg("-c")
-            .arg("pack.threads=8")
-            .arg(&self.url)
-            .arg(&self.path)
+            .current_dir(&self.path)
+            .arg("ls-files")
+            .args(args)
+            .arg("-z")
             .output()
             .await
-            .map_err(|e| BlameError::GitError(format!("Failed to execute git clone: {}", e)))?;
+            .map_err(|e| BlameError::GitError(format!("Failed to execute git ls-files: {}", e)))?;
 
         if !output.status.success() {
             return Err(BlameError::GitError(format!(
-                "Git clone of branch '{}' failed: {}",
-                branch,
+                "Failed to list files: {}",
                 String::from_utf8_lossy(&output.stderr)
             )));
         }
 
-        Ok(())
+        Ok(output
+            .stdout
+            .split(|byte| *byte == 0)
+            .filter(|path| !path.is_empty())
+            .map(|path| String::from_utf8_lossy(path).to_string())
+            .collect())
     }
 
-    async fn deepen_history(&self, additional_depth: u32) -> Result<(), BlameError> {
-        let output = Command::new("git")
+    /// Fail with `NotAGitRepository` unless git recognises the checkout as a repository
+    pub async fn ensure_git_repository(&self) -> Result<(), BlameError> {
+        let is_git_repository = Command::new("git")
             .current_dir(&self.path)
-            .arg("fetch")
-            .arg("--deepen")
-            .arg(additional_depth.to_string())
-            .arg("origin")
+            .args(GIT_DIR_ARGS)
             .output()
             .await
-            .map_err(|e| BlameError::GitError(format!("Failed to deepen history: {}", e)))?;
-
-        if !output.status.success() {
-            return Err(BlameError::GitError(format!(
-                "Failed to deepen history: {}",
-                String::from_utf8_lossy(&output.stderr)
-            )));
+            .is_ok_and(|output| output.status.success());
+        if is_git_repository {
+            Ok(())
+        } else {
+            Err(BlameError::NotAGitRepository(self.path.clone()))
         }
-
-        Ok(())
     }
 
-    /// Update an existing repository
-    async fn update(&self) -> Result<(), BlameError> {
-        // Fetch latest changes
+    async fn rev_parse(&self, rev: &str) -> Result<String, BlameError> {
         let output = Command::new("git")
             .current_dir(&self.path)
-            .arg("fetch")
-            .arg("--all")
+            .args(["rev-parse", rev])
             .output()
             .await
-            .map_err(|e| BlameError::GitError(format!("Failed to execute git fetch: {}", e)))?;
+            .map_err(|e| BlameError::GitError(format!("Failed to execute git rev-parse: {}", e)))?;
 
         if !output.status.success() {
             return Err(BlameError::GitError(format!(
-                "Git fetch failed: {}",
+                "Failed to read {}: {}",
+                rev,
                 String::from_utf8_lossy(&output.stderr)
             )));
         }
 
-        // Reset to match the fetched head
-        let output = Command::new("git")
-            .current_dir(&self.path)
-            .arg("reset")
-            .arg("--hard")
-            .arg("origin/main") // Try main first
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Whether the clone's HEAD already matches the remote's, so there's nothing to fetch
+    ///
+    /// Any failure to ask is treated as out of date, falling back to a full update.
+    async fn is_up_to_date(&self) -> bool {
+        let remote_ref = match (&self.branch, self.pull_request) {
+            (Some(branch), _) => format!("refs/heads/{}", branch),
+            (None, Some(number)) => format!("refs/pull/{}/head", number),
+            (None, None) => "HEAD".to_string(),
+        };
+
+        let local = self.head_sha().await;
+        // Never waits on a credential prompt, e.g. for a repo gone private, and dies
+        // with the caller if it gives up
+        let remote = self
+            .git_command()
+            .current_dir(&self.path)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .args(["ls-remote", "origin", &remote_ref])
+            .kill_on_drop(true)
+            .output()
+            .await;
+
+        match (local, remote) {
+            (Ok(local_head), Ok(remote)) if remote.status.success() => {
+                let remote_stdout = String::from_utf8_lossy(&remote.stdout);
+                let remote_head = remote_stdout.split_whitespace().next();
+                !local_head.is_empty() && remote_head == Some(local_head.as_str())
+            }
+            _ => false,
+        }
+    }
+
+    /// Clone the repository
+    async fn clone(
+        &self,
+        on_rate_limit: &(dyn Fn(Duration) + Send + Sync),
+    ) -> Result<(), BlameError> {
+        // Ensure parent directory exists
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                BlameError::DirectoryError(format!("Failed to create parent directory: {}", e))
+            })?;
+        }
+
+        if let Some(number) = self.pull_request {
+            return self.clone_pull_request(number, on_rate_limit).await;
+        }
+
+        let cloned = match &self.branch {
+            Some(branch) => self.clone_branch_retrying(branch, on_rate_limit).await,
+            None => {
+                // Try main branch first, fall back to master if needed
+                match self.clone_branch_retrying("main", on_rate_limit).await {
+                    Ok(()) => Ok(()),
+                    Err(_) => self.clone_branch_retrying("master", on_rate_limit).await,
+                }
+            }
+        };
+        if let Err(e) = cloned {
+            // Nothing pushed yet means no branch to clone, which git reports confusingly
+            if self.remote_is_empty().await {
+                return Err(BlameError::EmptyRepository(self.url.clone()));
+            }
+            return Err(e);
+        }
+
+        // Deepen history after successful clone, unless disabled
+        self.deepen_after_clone().await
+    }
+
+    /// Arguments for cloning a single branch with optimizations
+    fn clone_args(&self, branch: &str) -> Vec<String> {
+        vec![
+            "clone".to_string(),
+            "--single-branch".to_string(),
+            "--branch".to_string(),
+            branch.to_string(),
+            "--filter=blob:none".to_string(),
+            format!("--depth={}", self.clone_depth),
+            "-c".to_string(),
+            "core.compression=0".to_string(),
+            "-c".to_string(),
+            "http.postBuffer=524288000".to_string(),
+            "-c".to_string(),
+            "pack.threads=8".to_string(),
+            self.clone_url().to_string(),
+            self.path.to_string_lossy().to_string(),
+        ]
+    }
+
+    /// Clone one branch, retrying once if the host rate limits us
+    async fn clone_branch_retrying(
+        &self,
+        branch: &str,
+        on_rate_limit: &(dyn Fn(Duration) + Send + Sync),
+    ) -> Result<(), BlameError> {
+        retry_if_rate_limited(self.rate_limit_backoff, on_rate_limit, || {
+            self.clone_branch(branch)
+        })
+        .await
+    }
+
+    async fn clone_branch(&self, branch: &str) -> Result<(), BlameError> {
+        let output = self
+            .git_command()
+            .args(self.clone_args(branch))
             .output()
             .await
-            .map_err(|e| BlameError::GitError(format!("Failed to execute git reset: {}", e)))?;
+            .map_err(|e| BlameError::GitError(format!("Failed to execute git clone: {}", e)))?;
 
-        // If main doesn't exist, try master
         if !output.status.success() {
-            let output = Command::new("git")
-                .current_dir(&self.path)
-                .arg("reset")
-                .arg("--hard")
-                .arg("origin/master")
-                .output()
-                .await
-                .map_err(|e| BlameError::GitError(format!("Failed to execute git reset: {}", e)))?;
+            return Err(BlameError::GitError(format!(
+                "Git clone of branch '{}' failed: {}",
+                branch,
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
 
-            if !output.status.success() {
-                return Err(BlameError::GitError(format!(
-                    "Git reset failed: {}",
-                    String::from_utf8_lossy(&output.stderr)
-                )));
+        Ok(())
+    }
+
+    /// Fetch just a pull request's head into a new repository and check it out
+    ///
+    /// `git clone` can't start from `refs/pull/N/head`, so the repository is set up by
+    /// hand and the ref fetched the same way `update` does later.
+    async fn clone_pull_request(
+        &self,
+        number: u32,
+        on_rate_limit: &(dyn Fn(Duration) + Send + Sync),
+    ) -> Result<(), BlameError> {
+        if !self.remote_has_pull_request(number).await? {
+            return Err(BlameError::PullRequestNotFound {
+                url: self.url.clone(),
+                number,
+            });
+        }
+
+        let cloned = async {
+            self.run_git(&["init", "--quiet"]).await?;
+            self.run_git(&["remote", "add", "origin", self.clone_url()])
+                .await?;
+            retry_if_rate_limited(self.rate_limit_backoff, on_rate_limit, || {
+                self.fetch_pull_request(number)
+            })
+            .await?;
+            self.reset_to(&Self::pull_request_ref(number)).await?;
+            self.deepen_after_clone().await
+        }
+        .await;
+
+        // Leave nothing half set up for the next scan to mistake for a clone
+        if cloned.is_err() {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+        cloned
+    }
+
+    /// Whether the remote advertises `refs/pull/N/head`
+    async fn remote_has_pull_request(&self, number: u32) -> Result<bool, BlameError> {
+        let output = self
+            .git_command()
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .args([
+                "ls-remote",
+                self.clone_url(),
+                &format!("refs/pull/{}/head", number),
+            ])
+            .output()
+            .await
+            .map_err(|e| BlameError::GitError(format!("Failed to execute git ls-remote: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(BlameError::GitError(format!(
+                "Failed to look up pull request #{} of {}: {}",
+                number,
+                self.url,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(!output.stdout.trim_ascii().is_empty())
+    }
+
+    /// Refspec fetching a pull request's head into `pull_request_ref`
+    fn pull_request_refspec(number: u32) -> String {
+        format!(
+            "+refs/pull/{}/head:{}",
+            number,
+            Self::pull_request_ref(number)
+        )
+    }
+
+    async fn fetch_pull_request(&self, number: u32) -> Result<(), BlameError> {
+        self.run_git(&[
+            "fetch",
+            "--filter=blob:none",
+            &format!("--depth={}", self.clone_depth),
+            "origin",
+            &Self::pull_request_refspec(number),
+        ])
+        .await
+        .map_err(|e| match e {
+            BlameError::GitError(message) => BlameError::GitError(format!(
+                "Fetching pull request #{} failed: {}",
+                number, message
+            )),
+            other => other,
+        })
+    }
+
+    /// Run git inside the clone, failing with its stderr if it exits unsuccessfully
+    async fn run_git(&self, args: &[&str]) -> Result<(), BlameError> {
+        fs::create_dir_all(&self.path).map_err(|e| {
+            BlameError::DirectoryError(format!("Failed to create clone directory: {}", e))
+        })?;
+        let output = self
+            .git_command()
+            .current_dir(&self.path)
+            .args(args)
+            .output()
+            .await
+            .map_err(|e| {
+                BlameError::GitError(format!("Failed to execute git {}: {}", args[0], e))
+            })?;
+
+        if !output.status.success() {
+            return Err(BlameError::GitError(format!(
+                "git {} failed: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Names of the remote's branches, e.g. for picking one to scan
+    ///
+    /// Only asks the remote, so it works before anything is cloned. Never prompts for
+    /// credentials, a private repository just fails.
+    pub async fn remote_branches(&self) -> Result<Vec<String>, BlameError> {
+        // Counts against the host like a clone, and dies with the caller if it gives up
+        let _host_permit = self.acquire_host_permit().await;
+        let output = self
+            .git_command()
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .args(["ls-remote", "--heads", self.clone_url()])
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| BlameError::GitError(format!("Failed to execute git ls-remote: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(BlameError::GitError(format!(
+                "Failed to list branches of {}: {}",
+                self.url,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(parse_remote_heads(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Whether the remote has no refs at all, i.e. nothing has ever been pushed
+    async fn remote_is_empty(&self) -> bool {
+        match self
+            .git_command()
+            .args(["ls-remote", self.clone_url()])
+            .output()
+            .await
+        {
+            Ok(output) => output.status.success() && output.stdout.trim_ascii().is_empty(),
+            Err(_) => false,
+        }
+    }
+
+    /// Arguments for the post-clone deepen fetch, or `None` when deepening is disabled
+    fn deepen_args(&self) -> Option<Vec<String>> {
+        self.deepen_depth.map(|depth| self.deepen_by_args(depth))
+    }
+
+    /// Arguments for a fetch of `commits` more history
+    fn deepen_by_args(&self, commits: u32) -> Vec<String> {
+        let mut args = vec![
+            "fetch".to_string(),
+            "--deepen".to_string(),
+            commits.to_string(),
+            "origin".to_string(),
+        ];
+        // Without a refspec a pull request clone would deepen branches it never fetched
+        if let Some(number) = self.pull_request {
+            args.push(Self::pull_request_refspec(number));
+        }
+        args
+    }
+
+    /// Fetch `commits` more history into a managed shallow clone, as quick blame
+    /// does when TODOs land on its boundary. Local checkouts are left alone
+    pub(crate) async fn deepen(&self, commits: u32) -> Result<(), BlameError> {
+        if self.local {
+            return Err(BlameError::InvalidOption(
+                "local checkouts aren't deepened".to_string(),
+            ));
+        }
+        let _permit = self.acquire_host_permit().await;
+        self.deepen_history(self.deepen_by_args(commits)).await
+    }
+
+    /// Fetch more history into a fresh clone, unless deepening is disabled or the
+    /// clone is already past the history limit
+    async fn deepen_after_clone(&self) -> Result<(), BlameError> {
+        let Some(args) = self.deepen_args() else {
+            return Ok(());
+        };
+        if self.exceeds_history_limit().await? {
+            debug!("history is past the limit, skipping deepen");
+            fs::write(self.skipped_deepen_marker(), "").map_err(|e| {
+                BlameError::FileError(format!("Failed to mark the clone as shallow: {}", e))
+            })?;
+            return Ok(());
+        }
+        self.deepen_history(args).await
+    }
+
+    async fn deepen_history(&self, args: Vec<String>) -> Result<(), BlameError> {
+        let output = self
+            .git_command()
+            .current_dir(&self.path)
+            .args(args)
+            .output()
+            .await
+            .map_err(|e| BlameError::GitError(format!("Failed to deepen history: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(BlameError::GitError(format!(
+                "Failed to deepen history: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Update an existing repository
+    async fn update(
+        &self,
+        on_rate_limit: &(dyn Fn(Duration) + Send + Sync),
+    ) -> Result<(), BlameError> {
+        if let Some(number) = self.pull_request {
+            retry_if_rate_limited(self.rate_limit_backoff, on_rate_limit, || {
+                self.fetch_pull_request(number)
+            })
+            .await?;
+            self.reset_to(&Self::pull_request_ref(number)).await?;
+            return self.touch();
+        }
+
+        // Fetch latest changes
+        retry_if_rate_limited(self.rate_limit_backoff, on_rate_limit, || self.fetch()).await?;
+
+        // Reset to match the fetched head
+        match &self.branch {
+            Some(branch) => self.reset_to(&format!("origin/{}", branch)).await?,
+            None => {
+                // Try main first, if main doesn't exist, try master
+                if self.reset_to("origin/main").await.is_err() {
+                    self.reset_to("origin/master").await?;
+                }
             }
         }
 
-        // Update the last modified time
+        self.touch()
+    }
+
+    async fn fetch(&self) -> Result<(), BlameError> {
+        let output = self
+            .git_command()
+            .current_dir(&self.path)
+            .arg("fetch")
+            .arg("--all")
+            .output()
+            .await
+            .map_err(|e| BlameError::GitError(format!("Failed to execute git fetch: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(BlameError::GitError(format!(
+                "Git fetch failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Update the last modified time, so cleanup knows the clone is still in use
+    fn touch(&self) -> Result<(), BlameError> {
         let current_time = std::time::SystemTime::now();
         filetime::set_file_mtime(
             &self.path,
             filetime::FileTime::from_system_time(current_time),
         )
-        .map_err(BlameError::IoError)?;
+        .map_err(BlameError::IoError)
+    }
+
+    /// Hard reset the working tree to `target`, removing untracked files too
+    async fn reset_to(&self, target: &str) -> Result<(), BlameError> {
+        let output = Command::new("git")
+            .current_dir(&self.path)
+            .arg("reset")
+            .arg("--hard")
+            .arg(target)
+            .output()
+            .await
+            .map_err(|e| BlameError::GitError(format!("Failed to execute git reset: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(BlameError::GitError(format!(
+                "Git reset failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        let output = Command::new("git")
+            .current_dir(&self.path)
+            .args(["clean", "-fd"])
+            .output()
+            .await
+            .map_err(|e| BlameError::GitError(format!("Failed to execute git clean: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(BlameError::GitError(format!(
+                "Git clean failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{commit_file, git, init_repo};
+
+    #[tokio::test]
+    async fn test_deepen_enabled_by_default() {
+        let repo = Repository::new("https://github.com/rupert648/willdolater.dev")
+            .await
+            .unwrap();
+
+        let args = repo.deepen_args().expect("deepen should be enabled");
+        assert!(args.contains(&"--deepen".to_string()));
+        assert!(args.contains(&DEFAULT_DEEPEN_DEPTH.to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_deepen_disabled() {
+        let repo =
+            Repository::new_with_deepen("https://github.com/rupert648/willdolater.dev", None)
+                .await
+                .unwrap();
+
+        assert!(repo.deepen_args().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_builder_applies_options() {
+        let repos_dir = tempfile::tempdir().unwrap();
+        let repo = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .token("secret-token")
+            .branch("develop")
+            .depth(50)
+            .deepen(None)
+            .subpath("crates/server/")
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+
+        let args = repo.clone_args("develop");
+        assert!(args.contains(&"develop".to_string()));
+        assert!(args.contains(&"--depth=50".to_string()));
+        assert!(repo.deepen_args().is_none());
+        assert_eq!(repo.subpath(), Some("crates/server"));
+        assert_eq!(repo.branch(), Some("develop"));
+        assert!(repo.path().starts_with(repos_dir.path()));
+
+        // The token goes in a header, never the URL or debug output
+        assert!(!args.iter().any(|arg| arg.contains("secret-token")));
+        assert!(!repo.url().contains("secret-token"));
+        assert!(!format!("{:?}", repo).contains("secret-token"));
+    }
+
+    #[tokio::test]
+    async fn test_git_commands_carry_user_agent_and_identity() {
+        let repos_dir = tempfile::tempdir().unwrap();
+        let url = "https://github.com/rupert648/willdolater.dev";
+        let args = |repo: &Repository| -> Vec<String> {
+            repo.git_command()
+                .as_std()
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect()
+        };
+
+        let plain = Repository::builder(url)
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+        assert!(args(&plain).is_empty());
+
+        let repo = Repository::builder(url)
+            .user_agent("todo-crawler/1.0 (+https://example.com)")
+            .git_identity("Todo Crawler", "crawler@example.com")
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(
+            args(&repo),
+            [
+                "-c",
+                "http.userAgent=todo-crawler/1.0 (+https://example.com)",
+                "-c",
+                "user.name=Todo Crawler",
+                "-c",
+                "user.email=crawler@example.com",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_builder_branch_gets_own_clone_dir() {
+        let repos_dir = tempfile::tempdir().unwrap();
+        let url = "https://github.com/rupert648/willdolater.dev";
+
+        let default = Repository::builder(url)
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+        let develop = Repository::builder(url)
+            .branch("develop")
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+
+        assert_ne!(default.path(), develop.path());
+    }
+
+    #[test]
+    fn test_expand_url_shorthand() {
+        assert_eq!(
+            expand_url_shorthand("tokio-rs/tokio"),
+            "https://github.com/tokio-rs/tokio"
+        );
+        assert_eq!(
+            expand_url_shorthand("github.com/tokio-rs/tokio/"),
+            "https://github.com/tokio-rs/tokio"
+        );
+        assert_eq!(
+            expand_url_shorthand("gitlab.com/group/sub/repo"),
+            "https://gitlab.com/group/sub/repo"
+        );
+        // Full URLs and SSH remotes are left for `validate_url` to judge
+        assert_eq!(
+            expand_url_shorthand("https://github.com/o/r"),
+            "https://github.com/o/r"
+        );
+        assert_eq!(
+            expand_url_shorthand("git@github.com:o/r.git"),
+            "git@github.com:o/r.git"
+        );
+        // Ambiguous input isn't guessed at
+        assert_eq!(expand_url_shorthand("example.com/repo"), "example.com/repo");
+        assert_eq!(expand_url_shorthand("tokio"), "tokio");
+        assert_eq!(expand_url_shorthand("o/../r"), "o/../r");
+    }
+
+    #[tokio::test]
+    async fn test_shorthand_urls_are_accepted() {
+        let repo = Repository::new("tokio-rs/tokio").await.unwrap();
+        assert_eq!(repo.url(), "https://github.com/tokio-rs/tokio.git");
+        assert_eq!(repo.name(), "tokio-rs-tokio");
+
+        let repo = Repository::new("gitlab.com/o/r").await.unwrap();
+        assert_eq!(repo.url(), "https://gitlab.com/o/r.git");
+
+        assert!(matches!(
+            Repository::new("not a repo at all").await,
+            Err(BlameError::InvalidUrl(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_builder_rejects_escaping_subpath() {
+        let result = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .subpath("../../etc")
+            .build()
+            .await;
+        assert!(matches!(result, Err(BlameError::PathOutsideRepo(_))));
+
+        let result = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .subpath("/")
+            .build()
+            .await;
+        assert!(matches!(result, Err(BlameError::InvalidOption(_))));
+    }
+
+    #[tokio::test]
+    async fn test_builder_rejects_zero_depth() {
+        let result = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .depth(0)
+            .build()
+            .await;
+
+        assert!(matches!(result, Err(BlameError::InvalidOption(_))));
+    }
+
+    #[tokio::test]
+    async fn test_token_stays_out_of_git_arguments() {
+        let repo = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .token("secret-token")
+            .build()
+            .await
+            .unwrap();
+
+        let command = repo.git_command();
+        let command = command.as_std();
+        assert!(
+            command
+                .get_args()
+                .all(|arg| !arg.to_string_lossy().contains("Authorization"))
+        );
+        let header = command
+            .get_envs()
+            .find(|(key, _)| *key == "GIT_CONFIG_VALUE_0")
+            .and_then(|(_, value)| value)
+            .unwrap();
+        let expected = BASE64.encode("x-access-token:secret-token");
+        assert_eq!(
+            header.to_string_lossy(),
+            format!("Authorization: Basic {}", expected)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_builder_keywords_and_extensions() {
+        let repos_dir = tempfile::tempdir().unwrap();
+        let build = |builder: RepositoryBuilder| builder.repos_dir(repos_dir.path()).build();
+        let builder = || Repository::builder("https://github.com/rupert648/willdolater.dev");
+
+        let repo = build(
+            builder()
+                .keywords(["FIXME", "XXX?"])
+                .extensions([".rs", "py"]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(repo.custom_pattern(), Some(r"FIXME|XXX\?"));
+        assert_eq!(repo.extensions(), ["rs", "py"]);
+
+        for extension in ["", "*", "rs/../x", "{rs,py}"] {
+            let result = build(builder().extensions([extension])).await;
+            assert!(
+                matches!(result, Err(BlameError::InvalidPattern(_))),
+                "{:?}",
+                extension
+            );
+        }
+        assert!(matches!(
+            build(builder().keywords(Vec::<String>::new())).await,
+            Err(BlameError::InvalidPattern(_))
+        ));
+        for branch in ["--upload-pack=x", "a..b", "has space", "feature/"] {
+            let result = build(builder().branch(branch)).await;
+            assert!(
+                matches!(result, Err(BlameError::InvalidUrl(_))),
+                "{:?}",
+                branch
+            );
+        }
+    }
+
+    /// An upstream repo with one commit, cloned to where `repo` expects its checkout
+    fn clone_fixture(repo: &Repository) -> tempfile::TempDir {
+        let upstream = init_repo();
+        commit_file(upstream.path(), "main.rs", "// TODO: first\n", "First");
+        git(
+            Path::new("/"),
+            &[
+                "clone",
+                "-q",
+                upstream.path().to_str().unwrap(),
+                repo.path().to_str().unwrap(),
+            ],
+        );
+        upstream
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_prepares_of_one_url_clone_once() {
+        let upstream = init_repo();
+        commit_file(upstream.path(), "main.rs", "// TODO: shared\n", "First");
+        let repos_dir = tempfile::tempdir().unwrap();
+        let clone_url = format!("file://{}", upstream.path().display());
+        let build = || async {
+            Repository::builder("https://github.com/o/r")
+                .repos_dir(repos_dir.path())
+                .url_rewrite(UrlRewrite::new(".*", &clone_url).unwrap())
+                .build()
+                .await
+                .unwrap()
+        };
+        let (first, second) = (build().await, build().await);
+        assert_eq!(first.path(), second.path());
+
+        let (a, b) = tokio::join!(first.prepare(), second.prepare());
+
+        // One of them cloned, the other found that clone already up to date
+        let mut reused = [a.unwrap(), b.unwrap()];
+        reused.sort();
+        assert_eq!(reused, [false, true]);
+        git(first.path(), &["fsck", "--no-progress"]);
+        assert_eq!(git(first.path(), &["status", "--porcelain"]), "");
+        assert_eq!(
+            fs::read_to_string(first.path().join("main.rs")).unwrap(),
+            "// TODO: shared\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_clones_from_one_host_take_turns() {
+        let repos_dir = tempfile::tempdir().unwrap();
+        let build = |url: &str| {
+            Repository::builder(url)
+                .repos_dir(repos_dir.path())
+                .max_clones_per_host(1)
+                .build()
+        };
+        let first = build("https://git.same-host.example/a/one").await.unwrap();
+        let second = build("https://git.same-host.example/b/two").await.unwrap();
+        let elsewhere = build("https://git.other-host.example/c/three")
+            .await
+            .unwrap();
+
+        let held = first.acquire_host_permit().await.expect("URL has a host");
+        let waiting = tokio::spawn(async move { second.acquire_host_permit().await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        // Another host's clones don't wait on this one's
+        let other =
+            tokio::time::timeout(Duration::from_millis(50), elsewhere.acquire_host_permit())
+                .await
+                .expect("a different host isn't held up");
+        assert!(other.is_some());
+
+        drop(held);
+        assert!(waiting.await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_listing_branches_waits_for_the_host() {
+        let repos_dir = tempfile::tempdir().unwrap();
+        let build = |url: &str| {
+            Repository::builder(url)
+                .repos_dir(repos_dir.path())
+                .max_clones_per_host(1)
+                .build()
+        };
+        let cloning = build("https://git.branches-host.example/a/one")
+            .await
+            .unwrap();
+        let listing = build("https://git.branches-host.example/b/two")
+            .await
+            .unwrap();
+
+        let _held = cloning.acquire_host_permit().await.expect("URL has a host");
+        let waiting = tokio::spawn(async move { listing.remote_branches().await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+        waiting.abort();
+    }
+
+    #[tokio::test]
+    async fn test_frozen_prepare_skips_fetch() {
+        let repos_dir = tempfile::tempdir().unwrap();
+        let repo = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .repos_dir(repos_dir.path())
+            .freeze_after_clone(true)
+            .build()
+            .await
+            .unwrap();
+        let upstream = clone_fixture(&repo);
+        let pinned = git(repo.path(), &["rev-parse", "HEAD"]);
+
+        // Upstream moves on, but the frozen clone must not fetch it
+        commit_file(upstream.path(), "main.rs", "// TODO: second\n", "Second");
+        repo.prepare().await.unwrap();
+
+        assert_eq!(git(repo.path(), &["rev-parse", "HEAD"]), pinned);
+        let fetched = git(repo.path(), &["rev-list", "--all", "--count"]);
+        assert_eq!(fetched.trim(), "1");
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_self_removes_only_its_clone() {
+        let repos_dir = tempfile::tempdir().unwrap();
+        let repo = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+        let _upstream = clone_fixture(&repo);
+        let neighbour = repos_dir.path().join("other-clone");
+        fs::create_dir(&neighbour).unwrap();
+
+        repo.cleanup_self().await.unwrap();
+
+        assert!(!repo.path().exists());
+        assert!(neighbour.exists());
+        // Already gone, so there's nothing left to do
+        repo.cleanup_self().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_self_refuses_paths_outside_repos_dir() {
+        let repos_dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let mut repo = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+
+        repo.path = outside.path().to_path_buf();
+        assert!(matches!(
+            repo.cleanup_self().await,
+            Err(BlameError::DirectoryError(_))
+        ));
+        assert!(outside.path().exists());
+
+        // Nor the repositories directory itself, however it's spelled
+        repo.path = repos_dir.path().join("x").join("..");
+        fs::create_dir(repos_dir.path().join("x")).unwrap();
+        assert!(repo.cleanup_self().await.is_err());
+        assert!(repos_dir.path().exists());
+
+        git(outside.path(), &["init", "-q"]);
+        let local = Repository::from_local_path(outside.path()).unwrap();
+        assert!(local.cleanup_self().await.is_err());
+        assert!(outside.path().exists());
+    }
+
+    #[tokio::test]
+    async fn test_plain_directory_is_not_a_git_repository() {
+        let plain = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            Repository::from_local_path(plain.path()),
+            Err(BlameError::NotAGitRepository(_))
+        ));
+
+        // A checkout that stops being a repository after we were handed it
+        let fixture = init_repo();
+        commit_file(fixture.path(), "lib.rs", "// TODO: gone\n", "Add lib.rs");
+        let repo = Repository::from_local_path(fixture.path()).unwrap();
+        fs::remove_dir_all(fixture.path().join(".git")).unwrap();
+
+        assert!(matches!(
+            repo.prepare().await,
+            Err(BlameError::NotAGitRepository(path)) if path == repo.path()
+        ));
+        let todos = crate::todo::find_todos(&repo, &crate::SystemClock)
+            .await
+            .unwrap();
+        assert!(matches!(
+            crate::blame::blame_todos(&repo, todos, &crate::SystemClock).await,
+            Err(BlameError::NotAGitRepository(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_tree_hash_ignores_empty_commits() {
+        let fixture = init_repo();
+        commit_file(fixture.path(), "main.rs", "// TODO: first\n", "First");
+        let repo = Repository::from_local_path(fixture.path()).unwrap();
+        let tree = repo.tree_hash().await.unwrap();
+        assert_eq!(
+            tree,
+            git(fixture.path(), &["rev-parse", "HEAD^{tree}"]).trim()
+        );
+
+        git(
+            fixture.path(),
+            &["commit", "--allow-empty", "-q", "-m", "Empty"],
+        );
+        assert_eq!(repo.tree_hash().await.unwrap(), tree);
+
+        commit_file(fixture.path(), "main.rs", "// TODO: changed\n", "Change");
+        assert_ne!(repo.tree_hash().await.unwrap(), tree);
+    }
+
+    #[tokio::test]
+    async fn test_clone_of_empty_repository() {
+        let repos_dir = tempfile::tempdir().unwrap();
+        let mut repo = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+        // A remote that exists but has never had anything pushed to it
+        let upstream = tempfile::tempdir().unwrap();
+        git(upstream.path(), &["init", "--bare", "--quiet"]);
+        repo.url = upstream.path().to_string_lossy().to_string();
+
+        let result = repo.prepare().await;
+
+        assert!(
+            matches!(result, Err(BlameError::EmptyRepository(_))),
+            "expected EmptyRepository, got {:?}",
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn test_url_rewrite_clones_mirror_but_links_original() {
+        let mirror = init_repo();
+        commit_file(mirror.path(), "main.rs", "// TODO: mirrored\n", "First");
+        let rewrite = UrlRewrite::new(
+            r"^https://github\.com/o/r\.git$",
+            mirror.path().to_str().unwrap(),
+        )
+        .unwrap();
+
+        let repos_dir = tempfile::tempdir().unwrap();
+        let repo = Repository::builder("https://github.com/o/r")
+            .repos_dir(repos_dir.path())
+            .url_rewrite(rewrite)
+            .deepen(None)
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(repo.url(), "https://github.com/o/r.git");
+        assert_eq!(repo.clone_url(), mirror.path().to_str().unwrap());
+
+        // github.com is never contacted, so this only works through the mirror
+        repo.prepare().await.unwrap();
+        let todos = crate::find_all_todos(&repo, &crate::SystemClock)
+            .await
+            .unwrap();
+
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].source_repo_url, "https://github.com/o/r.git");
+        assert!(
+            todos[0]
+                .get_permalink_url()
+                .starts_with("https://github.com/o/r/blob/")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_url_rewrite_that_does_not_match_keeps_url() {
+        let rewrite = UrlRewrite::parse("^https://gitlab.com/=>https://mirror/").unwrap();
+        let repos_dir = tempfile::tempdir().unwrap();
+        let repo = Repository::builder("https://github.com/o/r")
+            .repos_dir(repos_dir.path())
+            .url_rewrite(rewrite)
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!(repo.clone_url(), repo.url());
+    }
+
+    #[tokio::test]
+    async fn test_head_sha_matches_rev_parse() {
+        let fixture = init_repo();
+        commit_file(fixture.path(), "main.rs", "// TODO: first\n", "First");
+        commit_file(fixture.path(), "lib.rs", "// TODO: second\n", "Second");
+        let repo = Repository::from_local_path(fixture.path()).unwrap();
+
+        let sha = repo.head_sha().await.unwrap();
+
+        assert_eq!(sha, git(fixture.path(), &["rev-parse", "HEAD"]).trim());
+        assert_eq!(sha.len(), 40);
+    }
+
+    #[test]
+    fn test_parse_remote_heads() {
+        let output = "\
+3f2a9c1e0b7d4a6f8e5c2b1a0d9e8f7c6b5a4d3e\trefs/heads/main
+a1b2c3d4e5f60718293a4b5c6d7e8f9012345678\trefs/heads/feature/login
+0123456789abcdef0123456789abcdef01234567\trefs/tags/v1.0
+";
+
+        assert_eq!(parse_remote_heads(output), ["main", "feature/login"]);
+        assert!(parse_remote_heads("").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_remote_branches_before_clone() {
+        let upstream = init_repo();
+        commit_file(upstream.path(), "main.rs", "// TODO: main\n", "Add main.rs");
+        git(upstream.path(), &["branch", "release"]);
+        let repos_dir = tempfile::tempdir().unwrap();
+        let rewrite = UrlRewrite::new(".*", upstream.path().to_str().unwrap()).unwrap();
+        let repo = Repository::builder("https://github.com/o/r")
+            .repos_dir(repos_dir.path())
+            .url_rewrite(rewrite)
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!(repo.remote_branches().await.unwrap(), ["main", "release"]);
+        assert!(!repo.path().exists());
+
+        let missing = UrlRewrite::new(".*", "/nonexistent/repo").unwrap();
+        let repo = Repository::builder("https://github.com/o/r")
+            .repos_dir(repos_dir.path())
+            .url_rewrite(missing)
+            .build()
+            .await
+            .unwrap();
+        assert!(matches!(
+            repo.remote_branches().await,
+            Err(BlameError::GitError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pull_request_url_fetches_pull_head() {
+        let upstream = init_repo();
+        commit_file(upstream.path(), "main.rs", "// TODO: main\n", "Add main.rs");
+        git(upstream.path(), &["checkout", "-q", "-b", "contributor"]);
+        commit_file(
+            upstream.path(),
+            "pr.rs",
+            "// TODO: from the PR\n",
+            "Add pr.rs",
+        );
+        git(upstream.path(), &["update-ref", "refs/pull/1/head", "HEAD"]);
+        git(upstream.path(), &["checkout", "-q", "main"]);
+        git(upstream.path(), &["branch", "-q", "-D", "contributor"]);
+        let pull_head = git(upstream.path(), &["rev-parse", "refs/pull/1/head"]);
+
+        let repos_dir = tempfile::tempdir().unwrap();
+        let build = |url: &'static str| {
+            Repository::builder(url)
+                .repos_dir(repos_dir.path())
+                .url_rewrite(UrlRewrite::new(".*", upstream.path().to_str().unwrap()).unwrap())
+                .deepen(None)
+                .build()
+        };
+
+        let repo = build("https://github.com/o/r/pull/1/files").await.unwrap();
+        assert_eq!(repo.url(), "https://github.com/o/r.git");
+        assert_eq!(repo.pull_request(), Some(1));
+
+        assert!(!repo.prepare().await.unwrap());
+        assert_eq!(repo.head_sha().await.unwrap(), pull_head);
+        assert!(repo.path().join("pr.rs").exists());
+
+        // A second scan finds the pull request's clone already up to date
+        assert!(repo.prepare().await.unwrap());
+
+        let missing = build("https://github.com/o/r/pull/2").await.unwrap();
+        assert!(matches!(
+            missing.prepare().await,
+            Err(BlameError::PullRequestNotFound { number: 2, .. })
+        ));
+        assert!(!missing.path().exists());
+
+        let both = Repository::builder("https://github.com/o/r/pull/1")
+            .branch("main")
+            .build()
+            .await;
+        assert!(matches!(both, Err(BlameError::InvalidUrl(_))));
+    }
+
+    #[tokio::test]
+    async fn test_history_limit_skips_deepen() {
+        let upstream = init_repo();
+        for commit in 1..=12 {
+            commit_file(
+                upstream.path(),
+                "main.rs",
+                &format!("// TODO: {}\n", commit),
+                &format!("Commit {}", commit),
+            );
+        }
+        let repos_dir = tempfile::tempdir().unwrap();
+        let clone_url = format!("file://{}", upstream.path().display());
+        let build = |name: &'static str| {
+            Repository::builder(&format!("https://github.com/o/{}", name))
+                .repos_dir(repos_dir.path())
+                .url_rewrite(UrlRewrite::new(".*", &clone_url).unwrap())
+                .depth(8)
+                .deepen(Some(100))
+        };
+
+        let limited = build("limited").history_limit(5).build().await.unwrap();
+        limited.prepare().await.unwrap();
+        // Left as shallow as it was cloned
+        assert_eq!(limited.commit_count().await.unwrap(), 8);
+        assert!(limited.exceeds_history_limit().await.unwrap());
+        assert!(limited.skipped_deepen());
+
+        let roomy = build("roomy").history_limit(50).build().await.unwrap();
+        roomy.prepare().await.unwrap();
+        assert_eq!(roomy.commit_count().await.unwrap(), 12);
+        assert!(!roomy.exceeds_history_limit().await.unwrap());
+        assert!(!roomy.skipped_deepen());
+
+        let unlimited = build("unlimited").build().await.unwrap();
+        unlimited.prepare().await.unwrap();
+        assert!(!unlimited.exceeds_history_limit().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_prepare_reuses_clone_matching_remote() {
+        let repos_dir = tempfile::tempdir().unwrap();
+        let repo = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+        let _upstream = clone_fixture(&repo);
+        let head = git(repo.path(), &["rev-parse", "HEAD"]);
+
+        // Reused without a fetch, but edits to the clone never get scanned
+        std::fs::write(repo.path().join("main.rs"), "// TODO: local edit\n").unwrap();
+        std::fs::write(repo.path().join("stray.rs"), "// TODO: untracked\n").unwrap();
+
+        assert!(repo.prepare().await.unwrap());
+        assert_eq!(git(repo.path(), &["rev-parse", "HEAD"]), head);
+        let contents = std::fs::read_to_string(repo.path().join("main.rs")).unwrap();
+        assert_ne!(contents, "// TODO: local edit\n");
+        assert!(!repo.path().join("stray.rs").exists());
+    }
+
+    #[tokio::test]
+    async fn test_prepare_updates_clone_behind_remote() {
+        let repos_dir = tempfile::tempdir().unwrap();
+        let repo = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+        let upstream = clone_fixture(&repo);
+
+        commit_file(upstream.path(), "main.rs", "// TODO: second\n", "Second");
+
+        assert!(!repo.prepare().await.unwrap());
+        assert_eq!(
+            git(repo.path(), &["rev-parse", "HEAD"]),
+            git(upstream.path(), &["rev-parse", "HEAD"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_quick_builder_skips_deepen() {
+        let repos_dir = tempfile::tempdir().unwrap();
+        let repo = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .repos_dir(repos_dir.path())
+            .quick()
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!(repo.clone_depth, 1);
+        assert!(repo.deepen_args().is_none());
+
+        let full = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+        assert_ne!(repo.path(), full.path());
+    }
+
+    #[tokio::test]
+    async fn test_builder_applies_host_policy() {
+        let repos_dir = tempfile::tempdir().unwrap();
+        let policy = HostPolicy::new(vec!["code.example.com".to_string()], vec![]);
+
+        let allowed = Repository::builder("https://code.example.com/o/r")
+            .host_policy(policy.clone())
+            .repos_dir(repos_dir.path())
+            .build()
+            .await;
+        let rejected = Repository::builder("https://github.com/o/r")
+            .host_policy(policy)
+            .repos_dir(repos_dir.path())
+            .build()
+            .await;
+        let internal = Repository::builder("https://127.0.0.1/o/r.git")
+            .repos_dir(repos_dir.path())
+            .build()
+            .await;
+
+        assert!(allowed.is_ok());
+        assert!(matches!(rejected, Err(BlameError::HostNotAllowed(_))));
+        assert!(matches!(internal, Err(BlameError::HostNotAllowed(_))));
+    }
+}
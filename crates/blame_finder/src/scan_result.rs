@@ -0,0 +1,26 @@
+use crate::todo::TodoItem;
+
+/// Outcome of scanning a repository for its oldest TODO
+// Only one of these exists per scan, so boxing the TODO isn't worth the noise
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+pub enum ScanResult {
+    /// The oldest TODO, with blame info filled in
+    Found(TodoItem),
+
+    /// The repository contains no TODOs at all
+    NoTodos,
+
+    /// TODOs were found, but none of them could be git blamed
+    AllBlameFailed { attempted: usize },
+}
+
+impl ScanResult {
+    /// The oldest TODO, if one was found
+    pub fn into_todo(self) -> Option<TodoItem> {
+        match self {
+            ScanResult::Found(todo) => Some(todo),
+            _ => None,
+        }
+    }
+}
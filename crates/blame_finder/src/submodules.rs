@@ -0,0 +1,64 @@
+use log::warn;
+use std::path::Path;
+
+/// Name of the file at the repository root declaring its submodules
+pub const GITMODULES_FILE: &str = ".gitmodules";
+
+/// Paths, relative to the repository root, of every submodule declared in
+/// `.gitmodules`
+///
+/// A submodule's files belong to another repository, so blaming them from this
+/// one fails. Clones don't recurse into submodules, but a local checkout may have
+/// them populated. An unreadable `.gitmodules` is treated as declaring none.
+pub fn submodule_paths(repo_path: &Path) -> Vec<String> {
+    let path = repo_path.join(GITMODULES_FILE);
+    if !path.exists() {
+        return Vec::new();
+    }
+
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => parse_gitmodules(&contents),
+        Err(e) => {
+            warn!("Failed to read {}: {}", GITMODULES_FILE, e);
+            Vec::new()
+        }
+    }
+}
+
+/// The `path = ...` entries of a `.gitmodules` file
+fn parse_gitmodules(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            if key.trim() != "path" {
+                return None;
+            }
+            let path = value.trim().trim_matches('"').trim_matches('/');
+            (!path.is_empty()).then(|| path.to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gitmodules() {
+        let contents = r#"
+[submodule "vendor/inner"]
+	path = vendor/inner
+	url = https://github.com/o/inner.git
+[submodule "quoted"]
+	path = "third party/lib/"
+	url = ../lib
+"#;
+
+        assert_eq!(
+            parse_gitmodules(contents),
+            vec!["vendor/inner".to_string(), "third party/lib".to_string()]
+        );
+        assert!(parse_gitmodules("").is_empty());
+    }
+}
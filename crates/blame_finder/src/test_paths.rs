@@ -0,0 +1,37 @@
+use std::sync::LazyLock;
+
+use crate::glob::Glob;
+
+/// Paths that look like test code. Their TODOs still count, but can be made to
+/// look younger with [`ScanOptions::test_file_penalty_days`](crate::ScanOptions::test_file_penalty_days)
+pub const TEST_PATH_PATTERNS: &[&str] = &["*_test.*", "test_*", "*.spec.*", "**/tests/**"];
+
+static TEST_PATH_GLOBS: LazyLock<Vec<Glob>> = LazyLock::new(|| {
+    TEST_PATH_PATTERNS
+        .iter()
+        .map(|pattern| Glob::new(pattern).expect("test path patterns are valid globs"))
+        .collect()
+});
+
+/// Whether the repo-relative `path` matches one of the [`TEST_PATH_PATTERNS`]
+pub fn is_test_path(path: &str) -> bool {
+    TEST_PATH_GLOBS.iter().any(|glob| glob.is_match(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_test_path() {
+        assert!(is_test_path("src/parser_test.go"));
+        assert!(is_test_path("pkg/test_parser.py"));
+        assert!(is_test_path("web/app.spec.ts"));
+        assert!(is_test_path("tests/integration.rs"));
+        assert!(is_test_path("crates/x/tests/fixtures/a.rs"));
+
+        assert!(!is_test_path("src/parser.rs"));
+        assert!(!is_test_path("src/contest/main.rs"));
+        assert!(!is_test_path("src/latest_version.rs"));
+    }
+}
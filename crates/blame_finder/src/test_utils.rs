@@ -0,0 +1,107 @@
+//! Throwaway git repositories and TODOs for tests, here and in crates built on this one
+
+use std::path::Path;
+use std::process::Command;
+
+use chrono::{DateTime, Utc};
+use tempfile::TempDir;
+
+use crate::blame::BlameInfo;
+use crate::todo::TodoItem;
+
+/// An unblamed TODO at `file_path:line_number` of `https://github.com/o/r.git`.
+/// Override any other field with struct update syntax
+pub fn todo_at(file_path: &str, line_number: u32) -> TodoItem {
+    TodoItem {
+        file_path: file_path.to_string(),
+        line_number,
+        column: None,
+        todo_text: "// TODO".to_string(),
+        context_code: String::new(),
+        blame_info: None,
+        source_repo_url: "https://github.com/o/r.git".to_string(),
+        discovered_at: None,
+        untracked: false,
+        request_id: None,
+        context_truncated: false,
+    }
+}
+
+/// A TODO on line 1 of `file_path`, blamed to a commit made at `date`
+pub fn todo_dated(file_path: &str, date: DateTime<Utc>) -> TodoItem {
+    TodoItem {
+        blame_info: Some(BlameInfo {
+            commit_hash: "abc123".to_string(),
+            author: "Test".to_string(),
+            author_email: "test@example.com".to_string(),
+            date,
+            summary: "Add TODO".to_string(),
+            body: None,
+            boundary: false,
+            approximate: false,
+            later_commits: None,
+        }),
+        ..todo_at(file_path, 1)
+    }
+}
+
+/// Run a git command in `dir`, panicking if it fails
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .expect("failed to run git");
+
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Create an empty git repository in a temp dir with a committer identity set
+pub fn init_repo() -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    git(dir.path(), &["init", "-q", "-b", "main"]);
+    git(dir.path(), &["config", "user.name", "Test Author"]);
+    git(dir.path(), &["config", "user.email", "test@example.com"]);
+    dir
+}
+
+/// Write a file and commit it
+pub fn commit_file(dir: &Path, file: &str, contents: &str, message: &str) {
+    let path = dir.join(file);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).unwrap();
+    }
+    std::fs::write(path, contents).unwrap();
+    git(dir, &["add", file]);
+    git(dir, &["commit", "-q", "-m", message]);
+}
+
+/// Write a file and commit it with both author and committer dates set to `date`
+pub fn commit_file_at(dir: &Path, file: &str, contents: &str, message: &str, date: &str) {
+    let path = dir.join(file);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).unwrap();
+    }
+    std::fs::write(path, contents).unwrap();
+    git(dir, &["add", file]);
+
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(["commit", "-q", "-m", message])
+        .env("GIT_AUTHOR_DATE", date)
+        .env("GIT_COMMITTER_DATE", date)
+        .output()
+        .expect("failed to run git");
+    assert!(
+        output.status.success(),
+        "git commit failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
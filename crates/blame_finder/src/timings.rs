@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Instant;
+
+/// How long each phase of a scan took, in milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanTimings {
+    /// Cloning or updating the repository
+    pub clone_ms: u64,
+
+    /// Searching for TODOs with ripgrep
+    pub scan_ms: u64,
+
+    /// Running git blame to find the oldest TODO
+    pub blame_ms: u64,
+}
+
+impl ScanTimings {
+    pub fn total_ms(&self) -> u64 {
+        self.clone_ms + self.scan_ms + self.blame_ms
+    }
+
+    /// Human readable breakdown, e.g. "12.0s to clone, 4.0s to scan, 1.2s to blame"
+    pub fn summary(&self) -> String {
+        format!(
+            "{} to clone, {} to scan, {} to blame",
+            format_secs(self.clone_ms),
+            format_secs(self.scan_ms),
+            format_secs(self.blame_ms)
+        )
+    }
+}
+
+fn format_secs(ms: u64) -> String {
+    format!("{:.1}s", ms as f64 / 1000.0)
+}
+
+/// Await a future, returning its output along with how long it took in milliseconds.
+/// Rounded up, so a phase that ran never reads as the 0 of one that didn't
+pub async fn timed<F: Future>(future: F) -> (F::Output, u64) {
+    let start = Instant::now();
+    let output = future.await;
+    let micros = start.elapsed().as_micros() as u64;
+    (output, micros.div_ceil(1000).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_timed_measures_duration() {
+        let (output, ms) = timed(async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            42
+        })
+        .await;
+
+        assert_eq!(output, 42);
+        assert!(ms >= 20);
+
+        let (_, ms) = timed(async {}).await;
+        assert_eq!(ms, 1);
+    }
+
+    #[test]
+    fn test_summary() {
+        let timings = ScanTimings {
+            clone_ms: 12000,
+            scan_ms: 4000,
+            blame_ms: 1250,
+        };
+
+        assert_eq!(timings.total_ms(), 17250);
+        assert_eq!(
+            timings.summary(),
+            "12.0s to clone, 4.0s to scan, 1.2s to blame"
+        );
+    }
+}
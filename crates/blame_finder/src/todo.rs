@@ -1,15 +1,26 @@
 use anyhow::Result;
-use chrono::Utc;
-use log::debug;
+use chrono::{DateTime, Utc};
+use log::{debug, warn};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
 use tokio::process::Command;
+use tokio::sync::mpsc;
+use url::Url;
 
 use crate::blame::BlameInfo;
+use crate::clock::Clock;
 use crate::error::BlameError;
-use crate::helpers::extract_path_segments;
+use crate::glob::Glob;
+use crate::helpers::{
+    UTF8_BOM, extract_path_segments, looks_binary, strip_bom, strip_control_chars,
+};
+use crate::ignore::TodoIgnore;
 use crate::repo::Repository;
+use crate::submodules::submodule_paths;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TodoItem {
@@ -19,6 +30,10 @@ pub struct TodoItem {
     /// Line number where the TODO appears
     pub line_number: u32,
 
+    /// Column (1-indexed, in bytes) where the first TODO match on the line starts
+    #[serde(default)]
+    pub column: Option<u32>,
+
     /// The actual TODO text
     pub todo_text: String,
 
@@ -30,6 +45,24 @@ pub struct TodoItem {
 
     /// The source repo url, copied here for easy displaying
     pub source_repo_url: String,
+
+    /// When a scan first found this TODO. Missing for items saved before this was recorded
+    #[serde(default)]
+    pub discovered_at: Option<DateTime<Utc>>,
+
+    /// Whether the TODO is in a file git doesn't track yet, so it has no blame and no age
+    #[serde(default)]
+    pub untracked: bool,
+
+    /// The scan request that found this TODO, so a leaderboard entry can be traced
+    /// back to its logs. Never used for equality or ranking
+    #[serde(default)]
+    pub request_id: Option<String>,
+
+    /// Whether `context_code` was cut short, or left empty, to keep a long list of
+    /// TODOs inside its context budget. See [`apply_context_budget`]
+    #[serde(default)]
+    pub context_truncated: bool,
 }
 
 impl PartialEq for TodoItem {
@@ -46,9 +79,12 @@ impl Eq for TodoItem {}
 impl Ord for TodoItem {
     fn cmp(&self, other: &Self) -> Ordering {
         // Compare by age (oldest first), then by file path, then by line number
-        // for stable ordering when ages are equal
-        self.get_age_in_days()
-            .cmp(&other.get_age_in_days())
+        // for stable ordering when ages are equal. Age is derived from the
+        // commit date, so compare dates directly rather than sampling the clock
+        // on every comparison (an older date means a greater age)
+        other
+            .commit_date()
+            .cmp(&self.commit_date())
             .then_with(|| self.file_path.cmp(&other.file_path))
             .then_with(|| self.line_number.cmp(&other.line_number))
     }
@@ -61,18 +97,32 @@ impl PartialOrd for TodoItem {
 }
 
 impl BlameInfo {
-    pub fn get_age_in_days(&self) -> i64 {
-        (Utc::now() - self.date).num_days()
+    /// Age of the blamed commit in whole days, according to `clock`. Ages are never
+    /// persisted, so a reloaded leaderboard always shows fresh values
+    pub fn age_in_days(&self, clock: &dyn Clock) -> i64 {
+        self.age_in_days_at(clock.now())
+    }
+
+    /// Age of the blamed commit in whole days as of `now`
+    pub fn age_in_days_at(&self, now: DateTime<Utc>) -> i64 {
+        (now - self.date).num_days()
     }
 }
 
 impl TodoItem {
-    // Helper method to get the age of a TODO item
-    fn get_age_in_days(&self) -> i64 {
+    /// Age of the TODO's commit in whole days, `None` if it hasn't been blamed
+    pub fn age_in_days(&self, clock: &dyn Clock) -> Option<i64> {
+        self.blame_info
+            .as_ref()
+            .map(|blame| blame.age_in_days(clock))
+    }
+
+    /// Commit date used for ordering. TODOs without blame info sort as the newest
+    pub(crate) fn commit_date(&self) -> DateTime<Utc> {
         self.blame_info
             .as_ref()
-            .map(|blame| blame.get_age_in_days())
-            .unwrap_or(0)
+            .map(|blame| blame.date)
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
     }
 
     pub fn get_permalink_url(&self) -> String {
@@ -90,31 +140,55 @@ impl TodoItem {
         };
 
         // Create different permalink formats based on the repository host
-        let source_repo_url = match self.source_repo_url.strip_suffix(".git") {
-            Some(s) => s.to_string(),
-            None => self.source_repo_url.clone(),
-        };
-        if source_repo_url.contains("github.com") {
+        let source_repo_url = self.web_repo_url();
+        match RepoHost::detect(&source_repo_url) {
             // GitHub format: https://github.com/owner/repo/blob/commit-hash/path/to/file#L123
-            format!(
+            Some(RepoHost::GitHub) => format!(
                 "{}/blob/{}/{}#L{}",
                 source_repo_url, blame_info.commit_hash, path, self.line_number
-            )
-        } else if source_repo_url.contains("gitlab.com") {
+            ),
             // GitLab format: https://gitlab.com/owner/repo/-/blob/commit-hash/path/to/file#L123
-            format!(
+            Some(RepoHost::GitLab) => format!(
                 "{}/-/blob/{}/{}#L{}",
                 source_repo_url, blame_info.commit_hash, path, self.line_number
-            )
-        } else if source_repo_url.contains("bitbucket.org") {
+            ),
             // Bitbucket format: https://bitbucket.org/owner/repo/src/commit-hash/path/to/file#lines-123
-            format!(
+            Some(RepoHost::Bitbucket) => format!(
                 "{}/src/{}/{}#lines-{}",
                 source_repo_url, blame_info.commit_hash, path, self.line_number
-            )
-        } else {
+            ),
+            // Gitea format: https://codeberg.org/owner/repo/src/commit/commit-hash/path/to/file#L123
+            Some(RepoHost::Gitea) => format!(
+                "{}/src/commit/{}/{}#L{}",
+                source_repo_url, blame_info.commit_hash, path, self.line_number
+            ),
             // Default case for other repository hosts - return repo URL
-            self.source_repo_url.clone()
+            None => self.source_repo_url.clone(),
+        }
+    }
+
+    /// Link to the commit that introduced this TODO, if we know it and the host
+    pub fn get_commit_url(&self) -> Option<String> {
+        let commit_hash = &self.blame_info.as_ref()?.commit_hash;
+        let source_repo_url = self.web_repo_url();
+        let url = match RepoHost::detect(&source_repo_url)? {
+            // https://github.com/owner/repo/commit/commit-hash
+            RepoHost::GitHub | RepoHost::Gitea => {
+                format!("{}/commit/{}", source_repo_url, commit_hash)
+            }
+            // https://gitlab.com/owner/repo/-/commit/commit-hash
+            RepoHost::GitLab => format!("{}/-/commit/{}", source_repo_url, commit_hash),
+            // https://bitbucket.org/owner/repo/commits/commit-hash
+            RepoHost::Bitbucket => format!("{}/commits/{}", source_repo_url, commit_hash),
+        };
+        Some(url)
+    }
+
+    /// The source repo URL as browsed on the web, without a trailing `.git`
+    fn web_repo_url(&self) -> String {
+        match self.source_repo_url.strip_suffix(".git") {
+            Some(s) => s.to_string(),
+            None => self.source_repo_url.clone(),
         }
     }
 
@@ -141,44 +215,68 @@ impl TodoItem {
 
         // For other repository hosts, return the domain + first path segment
         // Try to extract something meaningful from the URL
-        if let Some(domain_start) = url.find("://") {
-            if let Some(domain_end) = url[domain_start + 3..].find('/') {
-                let path = &url[domain_start + 3 + domain_end + 1..];
-
-                // Return first two path segments if available
-                if let Some(path_sep) = path.find('/') {
-                    let owner = &path[..path_sep];
-                    let repo = &path[path_sep + 1..];
-
-                    if let Some(query_sep) = repo.find('?') {
-                        return format!("{}/{}", owner, &repo[..query_sep]);
-                    } else {
-                        return format!("{}/{}", owner, repo);
-                    }
-                }
+        if let Some(domain_start) = url.find("://")
+            && let Some(domain_end) = url[domain_start + 3..].find('/')
+        {
+            let path = &url[domain_start + 3 + domain_end + 1..];
 
-                // If only one segment, return it
-                return path.to_string();
+            // Return first two path segments if available
+            if let Some(path_sep) = path.find('/') {
+                let owner = &path[..path_sep];
+                let repo = &path[path_sep + 1..];
+
+                if let Some(query_sep) = repo.find('?') {
+                    return format!("{}/{}", owner, &repo[..query_sep]);
+                } else {
+                    return format!("{}/{}", owner, repo);
+                }
             }
+
+            // If only one segment, return it
+            return path.to_string();
         }
 
         // Fallback: just return the URL as is
         url.clone()
     }
+
+    /// Host of the source repository, e.g. `github.com`
+    pub fn get_repo_host(&self) -> Option<String> {
+        Url::parse(&self.source_repo_url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_lowercase()))
+    }
 }
 
-/// Find all TODOs in the repository using ripgrep
-pub async fn find_todos(repo: &Repository) -> Result<Vec<TodoItem>, BlameError> {
+/// Hosts we know how to build links into
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RepoHost {
+    GitHub,
+    GitLab,
+    Bitbucket,
+    Gitea,
+}
+
+impl RepoHost {
+    fn detect(repo_url: &str) -> Option<Self> {
+        if repo_url.contains("github.com") {
+            Some(RepoHost::GitHub)
+        } else if repo_url.contains("gitlab.com") {
+            Some(RepoHost::GitLab)
+        } else if repo_url.contains("bitbucket.org") {
+            Some(RepoHost::Bitbucket)
+        } else if repo_url.contains("codeberg.org") || repo_url.contains("gitea.com") {
+            Some(RepoHost::Gitea)
+        } else {
+            None
+        }
+    }
+}
+
+/// Find all TODOs in the repository using ripgrep, each discovered at `clock`'s time
+pub async fn find_todos(repo: &Repository, clock: &dyn Clock) -> Result<Vec<TodoItem>, BlameError> {
     debug!("Starting search for todos w/ rg");
-    let output = Command::new("rg")
-        .current_dir(repo.path())
-        .arg("TODO")
-        .arg("--line-number") // Include line numbers in the output
-        .arg("--no-heading") // Don't group matches by file
-        .arg("--color=never") // No color codes in output
-        .arg("--max-columns=1000") // Avoid truncating long lines
-        .arg("-g") // Specify glob patterns
-        .arg("!.git/") // Exclude .git directory
+    let output = ripgrep_command(repo)
         .output()
         .await
         .map_err(|e| BlameError::SearchError(format!("Failed to execute ripgrep: {}", e)))?;
@@ -198,49 +296,539 @@ pub async fn find_todos(repo: &Repository) -> Result<Vec<TodoItem>, BlameError>
 
     // Parse the output
     let output_str = String::from_utf8_lossy(&output.stdout);
-    parse_ripgrep_output(repo.path(), repo.url().to_owned(), &output_str)
+    let todos = RipgrepParser::for_repo(repo, clock).parse_output(&output_str)?;
+
+    let filter = TodoFilter::load(repo)?;
+    Ok(todos
+        .into_iter()
+        .filter(|todo| filter.keeps(todo))
+        .collect())
+}
+
+/// Find all TODOs like [`find_todos`], but send each one down `sender` as soon as
+/// ripgrep reports it rather than collecting them first
+///
+/// Lets blame start on the first TODOs while the search is still running, and the
+/// channel's capacity caps how many unblamed TODOs are held at once. Returns how many
+/// TODOs were sent. Stops searching early, without error, if the receiver is dropped.
+pub async fn stream_todos(
+    repo: &Repository,
+    clock: &dyn Clock,
+    sender: mpsc::Sender<TodoItem>,
+) -> Result<usize, BlameError> {
+    let filter = TodoFilter::load(repo)?;
+
+    debug!("Starting streaming search for todos w/ rg");
+    let mut child = ripgrep_command(repo)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| BlameError::SearchError(format!("Failed to execute ripgrep: {}", e)))?;
+
+    // Drain stderr alongside stdout so a chatty rg can't block on a full pipe
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let stderr_task = tokio::spawn(async move {
+        let mut err = Vec::new();
+        let _ = stderr.read_to_end(&mut err).await;
+        err
+    });
+
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let mut lines = BufReader::new(stdout).lines();
+    let mut parser = RipgrepParser::for_repo(repo, clock);
+    let mut sent = 0;
+
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .map_err(|e| BlameError::SearchError(format!("Failed to read ripgrep output: {}", e)))?
+    {
+        let Some(todo) = parser.parse_line(&line)? else {
+            continue;
+        };
+        if !filter.keeps(&todo) {
+            continue;
+        }
+        if sender.send(todo).await.is_err() {
+            debug!("TODO receiver went away, stopping the search");
+            return Ok(sent);
+        }
+        sent += 1;
+    }
+
+    let status = child
+        .wait()
+        .await
+        .map_err(|e| BlameError::SearchError(format!("Failed to wait for ripgrep: {}", e)))?;
+    let err = stderr_task.await.unwrap_or_default();
+    debug!("finished streaming search with rg");
+
+    // Exit code 1 with nothing on stderr is just "no matches"
+    if !status.success() && !err.is_empty() {
+        return Err(BlameError::SearchError(format!(
+            "Ripgrep search failed: {}",
+            String::from_utf8_lossy(&err)
+        )));
+    }
+
+    Ok(sent)
+}
+
+/// The `rg` invocation shared by the collecting and streaming searches
+fn ripgrep_command(repo: &Repository) -> Command {
+    let mut command = Command::new("rg");
+    command.current_dir(repo.path());
+    match repo.custom_pattern() {
+        Some(pattern) => command.args(["-e", pattern]),
+        // `@todo` is the JSDoc/PHPDoc tag, `@TODO` is covered by the first
+        None => command.args(["-e", "TODO", "-e", "@todo"]),
+    };
+    command
+        .arg("--json") // Structured output, unambiguous for paths containing colons
+        .arg("--no-follow") // Never follow symlinks out of the clone (rg's default, made explicit)
+        .arg("-g") // Specify glob patterns
+        .arg("!.git/"); // Exclude .git directory
+    for extension in repo.extensions() {
+        command.arg("--iglob").arg(format!("*.{}", extension));
+    }
+    command.args(repo.subpath()); // Restrict the search to a subdirectory
+    command
+}
+
+/// Which found TODOs the caller actually wants
+struct TodoFilter {
+    /// Anything the maintainers asked us to ignore
+    ignore: TodoIgnore,
+
+    /// The files the caller asked about, all of them when `None`
+    path_glob: Option<Glob>,
+
+    /// Submodule directories, their files belong to other repositories and can't
+    /// be blamed from this one
+    submodules: Vec<String>,
+}
+
+impl TodoFilter {
+    fn load(repo: &Repository) -> Result<Self, BlameError> {
+        Ok(TodoFilter {
+            ignore: TodoIgnore::load(repo.path())?,
+            path_glob: repo.path_glob()?,
+            submodules: submodule_paths(repo.path()),
+        })
+    }
+
+    fn keeps(&self, todo: &TodoItem) -> bool {
+        if let Some(submodule) = self.submodule_of(&todo.file_path) {
+            debug!(
+                "Skipping {}:{}, it's inside submodule {}",
+                todo.file_path, todo.line_number, submodule
+            );
+            return false;
+        }
+
+        !self.ignore.is_ignored(todo)
+            && self
+                .path_glob
+                .as_ref()
+                .is_none_or(|glob| glob.is_match(&todo.file_path))
+    }
+
+    /// The submodule `file_path` lives in, if any
+    fn submodule_of(&self, file_path: &str) -> Option<&str> {
+        self.submodules
+            .iter()
+            .map(String::as_str)
+            .find(|submodule| {
+                file_path
+                    .strip_prefix(submodule)
+                    .is_some_and(|rest| rest.starts_with('/'))
+            })
+    }
+}
+
+/// Keep the combined `context_code` of `todos` within `budget` bytes
+///
+/// Context is kept in list order, so earlier TODOs keep theirs in full. The first one
+/// that doesn't fit keeps the whole lines that do, and every one after it loses its
+/// context entirely. Each cut TODO is marked [`context_truncated`](TodoItem::context_truncated)
+pub fn apply_context_budget(todos: &mut [TodoItem], budget: usize) {
+    let mut remaining = budget;
+    for todo in todos {
+        let len = todo.context_code.len();
+        if len <= remaining {
+            remaining -= len;
+            continue;
+        }
+
+        let mut cut = remaining;
+        while !todo.context_code.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        // Prefer ending on a whole line, unless not even the first one fits
+        let cut = todo.context_code[..cut].rfind('\n').unwrap_or(cut);
+        todo.context_code.truncate(cut);
+        todo.context_truncated = true;
+        remaining = 0;
+    }
+}
+
+/// How many of `todos` are in each kind of file, most first and ties by name
+///
+/// Files are told apart by extension, e.g. `.rs`, or by their whole name when they
+/// have none, e.g. `Makefile`.
+pub fn count_by_extension(todos: &[TodoItem]) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for todo in todos {
+        let path = Path::new(&todo.file_path);
+        let kind = match path.extension() {
+            Some(extension) => format!(".{}", extension.to_string_lossy()),
+            None => path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| todo.file_path.clone()),
+        };
+        *counts.entry(kind).or_default() += 1;
+    }
+
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+    counts
+}
+
+/// Longest TODO line we keep, minified files can otherwise produce enormous matches.
+/// Also caps each line of context
+const MAX_TODO_TEXT_CHARS: usize = 1000;
+
+/// How much of the line before the TODO to keep when a long line is cut down
+const TODO_LEAD_IN_CHARS: usize = 40;
+
+/// Marks where a long line was cut
+const ELLIPSIS: char = '…';
+
+/// The `type` tag of a `rg --json` record
+#[derive(Deserialize)]
+struct RipgrepRecordType {
+    #[serde(rename = "type")]
+    kind: String,
 }
 
-/// Parse the output from ripgrep into TodoItem structs
+/// A `match` record from `rg --json`
+#[derive(Deserialize)]
+struct RipgrepMatchRecord {
+    data: RipgrepMatch,
+}
+
+#[derive(Deserialize)]
+struct RipgrepMatch {
+    path: RipgrepText,
+    lines: RipgrepText,
+    line_number: Option<u32>,
+    submatches: Vec<RipgrepSubmatch>,
+}
+
+/// ripgrep emits `{"text": ...}` for UTF-8 data and `{"bytes": ...}` (base64) otherwise
+#[derive(Deserialize)]
+struct RipgrepText {
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RipgrepSubmatch {
+    start: usize,
+}
+
+/// A single TODO match pulled out of the ripgrep output
+#[derive(Debug, PartialEq)]
+struct RipgrepHit {
+    file_path: String,
+    line_number: u32,
+    column: Option<u32>,
+    text: String,
+}
+
+/// Parse the `rg --json` output of a TODO keyword search into TodoItem structs
+///
+/// `context_lines` is how many lines to keep above and below each TODO
+#[cfg(test)]
 fn parse_ripgrep_output(
     repo_path: &Path,
     repo_url: String,
     output: &str,
+    context_lines: (u32, u32),
 ) -> Result<Vec<TodoItem>, BlameError> {
-    let mut todos = Vec::new();
+    RipgrepParser::new(
+        repo_path,
+        repo_url,
+        context_lines,
+        &crate::clock::SystemClock,
+    )
+    .parse_output(output)
+}
 
-    for line in output.lines() {
-        // Format: file:line:content
-        let parts: Vec<&str> = line.splitn(3, ':').collect();
-        if parts.len() != 3 {
-            continue; // Skip invalid lines
+/// Turns `rg --json` records into TodoItems one line at a time, remembering which
+/// lines it has already seen so a repeated match is only reported once
+struct RipgrepParser<'a> {
+    repo_path: &'a Path,
+    repo_url: String,
+    context_lines: (u32, u32),
+    seen: HashSet<(String, u32)>,
+    discovered_at: DateTime<Utc>,
+    /// Tidy `@todo` docblock tags into plain TODOs. Only our own keywords search for
+    /// them, a custom pattern's matches are left as they are
+    doc_tags: bool,
+}
+
+impl<'a> RipgrepParser<'a> {
+    /// A parser stamping every TODO as discovered at `clock`'s time
+    fn new(
+        repo_path: &'a Path,
+        repo_url: String,
+        context_lines: (u32, u32),
+        clock: &dyn Clock,
+    ) -> Self {
+        RipgrepParser {
+            repo_path,
+            repo_url,
+            context_lines,
+            seen: HashSet::new(),
+            discovered_at: clock.now(),
+            doc_tags: true,
         }
+    }
 
-        let file_path = parts[0].trim();
-        let line_number = parts[1]
-            .trim()
-            .parse::<u32>()
-            .map_err(|_| BlameError::ParseError(format!("Invalid line number: {}", parts[1])))?;
-        let todo_text = parts[2].trim();
+    /// A parser for the output of [`ripgrep_command`] on `repo`
+    fn for_repo(repo: &'a Repository, clock: &dyn Clock) -> Self {
+        RipgrepParser {
+            doc_tags: repo.custom_pattern().is_none(),
+            ..RipgrepParser::new(
+                repo.path(),
+                repo.url().to_owned(),
+                repo.context_lines(),
+                clock,
+            )
+        }
+    }
+
+    /// Every new match in a whole `rg --json` output
+    ///
+    /// Malformed records are logged and skipped so one odd match doesn't abort the whole
+    /// scan. A line reported more than once only yields one TodoItem, from its first record.
+    fn parse_output(&mut self, output: &str) -> Result<Vec<TodoItem>, BlameError> {
+        let mut todos = Vec::new();
+        for line in output.lines() {
+            if let Some(todo) = self.parse_line(line)? {
+                todos.push(todo);
+            }
+        }
+        Ok(todos)
+    }
+
+    /// The TodoItem for one line of output, `None` if it isn't a new match
+    fn parse_line(&mut self, line: &str) -> Result<Option<TodoItem>, BlameError> {
+        let hit = match parse_ripgrep_record(line) {
+            Ok(Some(hit)) => hit,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                warn!("Skipping ripgrep output: {}", e);
+                return Ok(None);
+            }
+        };
+
+        // Overlapping patterns can report the same line twice, which would otherwise
+        // be blamed twice and could take two leaderboard spots
+        if !self.seen.insert((hit.file_path.clone(), hit.line_number)) {
+            debug!(
+                "Skipping duplicate match at {}:{}",
+                hit.file_path, hit.line_number
+            );
+            return Ok(None);
+        }
 
         // Read the file to get context
-        let context_code = get_context(repo_path, file_path, line_number)?;
+        let (context_before, context_after) = self.context_lines;
+        let context_code = match get_context(
+            self.repo_path,
+            &hit.file_path,
+            hit.line_number,
+            context_before,
+            context_after,
+        ) {
+            Ok(context_code) => context_code,
+            Err(BlameError::PathOutsideRepo(path)) => {
+                warn!(
+                    "Skipping TODO in {}, it resolves outside the repository",
+                    path
+                );
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
 
-        todos.push(TodoItem {
-            file_path: file_path.to_string(),
-            line_number,
-            todo_text: todo_text.to_string(),
+        Ok(Some(TodoItem {
+            file_path: hit.file_path,
+            line_number: hit.line_number,
+            column: hit.column,
+            todo_text: if self.doc_tags {
+                normalize_doc_tag(&hit.text)
+            } else {
+                hit.text
+            },
             context_code,
             blame_info: None, // Will be filled in later
-            source_repo_url: repo_url.clone(),
-        });
+            source_repo_url: self.repo_url.clone(),
+            discovered_at: Some(self.discovered_at),
+            untracked: false,
+            request_id: None,
+            context_truncated: false,
+        }))
+    }
+}
+
+/// Parse a single `rg --json` record, returning `None` for non-match records
+/// (`begin`, `end`, `summary`, ...)
+fn parse_ripgrep_record(line: &str) -> Result<Option<RipgrepHit>, BlameError> {
+    let malformed = |reason: String| BlameError::RipgrepLineError {
+        raw_line: line.to_string(),
+        reason,
+    };
+
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let record: RipgrepRecordType =
+        serde_json::from_str(line).map_err(|e| malformed(e.to_string()))?;
+    if record.kind != "match" {
+        return Ok(None);
+    }
+
+    let record: RipgrepMatchRecord =
+        serde_json::from_str(line).map_err(|e| malformed(e.to_string()))?;
+    let data = record.data;
+
+    let file_path = data
+        .path
+        .text
+        .ok_or_else(|| malformed("path is not valid UTF-8".to_string()))?;
+    let line_number = match data.line_number {
+        Some(0) | None => {
+            return Err(malformed(format!("missing line number in {}", file_path)));
+        }
+        Some(n) => n,
+    };
+    let text = data.lines.text.ok_or_else(|| {
+        malformed(format!(
+            "line {} of {} is not valid UTF-8",
+            line_number, file_path
+        ))
+    })?;
+
+    // ripgrep reports a leading BOM as part of the first line, so drop it from both
+    // the text and the match offsets
+    let (text, bom_len) = match text.strip_prefix(UTF8_BOM) {
+        Some(stripped) if line_number == 1 => (stripped.to_string(), UTF8_BOM.len()),
+        _ => (text, 0),
+    };
+
+    // ripgrep gives byte offsets, columns are 1-indexed
+    let match_start = data
+        .submatches
+        .first()
+        .map(|submatch| submatch.start.saturating_sub(bom_len));
+    let column = match_start.and_then(|start| u32::try_from(start + 1).ok());
+
+    Ok(Some(RipgrepHit {
+        file_path,
+        line_number,
+        column,
+        text: strip_control_chars(&todo_text_window(&text, match_start.unwrap_or(0))),
+    }))
+}
+
+/// Trim a matched line for display, cutting overly long lines down to a window
+/// that still contains the TODO at byte offset `match_start`
+fn todo_text_window(line: &str, match_start: usize) -> String {
+    let trimmed = line.trim();
+    let total = trimmed.chars().count();
+    if total <= MAX_TODO_TEXT_CHARS {
+        return trimmed.to_string();
+    }
+
+    // Find the match within the trimmed line, in chars rather than bytes
+    let leading = line.len() - line.trim_start().len();
+    let mut start = match_start.saturating_sub(leading).min(trimmed.len());
+    while !trimmed.is_char_boundary(start) {
+        start -= 1;
+    }
+    let match_char = trimmed[..start].chars().count();
+
+    // Start a little before the TODO, but don't run the window off the end of the line
+    let window_start = match_char
+        .saturating_sub(TODO_LEAD_IN_CHARS)
+        .min(total - MAX_TODO_TEXT_CHARS);
+    let window_end = window_start + MAX_TODO_TEXT_CHARS;
+
+    let mut text = String::new();
+    if window_start > 0 {
+        text.push(ELLIPSIS);
+    }
+    text.extend(trimmed.chars().skip(window_start).take(MAX_TODO_TEXT_CHARS));
+    if window_end < total {
+        text.push(ELLIPSIS);
+    }
+    text
+}
+
+/// Docblock tag that marks a TODO, matched case-insensitively
+const DOC_TAG: &str = "@todo";
+
+/// Turn a docblock tag line such as ` * @todo fix this` or `/** @TODO */` into
+/// `TODO: fix this` or `TODO`, without the comment markers and `@`. Any other line
+/// comes back unchanged
+fn normalize_doc_tag(text: &str) -> String {
+    let inner =
+        text.trim_start_matches(|c: char| matches!(c, '/' | '*' | '#') || c.is_whitespace());
+    let Some(tag) = inner.get(..DOC_TAG.len()) else {
+        return text.to_string();
+    };
+    let rest = &inner[DOC_TAG.len()..];
+    // `@todos` or `@todo_list` aren't the tag
+    if !tag.eq_ignore_ascii_case(DOC_TAG)
+        || rest.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+    {
+        return text.to_string();
+    }
+
+    let message = rest.trim();
+    let message = message.strip_suffix("*/").unwrap_or(message).trim_end();
+    let message = message.strip_prefix(':').unwrap_or(message).trim_start();
+    if message.is_empty() {
+        "TODO".to_string()
+    } else {
+        format!("TODO: {}", message)
     }
+}
 
-    Ok(todos)
+/// Cut a context line down to `MAX_TODO_TEXT_CHARS`, marking that it was cut
+fn shorten_line(line: &str) -> String {
+    if line.chars().count() <= MAX_TODO_TEXT_CHARS {
+        return line.to_string();
+    }
+    let mut short: String = line.chars().take(MAX_TODO_TEXT_CHARS).collect();
+    short.push(ELLIPSIS);
+    short
 }
 
-/// Get the code context around a specific line in a file
-fn get_context(repo_path: &Path, file_path: &str, line_number: u32) -> Result<String, BlameError> {
+/// Get the code context around a specific line in a file, with `before` lines above it
+/// and `after` lines below, cut short at the start and end of the file
+fn get_context(
+    repo_path: &Path,
+    file_path: &str,
+    line_number: u32,
+    before: u32,
+    after: u32,
+) -> Result<String, BlameError> {
     let full_path = repo_path.join(file_path);
     if !full_path.exists() {
         return Err(BlameError::FileError(format!(
@@ -249,19 +837,836 @@ fn get_context(repo_path: &Path, file_path: &str, line_number: u32) -> Result<St
         )));
     }
 
-    let content = std::fs::read_to_string(&full_path)
+    // Symlinks or `..` entries in a crafted repo must not let us read files outside it
+    let resolved = full_path
+        .canonicalize()
+        .map_err(|e| BlameError::FileError(format!("Failed to resolve {}: {}", file_path, e)))?;
+    let repo_root = repo_path
+        .canonicalize()
+        .map_err(|e| BlameError::FileError(format!("Failed to resolve repository path: {}", e)))?;
+    if !resolved.starts_with(&repo_root) {
+        return Err(BlameError::PathOutsideRepo(file_path.to_string()));
+    }
+
+    let bytes = std::fs::read(&resolved)
         .map_err(|e| BlameError::FileError(format!("Failed to read file {}: {}", file_path, e)))?;
 
-    let lines: Vec<&str> = content.lines().collect();
+    // Binary files have no meaningful context to show, so skip it rather than failing
+    if looks_binary(&bytes) {
+        debug!("Skipping context for binary file {}", file_path);
+        return Ok(String::new());
+    }
+
+    // Near-text files (e.g. odd encodings) still get context, with invalid bytes replaced.
+    // A BOM isn't a line, so stripping it keeps line numbers aligned with ripgrep's
+    let content = String::from_utf8_lossy(strip_bom(&bytes));
 
-    // Line numbers in the file are 1-indexed
-    let line_idx = line_number as usize - 1;
+    // `lines()` keeps a final line with no trailing newline, as ripgrep does, but leaves
+    // a bare `\r` on it when the file ends in an old-style line ending
+    let lines: Vec<&str> = content
+        .lines()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
 
-    // Get context (2 lines before and after)
-    let start_line = line_idx.saturating_sub(2);
-    let end_line = std::cmp::min(line_idx + 3, lines.len());
+    // Line numbers in the file are 1-indexed. One past the end (say the file changed
+    // since ripgrep read it) gets no context rather than an empty or shifted slice
+    let Some(line_idx) = (line_number as usize)
+        .checked_sub(1)
+        .filter(|idx| *idx < lines.len())
+    else {
+        debug!(
+            "Line {} is out of range for {} ({} lines)",
+            line_number,
+            file_path,
+            lines.len()
+        );
+        return Ok(String::new());
+    };
 
-    let context = lines[start_line..end_line].join("\n");
+    let start_line = line_idx.saturating_sub(before as usize);
+    let end_line = line_idx
+        .saturating_add(after as usize)
+        .saturating_add(1)
+        .min(lines.len());
+
+    let context = lines[start_line..end_line]
+        .iter()
+        .map(|line| shorten_line(&strip_control_chars(line)))
+        .collect::<Vec<_>>()
+        .join("\n");
 
     Ok(context)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{FixedClock, SystemClock};
+    use crate::test_utils::{commit_file, git, init_repo, todo_dated};
+    use chrono::{Duration, TimeZone};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_commit_url_per_host() {
+        let cases = [
+            (
+                "https://github.com/o/r.git",
+                "https://github.com/o/r/commit/abc123",
+            ),
+            (
+                "https://gitlab.com/o/r",
+                "https://gitlab.com/o/r/-/commit/abc123",
+            ),
+            (
+                "https://bitbucket.org/o/r.git",
+                "https://bitbucket.org/o/r/commits/abc123",
+            ),
+            (
+                "https://codeberg.org/o/r.git",
+                "https://codeberg.org/o/r/commit/abc123",
+            ),
+        ];
+
+        for (repo_url, commit_url) in cases {
+            let mut todo = todo_dated("a.rs", Utc::now());
+            todo.source_repo_url = repo_url.to_string();
+            assert_eq!(todo.get_commit_url().as_deref(), Some(commit_url));
+        }
+    }
+
+    #[test]
+    fn test_commit_url_none_without_blame_or_known_host() {
+        let mut todo = todo_dated("a.rs", Utc::now());
+        todo.source_repo_url = "https://git.example.com/o/r.git".to_string();
+        assert_eq!(todo.get_commit_url(), None);
+
+        let mut todo = todo_dated("a.rs", Utc::now());
+        todo.blame_info = None;
+        assert_eq!(todo.get_commit_url(), None);
+    }
+
+    #[test]
+    fn test_age_is_computed_from_now_not_persist_time() {
+        // Persist a TODO that was 10 days old at the time it was saved
+        let persisted_at = Utc::now() - Duration::days(20);
+        let todo = todo_dated("a.rs", persisted_at - Duration::days(10));
+        let json = serde_json::to_string(&todo).unwrap();
+        assert!(!json.contains("age"));
+
+        // Reloading it later shows its age as of now
+        let reloaded: TodoItem = serde_json::from_str(&json).unwrap();
+        let blame = reloaded.blame_info.unwrap();
+        assert_eq!(blame.age_in_days_at(persisted_at), 10);
+        assert_eq!(blame.age_in_days(&SystemClock), 30);
+    }
+
+    #[test]
+    fn test_age_with_fixed_clock() {
+        let committed = Utc.with_ymd_and_hms(2020, 1, 1, 12, 0, 0).unwrap();
+        let todo = todo_dated("a.rs", committed);
+
+        // Whole days only, so a second short of the 30th day still counts as 29
+        let just_before = FixedClock(committed + Duration::days(30) - Duration::seconds(1));
+        let exactly = FixedClock(committed + Duration::days(30));
+        assert_eq!(todo.age_in_days(&just_before), Some(29));
+        assert_eq!(todo.age_in_days(&exactly), Some(30));
+
+        let mut unblamed = todo;
+        unblamed.blame_info = None;
+        assert_eq!(unblamed.age_in_days(&exactly), None);
+    }
+
+    #[test]
+    fn test_ordering_uses_commit_date() {
+        let now = Utc::now();
+        let older = todo_dated("b.rs", now - Duration::days(100));
+        let newer = todo_dated("a.rs", now - Duration::days(1));
+        let mut unblamed = todo_dated("c.rs", now);
+        unblamed.blame_info = None;
+
+        assert!(older > newer);
+        assert!(newer > unblamed);
+    }
+
+    fn match_record(path: &str, line_number: u32, line: &str, starts: &[usize]) -> String {
+        let submatches: Vec<_> = starts
+            .iter()
+            .map(|start| serde_json::json!({"match": {"text": "TODO"}, "start": start, "end": start + 4}))
+            .collect();
+        serde_json::json!({
+            "type": "match",
+            "data": {
+                "path": {"text": path},
+                "lines": {"text": format!("{}\n", line)},
+                "line_number": line_number,
+                "absolute_offset": 0,
+                "submatches": submatches,
+            }
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_parse_ripgrep_output_skips_malformed_lines() {
+        let dir = tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("main.rs"),
+            "fn main() {\n    // TODO: first\n}\n// TODO: second\n",
+        )
+        .unwrap();
+
+        let output = [
+            r#"{"type":"begin","data":{"path":{"text":"main.rs"}}}"#.to_string(),
+            match_record("main.rs", 2, "    // TODO: first", &[7]),
+            "Binary file matches (found \"\\0\" byte around offset 12)".to_string(),
+            r#"{"type":"match","data":{"path":{"text":"main.rs"}}}"#.to_string(),
+            match_record("main.rs", 4, "// TODO: second", &[3]),
+            r#"{"type":"end","data":{"path":{"text":"main.rs"}}}"#.to_string(),
+        ]
+        .join("\n");
+
+        let todos = parse_ripgrep_output(
+            dir.path(),
+            "https://github.com/o/r.git".to_string(),
+            &output,
+            (2, 2),
+        )
+        .unwrap();
+
+        assert_eq!(todos.len(), 2);
+        assert_eq!(todos[0].line_number, 2);
+        assert_eq!(todos[0].column, Some(8));
+        assert_eq!(todos[0].todo_text, "// TODO: first");
+        assert_eq!(todos[1].line_number, 4);
+    }
+
+    #[test]
+    fn test_parse_ripgrep_output_merges_matches_on_same_line() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("main.rs"), "// FIXME: TODO: both\n").unwrap();
+
+        // As emitted by a search for several keywords, one record per keyword
+        let output = [
+            match_record("main.rs", 1, "// FIXME: TODO: both", &[3]),
+            match_record("main.rs", 1, "// FIXME: TODO: both", &[10]),
+        ]
+        .join("\n");
+
+        let todos = parse_ripgrep_output(
+            dir.path(),
+            "https://github.com/o/r.git".to_string(),
+            &output,
+            (2, 2),
+        )
+        .unwrap();
+
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].line_number, 1);
+        // The first record wins, pointing at the first keyword
+        assert_eq!(todos[0].column, Some(4));
+        assert_eq!(todos[0].todo_text, "// FIXME: TODO: both");
+    }
+
+    #[test]
+    fn test_parser_stamps_discovery_with_clock() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("main.rs"), "// TODO: when\n").unwrap();
+        let found_at = Utc.with_ymd_and_hms(2024, 6, 1, 9, 30, 0).unwrap();
+
+        let todos = RipgrepParser::new(
+            dir.path(),
+            "https://github.com/o/r.git".to_string(),
+            (2, 2),
+            &FixedClock(found_at),
+        )
+        .parse_output(&match_record("main.rs", 1, "// TODO: when", &[3]))
+        .unwrap();
+
+        assert_eq!(todos[0].discovered_at, Some(found_at));
+    }
+
+    #[test]
+    fn test_context_budget_truncates_later_todos() {
+        let date = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let mut todos: Vec<TodoItem> = (0..10)
+            .map(|i| {
+                let mut todo = todo_dated(&format!("file{}.rs", i), date);
+                // 3 lines of 10 bytes, 32 bytes with the newlines
+                todo.context_code = "aaaaaaaaaa\nbbbbbbbbbb\ncccccccccc".to_string();
+                todo
+            })
+            .collect();
+
+        apply_context_budget(&mut todos, 80);
+
+        // Two fit whole, leaving 16 bytes: one line of the third
+        for todo in &todos[..2] {
+            assert_eq!(todo.context_code.len(), 32);
+            assert!(!todo.context_truncated);
+        }
+        assert_eq!(todos[2].context_code, "aaaaaaaaaa");
+        assert!(todos[2].context_truncated);
+        for todo in &todos[3..] {
+            assert_eq!(todo.context_code, "");
+            assert!(todo.context_truncated);
+        }
+    }
+
+    #[test]
+    fn test_context_budget_cuts_on_char_boundary() {
+        let date = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let mut todos = vec![todo_dated("main.rs", date)];
+        todos[0].context_code = "// TODO: é".to_string();
+
+        // The `é` is two bytes, the budget ends halfway through it
+        apply_context_budget(&mut todos, 10);
+
+        assert_eq!(todos[0].context_code, "// TODO: ");
+        assert!(todos[0].context_truncated);
+    }
+
+    #[test]
+    fn test_count_by_extension() {
+        let date = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let todos: Vec<_> = [
+            "src/main.rs",
+            "README.md",
+            "src/lib.rs",
+            "Cargo.toml",
+            "Makefile",
+            "docs/guide.md",
+            "src/deep/mod.rs",
+        ]
+        .into_iter()
+        .map(|file| todo_dated(file, date))
+        .collect();
+
+        assert_eq!(
+            count_by_extension(&todos),
+            [
+                (".rs".to_string(), 3),
+                (".md".to_string(), 2),
+                (".toml".to_string(), 1),
+                ("Makefile".to_string(), 1),
+            ]
+        );
+        assert!(count_by_extension(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_ripgrep_output_strips_escape_sequences() {
+        let dir = tempdir().unwrap();
+        let line = "// \u{1b}[2J\u{1b}]0;owned\u{7}TODO: \u{1b}[31mclean\u{1b}[0m me\u{8}";
+        std::fs::write(
+            dir.path().join("main.rs"),
+            format!("fn main() {{\u{1b}[1A\r\n{}\n}}\n", line),
+        )
+        .unwrap();
+
+        let output = match_record("main.rs", 2, line, &[17]);
+        let todos = parse_ripgrep_output(
+            dir.path(),
+            "https://github.com/o/r.git".to_string(),
+            &output,
+            (1, 1),
+        )
+        .unwrap();
+
+        assert_eq!(todos[0].todo_text, "// TODO: clean me");
+        assert_eq!(todos[0].context_code, "fn main() {\n// TODO: clean me\n}");
+    }
+
+    #[test]
+    fn test_parse_ripgrep_record_path_with_colon() {
+        let record = match_record("docs/notes:v2.md", 12, "TODO: fix", &[0]);
+
+        let hit = parse_ripgrep_record(&record).unwrap().unwrap();
+
+        assert_eq!(hit.file_path, "docs/notes:v2.md");
+        assert_eq!(hit.line_number, 12);
+        assert_eq!(hit.column, Some(1));
+        assert_eq!(hit.text, "TODO: fix");
+    }
+
+    #[test]
+    fn test_parse_ripgrep_record_multiple_submatches() {
+        let record = match_record(
+            r"C:\src\lib.rs",
+            3,
+            "// TODO: a TODO inside a TODO",
+            &[3, 12, 26],
+        );
+
+        let hit = parse_ripgrep_record(&record).unwrap().unwrap();
+
+        assert_eq!(hit.file_path, r"C:\src\lib.rs");
+        assert_eq!(hit.line_number, 3);
+        // The column points at the first match on the line
+        assert_eq!(hit.column, Some(4));
+        assert_eq!(hit.text, "// TODO: a TODO inside a TODO");
+    }
+
+    #[tokio::test]
+    async fn test_long_todo_line_keeps_todo_in_view() {
+        let dir = init_repo();
+        let line = format!(
+            "{}// TODO: buried deep{}",
+            "x".repeat(2500),
+            "y".repeat(480)
+        );
+        assert_eq!(line.len(), 3000);
+        std::fs::write(dir.path().join("min.js"), format!("a\n{}\nb\n", line)).unwrap();
+
+        let repo = Repository::from_local_path(dir.path()).unwrap();
+        let todos = find_todos(&repo, &SystemClock).await.unwrap();
+        assert_eq!(todos.len(), 1);
+        let todo = &todos[0];
+
+        // The column still points into the real line
+        assert_eq!(todo.column, Some(2504));
+        assert_eq!(&line[2503..2507], "TODO");
+
+        // The text is cut down around the TODO rather than before it
+        assert!(todo.todo_text.contains("// TODO: buried deep"));
+        assert!(todo.todo_text.starts_with(ELLIPSIS));
+        assert!(todo.todo_text.chars().count() <= MAX_TODO_TEXT_CHARS + 2);
+
+        // Context lines are capped too
+        let context_lines: Vec<&str> = todo.context_code.lines().collect();
+        assert_eq!(context_lines.len(), 3);
+        assert_eq!(context_lines[0], "a");
+        assert!(context_lines[1].ends_with(ELLIPSIS));
+        assert_eq!(context_lines[1].chars().count(), MAX_TODO_TEXT_CHARS + 1);
+    }
+
+    #[test]
+    fn test_todo_text_window_near_line_end() {
+        let line = format!("{}TODO", "z".repeat(MAX_TODO_TEXT_CHARS * 2));
+        let text = todo_text_window(&line, MAX_TODO_TEXT_CHARS * 2);
+
+        // The window is pinned to the end of the line, so nothing is cut after it
+        assert!(text.starts_with(ELLIPSIS));
+        assert!(text.ends_with("zTODO"));
+        assert_eq!(text.chars().count(), MAX_TODO_TEXT_CHARS + 1);
+    }
+
+    #[test]
+    fn test_normalize_doc_tag() {
+        assert_eq!(normalize_doc_tag("* @todo fix this"), "TODO: fix this");
+        assert_eq!(normalize_doc_tag("/** @TODO */"), "TODO");
+        assert_eq!(normalize_doc_tag("// @todo: tidy up */"), "TODO: tidy up");
+        assert_eq!(normalize_doc_tag("# @Todo later"), "TODO: later");
+
+        // Not a docblock tag, left as ripgrep found it
+        assert_eq!(normalize_doc_tag("// TODO: plain"), "// TODO: plain");
+        assert_eq!(normalize_doc_tag("* @todos list"), "* @todos list");
+        assert_eq!(normalize_doc_tag("x = 1 // @todo"), "x = 1 // @todo");
+    }
+
+    #[tokio::test]
+    async fn test_find_todos_cleans_docblock_tags() {
+        let repos_dir = tempdir().unwrap();
+        let repo = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+        std::fs::create_dir_all(repo.path()).unwrap();
+        std::fs::write(
+            repo.path().join("lib.js"),
+            "/**\n * Parse input\n * @todo fix this\n */\nfunction parse() {}\n/** @TODO */\n",
+        )
+        .unwrap();
+
+        let mut todos = find_todos(&repo, &SystemClock).await.unwrap();
+        todos.sort_by_key(|todo| todo.line_number);
+
+        let found: Vec<_> = todos
+            .iter()
+            .map(|todo| (todo.line_number, todo.todo_text.as_str()))
+            .collect();
+        assert_eq!(found, vec![(3, "TODO: fix this"), (6, "TODO")]);
+    }
+
+    #[tokio::test]
+    async fn test_custom_pattern_replaces_keywords() {
+        let repos_dir = tempdir().unwrap();
+        let repo = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .repos_dir(repos_dir.path())
+            .custom_pattern(r"TODO\(@me\)|JIRA-\d+")
+            .build()
+            .await
+            .unwrap();
+        std::fs::create_dir_all(repo.path()).unwrap();
+        std::fs::write(
+            repo.path().join("lib.rs"),
+            "// TODO: someone else's\n// TODO(@me): mine\n/** @todo not mine */\nlet x = 1; // see JIRA-42\n",
+        )
+        .unwrap();
+
+        let mut todos = find_todos(&repo, &SystemClock).await.unwrap();
+        todos.sort_by_key(|todo| todo.line_number);
+
+        let found: Vec<_> = todos
+            .iter()
+            .map(|todo| (todo.line_number, todo.column, todo.todo_text.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (2, Some(4), "// TODO(@me): mine"),
+                (4, Some(18), "let x = 1; // see JIRA-42")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_custom_pattern_is_rejected() {
+        let result = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .custom_pattern("TODO(")
+            .build()
+            .await;
+        assert!(matches!(result, Err(BlameError::InvalidPattern(_))));
+
+        let dir = tempdir().unwrap();
+        crate::test_utils::git(dir.path(), &["init", "-q"]);
+        let local = Repository::from_local_path(dir.path()).unwrap();
+        assert!(matches!(
+            local.clone().with_custom_pattern(""),
+            Err(BlameError::InvalidPattern(_))
+        ));
+        assert_eq!(
+            local.with_custom_pattern("FIXME").unwrap().custom_pattern(),
+            Some("FIXME")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_outside_repo_is_not_read() {
+        let outside = tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.rs"), "// TODO: secret\n").unwrap();
+        let repo = tempdir().unwrap();
+        std::os::unix::fs::symlink(
+            outside.path().join("secret.rs"),
+            repo.path().join("link.rs"),
+        )
+        .unwrap();
+        std::fs::write(repo.path().join("lib.rs"), "// TODO: inside\n").unwrap();
+
+        let err = get_context(repo.path(), "link.rs", 1, 2, 2).unwrap_err();
+        assert!(matches!(err, BlameError::PathOutsideRepo(_)));
+        assert!(matches!(
+            get_context(repo.path(), "../secret.rs", 1, 2, 2),
+            Err(BlameError::FileError(_) | BlameError::PathOutsideRepo(_))
+        ));
+
+        // A match through the link is skipped, the rest of the scan carries on
+        let output = [
+            match_record("link.rs", 1, "// TODO: secret", &[3]),
+            match_record("lib.rs", 1, "// TODO: inside", &[3]),
+        ]
+        .join("\n");
+        let todos = parse_ripgrep_output(
+            repo.path(),
+            "https://github.com/o/r.git".to_string(),
+            &output,
+            (2, 2),
+        )
+        .unwrap();
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].file_path, "lib.rs");
+    }
+
+    #[test]
+    fn test_bom_is_stripped_from_context_and_matches() {
+        let dir = tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("win.cs"),
+            "\u{FEFF}// TODO: first\r\nclass A {}\r\n// TODO: third\r\n",
+        )
+        .unwrap();
+
+        let context = get_context(dir.path(), "win.cs", 1, 2, 2).unwrap();
+        assert!(!context.contains('\u{FEFF}'));
+        assert!(context.starts_with("// TODO: first"));
+        assert_eq!(
+            get_context(dir.path(), "win.cs", 3, 2, 2).unwrap(),
+            "// TODO: first\nclass A {}\n// TODO: third"
+        );
+
+        // ripgrep includes the BOM in the first line and its byte offsets
+        let record = match_record("win.cs", 1, "\u{FEFF}// TODO: first", &[6]);
+        let hit = parse_ripgrep_record(&record).unwrap().unwrap();
+        assert_eq!(hit.text, "// TODO: first");
+        assert_eq!(hit.column, Some(4));
+    }
+
+    #[test]
+    fn test_get_context_skips_binary_files() {
+        let dir = tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("blob.bin"),
+            b"\x00\x01// TODO: hidden\n\xff\xfe",
+        )
+        .unwrap();
+
+        let context = get_context(dir.path(), "blob.bin", 1, 2, 2).unwrap();
+
+        assert_eq!(context, "");
+    }
+
+    #[test]
+    fn test_get_context_reads_invalid_utf8_lossily() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("latin1.c"), b"// caf\xe9\n// TODO: fix\n").unwrap();
+
+        let context = get_context(dir.path(), "latin1.c", 2, 2, 2).unwrap();
+
+        assert_eq!(context, "// caf\u{FFFD}\n// TODO: fix");
+    }
+
+    #[tokio::test]
+    async fn test_submodule_todos_are_skipped() {
+        let inner = init_repo();
+        commit_file(
+            inner.path(),
+            "inner.rs",
+            "// TODO: not ours\n",
+            "Add inner.rs",
+        );
+        let outer = init_repo();
+        commit_file(outer.path(), "outer.rs", "// TODO: ours\n", "Add outer.rs");
+        git(
+            outer.path(),
+            &[
+                "-c",
+                "protocol.file.allow=always",
+                "submodule",
+                "add",
+                "-q",
+                inner.path().to_str().unwrap(),
+                "vendor/inner",
+            ],
+        );
+        git(outer.path(), &["commit", "-q", "-m", "Add submodule"]);
+        assert!(outer.path().join("vendor/inner/inner.rs").exists());
+
+        let repo = Repository::from_local_path(outer.path()).unwrap();
+        let todos = find_todos(&repo, &SystemClock).await.unwrap();
+        let paths: Vec<_> = todos.iter().map(|todo| todo.file_path.as_str()).collect();
+        assert_eq!(paths, ["outer.rs"]);
+
+        let (sender, mut receiver) = mpsc::channel(8);
+        assert_eq!(stream_todos(&repo, &SystemClock, sender).await.unwrap(), 1);
+        assert_eq!(receiver.recv().await.unwrap().file_path, "outer.rs");
+    }
+
+    #[tokio::test]
+    async fn test_todo_on_last_line_without_trailing_newline() {
+        let dir = init_repo();
+        std::fs::write(
+            dir.path().join("tail.rs"),
+            "fn a() {}\nfn b() {}\nfn c() {}\n// TODO: last byte",
+        )
+        .unwrap();
+
+        let repo = Repository::from_local_path(dir.path()).unwrap();
+        let todos = find_todos(&repo, &SystemClock).await.unwrap();
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].line_number, 4);
+        assert_eq!(todos[0].todo_text, "// TODO: last byte");
+        assert_eq!(
+            todos[0].context_code,
+            "fn b() {}\nfn c() {}\n// TODO: last byte"
+        );
+
+        // Same again with a stray carriage return as the very last byte
+        std::fs::write(dir.path().join("tail.rs"), "fn a() {}\r\n// TODO: cr\r").unwrap();
+        assert_eq!(
+            get_context(dir.path(), "tail.rs", 2, 2, 2).unwrap(),
+            "fn a() {}\n// TODO: cr"
+        );
+
+        // A line number past the end gets no context instead of panicking
+        assert_eq!(get_context(dir.path(), "tail.rs", 3, 2, 2).unwrap(), "");
+        assert_eq!(get_context(dir.path(), "tail.rs", 0, 2, 2).unwrap(), "");
+    }
+
+    #[test]
+    fn test_get_context_asymmetric_windows() {
+        let dir = tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("main.rs"),
+            "one\ntwo\nthree\n// TODO: four\nfive\nsix\nseven\n",
+        )
+        .unwrap();
+        let context = |line, before, after| get_context(dir.path(), "main.rs", line, before, after);
+
+        assert_eq!(context(4, 0, 2).unwrap(), "// TODO: four\nfive\nsix");
+        assert_eq!(
+            context(4, 3, 3).unwrap(),
+            "one\ntwo\nthree\n// TODO: four\nfive\nsix\nseven"
+        );
+        assert_eq!(context(4, 1, 0).unwrap(), "three\n// TODO: four");
+        assert_eq!(context(4, 0, 0).unwrap(), "// TODO: four");
+
+        // Windows running off either end of the file are cut short
+        assert_eq!(context(2, 5, 0).unwrap(), "one\ntwo");
+        assert_eq!(context(6, 0, 5).unwrap(), "six\nseven");
+        assert_eq!(context(4, u32::MAX, u32::MAX).unwrap().lines().count(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_find_todos_uses_repository_context_lines() {
+        let repos_dir = tempdir().unwrap();
+        let repo = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .repos_dir(repos_dir.path())
+            .context_before(0)
+            .context_after(2)
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(repo.context_lines(), (0, 2));
+        std::fs::create_dir_all(repo.path()).unwrap();
+        std::fs::write(
+            repo.path().join("main.rs"),
+            "one\ntwo\n// TODO: three\nfour\nfive\nsix\n",
+        )
+        .unwrap();
+
+        let todos = find_todos(&repo, &SystemClock).await.unwrap();
+
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].context_code, "// TODO: three\nfour\nfive");
+    }
+
+    #[tokio::test]
+    async fn test_path_filter_excludes_todos_outside_glob() {
+        let repos_dir = tempdir().unwrap();
+        let repo = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .repos_dir(repos_dir.path())
+            .path_filter("src/**")
+            .build()
+            .await
+            .unwrap();
+        for (file, text) in [
+            ("src/parser/lexer.rs", "// TODO: in src\n"),
+            ("src/main.rs", "// TODO: also in src\n"),
+            ("tests/parser.rs", "// TODO: outside\n"),
+            ("build.rs", "// TODO: top level\n"),
+        ] {
+            let path = repo.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+
+        let mut files: Vec<String> = find_todos(&repo, &SystemClock)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|todo| todo.file_path)
+            .collect();
+        files.sort();
+
+        assert_eq!(files, vec!["src/main.rs", "src/parser/lexer.rs"]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_todos_matches_find_todos() {
+        let repos_dir = tempdir().unwrap();
+        let repo = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+        for (file, text) in [
+            ("src/lib.rs", "fn a() {}\n// TODO: one\n// TODO: two\n"),
+            ("src/nested/deep.rs", "// TODO: three\nfn b() {}\n"),
+            ("README.md", "No todos here\n"),
+            ("notes.txt", "TODO: four\n"),
+            ("skipped.rs", "// TODO: ignored\n"),
+        ] {
+            let path = repo.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+        std::fs::write(repo.path().join(".todoignore"), "skipped.rs\n").unwrap();
+
+        let key = |todo: &TodoItem| {
+            (
+                todo.file_path.clone(),
+                todo.line_number,
+                todo.column,
+                todo.todo_text.clone(),
+                todo.context_code.clone(),
+            )
+        };
+        let mut batched: Vec<_> = find_todos(&repo, &SystemClock)
+            .await
+            .unwrap()
+            .iter()
+            .map(key)
+            .collect();
+        batched.sort();
+
+        // A tiny channel makes the search wait on the consumer
+        let (sender, mut receiver) = mpsc::channel(1);
+        let search = stream_todos(&repo, &SystemClock, sender);
+        let collect = async {
+            let mut streamed = Vec::new();
+            while let Some(todo) = receiver.recv().await {
+                streamed.push(key(&todo));
+            }
+            streamed
+        };
+        let (sent, mut streamed) = tokio::join!(search, collect);
+        streamed.sort();
+
+        assert_eq!(sent.unwrap(), 4);
+        assert_eq!(streamed, batched);
+    }
+
+    #[tokio::test]
+    async fn test_stream_todos_stops_when_receiver_dropped() {
+        let repos_dir = tempdir().unwrap();
+        let repo = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+        std::fs::create_dir_all(repo.path()).unwrap();
+        let many = "// TODO: again\n".repeat(50);
+        std::fs::write(repo.path().join("main.rs"), many).unwrap();
+
+        let (sender, receiver) = mpsc::channel(1);
+        drop(receiver);
+
+        assert_eq!(stream_todos(&repo, &SystemClock, sender).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_path_filter_is_rejected() {
+        let result = Repository::builder("https://github.com/rupert648/willdolater.dev")
+            .path_filter("src/[")
+            .build()
+            .await;
+
+        assert!(matches!(result, Err(BlameError::InvalidPattern(_))));
+    }
+
+    #[test]
+    fn test_parse_ripgrep_record_error_contains_raw_line() {
+        let line = r#"{"type":"match","data":{"path":{"bytes":"/w=="},"lines":{"text":"TODO"},"line_number":1,"submatches":[]}}"#;
+
+        let err = parse_ripgrep_record(line).unwrap_err();
+
+        match err {
+            BlameError::RipgrepLineError { raw_line, reason } => {
+                assert_eq!(raw_line, line);
+                assert!(reason.contains("UTF-8"));
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use regex::Regex;
+
+use crate::error::BlameError;
+
+/// Separates the pattern from the replacement in a rule like `^https://github.com/=>https://git.internal/github/`
+const RULE_SEPARATOR: &str = "=>";
+
+/// Rewrites repository URLs before they're cloned, e.g. to go through an internal mirror
+///
+/// Only the clone and fetch see the rewritten URL, permalinks and the leaderboard
+/// keep showing the URL the user submitted.
+#[derive(Debug, Clone)]
+pub struct UrlRewrite {
+    pattern: Regex,
+    replacement: String,
+}
+
+impl UrlRewrite {
+    /// Replace the first match of the `pattern` regex with `replacement`, which may
+    /// refer to capture groups as `$1` or `${name}`
+    pub fn new(pattern: &str, replacement: &str) -> Result<Self, BlameError> {
+        let pattern = Regex::new(pattern).map_err(|e| {
+            BlameError::InvalidPattern(format!("Invalid URL rewrite {:?}: {}", pattern, e))
+        })?;
+        Ok(UrlRewrite {
+            pattern,
+            replacement: replacement.to_string(),
+        })
+    }
+
+    /// Parse a `pattern=>replacement` rule
+    pub fn parse(rule: &str) -> Result<Self, BlameError> {
+        let (pattern, replacement) = rule.split_once(RULE_SEPARATOR).ok_or_else(|| {
+            BlameError::InvalidPattern(format!(
+                "URL rewrite {:?} must look like pattern{}replacement",
+                rule, RULE_SEPARATOR
+            ))
+        })?;
+        Self::new(pattern.trim(), replacement.trim())
+    }
+
+    /// The URL to clone `url` from, unchanged if the pattern doesn't match
+    pub fn apply(&self, url: &str) -> String {
+        self.pattern
+            .replace(url, self.replacement.as_str())
+            .into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_rewrites_matching_urls_only() {
+        let rewrite =
+            UrlRewrite::parse("^https://github.com/=>https://git.internal/github/").unwrap();
+
+        assert_eq!(
+            rewrite.apply("https://github.com/owner/repo.git"),
+            "https://git.internal/github/owner/repo.git"
+        );
+        assert_eq!(
+            rewrite.apply("https://gitlab.com/owner/repo.git"),
+            "https://gitlab.com/owner/repo.git"
+        );
+    }
+
+    #[test]
+    fn test_apply_expands_capture_groups() {
+        let rewrite = UrlRewrite::new(
+            r"^https://github\.com/(?<owner>[^/]+)/",
+            "https://mirror.example/${owner}-mirror/",
+        )
+        .unwrap();
+
+        assert_eq!(
+            rewrite.apply("https://github.com/o/r.git"),
+            "https://mirror.example/o-mirror/r.git"
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_rules() {
+        assert!(matches!(
+            UrlRewrite::parse("^https://github.com/"),
+            Err(BlameError::InvalidPattern(_))
+        ));
+        assert!(matches!(
+            UrlRewrite::parse("(unclosed=>x"),
+            Err(BlameError::InvalidPattern(_))
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+//! Runs the `find_todos` example against a local fixture repository
+
+// Run in-process rather than building and spawning the example binary
+#[allow(dead_code)]
+#[path = "../examples/find_todos.rs"]
+mod find_todos;
+
+use blame_finder::SystemClock;
+use blame_finder::test_utils::{commit_file_at, git, init_repo};
+use tempfile::TempDir;
+
+/// A repo with an old TODO and a newer one
+fn fixture() -> TempDir {
+    let repo = init_repo();
+    commit_file_at(
+        repo.path(),
+        "old.rs",
+        "// TODO: old.rs\n",
+        "old.rs",
+        "2015-01-01T00:00:00Z",
+    );
+    commit_file_at(
+        repo.path(),
+        "new.rs",
+        "// TODO: new.rs\n",
+        "new.rs",
+        "2022-01-01T00:00:00Z",
+    );
+    repo
+}
+
+/// Output of one in-process run of the example
+struct Output {
+    code: i32,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+async fn run(args: &[&str]) -> Output {
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+    let args = args.iter().map(|arg| arg.to_string());
+    let code = find_todos::run(args, &SystemClock, &mut stdout, &mut stderr).await;
+    Output {
+        code,
+        stdout,
+        stderr,
+    }
+}
+
+async fn run_example(args: &[&str]) -> Output {
+    let output = run(args).await;
+    assert_eq!(
+        output.code,
+        0,
+        "example failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+#[tokio::test]
+async fn test_local_prints_oldest_todo() {
+    let repo = fixture();
+
+    let output = run_example(&["--local", repo.path().to_str().unwrap()]).await;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Found oldest TODO!"));
+    assert!(stdout.contains("File: old.rs"));
+}
+
+#[tokio::test]
+async fn test_local_all_prints_every_todo_oldest_first() {
+    let repo = fixture();
+
+    let output = run_example(&["--all", "--local", repo.path().to_str().unwrap()]).await;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let old = stdout.find("old.rs:1").expect("old TODO missing");
+    let new = stdout.find("new.rs:1").expect("new TODO missing");
+    assert!(old < new);
+}
+
+#[tokio::test]
+async fn test_local_all_labels_untracked_todos() {
+    let repo = fixture();
+    std::fs::write(repo.path().join("draft.rs"), "// TODO: draft\n").unwrap();
+
+    let output = run_example(&[
+        "--all",
+        "--untracked",
+        "--local",
+        repo.path().to_str().unwrap(),
+    ])
+    .await;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let draft = stdout
+        .lines()
+        .find(|line| line.contains("draft.rs:1"))
+        .expect("untracked TODO missing");
+    assert!(draft.contains("untracked"));
+    assert!(stdout.find("new.rs:1").unwrap() < stdout.find("draft.rs:1").unwrap());
+}
+
+#[tokio::test]
+async fn test_local_json_prints_oldest_todo_item() {
+    let repo = fixture();
+
+    let output = run_example(&["--format", "json", "--local", repo.path().to_str().unwrap()]).await;
+
+    let todo: Option<blame_finder::TodoItem> = serde_json::from_slice(&output.stdout).unwrap();
+    let todo = todo.expect("no TODO in the JSON");
+    assert_eq!(todo.file_path, "old.rs");
+    assert_eq!(todo.line_number, 1);
+    assert!(todo.blame_info.is_some());
+}
+
+#[tokio::test]
+async fn test_local_json_prints_null_without_todos() {
+    let repo = fixture();
+    git(repo.path(), &["rm", "-q", "old.rs", "new.rs"]);
+    git(repo.path(), &["commit", "-q", "-m", "Done"]);
+
+    let output = run_example(&["--format", "json", "--local", repo.path().to_str().unwrap()]).await;
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "null");
+}
+
+#[tokio::test]
+async fn test_missing_target_prints_usage() {
+    let output = run(&[]).await;
+
+    assert_eq!(output.code, 1);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Usage"));
+}
@@ -0,0 +1,198 @@
+//! Scans real, if tiny, git repositories from end to end: search, blame and ranking
+
+use std::path::Path;
+
+use blame_finder::test_utils::{commit_file_at, init_repo};
+use blame_finder::{
+    BlameError, FixedClock, Repository, ScanOptions, ScanResult, SystemClock, TodoItem,
+};
+use chrono::{DateTime, Utc};
+use tempfile::TempDir;
+
+fn date(rfc3339: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(rfc3339)
+        .unwrap()
+        .with_timezone(&Utc)
+}
+
+/// Write `file` and commit it at `date`, with the file's path as the message
+fn commit_at(dir: &Path, file: &str, contents: &str, date: &str) {
+    commit_file_at(dir, file, contents, file, date);
+}
+
+/// Scan the fixture in place, through the local checkout API
+fn repository(dir: &TempDir) -> Repository {
+    Repository::from_local_path(dir.path()).unwrap()
+}
+
+/// Three TODOs in three files, committed years apart and out of order
+fn fixture() -> TempDir {
+    let dir = init_repo();
+    commit_at(
+        dir.path(),
+        "src/middle.rs",
+        "// TODO: middle\n",
+        "2018-06-01T12:00:00Z",
+    );
+    commit_at(
+        dir.path(),
+        "src/old.rs",
+        "fn main() {}\n// TODO: old\n",
+        "2012-03-04T12:00:00Z",
+    );
+    commit_at(
+        dir.path(),
+        "src/new.rs",
+        "// TODO: new\n",
+        "2023-09-10T12:00:00Z",
+    );
+    dir
+}
+
+fn locations(todos: &[TodoItem]) -> Vec<String> {
+    todos
+        .iter()
+        .map(|todo| format!("{}:{}", todo.file_path, todo.line_number))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_scan_finds_the_oldest_todo() {
+    let fixture = fixture();
+
+    let result = blame_finder::scan(&repository(&fixture), &SystemClock)
+        .await
+        .unwrap();
+
+    let todo = result.into_todo().expect("expected a TODO");
+    assert_eq!(todo.file_path, "src/old.rs");
+    assert_eq!(todo.line_number, 2);
+    assert!(todo.todo_text.contains("TODO: old"));
+    let blame = todo.blame_info.expect("the oldest TODO is blamed");
+    assert_eq!(blame.date, date("2012-03-04T12:00:00Z"));
+    assert_eq!(blame.summary, "src/old.rs");
+}
+
+#[tokio::test]
+#[allow(deprecated)]
+async fn test_find_oldest_todo() {
+    let fixture = fixture();
+
+    let todo = blame_finder::find_oldest_todo(&repository(&fixture), &SystemClock)
+        .await
+        .unwrap()
+        .expect("expected a TODO");
+    assert_eq!(todo.file_path, "src/old.rs");
+
+    let empty = init_repo();
+    commit_at(
+        empty.path(),
+        "lib.rs",
+        "pub fn done() {}\n",
+        "2020-01-01T00:00:00Z",
+    );
+    let none = blame_finder::find_oldest_todo(&repository(&empty), &SystemClock)
+        .await
+        .unwrap();
+    assert!(none.is_none());
+}
+
+#[tokio::test]
+async fn test_find_all_todos_lists_oldest_first() {
+    let fixture = fixture();
+    // A TODO added to an existing file is dated by its own commit, not the file's
+    commit_at(
+        fixture.path(),
+        "src/old.rs",
+        "fn main() {}\n// TODO: old\n// TODO: added later\n",
+        "2020-02-02T12:00:00Z",
+    );
+
+    let todos = blame_finder::find_all_todos(&repository(&fixture), &SystemClock)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        locations(&todos),
+        [
+            "src/old.rs:2",
+            "src/middle.rs:1",
+            "src/old.rs:3",
+            "src/new.rs:1"
+        ]
+    );
+    let dates: Vec<_> = todos
+        .iter()
+        .map(|todo| todo.blame_info.as_ref().unwrap().date)
+        .collect();
+    assert_eq!(
+        dates,
+        [
+            date("2012-03-04T12:00:00Z"),
+            date("2018-06-01T12:00:00Z"),
+            date("2020-02-02T12:00:00Z"),
+            date("2023-09-10T12:00:00Z"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_ages_follow_commit_dates() {
+    let fixture = fixture();
+    let clock = FixedClock(date("2024-01-01T12:00:00Z"));
+
+    let mut todos = blame_finder::find_all_todos(&repository(&fixture), &clock)
+        .await
+        .unwrap();
+    let ages: Vec<_> = todos
+        .iter()
+        .map(|todo| todo.age_in_days(&clock).unwrap())
+        .collect();
+    assert_eq!(ages, [4320, 2040, 113]);
+
+    // `Ord` ranks older TODOs greater, so sorting puts the newest first
+    todos.sort();
+    assert_eq!(
+        locations(&todos),
+        ["src/new.rs:1", "src/middle.rs:1", "src/old.rs:2"]
+    );
+}
+
+#[tokio::test]
+async fn test_date_range_limits_the_oldest() {
+    let fixture = fixture();
+    let options = ScanOptions {
+        since: Some(date("2015-01-01T00:00:00Z")),
+        ..ScanOptions::default()
+    };
+
+    let result = blame_finder::scan_with_options(&repository(&fixture), &options, &SystemClock)
+        .await
+        .unwrap();
+    let todo = result.into_todo().expect("expected a TODO");
+    assert_eq!(todo.file_path, "src/middle.rs");
+
+    let options = ScanOptions {
+        since: Some(date("2024-01-01T00:00:00Z")),
+        ..ScanOptions::default()
+    };
+    let result =
+        blame_finder::scan_with_options(&repository(&fixture), &options, &SystemClock).await;
+    assert!(matches!(result, Err(BlameError::NoTodosInDateRange)));
+}
+
+#[tokio::test]
+async fn test_repository_without_todos() {
+    let fixture = init_repo();
+    commit_at(
+        fixture.path(),
+        "lib.rs",
+        "pub fn done() {}\n",
+        "2020-01-01T00:00:00Z",
+    );
+
+    let result = blame_finder::scan(&repository(&fixture), &SystemClock)
+        .await
+        .unwrap();
+    assert!(matches!(result, ScanResult::NoTodos));
+}
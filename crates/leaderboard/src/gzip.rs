@@ -0,0 +1,63 @@
+//! gzip (RFC 1952) storage format, by way of flate2
+
+use std::io::{Read, Write};
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+use crate::LeaderboardError;
+
+const MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Whether `data` starts with the gzip magic bytes
+pub fn is_gzip(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    // Writing to a Vec can't fail
+    encoder.write_all(data).expect("writing to memory");
+    encoder.finish().expect("writing to memory")
+}
+
+pub fn decode(data: &[u8]) -> Result<Vec<u8>, LeaderboardError> {
+    if !is_gzip(data) {
+        return Err(LeaderboardError::CompressionError(
+            "not a gzip file".to_string(),
+        ));
+    }
+
+    let mut decompressed = Vec::new();
+    GzDecoder::new(data)
+        .read_to_end(&mut decompressed)
+        .map_err(|e| LeaderboardError::CompressionError(e.to_string()))?;
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let data = b"{\"items\": []}".repeat(100);
+
+        let encoded = encode(&data);
+
+        assert!(is_gzip(&encoded));
+        assert!(encoded.len() < data.len());
+        assert_eq!(decode(&encoded).unwrap(), data);
+    }
+
+    #[test]
+    fn test_decode_rejects_corruption() {
+        let mut encoded = encode(b"hello leaderboard");
+        let last = encoded.len() - 5;
+        encoded[last] ^= 0xff;
+
+        assert!(decode(&encoded).is_err());
+        assert!(decode(b"plain text").is_err());
+    }
+}
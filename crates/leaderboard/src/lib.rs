@@ -1,24 +1,50 @@
+use blame_finder::{Clock, SystemClock};
+use chrono::{DateTime, SubsecRound, Utc};
 use serde::{Deserialize, Serialize};
-use std::cmp::Ord;
-use std::collections::BTreeSet;
+use std::cmp::{Ord, Ordering};
 use std::fs;
-use std::path::Path;
-use std::sync::Arc;
+use std::iter::Rev;
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
 use tokio::sync::RwLock;
 
-// Trait that defines all requirements for an item that can be stored in a leaderboard
+mod gzip;
+
+// Trait that defines all requirements for an item that can be stored in a leaderboard.
+// Ranking comes from `Ord` by default, or from a comparator passed to `new_with_cmp`
 pub trait Leaderboardable:
-    Clone + Serialize + for<'de> Deserialize<'de> + Ord + Send + Sync + 'static
+    Clone + Serialize + for<'de> Deserialize<'de> + PartialEq + Send + Sync + 'static
 {
 }
 
 // Implement Leaderboardable for any type that satisfies all the required traits
 impl<T> Leaderboardable for T where
-    T: Clone + Serialize + for<'de> Deserialize<'de> + Ord + Send + Sync + 'static
+    T: Clone + Serialize + for<'de> Deserialize<'de> + PartialEq + Send + Sync + 'static
 {
 }
 
+/// Ranks two leaderboard items, `Greater` meaning the first is the better entry
+pub type Comparator<T> = Arc<dyn Fn(&T, &T) -> Ordering + Send + Sync>;
+
+/// What became of an item offered to a leaderboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryAddResult {
+    /// Already on the board, or not good enough to make it
+    NotAdded,
+    /// On the board below the top, `rank` counting from 1 for the best item
+    Added { rank: usize },
+    /// On the board as its new best item
+    AddedTop,
+}
+
+impl TryAddResult {
+    pub fn is_added(&self) -> bool {
+        !matches!(self, TryAddResult::NotAdded)
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum LeaderboardError {
     #[error("Failed to read leaderboard file: {0}")]
@@ -26,17 +52,43 @@ pub enum LeaderboardError {
 
     #[error("Failed to parse leaderboard data: {0}")]
     ParseError(#[from] serde_json::Error),
+
+    #[error("Failed to decompress leaderboard file: {0}")]
+    CompressionError(String),
+
+    #[error("Failed to create leaderboard directory {path}: {source}")]
+    CreateDirError {
+        path: String,
+        source: std::io::Error,
+    },
 }
 
 pub struct Leaderboard<T>
 where
     T: Leaderboardable,
 {
-    items: BTreeSet<T>,
+    // Kept sorted by `cmp`, worst item first
+    items: Vec<T>,
     max_items: usize,
     storage_path: String,
+    cmp: Comparator<T>,
+    // Gzip the storage file, chosen by a `.gz` extension on the storage path
+    compress: bool,
+    // How many timestamped copies of the storage file to keep, 0 takes none
+    keep_snapshots: usize,
+    // When the newest snapshot was taken, so each one gets a later name
+    last_snapshot: Option<DateTime<Utc>>,
+    // What snapshots are timestamped by
+    clock: Arc<dyn Clock>,
+    // The items best first as of the last change, shared by readers until the next
+    // one. Built on demand, behind its own lock so readers can fill it in
+    view: Mutex<Option<Arc<Vec<T>>>>,
 }
 
+/// Timestamp in snapshot names, e.g. `leaderboard.20250102-030405-678.json`.
+/// Sorting names sorts snapshots oldest first
+const SNAPSHOT_STAMP_FORMAT: &str = "%Y%m%d-%H%M%S-%3f";
+
 #[derive(Clone)]
 pub struct SharedLeaderboard<T>
 where
@@ -45,9 +97,20 @@ where
     inner: Arc<RwLock<Leaderboard<T>>>,
 }
 
-impl<T> SharedLeaderboard<T>
+impl<T> From<Leaderboard<T>> for SharedLeaderboard<T>
 where
     T: Leaderboardable,
+{
+    fn from(leaderboard: Leaderboard<T>) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(leaderboard)),
+        }
+    }
+}
+
+impl<T> SharedLeaderboard<T>
+where
+    T: Leaderboardable + Ord,
 {
     pub async fn new(storage_path: String, max_items: usize) -> Result<Self, LeaderboardError> {
         let leaderboard = Leaderboard::new(storage_path, max_items)?;
@@ -55,17 +118,82 @@ where
             inner: Arc::new(RwLock::new(leaderboard)),
         })
     }
+}
+
+impl<T> SharedLeaderboard<T>
+where
+    T: Leaderboardable,
+{
+    /// A leaderboard ranked by `cmp` rather than the item's `Ord`, so the same
+    /// type can be ranked differently on different boards
+    pub async fn new_with_cmp(
+        storage_path: String,
+        max_items: usize,
+        cmp: Comparator<T>,
+    ) -> Result<Self, LeaderboardError> {
+        let leaderboard = Leaderboard::new_with_cmp(storage_path, max_items, cmp)?;
+        Ok(Self {
+            inner: Arc::new(RwLock::new(leaderboard)),
+        })
+    }
 
     pub async fn try_add(&self, item: T) -> bool {
+        self.try_add_ranked(item).await.is_added()
+    }
+
+    /// Like [`try_add`](Self::try_add), also telling where the item landed
+    pub async fn try_add_ranked(&self, item: T) -> TryAddResult {
+        let mut leaderboard = self.inner.write().await;
+        leaderboard.try_add_ranked(item)
+    }
+
+    /// Remove every item matching `predicate`, returning the removed items
+    pub async fn remove(&self, predicate: impl Fn(&T) -> bool) -> Vec<T> {
         let mut leaderboard = self.inner.write().await;
-        leaderboard.try_add(item)
+        leaderboard.remove(predicate)
+    }
+
+    /// Every item, best first, shared with every other reader until the board next
+    /// changes. See [`Leaderboard::view`]
+    pub async fn snapshot(&self) -> Arc<Vec<T>> {
+        let leaderboard = self.inner.read().await;
+        leaderboard.view()
     }
 
+    /// Every item, best first. See [`Leaderboard::best_first`]
     pub async fn get_items(&self) -> Vec<T> {
         let leaderboard = self.inner.read().await;
-        // Convert BTreeSet to Vec - items will already be sorted based on Ord implementation
-        // For a leaderboard, we typically want highest scores first, so we reverse
-        leaderboard.items.iter().cloned().rev().collect()
+        leaderboard.best_first().cloned().collect()
+    }
+
+    /// Run `f` over the items (best first) under a single read lock, without cloning them
+    pub async fn with_items<R>(&self, f: impl FnOnce(Rev<slice::Iter<'_, T>>) -> R) -> R {
+        let leaderboard = self.inner.read().await;
+        f(leaderboard.best_first())
+    }
+
+    /// The lowest ranked item, i.e. the one a new item has to beat once the board is full
+    pub async fn peek_worst(&self) -> Option<T> {
+        let leaderboard = self.inner.read().await;
+        leaderboard.peek_worst().cloned()
+    }
+
+    /// The highest ranked item
+    pub async fn peek_best(&self) -> Option<T> {
+        let leaderboard = self.inner.read().await;
+        leaderboard.peek_best().cloned()
+    }
+
+    /// Replace the items with what's in the storage file, see [`Leaderboard::reload`]
+    pub async fn reload(&self) -> Result<(), LeaderboardError> {
+        let mut leaderboard = self.inner.write().await;
+        leaderboard.reload()
+    }
+
+    /// Write the items to the storage file now, see [`Leaderboard::persist`]
+    pub async fn persist(&self) -> Result<(), LeaderboardError> {
+        let mut leaderboard = self.inner.write().await;
+        leaderboard.persist()
     }
 
     // For convenience when you want to clone the shared instance
@@ -78,72 +206,368 @@ where
 
 impl<T> Leaderboard<T>
 where
-    T: Leaderboardable,
+    T: Leaderboardable + Ord,
 {
     pub fn new(storage_path: String, max_items: usize) -> Result<Self, LeaderboardError> {
-        let items_vec = if Path::new(&storage_path).exists() {
-            let file_content = fs::read_to_string(&storage_path)?;
-            serde_json::from_str::<Vec<T>>(&file_content)?
+        Self::new_with_cmp(storage_path, max_items, Arc::new(T::cmp))
+    }
+}
+
+impl<T> Leaderboard<T>
+where
+    T: Leaderboardable,
+{
+    pub fn new_with_cmp(
+        storage_path: String,
+        max_items: usize,
+        cmp: Comparator<T>,
+    ) -> Result<Self, LeaderboardError> {
+        let items = if Path::new(&storage_path).exists() {
+            load_items(Path::new(&storage_path), &cmp)?
         } else {
+            ensure_parent_dir(&storage_path)?;
             Vec::new()
         };
 
-        // Convert Vec to BTreeSet
-        let items: BTreeSet<T> = items_vec.into_iter().collect();
-
         Ok(Self {
             items,
             max_items,
+            compress: storage_path.ends_with(".gz"),
             storage_path,
+            cmp,
+            keep_snapshots: 0,
+            last_snapshot: None,
+            clock: Arc::new(SystemClock),
+            view: Mutex::new(None),
         })
     }
 
+    /// Before each save, copy the storage file aside with a timestamp in its name,
+    /// keeping the newest `keep` copies to recover from a bad eviction or corruption
+    pub fn with_snapshots(mut self, keep: usize) -> Self {
+        self.keep_snapshots = keep;
+        self
+    }
+
+    /// Timestamp snapshots by `clock` rather than the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Snapshots of this board's storage file, oldest first
+    pub fn snapshots(&self) -> Result<Vec<PathBuf>, LeaderboardError> {
+        let (dir, stem, suffix) = snapshot_name_parts(&self.storage_path);
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut snapshots = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let is_snapshot = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(stem.as_str()))
+                .and_then(|rest| rest.strip_prefix('.'))
+                .and_then(|rest| rest.strip_suffix(suffix.as_str()))
+                .is_some_and(is_snapshot_stamp);
+            if is_snapshot {
+                snapshots.push(path);
+            }
+        }
+        snapshots.sort();
+        Ok(snapshots)
+    }
+
+    /// Replace every item with those saved in `snapshot`, e.g. one from
+    /// [`snapshots`](Self::snapshots). The state being replaced is snapshotted
+    /// first when snapshots are on, so a restore can itself be undone
+    pub fn restore_from_snapshot(
+        &mut self,
+        snapshot: impl AsRef<Path>,
+    ) -> Result<(), LeaderboardError> {
+        let items = load_items(snapshot.as_ref(), &self.cmp)?;
+        self.replace_items(items);
+        self.save()
+    }
+
+    /// Throw away the in-memory items and read the storage file again, e.g. after
+    /// another process edited it. This is a full replace, not a merge: anything added
+    /// since the last save is lost. A missing file leaves the board empty
+    pub fn reload(&mut self) -> Result<(), LeaderboardError> {
+        let storage_path = Path::new(&self.storage_path);
+        let items = if storage_path.exists() {
+            load_items(storage_path, &self.cmp)?
+        } else {
+            Vec::new()
+        };
+        self.replace_items(items);
+        Ok(())
+    }
+
+    /// Write the items to the storage file now. Every change already saves, so this
+    /// is only needed to recreate a file removed from under the board
+    pub fn persist(&mut self) -> Result<(), LeaderboardError> {
+        self.save()
+    }
+
+    /// Swap in freshly loaded items, sorted worst first, dropping the worst of any
+    /// that don't fit
+    fn replace_items(&mut self, mut items: Vec<T>) {
+        let excess = items.len().saturating_sub(self.max_items);
+        items.drain(..excess);
+        self.items = items;
+        self.invalidate_view();
+    }
+
+    /// The items from best to worst, "best" being whatever the comparator ranks
+    /// `Greater`. `TodoItem`'s `Ord` ranks older TODOs greater, so a board of them
+    /// comes out oldest first
+    pub fn best_first(&self) -> Rev<slice::Iter<'_, T>> {
+        // Stored worst first, so a new item's competition is at the front
+        self.items.iter().rev()
+    }
+
+    /// The items best first, like [`best_first`](Self::best_first), as one shared
+    /// allocation. Built on the first call after a change, every later call until the
+    /// next change gets the same `Arc`
+    pub fn view(&self) -> Arc<Vec<T>> {
+        let mut view = self
+            .view
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        view.get_or_insert_with(|| Arc::new(self.best_first().cloned().collect()))
+            .clone()
+    }
+
+    fn invalidate_view(&mut self) {
+        *self
+            .view
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+    }
+
+    /// The lowest ranked item, i.e. the one a new item has to beat once the board is full
+    pub fn peek_worst(&self) -> Option<&T> {
+        self.items.first()
+    }
+
+    /// The highest ranked item
+    pub fn peek_best(&self) -> Option<&T> {
+        self.items.last()
+    }
+
     pub fn try_add(&mut self, item: T) -> bool {
-        // If we already have this exact item, return false
+        self.try_add_ranked(item).is_added()
+    }
+
+    /// Add `item` if it isn't already listed and either there's room or it beats the
+    /// worst item, which it then replaces. An item tying with the best becomes the top
+    pub fn try_add_ranked(&mut self, item: T) -> TryAddResult {
+        // Duplicates are found with PartialEq, so distinct items may still tie
+        // under the comparator
         if self.items.contains(&item) {
-            return false;
+            return TryAddResult::NotAdded;
         }
 
         // If we have space, just add it
         if self.items.len() < self.max_items {
-            self.items.insert(item);
+            let result = self.insert_ranked(item);
             self.save().unwrap_or_else(|e| {
                 eprintln!("Failed to save leaderboard: {}", e);
             });
-            return true;
+            return result;
         }
 
         // Otherwise, we need to check if this item is better than the worst item
-        // Since BTreeSet is ordered, the first item is the lowest/worst
-        if let Some(worst_item) = self.items.iter().next().cloned() {
-            if item > worst_item {
-                // Remove the worst item
-                self.items.remove(&worst_item);
-                // Add the new item
-                self.items.insert(item);
-
-                self.save().unwrap_or_else(|e| {
-                    eprintln!("Failed to save leaderboard: {}", e);
-                });
-                return true;
-            }
+        // Since items are kept sorted, the first item is the lowest/worst
+        if let Some(worst_item) = self.peek_worst()
+            && (self.cmp)(&item, worst_item) == Ordering::Greater
+        {
+            // Remove the worst item
+            self.items.remove(0);
+            // Add the new item
+            let result = self.insert_ranked(item);
+
+            self.save().unwrap_or_else(|e| {
+                eprintln!("Failed to save leaderboard: {}", e);
+            });
+            return result;
+        }
+
+        TryAddResult::NotAdded
+    }
+
+    /// Remove every item matching `predicate`, saving if anything was removed
+    pub fn remove(&mut self, predicate: impl Fn(&T) -> bool) -> Vec<T> {
+        let (removed, kept) = self.items.drain(..).partition(|item| predicate(item));
+        self.items = kept;
+
+        if !removed.is_empty() {
+            self.invalidate_view();
+            self.save().unwrap_or_else(|e| {
+                eprintln!("Failed to save leaderboard: {}", e);
+            });
         }
+        removed
+    }
 
-        false
+    /// Insert keeping `items` sorted, after any items it ties with, reporting the
+    /// item's rank from the best end
+    fn insert_ranked(&mut self, item: T) -> TryAddResult {
+        let index = self
+            .items
+            .partition_point(|existing| (self.cmp)(existing, &item) != Ordering::Greater);
+        self.items.insert(index, item);
+        self.invalidate_view();
+        match self.items.len() - index {
+            1 => TryAddResult::AddedTop,
+            rank => TryAddResult::Added { rank },
+        }
     }
 
-    fn save(&self) -> Result<(), LeaderboardError> {
-        // Convert BTreeSet to Vec for serialization
-        let items_vec: Vec<T> = self.items.iter().cloned().collect();
-        let json = serde_json::to_string_pretty(&items_vec)?;
-        fs::write(&self.storage_path, json)?;
+    fn save(&mut self) -> Result<(), LeaderboardError> {
+        // A failed snapshot shouldn't cost us the save itself
+        if self.keep_snapshots > 0 {
+            self.rotate_snapshots().unwrap_or_else(|e| {
+                eprintln!("Failed to snapshot leaderboard: {}", e);
+            });
+        }
+
+        if self.compress {
+            let json = serde_json::to_vec(&self.items)?;
+            fs::write(&self.storage_path, gzip::encode(&json))?;
+        } else {
+            // Plain, pretty JSON by default so the file is easy to inspect
+            let json = serde_json::to_string_pretty(&self.items)?;
+            fs::write(&self.storage_path, json)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> Leaderboard<T>
+where
+    T: Leaderboardable,
+{
+    /// Copy the storage file aside before it's overwritten, then prune all but
+    /// the newest `keep_snapshots` copies
+    fn rotate_snapshots(&mut self) -> Result<(), LeaderboardError> {
+        let storage_path = Path::new(&self.storage_path);
+        if !storage_path.exists() {
+            return Ok(());
+        }
+
+        // Saves can land in the same millisecond, and each needs its own name
+        let mut taken_at = self.clock.now().trunc_subsecs(3);
+        if let Some(last) = self.last_snapshot
+            && taken_at <= last
+        {
+            taken_at = last + chrono::Duration::milliseconds(1);
+        }
+        self.last_snapshot = Some(taken_at);
+
+        let (dir, stem, suffix) = snapshot_name_parts(&self.storage_path);
+        let name = format!(
+            "{}.{}{}",
+            stem,
+            taken_at.format(SNAPSHOT_STAMP_FORMAT),
+            suffix
+        );
+        fs::copy(storage_path, dir.join(name))?;
+
+        let snapshots = self.snapshots()?;
+        let excess = snapshots.len().saturating_sub(self.keep_snapshots);
+        for old in &snapshots[..excess] {
+            fs::remove_file(old)?;
+        }
         Ok(())
     }
 }
 
+/// Read, dedupe and sort a stored board, gzipped or not
+fn load_items<T: Leaderboardable>(
+    path: &Path,
+    cmp: &Comparator<T>,
+) -> Result<Vec<T>, LeaderboardError> {
+    // Gzipped files are detected by content, so either format loads from any path
+    let mut file_content = fs::read(path)?;
+    if gzip::is_gzip(&file_content) {
+        file_content = gzip::decode(&file_content)?;
+    }
+    let items_vec = serde_json::from_slice::<Vec<T>>(&file_content)?;
+
+    // Sort by this board's ranking, dropping any duplicates
+    let mut items: Vec<T> = Vec::with_capacity(items_vec.len());
+    for item in items_vec {
+        if !items.contains(&item) {
+            items.push(item);
+        }
+    }
+    items.sort_by(|a, b| cmp(a, b));
+    Ok(items)
+}
+
+/// Split a storage path into its directory, the name before the extension and
+/// the extension, so `data/leaderboard.json.gz` snapshots as
+/// `data/leaderboard.<stamp>.json.gz`
+fn snapshot_name_parts(storage_path: &str) -> (PathBuf, String, String) {
+    let path = Path::new(storage_path);
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let extension_start = if file_name.ends_with(".json.gz") {
+        file_name.len() - ".json.gz".len()
+    } else {
+        file_name.rfind('.').unwrap_or(file_name.len())
+    };
+    let (stem, suffix) = file_name.split_at(extension_start);
+    (dir, stem.to_string(), suffix.to_string())
+}
+
+/// Whether `stamp` is laid out like `SNAPSHOT_STAMP_FORMAT`, e.g. `20250102-030405-678`
+fn is_snapshot_stamp(stamp: &str) -> bool {
+    stamp.len() == 19
+        && stamp.char_indices().all(|(i, c)| match i {
+            8 | 15 => c == '-',
+            _ => c.is_ascii_digit(),
+        })
+}
+
+/// Create the directory a new storage file will live in, otherwise every save
+/// fails on a fresh checkout and the leaderboard never persists
+fn ensure_parent_dir(storage_path: &str) -> Result<(), LeaderboardError> {
+    let Some(parent) = Path::new(storage_path).parent() else {
+        return Ok(());
+    };
+    if parent.as_os_str().is_empty() || parent.exists() {
+        return Ok(());
+    }
+
+    eprintln!(
+        "Warning: leaderboard directory {} is missing, creating it",
+        parent.display()
+    );
+    fs::create_dir_all(parent).map_err(|source| LeaderboardError::CreateDirError {
+        path: parent.display().to_string(),
+        source,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use blame_finder::FixedClock;
+    use chrono::TimeZone;
     use serde::{Deserialize, Serialize};
     use tempfile::tempdir;
 
@@ -255,7 +679,7 @@ mod tests {
         assert_eq!(leaderboard.items.len(), 3);
 
         // Get items and verify (lowest score was removed)
-        let items: Vec<TestScore> = leaderboard.items.iter().cloned().collect();
+        let items: Vec<TestScore> = leaderboard.items.to_vec();
         let has_alice = items.iter().any(|s| s.name == "Alice");
         let has_bob = items.iter().any(|s| s.name == "Bob");
         let has_charlie = items.iter().any(|s| s.name == "Charlie");
@@ -267,6 +691,49 @@ mod tests {
         assert!(has_eve); // Eve (120) should be added
     }
 
+    #[test]
+    fn test_try_add_ranked_reports_where_items_land() {
+        let dir = tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("test_leaderboard.json")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let mut leaderboard = Leaderboard::<TestScore>::new(path, 3).unwrap();
+
+        assert_eq!(
+            leaderboard.try_add_ranked(TestScore::new("Alice", 60)),
+            TryAddResult::AddedTop
+        );
+        assert_eq!(
+            leaderboard.try_add_ranked(TestScore::new("Bob", 100)),
+            TryAddResult::AddedTop
+        );
+        // Mid-board, between Bob and Alice
+        assert_eq!(
+            leaderboard.try_add_ranked(TestScore::new("Charlie", 80)),
+            TryAddResult::Added { rank: 2 }
+        );
+
+        // Full, and worse than everyone
+        assert_eq!(
+            leaderboard.try_add_ranked(TestScore::new("Dave", 40)),
+            TryAddResult::NotAdded
+        );
+        assert_eq!(
+            leaderboard.try_add_ranked(TestScore::new("Bob", 100)),
+            TryAddResult::NotAdded
+        );
+
+        // Full, but beats Alice and lands behind Charlie
+        assert_eq!(
+            leaderboard.try_add_ranked(TestScore::new("Eve", 70)),
+            TryAddResult::Added { rank: 3 }
+        );
+    }
+
     #[test]
     fn test_leaderboard_save_and_load() {
         let dir = tempdir().unwrap();
@@ -301,6 +768,112 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_missing_storage_directory_is_created() {
+        let dir = tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("data")
+            .join("nested")
+            .join("leaderboard.json");
+        let path_str = path.to_str().unwrap().to_string();
+
+        let mut leaderboard = Leaderboard::<TestScore>::new(path_str.clone(), 3).unwrap();
+        assert!(leaderboard.try_add(TestScore::new("Alice", 100)));
+
+        assert!(path.exists());
+        let reloaded = Leaderboard::<TestScore>::new(path_str, 3).unwrap();
+        assert_eq!(reloaded.items, vec![TestScore::new("Alice", 100)]);
+    }
+
+    #[test]
+    fn test_snapshots_rotate_and_restore() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("leaderboard.json");
+        let mut leaderboard = Leaderboard::<TestScore>::new(path.to_str().unwrap().to_string(), 10)
+            .unwrap()
+            .with_snapshots(3);
+
+        // The first save has nothing to snapshot, each later one snapshots the one before
+        for (i, name) in ["a", "b", "c", "d", "e", "f"].into_iter().enumerate() {
+            leaderboard.try_add(TestScore::new(name, i as u32));
+        }
+
+        let snapshots = leaderboard.snapshots().unwrap();
+        assert_eq!(snapshots.len(), 3);
+        let names: Vec<String> = snapshots
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert!(names.iter().all(|name| {
+            name.starts_with("leaderboard.") && name.ends_with(".json") && name.len() == 36
+        }));
+        // Another board's file beside it is never mistaken for a snapshot
+        assert!(!snapshots.contains(&path));
+
+        // The oldest kept snapshot is from just after "c" was added
+        leaderboard.restore_from_snapshot(&snapshots[0]).unwrap();
+        let restored: Vec<&str> = leaderboard.items.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(restored, vec!["a", "b", "c"]);
+
+        // And the restore is what's on disk now
+        let reloaded =
+            Leaderboard::<TestScore>::new(path.to_str().unwrap().to_string(), 10).unwrap();
+        assert_eq!(reloaded.items.len(), 3);
+    }
+
+    #[test]
+    fn test_snapshots_are_named_by_the_clock() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("leaderboard.json");
+        let taken_at = Utc.with_ymd_and_hms(2025, 1, 2, 3, 4, 5).unwrap()
+            + chrono::Duration::milliseconds(678);
+        let mut leaderboard = Leaderboard::<TestScore>::new(path.to_str().unwrap().to_string(), 10)
+            .unwrap()
+            .with_snapshots(3)
+            .with_clock(Arc::new(FixedClock(taken_at)));
+
+        for (i, name) in ["a", "b", "c"].into_iter().enumerate() {
+            leaderboard.try_add(TestScore::new(name, i as u32));
+        }
+
+        // Saves at the same instant still get names of their own, a millisecond apart
+        let names: Vec<String> = leaderboard
+            .snapshots()
+            .unwrap()
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "leaderboard.20250102-030405-678.json",
+                "leaderboard.20250102-030405-679.json"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snapshot_name_parts() {
+        let (dir, stem, suffix) = snapshot_name_parts("data/leaderboard.rust.json");
+        assert_eq!(dir, PathBuf::from("data"));
+        assert_eq!(
+            (stem.as_str(), suffix.as_str()),
+            ("leaderboard.rust", ".json")
+        );
+
+        let (dir, stem, suffix) = snapshot_name_parts("leaderboard.json.gz");
+        assert_eq!(dir, PathBuf::from("."));
+        assert_eq!(
+            (stem.as_str(), suffix.as_str()),
+            ("leaderboard", ".json.gz")
+        );
+
+        assert!(is_snapshot_stamp("20250102-030405-678"));
+        assert!(!is_snapshot_stamp("rust"));
+        assert!(!is_snapshot_stamp("20250102-030405-67x"));
+    }
+
     #[tokio::test]
     async fn test_shared_leaderboard() {
         let dir = tempdir().unwrap();
@@ -336,6 +909,68 @@ mod tests {
         assert_eq!(updated_items[0].name, "Charlie");
     }
 
+    #[tokio::test]
+    async fn test_reload_replaces_items_with_file_contents() {
+        let dir = tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("test_reload.json")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let shared_leaderboard = SharedLeaderboard::<TestScore>::new(path.clone(), 3)
+            .await
+            .unwrap();
+        shared_leaderboard
+            .try_add(TestScore::new("Alice", 100))
+            .await;
+
+        // Another process rewrites the file behind our back, with more than fits
+        let external = vec![
+            TestScore::new("Bob", 80),
+            TestScore::new("Charlie", 120),
+            TestScore::new("Dave", 10),
+            TestScore::new("Eve", 90),
+        ];
+        fs::write(&path, serde_json::to_string(&external).unwrap()).unwrap();
+
+        shared_leaderboard.reload().await.unwrap();
+
+        // Alice is gone rather than merged in, and the worst extra item is dropped
+        let names: Vec<_> = shared_leaderboard
+            .get_items()
+            .await
+            .into_iter()
+            .map(|item| item.name)
+            .collect();
+        assert_eq!(names, vec!["Charlie", "Eve", "Bob"]);
+    }
+
+    #[tokio::test]
+    async fn test_persist_recreates_removed_file() {
+        let dir = tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("test_persist.json")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let shared_leaderboard = SharedLeaderboard::<TestScore>::new(path.clone(), 3)
+            .await
+            .unwrap();
+        shared_leaderboard
+            .try_add(TestScore::new("Alice", 100))
+            .await;
+        fs::remove_file(&path).unwrap();
+
+        shared_leaderboard.persist().await.unwrap();
+
+        let reloaded = Leaderboard::<TestScore>::new(path, 3).unwrap();
+        assert_eq!(reloaded.items, vec![TestScore::new("Alice", 100)]);
+    }
+
     #[tokio::test]
     async fn test_concurrent_access() {
         let dir = tempdir().unwrap();
@@ -375,4 +1010,268 @@ mod tests {
             assert!(items[i].score > items[i + 1].score);
         }
     }
+
+    #[tokio::test]
+    async fn test_snapshot_is_shared_until_the_board_changes() {
+        let dir = tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("snapshot.json")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let shared_leaderboard = SharedLeaderboard::<TestScore>::new(path, 10).await.unwrap();
+        shared_leaderboard.try_add(TestScore::new("Bob", 80)).await;
+        shared_leaderboard
+            .try_add(TestScore::new("Alice", 100))
+            .await;
+
+        let first = shared_leaderboard.snapshot().await;
+        let second = shared_leaderboard.snapshot().await;
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(first[0].name, "Alice");
+
+        // Failing to add changes nothing, so the snapshot stays valid
+        assert!(!shared_leaderboard.try_add(TestScore::new("Bob", 80)).await);
+        assert!(Arc::ptr_eq(&first, &shared_leaderboard.snapshot().await));
+
+        shared_leaderboard
+            .try_add(TestScore::new("Carol", 90))
+            .await;
+        let added = shared_leaderboard.snapshot().await;
+        assert!(!Arc::ptr_eq(&first, &added));
+        assert_eq!(added.len(), 3);
+        // Readers holding the old snapshot keep what they saw
+        assert_eq!(first.len(), 2);
+
+        shared_leaderboard.remove(|item| item.name == "Carol").await;
+        let removed = shared_leaderboard.snapshot().await;
+        assert!(!Arc::ptr_eq(&added, &removed));
+        assert_eq!(removed.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_with_items_visits_best_first() {
+        let dir = tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("with_items.json")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let shared_leaderboard = SharedLeaderboard::<TestScore>::new(path, 10).await.unwrap();
+        shared_leaderboard.try_add(TestScore::new("Bob", 80)).await;
+        shared_leaderboard
+            .try_add(TestScore::new("Alice", 100))
+            .await;
+
+        let names: Vec<String> = shared_leaderboard
+            .with_items(|items| items.map(|item| item.name.clone()).collect())
+            .await;
+        let total: u32 = shared_leaderboard
+            .with_items(|items| items.map(|item| item.score).sum())
+            .await;
+
+        assert_eq!(names, vec!["Alice", "Bob"]);
+        assert_eq!(total, 180);
+    }
+
+    #[tokio::test]
+    async fn test_custom_comparators_rank_independently() {
+        let dir = tempdir().unwrap();
+        let by_score = SharedLeaderboard::<TestScore>::new_with_cmp(
+            dir.path()
+                .join("by_score.json")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            10,
+            Arc::new(|a: &TestScore, b: &TestScore| a.score.cmp(&b.score)),
+        )
+        .await
+        .unwrap();
+        // Alphabetically first name ranks best
+        let by_name = SharedLeaderboard::<TestScore>::new_with_cmp(
+            dir.path()
+                .join("by_name.json")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            10,
+            Arc::new(|a: &TestScore, b: &TestScore| b.name.cmp(&a.name)),
+        )
+        .await
+        .unwrap();
+
+        for item in [
+            TestScore::new("Bob", 100),
+            TestScore::new("Alice", 50),
+            TestScore::new("Charlie", 75),
+        ] {
+            by_score.try_add(item.clone()).await;
+            by_name.try_add(item).await;
+        }
+        // Duplicates are still rejected
+        assert!(!by_name.try_add(TestScore::new("Alice", 50)).await);
+
+        let names = |items: Vec<TestScore>| -> Vec<String> {
+            items.into_iter().map(|item| item.name).collect()
+        };
+        assert_eq!(
+            names(by_score.get_items().await),
+            vec!["Bob", "Charlie", "Alice"]
+        );
+        assert_eq!(
+            names(by_name.get_items().await),
+            vec!["Alice", "Bob", "Charlie"]
+        );
+    }
+
+    #[test]
+    fn test_custom_comparator_evicts_worst() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("evict.json").to_str().unwrap().to_string();
+        let mut leaderboard = Leaderboard::new_with_cmp(
+            path,
+            2,
+            Arc::new(|a: &TestScore, b: &TestScore| b.name.cmp(&a.name)),
+        )
+        .unwrap();
+
+        leaderboard.try_add(TestScore::new("Bob", 1));
+        leaderboard.try_add(TestScore::new("Charlie", 2));
+
+        // "Alice" outranks "Charlie" on this board, "Dave" doesn't
+        assert!(leaderboard.try_add(TestScore::new("Alice", 0)));
+        assert!(!leaderboard.try_add(TestScore::new("Dave", 100)));
+
+        let names: Vec<&str> = leaderboard
+            .items
+            .iter()
+            .map(|item| item.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Bob", "Alice"]);
+    }
+
+    #[tokio::test]
+    async fn test_peek_worst_and_best() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("peek.json").to_str().unwrap().to_string();
+        let shared_leaderboard = SharedLeaderboard::<TestScore>::new(path, 10).await.unwrap();
+
+        assert_eq!(shared_leaderboard.peek_worst().await, None);
+        assert_eq!(shared_leaderboard.peek_best().await, None);
+
+        for (name, score) in [("Bob", 80), ("Alice", 100), ("Charlie", 60)] {
+            shared_leaderboard
+                .try_add(TestScore::new(name, score))
+                .await;
+        }
+
+        assert_eq!(
+            shared_leaderboard.peek_worst().await,
+            Some(TestScore::new("Charlie", 60))
+        );
+        assert_eq!(
+            shared_leaderboard.peek_best().await,
+            Some(TestScore::new("Alice", 100))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_matching_items_and_persist() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("remove.json").to_str().unwrap().to_string();
+        let shared_leaderboard = SharedLeaderboard::<TestScore>::new(path.clone(), 10)
+            .await
+            .unwrap();
+        for (name, score) in [("Bob", 80), ("Alice", 100), ("Charlie", 60)] {
+            shared_leaderboard
+                .try_add(TestScore::new(name, score))
+                .await;
+        }
+
+        let removed = shared_leaderboard.remove(|item| item.name == "Bob").await;
+        assert_eq!(removed, vec![TestScore::new("Bob", 80)]);
+        assert!(
+            shared_leaderboard
+                .remove(|item| item.name == "Bob")
+                .await
+                .is_empty()
+        );
+
+        // Order is kept, and the removal survives a reload
+        let reloaded = Leaderboard::<TestScore>::new(path, 10).unwrap();
+        assert_eq!(
+            reloaded.items,
+            vec![TestScore::new("Charlie", 60), TestScore::new("Alice", 100)]
+        );
+    }
+
+    #[test]
+    fn test_peek_follows_custom_comparator() {
+        let dir = tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("peek_cmp.json")
+            .to_str()
+            .unwrap()
+            .to_string();
+        // Lowest score ranks best on this board
+        let mut leaderboard = Leaderboard::new_with_cmp(
+            path,
+            10,
+            Arc::new(|a: &TestScore, b: &TestScore| b.score.cmp(&a.score)),
+        )
+        .unwrap();
+
+        for (name, score) in [("Bob", 80), ("Alice", 100), ("Charlie", 60)] {
+            leaderboard.try_add(TestScore::new(name, score));
+        }
+
+        assert_eq!(leaderboard.peek_worst().unwrap().name, "Alice");
+        assert_eq!(leaderboard.peek_best().unwrap().name, "Charlie");
+    }
+
+    #[test]
+    fn test_compressed_storage_round_trip() {
+        let dir = tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("leaderboard.json.gz")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let mut leaderboard = Leaderboard::new(path.clone(), 5).unwrap();
+        leaderboard.try_add(TestScore::new("Alice", 100));
+        leaderboard.try_add(TestScore::new("Bob", 80));
+
+        let raw = fs::read(&path).unwrap();
+        assert!(gzip::is_gzip(&raw));
+
+        let loaded = Leaderboard::<TestScore>::new(path, 5).unwrap();
+        assert_eq!(loaded.items, leaderboard.items);
+    }
+
+    #[test]
+    fn test_plain_storage_still_loads() {
+        let dir = tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("leaderboard.json")
+            .to_str()
+            .unwrap()
+            .to_string();
+        fs::write(&path, r#"[{"score": 100, "name": "Alice"}]"#).unwrap();
+
+        let mut leaderboard = Leaderboard::<TestScore>::new(path.clone(), 5).unwrap();
+        assert_eq!(leaderboard.items, vec![TestScore::new("Alice", 100)]);
+
+        // Plain files stay plain when saved
+        leaderboard.try_add(TestScore::new("Bob", 80));
+        assert!(fs::read_to_string(&path).unwrap().contains("Bob"));
+    }
 }
@@ -0,0 +1,248 @@
+use axum::{
+    Json,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use blame_finder::BlameError;
+use serde::Serialize;
+
+/// Error body returned by every JSON API endpoint, e.g.
+/// `{"code": "invalid_url", "message": "Invalid repository URL: ..."}`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ApiError {
+    /// Stable, machine-readable error code
+    pub code: String,
+    /// Human readable description
+    pub message: String,
+    #[serde(skip)]
+    pub status: StatusCode,
+    /// Seconds the client should wait before retrying, sent as `Retry-After`
+    #[serde(skip)]
+    pub retry_after: Option<u64>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &str, message: impl Into<String>) -> Self {
+        ApiError {
+            code: code.to_string(),
+            message: message.into(),
+            status,
+            retry_after: None,
+        }
+    }
+
+    pub fn unauthorized() -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, "unauthorized", "Unauthorized")
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not_found", message)
+    }
+
+    /// The server is too busy, try again after `retry_after_secs`
+    pub fn busy(message: impl Into<String>, retry_after_secs: u64) -> Self {
+        ApiError {
+            retry_after: Some(retry_after_secs),
+            ..Self::new(StatusCode::SERVICE_UNAVAILABLE, "busy", message)
+        }
+    }
+}
+
+impl From<BlameError> for ApiError {
+    fn from(err: BlameError) -> Self {
+        let (status, code) = match &err {
+            BlameError::InvalidUrl(_) => (StatusCode::BAD_REQUEST, "invalid_url"),
+            BlameError::HostNotAllowed(_) => (StatusCode::BAD_REQUEST, "host_not_allowed"),
+            BlameError::InvalidPattern(_) => (StatusCode::BAD_REQUEST, "invalid_pattern"),
+            BlameError::InvalidOption(_) => (StatusCode::BAD_REQUEST, "invalid_option"),
+            BlameError::PathOutsideRepo(_) => (StatusCode::BAD_REQUEST, "path_outside_repo"),
+            BlameError::NotAGitRepository(_) => (StatusCode::BAD_REQUEST, "not_a_git_repository"),
+            // The git host or the repository itself let us down
+            BlameError::GitError(_) => (StatusCode::BAD_GATEWAY, "git_error"),
+            BlameError::EmptyRepository(_) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, "empty_repository")
+            }
+            BlameError::NoTodosInDateRange => (StatusCode::NOT_FOUND, "no_todos_in_date_range"),
+            BlameError::PullRequestNotFound { .. } => {
+                (StatusCode::NOT_FOUND, "pull_request_not_found")
+            }
+            BlameError::AllBlameFailed { .. } => {
+                (StatusCode::UNPROCESSABLE_ENTITY, "all_blame_failed")
+            }
+            BlameError::SearchError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "search_error"),
+            BlameError::IoError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "io_error"),
+            BlameError::ParseError(_) | BlameError::RipgrepLineError { .. } => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "parse_error")
+            }
+            BlameError::DirectoryError(_) | BlameError::FileError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "storage_error")
+            }
+            BlameError::BlameTasksPanicked { .. } | BlameError::InternalError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+            }
+        };
+        Self::new(status, code, err.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut response = (self.status, Json(&self)).into_response();
+        if let Some(retry_after) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, retry_after.into());
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status_and_code(err: BlameError) -> (StatusCode, String) {
+        let api_error = ApiError::from(err);
+        (api_error.status, api_error.code)
+    }
+
+    #[test]
+    fn test_blame_errors_map_to_statuses_and_codes() {
+        let cases = [
+            (
+                BlameError::InvalidUrl("x".into()),
+                StatusCode::BAD_REQUEST,
+                "invalid_url",
+            ),
+            (
+                BlameError::HostNotAllowed("x".into()),
+                StatusCode::BAD_REQUEST,
+                "host_not_allowed",
+            ),
+            (
+                BlameError::InvalidPattern("x".into()),
+                StatusCode::BAD_REQUEST,
+                "invalid_pattern",
+            ),
+            (
+                BlameError::InvalidOption("x".into()),
+                StatusCode::BAD_REQUEST,
+                "invalid_option",
+            ),
+            (
+                BlameError::PathOutsideRepo("x".into()),
+                StatusCode::BAD_REQUEST,
+                "path_outside_repo",
+            ),
+            (
+                BlameError::NotAGitRepository("/tmp/x".into()),
+                StatusCode::BAD_REQUEST,
+                "not_a_git_repository",
+            ),
+            (
+                BlameError::GitError("x".into()),
+                StatusCode::BAD_GATEWAY,
+                "git_error",
+            ),
+            (
+                BlameError::EmptyRepository("x".into()),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "empty_repository",
+            ),
+            (
+                BlameError::NoTodosInDateRange,
+                StatusCode::NOT_FOUND,
+                "no_todos_in_date_range",
+            ),
+            (
+                BlameError::PullRequestNotFound {
+                    url: "x".into(),
+                    number: 1,
+                },
+                StatusCode::NOT_FOUND,
+                "pull_request_not_found",
+            ),
+            (
+                BlameError::AllBlameFailed { attempted: 3 },
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "all_blame_failed",
+            ),
+            (
+                BlameError::SearchError("x".into()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "search_error",
+            ),
+            (
+                BlameError::IoError(std::io::Error::other("x")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "io_error",
+            ),
+            (
+                BlameError::ParseError("x".into()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "parse_error",
+            ),
+            (
+                BlameError::RipgrepLineError {
+                    raw_line: "x".into(),
+                    reason: "y".into(),
+                },
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "parse_error",
+            ),
+            (
+                BlameError::DirectoryError("x".into()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "storage_error",
+            ),
+            (
+                BlameError::FileError("x".into()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "storage_error",
+            ),
+            (
+                BlameError::BlameTasksPanicked {
+                    panicked: 2,
+                    total: 3,
+                },
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+            ),
+            (
+                BlameError::InternalError("x".into()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+            ),
+        ];
+
+        for (err, status, code) in cases {
+            assert_eq!(status_and_code(err), (status, code.to_string()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_response_body_is_structured() {
+        let response = ApiError::from(BlameError::InvalidUrl("nope".into())).into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response.headers().get(header::RETRY_AFTER).is_none());
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "code": "invalid_url",
+                "message": "Invalid repository URL: nope",
+            })
+        );
+    }
+
+    #[test]
+    fn test_busy_sets_retry_after() {
+        let response = ApiError::busy("full", 15).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "15");
+    }
+}
@@ -0,0 +1,318 @@
+use blame_finder::{Clock, TodoItem};
+use leaderboard::{Leaderboard, LeaderboardError, SharedLeaderboard};
+use log::warn;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// The board every TODO competes on, whatever its language
+pub const GLOBAL_CATEGORY: &str = "all";
+
+/// Language boards, with the name shown to users and the file extensions that
+/// count towards each
+const LANGUAGES: &[(&str, &str, &[&str])] = &[
+    ("rust", "Rust", &["rs"]),
+    ("python", "Python", &["py", "pyi"]),
+    ("javascript", "JavaScript", &["js", "jsx", "mjs", "cjs"]),
+    ("typescript", "TypeScript", &["ts", "tsx"]),
+    ("go", "Go", &["go"]),
+    ("java", "Java", &["java"]),
+    ("kotlin", "Kotlin", &["kt", "kts"]),
+    ("ruby", "Ruby", &["rb"]),
+    ("php", "PHP", &["php"]),
+    ("c", "C", &["c", "h"]),
+    ("cpp", "C++", &["cc", "cpp", "cxx", "hh", "hpp", "hxx"]),
+    ("csharp", "C#", &["cs"]),
+    ("swift", "Swift", &["swift"]),
+];
+
+/// Every category, the global board first
+pub fn categories() -> impl Iterator<Item = &'static str> {
+    std::iter::once(GLOBAL_CATEGORY).chain(LANGUAGES.iter().map(|(name, _, _)| *name))
+}
+
+/// Works out which language a file is written in, from its extension or, for a script
+/// without one, its `#!` line
+///
+/// The built-in extensions can be added to or overridden per deployment, e.g. for
+/// generated `.rs.in` sources or a language that claims `.h` files.
+#[derive(Debug, Clone, Default)]
+pub struct LanguageMap {
+    // Lowercase file name suffixes, without the leading dot, and the position of their
+    // language in `LANGUAGES`. Longest first, and checked before the built-in extensions
+    overrides: Vec<(String, usize)>,
+}
+
+impl LanguageMap {
+    /// Read `extension=language` overrides, e.g. `rs.in=rust` or `h=cpp`, where the
+    /// language is a board or display name. Invalid entries are ignored with a warning
+    pub fn new<S: AsRef<str>>(overrides: impl IntoIterator<Item = S>) -> Self {
+        let mut map = LanguageMap::default();
+        for entry in overrides {
+            let entry = entry.as_ref().trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let parsed = entry.split_once('=').and_then(|(extension, language)| {
+                let extension = extension.trim().trim_start_matches('.');
+                let index = language_named(language.trim())?;
+                (!extension.is_empty()).then(|| (extension.to_ascii_lowercase(), index))
+            });
+            match parsed {
+                Some(pair) => map.overrides.push(pair),
+                None => warn!("Ignoring language override {:?}", entry),
+            }
+        }
+        map.overrides
+            .sort_by_key(|(suffix, _)| std::cmp::Reverse(suffix.len()));
+        map
+    }
+
+    /// The language board a file's TODOs belong on, from its extension
+    pub fn language_for_path(&self, file_path: &str) -> Option<&'static str> {
+        self.language_index(file_path)
+            .map(|index| LANGUAGES[index].0)
+    }
+
+    /// The language board a TODO belongs on. Files without a known extension fall back
+    /// to a `#!` line, when the TODO's context starts at the top of the file
+    pub fn language_for_todo(&self, todo: &TodoItem) -> Option<&'static str> {
+        self.language_for_path(&todo.file_path).or_else(|| {
+            shebang_index(todo.context_code.lines().next()?).map(|index| LANGUAGES[index].0)
+        })
+    }
+
+    /// The display name of the language most of `file_paths` are written in, by extension.
+    /// Files in languages without a board don't count, and ties go to the earlier board
+    pub fn primary_language<'a>(
+        &self,
+        file_paths: impl IntoIterator<Item = &'a str>,
+    ) -> Option<&'static str> {
+        let mut counts = [0usize; LANGUAGES.len()];
+        for file_path in file_paths {
+            if let Some(index) = self.language_index(file_path) {
+                counts[index] += 1;
+            }
+        }
+
+        // `max_by_key` keeps the last of equal maxima, so search from the end
+        let (index, count) = counts
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, count)| **count)?;
+        (*count > 0).then(|| LANGUAGES[index].1)
+    }
+
+    /// Position in `LANGUAGES` of a file's language, from its extension
+    fn language_index(&self, file_path: &str) -> Option<usize> {
+        let file_name = Path::new(file_path)
+            .file_name()?
+            .to_str()?
+            .to_ascii_lowercase();
+        let overridden = self.overrides.iter().find(|(suffix, _)| {
+            file_name
+                .strip_suffix(suffix.as_str())
+                .is_some_and(|stem| stem.ends_with('.'))
+        });
+        if let Some((_, index)) = overridden {
+            return Some(*index);
+        }
+
+        let extension = Path::new(&file_name).extension()?.to_str()?;
+        LANGUAGES
+            .iter()
+            .position(|(_, _, extensions)| extensions.contains(&extension))
+    }
+}
+
+/// Position in `LANGUAGES` of a language given by board or display name, in any case
+fn language_named(name: &str) -> Option<usize> {
+    LANGUAGES.iter().position(|(board, display, _)| {
+        board.eq_ignore_ascii_case(name) || display.eq_ignore_ascii_case(name)
+    })
+}
+
+/// Position in `LANGUAGES` of the interpreter a `#!` line runs, e.g.
+/// `#!/usr/bin/env python3` or `#!/usr/local/bin/node`
+fn shebang_index(first_line: &str) -> Option<usize> {
+    let mut words = first_line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        // Skips flags such as `-S`, which lets the interpreter take arguments
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+
+    // Versioned interpreters such as `python3.12` run the same language
+    let interpreter = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let language = match interpreter {
+        "python" | "pypy" => "python",
+        "node" | "nodejs" => "javascript",
+        "deno" | "ts-node" | "tsx" => "typescript",
+        "ruby" => "ruby",
+        "php" => "php",
+        "swift" => "swift",
+        _ => return None,
+    };
+    language_named(language)
+}
+
+/// Where a category's board is stored, next to the global board:
+/// `data/leaderboard.json` becomes `data/leaderboard.rust.json`
+pub fn storage_path_for(global_path: &str, category: &str) -> String {
+    if category == GLOBAL_CATEGORY {
+        return global_path.to_string();
+    }
+
+    let path = Path::new(global_path);
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let category_file = match file_name.split_once('.') {
+        Some((stem, extension)) => format!("{}.{}.{}", stem, category, extension),
+        None => format!("{}.{}", file_name, category),
+    };
+    path.with_file_name(category_file)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Open the global board at `global_path` and a board for every language beside it,
+/// each keeping `snapshots` backups of its file timestamped by `clock`
+pub async fn open_leaderboards(
+    global_path: &str,
+    max_items: usize,
+    snapshots: usize,
+    clock: Arc<dyn Clock>,
+) -> Result<HashMap<String, SharedLeaderboard<TodoItem>>, LeaderboardError> {
+    let mut leaderboards = HashMap::new();
+    for category in categories() {
+        let board = Leaderboard::new(storage_path_for(global_path, category), max_items)?
+            .with_snapshots(snapshots)
+            .with_clock(clock.clone());
+        leaderboards.insert(category.to_string(), board.into());
+    }
+    Ok(leaderboards)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::todo_aged;
+
+    #[test]
+    fn test_language_for_path() {
+        let languages = LanguageMap::default();
+        assert_eq!(languages.language_for_path("src/main.rs"), Some("rust"));
+        assert_eq!(
+            languages.language_for_path("lib/App.TSX"),
+            Some("typescript")
+        );
+        assert_eq!(languages.language_for_path("include/foo.h"), Some("c"));
+        assert_eq!(languages.language_for_path("README.md"), None);
+        assert_eq!(languages.language_for_path("Makefile"), None);
+    }
+
+    #[test]
+    fn test_language_overrides() {
+        let languages = LanguageMap::new(["rs.in=rust", ".h=C++", "lua=lua", "nonsense", ""]);
+        assert_eq!(languages.language_for_path("src/gen.rs.in"), Some("rust"));
+        assert_eq!(languages.language_for_path("include/foo.h"), Some("cpp"));
+        assert_eq!(
+            languages.language_for_path("lib/App.tsx"),
+            Some("typescript")
+        );
+        // Unknown languages are ignored, and a suffix must be a whole extension
+        assert_eq!(languages.language_for_path("init.lua"), None);
+        assert_eq!(languages.language_for_path("burs.in"), None);
+    }
+
+    #[test]
+    fn test_language_for_todo_reads_shebang() {
+        let languages = LanguageMap::default();
+        let script = |file_path: &str, context_code: &str| TodoItem {
+            file_path: file_path.to_string(),
+            context_code: context_code.to_string(),
+            ..todo_aged(400)
+        };
+
+        let python = script("bin/deploy", "#!/usr/bin/env python\n# TODO: retry\n");
+        assert_eq!(languages.language_for_todo(&python), Some("python"));
+        let node = script("bin/serve", "#!/usr/local/bin/node\n");
+        assert_eq!(languages.language_for_todo(&node), Some("javascript"));
+        let versioned = script("run", "#!/usr/bin/env -S python3.12 -u\n");
+        assert_eq!(languages.language_for_todo(&versioned), Some("python"));
+
+        // The extension wins, and a shell script has no board
+        let extension = script("build.rb", "#!/usr/bin/env python\n");
+        assert_eq!(languages.language_for_todo(&extension), Some("ruby"));
+        assert_eq!(
+            languages.language_for_todo(&script("run", "#!/bin/sh\n")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_primary_language() {
+        let files = [
+            "src/main.rs",
+            "src/lib.rs",
+            "build.py",
+            "README.md",
+            "Cargo.toml",
+        ];
+        let languages = LanguageMap::default();
+        assert_eq!(languages.primary_language(files), Some("Rust"));
+
+        // Ties go to the earlier board, and unknown files never win
+        assert_eq!(languages.primary_language(["a.py", "b.rs"]), Some("Rust"));
+        assert_eq!(languages.primary_language(["README.md", "Makefile"]), None);
+        assert_eq!(languages.primary_language(["x.hpp", "y.cc"]), Some("C++"));
+    }
+
+    #[tokio::test]
+    async fn test_opened_boards_list_oldest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let global_path = dir.path().join("leaderboard.json");
+        let boards = open_leaderboards(
+            global_path.to_str().unwrap(),
+            10,
+            0,
+            Arc::new(blame_finder::SystemClock),
+        )
+        .await
+        .unwrap();
+        let board = &boards[GLOBAL_CATEGORY];
+
+        for days in [30, 3650, 400] {
+            let mut todo = todo_aged(days);
+            todo.line_number = days as u32;
+            board.try_add(todo).await;
+        }
+
+        let lines: Vec<u32> = board
+            .get_items()
+            .await
+            .iter()
+            .map(|todo| todo.line_number)
+            .collect();
+        assert_eq!(lines, [3650, 400, 30]);
+    }
+
+    #[test]
+    fn test_storage_path_for() {
+        assert_eq!(
+            storage_path_for("data/leaderboard.json", GLOBAL_CATEGORY),
+            "data/leaderboard.json"
+        );
+        assert_eq!(
+            storage_path_for("data/leaderboard.json", "rust"),
+            "data/leaderboard.rust.json"
+        );
+        assert_eq!(
+            storage_path_for("data/leaderboard.json.gz", "go"),
+            "data/leaderboard.go.json.gz"
+        );
+    }
+}
@@ -0,0 +1,452 @@
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use blame_finder::{
+    DEFAULT_CLEANUP_CONCURRENCY, DEFAULT_CLONE_DEPTH, DEFAULT_MAX_CLONES_PER_HOST, HostPolicy,
+    UrlRewrite,
+};
+use log::warn;
+use todo_templates::EmailDisplay;
+
+use crate::constants::{
+    DEFAULT_ALL_TODOS_CONTEXT_BUDGET, DEFAULT_COMMIT_BODY_MAX_LEN, DEFAULT_GIT_USER_AGENT,
+    DEFAULT_HEADER_READ_TIMEOUT_SECS, DEFAULT_LEADERBOARD_SNAPSHOTS,
+    DEFAULT_LEADERBOARD_VERIFY_SAMPLE, DEFAULT_MAX_CONCURRENT_SCANS, DEFAULT_MAX_QUEUED_SCANS,
+    DEFAULT_MIN_LEADERBOARD_AGE_DAYS, DEFAULT_MIN_TOTAL_TODOS, DEFAULT_RESULT_CACHE_CAPACITY,
+    DEFAULT_SCAN_QUEUE_RETRY_AFTER_SECS, DEFAULT_SLOW_SCAN_WARNING_SECS,
+    DEFAULT_STATUS_CHANNEL_CAPACITY, DEFAULT_TEST_FILE_PENALTY_DAYS,
+};
+
+/// Server configuration, read from the environment at startup
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// TODOs younger than this are still shown to the user, but kept off the leaderboard
+    pub min_leaderboard_age_days: i64,
+
+    /// Repos with fewer TODOs than this are still scanned and shown, but kept off the
+    /// leaderboard, so a throwaway repo with one ancient TODO can't game it
+    pub min_total_todos: usize,
+
+    /// How many old repositories the daily cleanup deletes in parallel
+    pub cleanup_concurrency: usize,
+
+    /// Which git hosts users may submit for scanning
+    pub host_policy: HostPolicy,
+
+    /// Scans running longer than this tell the user they're still in progress
+    pub slow_scan_warning_secs: u64,
+
+    /// Path prefix the app is served under, e.g. `/todo-finder`. Empty when served at the root
+    pub base_path: String,
+
+    /// How many scans may run at once; the rest wait in the queue
+    pub max_concurrent_scans: usize,
+
+    /// How many clones or fetches may run against one git host at once, so a busy
+    /// instance doesn't get rate limited or banned by e.g. github.com
+    pub max_clones_per_host: usize,
+
+    /// Repos whose shallow clone already holds more commits than this aren't deepened
+    /// and are scanned in quick mode instead, as line blame through that much history
+    /// is slow. Only bites below the clone depth. 0 turns it off
+    pub quick_mode_history_limit: u32,
+
+    /// HTTP user-agent clones and fetches send, so git hosts can identify and
+    /// allowlist us
+    pub git_user_agent: String,
+
+    /// Name and email git runs as while cloning, only set when both are configured
+    pub git_identity: Option<(String, String)>,
+
+    /// How many scans may wait for a slot before new submissions are turned away
+    pub max_queued_scans: u32,
+
+    /// What to tell turned-away clients in the `Retry-After` header
+    pub scan_queue_retry_after_secs: u64,
+
+    /// Where to remember blame results between scans, `None` re-blames every scan
+    pub blame_cache_dir: Option<PathBuf>,
+
+    /// Most scan results kept in memory for rescans of unchanged content, 0 keeps none
+    pub result_cache_capacity: usize,
+
+    /// Shared secret for the admin API, which is disabled when unset
+    pub admin_token: Option<String>,
+
+    /// How many status updates a slow websocket client may fall behind before skipping ahead
+    pub status_channel_capacity: usize,
+
+    /// Author patterns, e.g. `*[bot]`, never credited with the oldest TODO
+    pub excluded_authors: Vec<String>,
+
+    /// How much of authors' emails the leaderboard and result pages show
+    pub author_email_display: EmailDisplay,
+
+    /// Timestamped backups kept of each leaderboard file, 0 keeps none
+    pub leaderboard_snapshots: usize,
+
+    /// Off runs without any leaderboard: nothing is admitted or persisted and its
+    /// pages are gone, scans still show their own result
+    pub leaderboard_enabled: bool,
+
+    /// How often to re-check a sample of leaderboard entries and drop those whose TODO
+    /// or repo has gone. 0 never checks
+    pub leaderboard_verify_interval_secs: u64,
+
+    /// How many leaderboard entries each check looks at, taking turns through the board
+    pub leaderboard_verify_sample: usize,
+
+    /// Send small responses straight away rather than letting Nagle's algorithm batch them
+    pub tcp_nodelay: bool,
+
+    /// Reuse connections for further requests, off closes each one after its response
+    pub http_keep_alive: bool,
+
+    /// Drop connections that don't send a full request head within this long, which also
+    /// closes idle keep-alive connections. 0 waits forever
+    pub header_read_timeout_secs: u64,
+
+    /// Also accept HTTP/2 without TLS (h2c) from clients that know to send it up front
+    pub http2: bool,
+
+    /// Most bytes of context kept across every TODO of a keep-all scan. The oldest keep
+    /// theirs, later ones are cut short and flagged
+    pub all_todos_context_budget: usize,
+
+    /// Most characters of the oldest TODO's commit message body shown with its result.
+    /// 0 leaves the body out and skips reading it
+    pub commit_body_max_len: usize,
+
+    /// How many days younger TODOs in test code count as when picking a scan's oldest,
+    /// so they don't trivially beat production code. 0 ranks them like any other
+    pub test_file_penalty_days: u32,
+
+    /// How many days older each later commit to a TODO's file makes it count as when
+    /// picking a scan's oldest, favouring the most neglected. 0 ranks by age alone.
+    /// See [`ScanOptions::neglect_days_per_commit`](blame_finder::ScanOptions::neglect_days_per_commit)
+    pub neglect_days_per_commit: u32,
+
+    /// Pass over TODOs in generated files when picking a scan's oldest, they're
+    /// still counted. See [`ScanOptions::exclude_generated`](blame_finder::ScanOptions::exclude_generated)
+    pub exclude_generated_files: bool,
+
+    /// Globs, e.g. `*.pb.go`, counting as generated on top of what a repo's
+    /// `.gitattributes` marks `linguist-generated`
+    pub generated_file_globs: Vec<String>,
+
+    /// Where to actually clone from, e.g. an internal mirror of GitHub. Links keep the
+    /// URL the user submitted
+    pub url_rewrite: Option<UrlRewrite>,
+
+    /// `extension=language` entries deciding which language board a file's TODOs go
+    /// on, ahead of the built-in extensions, e.g. `rs.in=rust`
+    pub language_overrides: Vec<String>,
+
+    /// Scheme and host the site is publicly reached at, e.g. `https://todos.example.com`,
+    /// for absolute links read outside the site such as the RSS feed. `BASE_PATH` goes
+    /// after it
+    pub public_url: Option<String>,
+
+    /// Without a public URL, build those links from the request's `Host` and
+    /// `X-Forwarded-Proto` headers. Only safe behind a reverse proxy that sets both
+    pub trust_proxy_headers: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            min_leaderboard_age_days: DEFAULT_MIN_LEADERBOARD_AGE_DAYS,
+            min_total_todos: DEFAULT_MIN_TOTAL_TODOS,
+            cleanup_concurrency: DEFAULT_CLEANUP_CONCURRENCY,
+            host_policy: HostPolicy::default(),
+            slow_scan_warning_secs: DEFAULT_SLOW_SCAN_WARNING_SECS,
+            base_path: String::new(),
+            max_concurrent_scans: DEFAULT_MAX_CONCURRENT_SCANS,
+            max_clones_per_host: DEFAULT_MAX_CLONES_PER_HOST,
+            quick_mode_history_limit: 0,
+            git_user_agent: DEFAULT_GIT_USER_AGENT.to_string(),
+            git_identity: None,
+            max_queued_scans: DEFAULT_MAX_QUEUED_SCANS,
+            scan_queue_retry_after_secs: DEFAULT_SCAN_QUEUE_RETRY_AFTER_SECS,
+            blame_cache_dir: None,
+            result_cache_capacity: DEFAULT_RESULT_CACHE_CAPACITY,
+            admin_token: None,
+            status_channel_capacity: DEFAULT_STATUS_CHANNEL_CAPACITY,
+            excluded_authors: Vec::new(),
+            author_email_display: EmailDisplay::default(),
+            leaderboard_snapshots: DEFAULT_LEADERBOARD_SNAPSHOTS,
+            leaderboard_enabled: true,
+            leaderboard_verify_interval_secs: 0,
+            leaderboard_verify_sample: DEFAULT_LEADERBOARD_VERIFY_SAMPLE,
+            tcp_nodelay: true,
+            http_keep_alive: true,
+            header_read_timeout_secs: DEFAULT_HEADER_READ_TIMEOUT_SECS,
+            http2: true,
+            all_todos_context_budget: DEFAULT_ALL_TODOS_CONTEXT_BUDGET,
+            commit_body_max_len: DEFAULT_COMMIT_BODY_MAX_LEN,
+            test_file_penalty_days: DEFAULT_TEST_FILE_PENALTY_DAYS,
+            neglect_days_per_commit: 0,
+            exclude_generated_files: false,
+            generated_file_globs: Vec::new(),
+            url_rewrite: None,
+            language_overrides: Vec::new(),
+            public_url: None,
+            trust_proxy_headers: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Config {
+            min_leaderboard_age_days: env_or(
+                "MIN_LEADERBOARD_AGE_DAYS",
+                defaults.min_leaderboard_age_days,
+            ),
+            min_total_todos: env_or("MIN_TOTAL_TODOS", defaults.min_total_todos),
+            cleanup_concurrency: env_or("CLEANUP_CONCURRENCY", defaults.cleanup_concurrency),
+            host_policy: HostPolicy::new(env_list("ALLOWED_HOSTS"), env_list("DENIED_HOSTS")),
+            slow_scan_warning_secs: env_or(
+                "SLOW_SCAN_WARNING_SECS",
+                defaults.slow_scan_warning_secs,
+            ),
+            base_path: normalize_base_path(&env::var("BASE_PATH").unwrap_or_default()),
+            max_concurrent_scans: at_least_one(
+                "MAX_CONCURRENT_SCANS",
+                env_or("MAX_CONCURRENT_SCANS", defaults.max_concurrent_scans),
+            ),
+            max_clones_per_host: env_or("MAX_CLONES_PER_HOST", defaults.max_clones_per_host),
+            quick_mode_history_limit: below_clone_depth(
+                "QUICK_MODE_HISTORY_LIMIT",
+                env_or(
+                    "QUICK_MODE_HISTORY_LIMIT",
+                    defaults.quick_mode_history_limit,
+                ),
+            ),
+            git_user_agent: env::var("GIT_USER_AGENT")
+                .ok()
+                .filter(|user_agent| !user_agent.trim().is_empty())
+                .unwrap_or(defaults.git_user_agent),
+            git_identity: env::var("GIT_USER_NAME")
+                .ok()
+                .zip(env::var("GIT_USER_EMAIL").ok())
+                .filter(|(name, email)| !name.is_empty() && !email.is_empty()),
+            max_queued_scans: env_or("MAX_QUEUED_SCANS", defaults.max_queued_scans),
+            scan_queue_retry_after_secs: env_or(
+                "SCAN_QUEUE_RETRY_AFTER_SECS",
+                defaults.scan_queue_retry_after_secs,
+            ),
+            blame_cache_dir: env::var("BLAME_CACHE_DIR")
+                .ok()
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+            result_cache_capacity: env_or("RESULT_CACHE_CAPACITY", defaults.result_cache_capacity),
+            admin_token: env::var("ADMIN_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+            status_channel_capacity: env_or(
+                "STATUS_CHANNEL_CAPACITY",
+                defaults.status_channel_capacity,
+            ),
+            excluded_authors: env_list("EXCLUDED_AUTHORS"),
+            author_email_display: env_or("AUTHOR_EMAIL_DISPLAY", defaults.author_email_display),
+            leaderboard_snapshots: env_or("LEADERBOARD_SNAPSHOTS", defaults.leaderboard_snapshots),
+            leaderboard_enabled: env_flag("LEADERBOARD_ENABLED", defaults.leaderboard_enabled),
+            leaderboard_verify_interval_secs: env_or(
+                "LEADERBOARD_VERIFY_INTERVAL_SECS",
+                defaults.leaderboard_verify_interval_secs,
+            ),
+            leaderboard_verify_sample: env_or(
+                "LEADERBOARD_VERIFY_SAMPLE",
+                defaults.leaderboard_verify_sample,
+            ),
+            tcp_nodelay: env_flag("TCP_NODELAY", defaults.tcp_nodelay),
+            http_keep_alive: env_flag("HTTP_KEEP_ALIVE", defaults.http_keep_alive),
+            header_read_timeout_secs: env_or(
+                "HEADER_READ_TIMEOUT_SECS",
+                defaults.header_read_timeout_secs,
+            ),
+            http2: env_flag("HTTP2", defaults.http2),
+            all_todos_context_budget: env_or(
+                "ALL_TODOS_CONTEXT_BUDGET",
+                defaults.all_todos_context_budget,
+            ),
+            commit_body_max_len: env_or("COMMIT_BODY_MAX_LEN", defaults.commit_body_max_len),
+            test_file_penalty_days: env_or(
+                "TEST_FILE_PENALTY_DAYS",
+                defaults.test_file_penalty_days,
+            ),
+            neglect_days_per_commit: env_or(
+                "NEGLECT_DAYS_PER_COMMIT",
+                defaults.neglect_days_per_commit,
+            ),
+            exclude_generated_files: env_flag(
+                "EXCLUDE_GENERATED_FILES",
+                defaults.exclude_generated_files,
+            ),
+            generated_file_globs: env_list("GENERATED_FILE_GLOBS"),
+            url_rewrite: env_url_rewrite("URL_REWRITE"),
+            language_overrides: env_list("LANGUAGE_OVERRIDES"),
+            public_url: env_public_url("PUBLIC_URL"),
+            trust_proxy_headers: env_flag("TRUST_PROXY_HEADERS", defaults.trust_proxy_headers),
+        }
+    }
+}
+
+/// Read and parse an env var, falling back to the default if missing or invalid
+fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Read an on/off switch, falling back to the default if missing. An unrecognised value
+/// stops startup rather than quietly keep the default, as a switch like
+/// `LEADERBOARD_ENABLED` must never fail open
+fn env_flag(key: &str, default: bool) -> bool {
+    match env::var(key) {
+        Ok(value) => parse_flag(&value).unwrap_or_else(|| {
+            panic!(
+                "{} must be one of 1/0, true/false, yes/no or on/off, got {:?}",
+                key, value
+            )
+        }),
+        Err(_) => default,
+    }
+}
+
+/// Parse the usual spellings of a boolean switch, in any case
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Raise a count that must be positive to 1, with a warning. No scan could ever start
+/// with zero scan permits
+fn at_least_one(key: &str, value: usize) -> usize {
+    if value == 0 {
+        warn!("{} must be at least 1, using 1", key);
+        return 1;
+    }
+    value
+}
+
+/// A shallow clone never holds more commits than its depth, so a history limit at or
+/// past it could never apply. Turn it off with a warning rather than silently ignore it
+fn below_clone_depth(key: &str, limit: u32) -> u32 {
+    if limit >= DEFAULT_CLONE_DEPTH {
+        warn!(
+            "{} of {} can't apply to clones only {} commits deep, turning it off",
+            key, limit, DEFAULT_CLONE_DEPTH
+        );
+        return 0;
+    }
+    limit
+}
+
+/// Read a `pattern=>replacement` rewrite rule, ignoring it with a warning if it's invalid
+fn env_url_rewrite(key: &str) -> Option<UrlRewrite> {
+    let rule = env::var(key).ok().filter(|rule| !rule.trim().is_empty())?;
+    UrlRewrite::parse(&rule)
+        .inspect_err(|e| warn!("Ignoring {}: {}", key, e))
+        .ok()
+}
+
+/// Read the site's public URL, ignoring it with a warning unless it's http(s)
+fn env_public_url(key: &str) -> Option<String> {
+    let url = env::var(key).ok().filter(|url| !url.trim().is_empty())?;
+    normalize_public_url(&url).or_else(|| {
+        warn!("Ignoring {}: {:?} isn't an http(s) URL", key, url);
+        None
+    })
+}
+
+/// Turn `https://todos.example.com/` into `https://todos.example.com`, `None` unless
+/// it's an http(s) URL with a host
+fn normalize_public_url(url: &str) -> Option<String> {
+    let trimmed = url.trim().trim_end_matches('/');
+    let host = trimmed
+        .strip_prefix("https://")
+        .or_else(|| trimmed.strip_prefix("http://"))?;
+    (!host.is_empty()).then(|| trimmed.to_string())
+}
+
+/// Turn `todo-finder/` or `/todo-finder/` into `/todo-finder`, and `/` into an empty prefix
+pub fn normalize_base_path(base_path: &str) -> String {
+    let trimmed = base_path.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
+/// Read a comma-separated env var into a list, empty if missing
+fn env_list(key: &str) -> Vec<String> {
+    env::var(key)
+        .map(|value| value.split(',').map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_at_least_one() {
+        assert_eq!(at_least_one("MAX_CONCURRENT_SCANS", 0), 1);
+        assert_eq!(at_least_one("MAX_CONCURRENT_SCANS", 1), 1);
+        assert_eq!(at_least_one("MAX_CONCURRENT_SCANS", 8), 8);
+    }
+
+    #[test]
+    fn test_below_clone_depth() {
+        assert_eq!(below_clone_depth("QUICK_MODE_HISTORY_LIMIT", 0), 0);
+        assert_eq!(below_clone_depth("QUICK_MODE_HISTORY_LIMIT", 500), 500);
+        assert_eq!(
+            below_clone_depth("QUICK_MODE_HISTORY_LIMIT", DEFAULT_CLONE_DEPTH),
+            0
+        );
+    }
+
+    #[test]
+    fn test_parse_flag() {
+        assert_eq!(parse_flag("0"), Some(false));
+        assert_eq!(parse_flag("FALSE"), Some(false));
+        assert_eq!(parse_flag(" no "), Some(false));
+        assert_eq!(parse_flag("Off"), Some(false));
+        assert_eq!(parse_flag("1"), Some(true));
+        assert_eq!(parse_flag("Yes"), Some(true));
+        assert_eq!(parse_flag("on"), Some(true));
+        assert_eq!(parse_flag("disabled"), None);
+        assert_eq!(parse_flag(""), None);
+    }
+
+    #[test]
+    fn test_normalize_public_url() {
+        assert_eq!(
+            normalize_public_url("https://todos.example.com/").as_deref(),
+            Some("https://todos.example.com")
+        );
+        assert_eq!(
+            normalize_public_url(" http://localhost:8998 ").as_deref(),
+            Some("http://localhost:8998")
+        );
+        assert_eq!(normalize_public_url("todos.example.com"), None);
+        assert_eq!(normalize_public_url("https://"), None);
+    }
+
+    #[test]
+    fn test_normalize_base_path() {
+        assert_eq!(normalize_base_path(""), "");
+        assert_eq!(normalize_base_path("/"), "");
+        assert_eq!(normalize_base_path("todo-finder/"), "/todo-finder");
+        assert_eq!(normalize_base_path("/todo-finder"), "/todo-finder");
+    }
+}
@@ -1 +1,23 @@
 pub const MAX_AGE_REQUESTS_HOURS: i64 = 1;
+pub const DEFAULT_MIN_LEADERBOARD_AGE_DAYS: i64 = 30;
+pub const DEFAULT_MIN_TOTAL_TODOS: usize = 1;
+pub const DEFAULT_LEADERBOARD_PAGE_SIZE: usize = 100;
+pub const MAX_LEADERBOARD_PAGE_SIZE: usize = 100;
+pub const LEADERBOARD_FEED_SIZE: usize = 20;
+pub const DEFAULT_SLOW_SCAN_WARNING_SECS: u64 = 60;
+pub const DEFAULT_MAX_CONCURRENT_SCANS: usize = 4;
+pub const DEFAULT_MAX_QUEUED_SCANS: u32 = 20;
+pub const DEFAULT_SCAN_QUEUE_RETRY_AFTER_SECS: u64 = 30;
+pub const DEFAULT_STATUS_CHANNEL_CAPACITY: usize = 100;
+pub const DEFAULT_RESULT_CACHE_CAPACITY: usize = 256;
+pub const DEFAULT_LEADERBOARD_SNAPSHOTS: usize = 10;
+pub const DEFAULT_HEADER_READ_TIMEOUT_SECS: u64 = 30;
+pub const DEFAULT_ALL_TODOS_CONTEXT_BUDGET: usize = 1024 * 1024;
+pub const DEFAULT_COMMIT_BODY_MAX_LEN: usize = 1000;
+pub const DEFAULT_TEST_FILE_PENALTY_DAYS: u32 = 365;
+pub const DEFAULT_LEADERBOARD_VERIFY_SAMPLE: usize = 5;
+pub const DEFAULT_GIT_USER_AGENT: &str = concat!("oldest-todo-finder/", env!("CARGO_PKG_VERSION"));
+pub const MAX_FORM_KEYWORDS: usize = 10;
+/// Longest a branch listing may take, including waiting for a turn at the host
+pub const BRANCHES_TIMEOUT_SECS: u64 = 10;
+pub const ADMIN_TOKEN_HEADER: &str = "x-admin-token";
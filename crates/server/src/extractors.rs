@@ -0,0 +1,80 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path},
+    http::{StatusCode, request::Parts},
+    response::Html,
+};
+
+use crate::api_error::ApiError;
+use crate::constants::ADMIN_TOKEN_HEADER;
+use uuid::Uuid;
+
+use crate::state::AppState;
+use crate::templates::error_page;
+
+/// Path extractor for `:request_id` that rejects anything that isn't a UUID,
+/// so malformed ids never reach the state maps
+pub struct RequestId(pub String);
+
+#[async_trait]
+impl FromRequestParts<AppState> for RequestId {
+    type Rejection = (StatusCode, Html<String>);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let malformed = || {
+            (
+                StatusCode::BAD_REQUEST,
+                error_page(
+                    state.templates.as_ref(),
+                    &state.config.base_path,
+                    "Malformed request ID",
+                ),
+            )
+        };
+
+        let Path(raw) = Path::<String>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| malformed())?;
+
+        Uuid::parse_str(&raw)
+            .map(|id| RequestId(id.to_string()))
+            .map_err(|_| malformed())
+    }
+}
+
+/// Guard for admin routes: the request must carry the configured `ADMIN_TOKEN`
+/// in the `X-Admin-Token` header. Without a configured token every request is refused
+pub struct AdminAuth;
+
+#[async_trait]
+impl FromRequestParts<AppState> for AdminAuth {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let provided = parts
+            .headers
+            .get(ADMIN_TOKEN_HEADER)
+            .and_then(|value| value.to_str().ok());
+
+        match (&state.config.admin_token, provided) {
+            (Some(expected), Some(provided)) if tokens_match(expected, provided) => Ok(AdminAuth),
+            _ => Err(ApiError::unauthorized()),
+        }
+    }
+}
+
+/// Compare tokens without bailing out at the first differing byte
+fn tokens_match(expected: &str, provided: &str) -> bool {
+    expected.len() == provided.len()
+        && expected
+            .bytes()
+            .zip(provided.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
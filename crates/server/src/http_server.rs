@@ -0,0 +1,228 @@
+use std::time::Duration;
+
+use axum::Router;
+use hyper::server::conn::http1;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto;
+use hyper_util::service::TowerToHyperService;
+use log::{debug, error};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::config::Config;
+
+/// How each accepted connection is tuned, taken from the server config
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionOptions {
+    pub tcp_nodelay: bool,
+    pub keep_alive: bool,
+    pub header_read_timeout: Option<Duration>,
+    pub http2: bool,
+}
+
+impl ConnectionOptions {
+    pub fn from_config(config: &Config) -> Self {
+        ConnectionOptions {
+            tcp_nodelay: config.tcp_nodelay,
+            keep_alive: config.http_keep_alive,
+            header_read_timeout: (config.header_read_timeout_secs > 0)
+                .then(|| Duration::from_secs(config.header_read_timeout_secs)),
+            http2: config.http2,
+        }
+    }
+
+    fn http1_builder(&self) -> http1::Builder {
+        let mut builder = http1::Builder::new();
+        builder
+            .timer(TokioTimer::new())
+            .keep_alive(self.keep_alive)
+            .header_read_timeout(self.header_read_timeout);
+        builder
+    }
+
+    /// Speaks whichever of HTTP/1.1 and HTTP/2 each connection opens with
+    fn auto_builder(&self) -> auto::Builder<TokioExecutor> {
+        let mut builder = auto::Builder::new(TokioExecutor::new());
+        builder
+            .http1()
+            .timer(TokioTimer::new())
+            .keep_alive(self.keep_alive)
+            .header_read_timeout(self.header_read_timeout);
+        builder.http2().timer(TokioTimer::new());
+        builder
+    }
+}
+
+/// Serve the app over HTTP/1.1, and h2c when enabled, until the listener fails for good.
+///
+/// Stands in for `axum::serve`, which doesn't expose keep-alive or timeouts. Upgrades stay
+/// enabled so the scan status websocket keeps working
+pub async fn serve(listener: TcpListener, app: Router, options: ConnectionOptions) {
+    let http1 = options.http1_builder();
+    let auto = options.auto_builder();
+
+    loop {
+        let (stream, remote_addr) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                // Usually running out of file descriptors, back off rather than spin
+                error!("Failed to accept connection: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+
+        if let Err(e) = stream.set_nodelay(options.tcp_nodelay) {
+            debug!("Failed to set TCP_NODELAY for {}: {}", remote_addr, e);
+        }
+
+        let service = TowerToHyperService::new(app.clone());
+        let (http1, auto) = (http1.clone(), auto.clone());
+        tokio::spawn(async move {
+            if !first_bytes_arrive(&stream, options.header_read_timeout).await {
+                debug!("Connection from {} sent nothing, dropping it", remote_addr);
+                return;
+            }
+
+            let io = TokioIo::new(stream);
+            let result = if options.http2 {
+                auto.serve_connection_with_upgrades(io, service).await
+            } else {
+                http1
+                    .serve_connection(io, service)
+                    .with_upgrades()
+                    .await
+                    .map_err(Into::into)
+            };
+            if let Err(e) = result {
+                debug!("Connection from {} ended with error: {}", remote_addr, e);
+            }
+        });
+    }
+}
+
+/// Wait for the client to send something, or hang up, within `timeout`. hyper's header
+/// timeout only starts once it knows which HTTP version it's reading, so without this a
+/// silent connection would be held open forever while that's sniffed
+async fn first_bytes_arrive(stream: &TcpStream, timeout: Option<Duration>) -> bool {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, stream.readable())
+            .await
+            .is_ok(),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use http_body_util::{BodyExt, Empty};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    async fn spawn_server(options: ConnectionOptions) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route("/", get(|| async { "hello" }));
+        tokio::spawn(serve(listener, app, options));
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive_off_closes_after_response() {
+        let addr = spawn_server(ConnectionOptions {
+            tcp_nodelay: true,
+            keep_alive: false,
+            header_read_timeout: None,
+            http2: true,
+        })
+        .await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        // The server hangs up after one response, so this doesn't wait forever
+        let mut response = String::new();
+        tokio::time::timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+            .await
+            .expect("connection should be closed")
+            .unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.to_lowercase().contains("connection: close"));
+        assert!(response.ends_with("hello"));
+    }
+
+    #[tokio::test]
+    async fn test_idle_connection_closed_after_header_timeout() {
+        let addr = spawn_server(ConnectionOptions {
+            tcp_nodelay: true,
+            keep_alive: true,
+            header_read_timeout: Some(Duration::from_millis(100)),
+            http2: true,
+        })
+        .await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut buf = Vec::new();
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut buf))
+            .await
+            .expect("idle connection should be dropped");
+
+        assert_eq!(read.unwrap(), 0);
+    }
+
+    /// GET `/` over HTTP/2 with prior knowledge, i.e. without an upgrade first
+    async fn h2c_get(addr: std::net::SocketAddr) -> Result<String, hyper::Error> {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (mut sender, connection) =
+            hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                .await?;
+        tokio::spawn(connection);
+
+        let request = hyper::Request::get(format!("http://{}/", addr))
+            .body(Empty::<hyper::body::Bytes>::new())
+            .unwrap();
+        let response = sender.send_request(request).await?;
+        assert_eq!(response.version(), hyper::Version::HTTP_2);
+        let body = response.into_body().collect().await?.to_bytes();
+        Ok(String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_accepts_h2c_prior_knowledge() {
+        let options = ConnectionOptions {
+            tcp_nodelay: true,
+            keep_alive: true,
+            header_read_timeout: Some(Duration::from_secs(5)),
+            http2: true,
+        };
+        let addr = spawn_server(options).await;
+        assert_eq!(h2c_get(addr).await.unwrap(), "hello");
+
+        let addr = spawn_server(ConnectionOptions {
+            http2: false,
+            ..options
+        })
+        .await;
+        let refused = tokio::time::timeout(Duration::from_secs(5), h2c_get(addr))
+            .await
+            .expect("an HTTP/1.1 server hangs up on the preface");
+        assert!(refused.is_err());
+    }
+
+    #[test]
+    fn test_zero_timeout_disables_it() {
+        let config = Config {
+            header_read_timeout_secs: 0,
+            ..Config::default()
+        };
+        assert_eq!(
+            ConnectionOptions::from_config(&config).header_read_timeout,
+            None
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use blame_finder::{ScanOptions, TodoItem};
+use serde::Deserialize;
+use std::cmp::Reverse;
+
+use crate::constants::{DEFAULT_LEADERBOARD_PAGE_SIZE, MAX_LEADERBOARD_PAGE_SIZE};
+
+/// How the leaderboard page is ordered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LeaderboardSort {
+    /// Oldest TODO first
+    #[default]
+    Age,
+    /// Most recently discovered first
+    Recent,
+    /// Grouped by repository, oldest first within each repo
+    Repo,
+}
+
+impl LeaderboardSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LeaderboardSort::Age => "age",
+            LeaderboardSort::Recent => "recent",
+            LeaderboardSort::Repo => "repo",
+        }
+    }
+}
+
+// Query parameters for the leaderboard page, e.g. `?page=2&per_page=20&sort=repo`
+#[derive(Debug, Default, Deserialize)]
+pub struct LeaderboardQuery {
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+    pub sort: Option<LeaderboardSort>,
+    /// Rank by age with this many extra days per later commit to each TODO's file, as a
+    /// scan does with the same option. Only entries whose scan counted them are moved
+    pub neglect_days_per_commit: Option<u32>,
+}
+
+/// A single page of the leaderboard, ready to render
+#[derive(Debug)]
+pub struct LeaderboardWindow {
+    pub items: Vec<TodoItem>,
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+    pub sort: LeaderboardSort,
+    /// 0 when ranked by age alone
+    pub neglect_days_per_commit: u32,
+    pub prev_page: Option<usize>,
+    pub next_page: Option<usize>,
+}
+
+impl LeaderboardWindow {
+    /// Rank of the first item on this page, minus one
+    pub fn rank_offset(&self) -> usize {
+        (self.page - 1) * self.per_page
+    }
+}
+
+impl LeaderboardQuery {
+    /// Sort `items` (already in age order, oldest first) and cut out the requested page,
+    /// cloning only the items on it
+    pub fn apply(&self, items: &[TodoItem]) -> LeaderboardWindow {
+        let page = self.page.unwrap_or(1).max(1);
+        let per_page = self
+            .per_page
+            .unwrap_or(DEFAULT_LEADERBOARD_PAGE_SIZE)
+            .clamp(1, MAX_LEADERBOARD_PAGE_SIZE);
+        let sort = self.sort.unwrap_or_default();
+        let neglect_days_per_commit = self.neglect_days_per_commit.unwrap_or_default();
+
+        // Stable sorts keep age order as the tie-breaker
+        let mut items: Vec<&TodoItem> = items.iter().collect();
+        match sort {
+            LeaderboardSort::Age if neglect_days_per_commit > 0 => {
+                let ranking = ScanOptions {
+                    neglect_days_per_commit,
+                    test_file_penalty_days: 0,
+                    ..ScanOptions::default()
+                };
+                items.sort_by(|a, b| ranking.rank_oldest(b, a));
+            }
+            LeaderboardSort::Age => {}
+            LeaderboardSort::Recent => items.sort_by_key(|item| Reverse(item.discovered_at)),
+            LeaderboardSort::Repo => {
+                items.sort_by(|a, b| a.source_repo_url.cmp(&b.source_repo_url))
+            }
+        }
+
+        let total = items.len();
+        let last_page = total.div_ceil(per_page).max(1);
+
+        // Past the end, point "prev" at the last real page so it always lands somewhere useful
+        let prev_page = (page > 1).then(|| (page - 1).min(last_page));
+        let next_page = (page < last_page).then_some(page + 1);
+
+        let items = items
+            .into_iter()
+            .skip((page - 1).saturating_mul(per_page))
+            .take(per_page)
+            .cloned()
+            .collect();
+
+        LeaderboardWindow {
+            items,
+            total,
+            page,
+            per_page,
+            sort,
+            neglect_days_per_commit,
+            prev_page,
+            next_page,
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use blame_finder::{BlameError, TodoItem, todo};
+use log::{info, warn};
+
+use crate::state::AppState;
+
+/// Decides whether a leaderboard entry's TODO is still in its repository
+pub trait TodoValidator {
+    /// `Ok(false)` once the TODO is known to be gone. An error means we couldn't
+    /// tell, e.g. the host was unreachable, and the entry is kept
+    fn still_exists(
+        &self,
+        todo: &TodoItem,
+    ) -> impl Future<Output = Result<bool, BlameError>> + Send;
+}
+
+/// Checks entries against a fresh quick clone, looking only at the TODO's own file.
+/// Each check takes a scan slot, so checks never add to the configured scan load
+pub struct CloneValidator<'a> {
+    pub state: &'a AppState,
+}
+
+impl TodoValidator for CloneValidator<'_> {
+    async fn still_exists(&self, todo: &TodoItem) -> Result<bool, BlameError> {
+        let _permit = self
+            .state
+            .scan_permits
+            .acquire()
+            .await
+            .expect("scan semaphore is never closed");
+        let repo = crate::repo_builder(&self.state.config, &todo.source_repo_url)
+            .quick()
+            .build()
+            .await?;
+        repo.prepare().await?;
+
+        // Lines added above it move a TODO without making it stale. Compared by exact
+        // path, as a path filter is a glob and file names may hold glob characters
+        let found = todo::find_todos(&repo, self.state.clock.as_ref()).await?;
+        Ok(found.iter().any(|candidate| {
+            candidate.file_path == todo.file_path && candidate.todo_text == todo.todo_text
+        }))
+    }
+}
+
+/// Re-checks a few global leaderboard entries per run, taking turns through the
+/// board, and removes those whose TODO has gone from every board
+pub struct LeaderboardVerifier {
+    sample_size: usize,
+    // Where in the board the next run starts, best first
+    next: usize,
+}
+
+impl LeaderboardVerifier {
+    pub fn new(sample_size: usize) -> Self {
+        LeaderboardVerifier {
+            sample_size,
+            next: 0,
+        }
+    }
+
+    /// Check the next sample of entries, returning the ones removed as stale
+    pub async fn run(&mut self, state: &AppState, validator: &impl TodoValidator) -> Vec<TodoItem> {
+        let Some(board) = state.leaderboard() else {
+            return Vec::new();
+        };
+        let items = board.snapshot().await;
+        if items.is_empty() {
+            return Vec::new();
+        }
+
+        let start = self.next % items.len();
+        let sample: Vec<_> = items
+            .iter()
+            .cycle()
+            .skip(start)
+            .take(self.sample_size.min(items.len()))
+            .collect();
+        self.next = start + sample.len();
+
+        let mut removed = Vec::new();
+        for todo in sample {
+            match validator.still_exists(todo).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    removed.extend(state.remove_from_leaderboards(|item| item == todo).await)
+                }
+                Err(e) => warn!(
+                    "Couldn't verify {}:{} of {}, keeping it: {}",
+                    todo.file_path, todo.line_number, todo.source_repo_url, e
+                ),
+            }
+        }
+
+        // Later entries moved up into the removed ones' places
+        self.next -= removed.len();
+        for todo in &removed {
+            info!(
+                "Removed stale {}:{} of {} from the leaderboard",
+                todo.file_path, todo.line_number, todo.source_repo_url
+            );
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{test_state, todo_aged};
+
+    /// Reports TODOs in `stale_file` as gone and fails for `unreachable_file`
+    struct FakeValidator {
+        stale_file: &'static str,
+        unreachable_file: &'static str,
+    }
+
+    impl TodoValidator for FakeValidator {
+        async fn still_exists(&self, todo: &TodoItem) -> Result<bool, BlameError> {
+            if todo.file_path == self.unreachable_file {
+                return Err(BlameError::GitError("host unreachable".to_string()));
+            }
+            Ok(todo.file_path != self.stale_file)
+        }
+    }
+
+    fn todo_in(file: &str, days: i64) -> TodoItem {
+        TodoItem {
+            file_path: file.to_string(),
+            ..todo_aged(days)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stale_entries_are_removed_in_turn() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let board = state.leaderboard().unwrap();
+        for (file, days) in [
+            ("a.rs", 400),
+            ("gone.rs", 300),
+            ("down.rs", 200),
+            ("b.rs", 100),
+        ] {
+            assert!(board.try_add(todo_in(file, days)).await);
+        }
+        let validator = FakeValidator {
+            stale_file: "gone.rs",
+            unreachable_file: "down.rs",
+        };
+        let files = |items: Vec<TodoItem>| -> Vec<String> {
+            items.into_iter().map(|todo| todo.file_path).collect()
+        };
+
+        let mut verifier = LeaderboardVerifier::new(2);
+        let removed = verifier.run(&state, &validator).await;
+        assert_eq!(files(removed), ["gone.rs"]);
+        assert_eq!(files(board.get_items().await), ["a.rs", "down.rs", "b.rs"]);
+
+        // The next run carries on from where this one stopped, skipping nothing
+        assert!(verifier.run(&state, &validator).await.is_empty());
+        assert_eq!(verifier.next, 3);
+        assert_eq!(board.get_items().await.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_clone_check_waits_for_a_scan_slot() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let _held = state
+            .scan_permits
+            .acquire_many(state.config.max_concurrent_scans as u32)
+            .await
+            .unwrap();
+        let validator = CloneValidator { state: &state };
+
+        let todo = todo_aged(400);
+        let check = validator.still_exists(&todo);
+        let waited = tokio::time::timeout(std::time::Duration::from_millis(50), check).await;
+
+        assert!(waited.is_err());
+    }
+}
@@ -1,46 +1,109 @@
 use std::time::Duration;
 
-use axum::extract::Path;
-use axum::extract::ws::{WebSocket, WebSocketUpgrade};
+use api_error::ApiError;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::{
     Router,
-    extract::{Form, State},
-    response::IntoResponse,
-    routing::{get, post},
+    body::Body,
+    extract::{Form, Path, Query, State},
+    http::{HeaderMap, header},
+    response::{Html, IntoResponse},
+    routing::{delete, get, post},
 };
-use blame_finder::Repository;
-use constants::MAX_AGE_REQUESTS_HOURS;
-use futures::{sink::SinkExt, stream::StreamExt};
-use leaderboard::SharedLeaderboard;
-use log::{error, info};
+use blame_finder::{
+    BlameError, Glob, Repository, RepositoryBuilder, ScanOptions, ScanResult, SystemClock,
+};
+use categories::GLOBAL_CATEGORY;
+use config::Config;
+use constants::{
+    BRANCHES_TIMEOUT_SECS, LEADERBOARD_FEED_SIZE, MAX_AGE_REQUESTS_HOURS, MAX_FORM_KEYWORDS,
+};
+use extractors::{AdminAuth, RequestId};
+use futures::{Sink, Stream, sink::SinkExt, stream::StreamExt};
+use leaderboard_query::LeaderboardQuery;
+use leaderboard_verify::{CloneValidator, LeaderboardVerifier};
+use log::{debug, error, info};
+use scan_counter::ScanCounter;
 use serde::Deserialize;
-use state::{AppState, StatusUpdate};
-use templates::{error_page, index_page, leaderboard_page, result_page};
+use state::{AppState, LeaderboardRank, StatusUpdate};
+use stats::LeaderboardStats;
+use templates::{
+    ResultDetails, error_page, index_page, leaderboard_feed, leaderboard_page, result_page,
+};
+use tokio::sync::oneshot;
 use tokio::task;
 use tokio::time;
 use tower_http::services::ServeDir;
 
+mod api_error;
+mod categories;
+mod config;
 mod constants;
+mod extractors;
+mod http_server;
+mod leaderboard_query;
+mod leaderboard_verify;
 mod logger;
+mod result_cache;
+mod scan_counter;
 mod state;
+mod stats;
 mod templates;
+#[cfg(test)]
+mod test_utils;
 mod todo_entrypoint;
 
 // Form data for repository URL submission
 #[derive(Deserialize)]
 struct RepoForm {
     repo_url: String,
+    // Checkbox, present (as "on") only when ticked
+    #[serde(default)]
+    quick: Option<String>,
+    // Checkbox, keep every blamed TODO for download, not just the oldest
+    #[serde(default)]
+    all: Option<String>,
+    // Glob such as `src/parser/**`, only TODOs in matching files count. Empty scans everything
+    #[serde(default)]
+    path_filter: Option<String>,
+    // Comma-separated words searched for instead of TODO, e.g. `FIXME, HACK`
+    #[serde(default)]
+    keywords: Option<String>,
+    // Branch to scan instead of main/master
+    #[serde(default)]
+    branch: Option<String>,
+    // Comma-separated file extensions to search, e.g. `rs, py`. Empty searches every file
+    #[serde(default)]
+    extensions: Option<String>,
+    // Days of extra age per later commit to a TODO's file. Empty keeps the server's setting
+    #[serde(default)]
+    neglect_days_per_commit: Option<String>,
 }
 
 #[tokio::main]
 async fn main() {
     logger::setup_logger().unwrap();
 
-    let leaderboard = SharedLeaderboard::new("data/leaderboard.json".to_string(), 100)
-        .await
-        .expect("Failed to create leaderboard");
+    let config = Config::from_env();
+    let leaderboards = if config.leaderboard_enabled {
+        let boards = categories::open_leaderboards(
+            "data/leaderboard.json",
+            100,
+            config.leaderboard_snapshots,
+            std::sync::Arc::new(SystemClock),
+        );
+        Some(boards.await.expect("Failed to create leaderboards"))
+    } else {
+        info!("Leaderboard disabled, scan results won't be kept");
+        None
+    };
+    if config.leaderboard_enabled && config.public_url.is_none() && !config.trust_proxy_headers {
+        info!("PUBLIC_URL isn't set, the RSS feed links to the site by relative URL");
+    }
 
-    let state = AppState::new(leaderboard);
+    let scan_counter = ScanCounter::load("data/stats.json").await;
+
+    let state = AppState::new(leaderboards, scan_counter, config);
     // Start cleanup task for old repos
     let cleanup_state = state.clone();
     task::spawn(async move {
@@ -51,8 +114,12 @@ async fn main() {
             info!("Running repository cleanup task");
 
             // TODO: fine-tune, 7 days might be too long
-            match blame_finder::cleanup_old_repos(7, Some(cleanup_state.active_repo_paths.clone()))
-                .await
+            match blame_finder::cleanup_old_repos(
+                7,
+                Some(cleanup_state.active_repo_paths.clone()),
+                cleanup_state.config.cleanup_concurrency,
+            )
+            .await
             {
                 Ok(count) => {
                     if count > 0 {
@@ -79,14 +146,28 @@ async fn main() {
         }
     });
 
-    let app = Router::new()
-        .route("/", get(index_handler))
-        .route("/find-oldest-todo", post(find_todo_handler))
-        .route("/results/:request_id", get(results_handler))
-        .route("/ws/scan-status/:request_id", get(ws_status_handler))
-        .route("/leaderboard", get(leaderboard_handler))
-        .nest_service("/static", ServeDir::new("static"))
-        .with_state(state);
+    // Opt-in, as each check clones the repos it samples
+    let verify_interval = state.config.leaderboard_verify_interval_secs;
+    if verify_interval > 0 && state.leaderboards.is_some() {
+        let verify_state = state.clone();
+        task::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(verify_interval));
+            let mut verifier =
+                LeaderboardVerifier::new(verify_state.config.leaderboard_verify_sample);
+            let validator = CloneValidator {
+                state: &verify_state,
+            };
+
+            loop {
+                interval.tick().await;
+                info!("Verifying a sample of leaderboard entries");
+                verifier.run(&verify_state, &validator).await;
+            }
+        });
+    }
+
+    let connection_options = http_server::ConnectionOptions::from_config(&state.config);
+    let app = app(state);
 
     let port = std::env::var("PORT").unwrap_or_else(|_| "8998".to_string());
     let addr = format!("0.0.0.0:{}", port);
@@ -94,11 +175,51 @@ async fn main() {
     info!("Starting server on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    http_server::serve(listener, app, connection_options).await;
+}
+
+fn app(state: AppState) -> Router {
+    let routes = Router::new()
+        .route("/", get(index_handler))
+        .route("/find-oldest-todo", post(find_todo_handler))
+        .route("/results/:request_id", get(results_handler))
+        .route(
+            "/api/scan/:request_id/todos.ndjson",
+            get(todos_ndjson_handler),
+        )
+        .route("/ws/scan-status/:request_id", get(ws_status_handler))
+        .route("/leaderboard", get(leaderboard_handler))
+        .route("/leaderboard/:category", get(category_leaderboard_handler))
+        .route("/leaderboard.rss", get(leaderboard_feed_handler))
+        .route("/api/leaderboard/stats", get(leaderboard_stats_handler))
+        .route("/api/stats", get(stats_handler))
+        .route("/api/branches", get(branches_handler))
+        .route(
+            "/api/leaderboard/:repo_encoded/:line",
+            delete(evict_leaderboard_entry_handler),
+        )
+        .route("/api/cache", get(cached_repos_handler))
+        .nest_service("/static", ServeDir::new("static"));
+
+    // When running behind a reverse proxy, everything lives under the base path
+    let base_path = state.config.base_path.clone();
+    let router = if base_path.is_empty() {
+        routes
+    } else {
+        Router::new().nest(&base_path, routes)
+    };
+
+    router.with_state(state)
 }
 
-async fn index_handler() -> impl IntoResponse {
-    index_page()
+// A shared `/?repo=` link gets the plain page, its script fills in the form and submits
+// it, so the scan still starts with a POST
+async fn index_handler(State(state): State<AppState>) -> impl IntoResponse {
+    index_page(
+        state.templates.as_ref(),
+        &state.config.base_path,
+        state.scan_counter.total().await,
+    )
 }
 
 // Handler for finding the oldest TODO
@@ -110,8 +231,122 @@ use uuid::Uuid;
 async fn find_todo_handler(
     State(state): State<AppState>,
     Form(form): Form<RepoForm>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let request_id = start_scan(&state, form).await?;
+
+    // Return the request ID immediately
+    Ok(Json(serde_json::json!({
+        "request_id": request_id,
+        "status": "processing"
+    })))
+}
+
+/// What a submitted form asks to scan, with the server's own settings applied
+struct ScanRequest {
+    repo_url: String,
+    builder: RepositoryBuilder,
+    options: ScanOptions,
+    keep_all: bool,
+}
+
+impl ScanRequest {
+    /// Read the scan options out of a form, blank fields keeping their defaults.
+    /// Mistakes we can spot without the repository, like a bad glob, are reported
+    /// straight away, before the scan takes a queue slot
+    fn from_form(config: &Config, form: RepoForm) -> Result<Self, ApiError> {
+        let neglect_days_per_commit = match form_value(form.neglect_days_per_commit.as_deref()) {
+            Some(days) => days.parse().map_err(|_| {
+                ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    "invalid_option",
+                    format!("Not a number of days: {:?}", days),
+                )
+            })?,
+            None => config.neglect_days_per_commit,
+        };
+        let options = ScanOptions {
+            quick: form.quick.is_some(),
+            exclude_authors: config.excluded_authors.clone(),
+            commit_body_limit: (config.commit_body_max_len > 0)
+                .then_some(config.commit_body_max_len),
+            test_file_penalty_days: config.test_file_penalty_days,
+            neglect_days_per_commit,
+            exclude_generated: config.exclude_generated_files,
+            generated_globs: config.generated_file_globs.clone(),
+            ..ScanOptions::default()
+        };
+
+        let mut builder = repo_builder(config, &form.repo_url);
+        if options.quick {
+            builder = builder.quick();
+        }
+        if let Some(path_filter) = form_value(form.path_filter.as_deref()) {
+            Glob::new(&path_filter)?;
+            builder = builder.path_filter(path_filter);
+        }
+        let keywords = form_list(form.keywords.as_deref());
+        if keywords.len() > MAX_FORM_KEYWORDS {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_pattern",
+                format!("Search for at most {} keywords", MAX_FORM_KEYWORDS),
+            ));
+        }
+        if !keywords.is_empty() {
+            builder = builder.keywords(keywords);
+        }
+        let extensions = form_list(form.extensions.as_deref());
+        if !extensions.is_empty() {
+            builder = builder.extensions(extensions);
+        }
+        if let Some(branch) = form_value(form.branch.as_deref()) {
+            builder = builder.branch(branch);
+        }
+        if let Some(cache_dir) = &config.blame_cache_dir {
+            builder = builder.blame_cache_dir(cache_dir);
+        }
+
+        Ok(ScanRequest {
+            repo_url: form.repo_url,
+            builder,
+            options,
+            keep_all: form.all.is_some(),
+        })
+    }
+}
+
+/// A text field's trimmed value, `None` when it was left blank
+fn form_value(field: Option<&str>) -> Option<String> {
+    field
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+/// The non-blank items of a comma-separated text field
+fn form_list(field: Option<&str>) -> Vec<String> {
+    field
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|item| form_value(Some(item)))
+        .collect()
+}
+
+/// Queue a scan of `form.repo_url` in the background, returning its request id
+///
+/// Fails without queueing anything if the form is invalid or the queue is full.
+async fn start_scan(state: &AppState, form: RepoForm) -> Result<String, ApiError> {
+    let scan = ScanRequest::from_form(&state.config, form)?;
+
+    // Turn the request away now rather than letting the backlog grow without bound
     let request_id = Uuid::new_v4().to_string();
+    if !state.try_enqueue_scan(&request_id).await {
+        return Err(ApiError::busy(
+            "Too many scans are queued, please try again shortly",
+            state.config.scan_queue_retry_after_secs,
+        ));
+    }
+
     state.register_request(&request_id).await;
 
     // Send initial status
@@ -124,17 +359,46 @@ async fn find_todo_handler(
                 percentage: Some(0),
                 error: None,
                 redirect_url: None,
+                queue_position: None,
+                leaderboard_rank: None,
             },
         )
         .await;
 
-    let repo_url = form.repo_url.clone();
+    let ScanRequest {
+        repo_url,
+        builder,
+        options: scan_options,
+        keep_all,
+    } = scan;
     let state_clone = state.clone();
     let request_id_clone = request_id.clone();
 
     // Spawn background task
     tokio::spawn(async move {
-        match Repository::new(&repo_url).await {
+        // Wait for a free scan slot, letting the user know if there's a queue
+        let _permit = match state_clone.scan_permits.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                let position = state_clone
+                    .queue_position(&request_id_clone)
+                    .await
+                    .unwrap_or(1);
+                state_clone
+                    .send_status(&request_id_clone, state::queued_status(position))
+                    .await;
+                state_clone
+                    .scan_permits
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("scan semaphore is never closed")
+            }
+        };
+        state_clone.dequeue_scan(&request_id_clone).await;
+
+        let repo = builder.build().await;
+        match repo {
             Ok(repo) => {
                 // Track active job
                 let mut numb_active_jobs = state_clone.numb_active_jobs.lock().await;
@@ -150,6 +414,8 @@ async fn find_todo_handler(
                     &state_clone,
                     &request_id_clone,
                     &repo_url,
+                    &scan_options,
+                    keep_all,
                 )
                 .await;
 
@@ -161,55 +427,35 @@ async fn find_todo_handler(
                 drop(numb_active_jobs);
                 drop(active_repos);
 
+                if result.is_ok() {
+                    state_clone.scan_counter.increment().await;
+                }
+
                 // Process result and store it for later retrieval
                 match result {
-                    Ok(Some(todo)) => {
-                        // Add to leaderboard
-                        let _ = state_clone.leaderboard.try_add(todo.clone()).await;
-
-                        // Store the result for this request_id
-                        state_clone
-                            .store_result(&request_id_clone, Some(todo), None)
-                            .await;
-
-                        // Send complete status with redirect URL
-                        state_clone
-                            .send_status(
-                                &request_id_clone,
-                                StatusUpdate {
-                                    message: "Scan complete! Found oldest TODO.".to_string(),
-                                    stage: state::Stage::Complete,
-                                    percentage: Some(100),
-                                    error: None,
-                                    redirect_url: Some(format!("/results/{}", request_id_clone)),
-                                },
-                            )
-                            .await;
+                    Ok(ScanResult::Found(todo)) => {
+                        complete_scan(&state_clone, &request_id_clone, todo).await;
                     }
-                    Ok(None) => {
-                        // Store the empty result
-                        state_clone
-                            .store_result(
-                                &request_id_clone,
-                                None,
-                                Some("No TODO comments found in this repository".to_string()),
-                            )
-                            .await;
-
-                        // Send error status
-                        state_clone
-                            .send_status(
-                                &request_id_clone,
-                                StatusUpdate {
-                                    message: "Scan complete, but no TODO comments were found."
-                                        .to_string(),
-                                    stage: state::Stage::Error,
-                                    percentage: Some(100),
-                                    error: Some("No TODO comments found".to_string()),
-                                    redirect_url: Some(format!("/results/{}", request_id_clone)),
-                                },
-                            )
-                            .await;
+                    Ok(ScanResult::NoTodos) => {
+                        report_empty_scan(
+                            &state_clone,
+                            &request_id_clone,
+                            "No TODO comments found in this repository".to_string(),
+                            "Scan complete, but no TODO comments were found.".to_string(),
+                        )
+                        .await;
+                    }
+                    Ok(ScanResult::AllBlameFailed { attempted }) => {
+                        report_empty_scan(
+                            &state_clone,
+                            &request_id_clone,
+                            format!(
+                                "Found {} TODO comments, but couldn't work out when any of them were written",
+                                attempted
+                            ),
+                            "Scan complete, but git blame failed for every TODO.".to_string(),
+                        )
+                        .await;
                     }
                     Err(e) => {
                         let error_msg = format!("Error finding oldest TODO: {}", e);
@@ -217,7 +463,7 @@ async fn find_todo_handler(
 
                         // Store the error
                         state_clone
-                            .store_result(&request_id_clone, None, Some(error_msg.clone()))
+                            .store_result(&request_id_clone, None, Some(error_msg.clone()), None)
                             .await;
 
                         // Send error status
@@ -229,7 +475,9 @@ async fn find_todo_handler(
                                     stage: state::Stage::Error,
                                     percentage: Some(100),
                                     error: Some(error_msg),
-                                    redirect_url: Some(format!("/results/{}", request_id_clone)),
+                                    redirect_url: Some(state_clone.results_url(&request_id_clone)),
+                                    queue_position: None,
+                                    leaderboard_rank: None,
                                 },
                             )
                             .await;
@@ -242,7 +490,7 @@ async fn find_todo_handler(
 
                 // Store the error
                 state_clone
-                    .store_result(&request_id_clone, None, Some(error_msg.clone()))
+                    .store_result(&request_id_clone, None, Some(error_msg.clone()), None)
                     .await;
 
                 // Send error status
@@ -254,7 +502,9 @@ async fn find_todo_handler(
                             stage: state::Stage::Error,
                             percentage: Some(100),
                             error: Some(error_msg),
-                            redirect_url: Some(format!("/results/{}", request_id_clone)),
+                            redirect_url: Some(state_clone.results_url(&request_id_clone)),
+                            queue_position: None,
+                            leaderboard_rank: None,
                         },
                     )
                     .await;
@@ -262,17 +512,117 @@ async fn find_todo_handler(
         }
     });
 
-    // Return the request ID immediately
-    Ok(Json(serde_json::json!({
-        "request_id": request_id,
-        "status": "processing"
-    })))
+    Ok(request_id)
+}
+
+/// Offer a scan's oldest TODO to the leaderboard, store it as the request's result
+/// and tell the client it's done, with where it ranked so the page can celebrate
+async fn complete_scan(state: &AppState, request_id: &str, mut todo: blame_finder::TodoItem) {
+    todo.request_id = Some(request_id.to_string());
+
+    // Add to leaderboard, if old enough and from a big enough repo to qualify
+    let todo_count = state
+        .get_result(request_id)
+        .await
+        .and_then(|result| result.todo_count);
+    let admission = state.admit_to_leaderboard(&todo, todo_count).await;
+
+    // Store the result for this request_id
+    state
+        .store_result(request_id, Some(todo), None, admission.note)
+        .await;
+    if let Some(rank) = admission.rank {
+        state.record_leaderboard_rank(request_id, rank).await;
+    }
+
+    // Send complete status with redirect URL
+    let message = match admission.rank {
+        Some(LeaderboardRank { record: true, .. }) => {
+            "Scan complete! Found the new oldest TODO on the site!".to_string()
+        }
+        Some(LeaderboardRank { rank, .. }) => format!(
+            "Scan complete! Found oldest TODO, it's #{} on the leaderboard.",
+            rank
+        ),
+        None => "Scan complete! Found oldest TODO.".to_string(),
+    };
+    state
+        .send_status(
+            request_id,
+            StatusUpdate {
+                message,
+                stage: state::Stage::Complete,
+                percentage: Some(100),
+                error: None,
+                redirect_url: Some(state.results_url(request_id)),
+                queue_position: None,
+                leaderboard_rank: admission.rank,
+            },
+        )
+        .await;
+}
+
+/// Start building `repo_url` with the host policy and URL rewrite every repository gets
+fn repo_builder(config: &Config, repo_url: &str) -> RepositoryBuilder {
+    let mut builder = Repository::builder(repo_url)
+        .host_policy(config.host_policy.clone())
+        .max_clones_per_host(config.max_clones_per_host)
+        .user_agent(&config.git_user_agent);
+    if let Some(url_rewrite) = &config.url_rewrite {
+        builder = builder.url_rewrite(url_rewrite.clone());
+    }
+    if let Some((name, email)) = &config.git_identity {
+        builder = builder.git_identity(name, email);
+    }
+    if config.quick_mode_history_limit > 0 {
+        builder = builder.history_limit(config.quick_mode_history_limit);
+    }
+    builder
+}
+
+const BRANCHES_UNAVAILABLE_NOTE: &str =
+    "Couldn't list branches, the repository may be private or not exist";
+
+#[derive(Deserialize)]
+struct BranchesQuery {
+    repo: String,
+}
+
+/// Branches of a repository, for picking one before scanning. A repository we can't
+/// list, e.g. a private one, gets no branches and a note saying why. Anyone can ask,
+/// so listings share the per-host clone limit and give up after a while
+async fn branches_handler(
+    State(state): State<AppState>,
+    Query(query): Query<BranchesQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let repo = repo_builder(&state.config, &query.repo).build().await?;
+
+    let listing = time::timeout(
+        Duration::from_secs(BRANCHES_TIMEOUT_SECS),
+        repo.remote_branches(),
+    );
+    let branches = listing.await.unwrap_or_else(|_| {
+        Err(BlameError::GitError(format!(
+            "Gave up after {}s",
+            BRANCHES_TIMEOUT_SECS
+        )))
+    });
+    match branches {
+        Ok(branches) => Ok(Json(serde_json::json!({ "branches": branches }))),
+        Err(e) => {
+            debug!("Couldn't list branches of {}: {}", query.repo, e);
+            Ok(Json(serde_json::json!({
+                "branches": [],
+                "note": BRANCHES_UNAVAILABLE_NOTE,
+            })))
+        }
+    }
 }
 
 // WebSocket handler for status updates
 async fn ws_status_handler(
+    RequestId(request_id): RequestId,
     ws: WebSocketUpgrade,
-    Path(request_id): Path<String>,
     State(state): State<AppState>,
 ) -> impl IntoResponse {
     ws.on_upgrade(|socket| handle_socket(socket, request_id, state))
@@ -280,37 +630,61 @@ async fn ws_status_handler(
 
 // Handle the WebSocket connection
 async fn handle_socket(socket: WebSocket, request_id: String, state: AppState) {
-    let (mut sender, _receiver) = socket.split();
-
-    // Get a receiver for this request's status channel
-    let mut status_rx = match state.status_channels.lock().await.get(&request_id) {
-        Some(tx) => tx.subscribe(),
-        None => {
-            // Request ID not found, close the connection
-            let _ = sender
-                .send(axum::extract::ws::Message::Text(
-                    serde_json::to_string(&StatusUpdate {
-                        message: "Invalid request ID".to_string(),
-                        stage: state::Stage::Error,
-                        percentage: None,
-                        error: Some("Request not found or expired".to_string()),
-                        redirect_url: None,
-                    })
-                    .unwrap(),
-                ))
-                .await;
-            let _ = sender.close().await;
-            return;
+    let (sender, receiver) = socket.split();
+    stream_statuses(sender, receiver, request_id, state).await;
+}
+
+/// Resolve once the client closes the socket, or it errors or ends
+async fn client_closed<R>(mut receiver: R)
+where
+    R: Stream<Item = Result<Message, axum::Error>> + Unpin,
+{
+    while let Some(Ok(message)) = receiver.next().await {
+        if let Message::Close(_) = message {
+            break;
         }
-    };
+    }
+}
+
+/// Send a request's status updates to the client until its scan finishes or the
+/// client goes away, whichever comes first
+async fn stream_statuses<S, R>(mut sender: S, receiver: R, request_id: String, state: AppState)
+where
+    S: Sink<Message> + Unpin,
+    R: Stream<Item = Result<Message, axum::Error>> + Unpin + Send + 'static,
+{
+    // Subscribe before replaying history, so nothing sent in between is missed
+    let status_rx = state
+        .status_channels
+        .lock()
+        .await
+        .get(&request_id)
+        .map(|tx| tx.subscribe());
+    if status_rx.is_none() && state.get_result(&request_id).await.is_none() {
+        // Request ID not found, close the connection
+        let _ = sender
+            .send(Message::Text(
+                serde_json::to_string(&StatusUpdate {
+                    message: "Invalid request ID".to_string(),
+                    stage: state::Stage::Error,
+                    percentage: None,
+                    error: Some("Request not found or expired".to_string()),
+                    redirect_url: None,
+                    queue_position: None,
+                    leaderboard_rank: None,
+                })
+                .unwrap(),
+            ))
+            .await;
+        let _ = sender.close().await;
+        return;
+    }
 
     // First, send all past status updates
     let history = state.get_status_history(&request_id).await;
     for status in history {
         if sender
-            .send(axum::extract::ws::Message::Text(
-                serde_json::to_string(&status).unwrap(),
-            ))
+            .send(Message::Text(serde_json::to_string(&status).unwrap()))
             .await
             .is_err()
         {
@@ -319,43 +693,54 @@ async fn handle_socket(socket: WebSocket, request_id: String, state: AppState) {
         }
     }
 
-    // Check if there's already a result for this request
-    if let Some(result) = state.get_result(&request_id).await {
-        if result.completed {
-            let status = if result.todo_item.is_some() {
-                StatusUpdate {
-                    message: "Scan already completed.".to_string(),
-                    stage: state::Stage::Complete,
-                    percentage: Some(100),
-                    error: None,
-                    redirect_url: Some(format!("/results/{}", request_id)),
-                }
-            } else {
-                StatusUpdate {
-                    message: "Scan already completed with errors.".to_string(),
-                    stage: state::Stage::Error,
-                    percentage: Some(100),
-                    error: result.error,
-                    redirect_url: Some(format!("/results/{}", request_id)),
-                }
-            };
-
-            let _ = sender
-                .send(axum::extract::ws::Message::Text(
-                    serde_json::to_string(&status).unwrap(),
-                ))
-                .await;
-            let _ = sender.close().await;
-            return;
-        }
+    // Check if there's already a result for this request. The history may not
+    // reach its final update, so always end on one
+    if let Some(status) = completed_status(&state, &request_id).await {
+        let _ = sender
+            .send(Message::Text(serde_json::to_string(&status).unwrap()))
+            .await;
+        let _ = sender.close().await;
+        return;
     }
 
+    let Some(mut status_rx) = status_rx else {
+        // Its channel is gone without the scan finishing, so nothing more will come
+        let _ = sender.close().await;
+        return;
+    };
+
+    // Watch for the client leaving, so a closed tab doesn't keep us subscribed
+    // until the next update fails to send
+    let (closed_tx, mut closed_rx) = oneshot::channel();
+    let watcher = task::spawn(async move {
+        client_closed(receiver).await;
+        let _ = closed_tx.send(());
+    });
+
     // Forward status updates to the WebSocket
-    while let Ok(status) = status_rx.recv().await {
+    loop {
+        let status = tokio::select! {
+            _ = &mut closed_rx => {
+                debug!("Client for {} disconnected", request_id);
+                break;
+            }
+            status = state::next_status(&mut status_rx) => match status {
+                Some(status) => status,
+                None => {
+                    // The channel closed before its final update reached us, so
+                    // stand one in from the stored result
+                    if let Some(status) = completed_status(&state, &request_id).await {
+                        let _ = sender
+                            .send(Message::Text(serde_json::to_string(&status).unwrap()))
+                            .await;
+                    }
+                    let _ = sender.close().await;
+                    break;
+                }
+            },
+        };
         match sender
-            .send(axum::extract::ws::Message::Text(
-                serde_json::to_string(&status).unwrap(),
-            ))
+            .send(Message::Text(serde_json::to_string(&status).unwrap()))
             .await
         {
             Ok(_) => {
@@ -368,37 +753,1425 @@ async fn handle_socket(socket: WebSocket, request_id: String, state: AppState) {
             Err(_) => break, // Client disconnected
         }
     }
+    watcher.abort();
+}
+
+/// The final status of a request whose scan has finished, pointing at its results.
+/// `None` while it's still running
+async fn completed_status(state: &AppState, request_id: &str) -> Option<StatusUpdate> {
+    let result = state.get_result(request_id).await?;
+    if !result.completed {
+        return None;
+    }
+
+    let status = if result.todo_item.is_some() {
+        StatusUpdate {
+            message: "Scan already completed.".to_string(),
+            stage: state::Stage::Complete,
+            percentage: Some(100),
+            error: None,
+            redirect_url: Some(state.results_url(request_id)),
+            queue_position: None,
+            leaderboard_rank: None,
+        }
+    } else {
+        StatusUpdate {
+            message: "Scan already completed with errors.".to_string(),
+            stage: state::Stage::Error,
+            percentage: Some(100),
+            error: result.error,
+            redirect_url: Some(state.results_url(request_id)),
+            queue_position: None,
+            leaderboard_rank: None,
+        }
+    };
+    Some(status)
+}
+
+async fn leaderboard_handler(
+    State(state): State<AppState>,
+    Query(query): Query<LeaderboardQuery>,
+) -> impl IntoResponse {
+    category_leaderboard_handler(
+        State(state),
+        Path(GLOBAL_CATEGORY.to_string()),
+        Query(query),
+    )
+    .await
+}
+
+// Handler for a single category's board, e.g. /leaderboard/rust
+async fn category_leaderboard_handler(
+    State(state): State<AppState>,
+    Path(category): Path<String>,
+    Query(query): Query<LeaderboardQuery>,
+) -> (StatusCode, Html<String>) {
+    if state.leaderboards.is_none() {
+        return (
+            StatusCode::GONE,
+            error_page(
+                state.templates.as_ref(),
+                &state.config.base_path,
+                LEADERBOARD_DISABLED_MESSAGE,
+            ),
+        );
+    }
+    let Some(board) = state.category_leaderboard(&category) else {
+        return (
+            StatusCode::NOT_FOUND,
+            error_page(
+                state.templates.as_ref(),
+                &state.config.base_path,
+                &format!("There is no {} leaderboard", category),
+            ),
+        );
+    };
+
+    // Fetch the top TODOs from the leaderboard, then sort and page them
+    let items = board.snapshot().await;
+
+    (
+        StatusCode::OK,
+        leaderboard_page(
+            state.templates.as_ref(),
+            &state.config.base_path,
+            &category,
+            query.apply(&items),
+            state.clock.as_ref(),
+        ),
+    )
+}
+
+// Store and announce a scan that finished without finding an oldest TODO
+async fn report_empty_scan(
+    state: &AppState,
+    request_id: &str,
+    error_message: String,
+    status_message: String,
+) {
+    state
+        .store_result(request_id, None, Some(error_message.clone()), None)
+        .await;
+
+    state
+        .send_status(
+            request_id,
+            StatusUpdate {
+                message: status_message,
+                stage: state::Stage::Error,
+                percentage: Some(100),
+                error: Some(error_message),
+                redirect_url: Some(state.results_url(request_id)),
+                queue_position: None,
+                leaderboard_rank: None,
+            },
+        )
+        .await;
+}
+
+// Stream every TODO kept by a finished scan as newline-delimited JSON
+async fn todos_ndjson_handler(
+    RequestId(request_id): RequestId,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let result = state
+        .get_result(&request_id)
+        .await
+        .ok_or_else(|| ApiError::not_found("Request not found or expired"))?;
+    if !result.completed {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "scan_in_progress",
+            "The scan hasn't finished yet",
+        ));
+    }
+    let todos = result.all_todos.ok_or_else(|| {
+        ApiError::new(
+            StatusCode::CONFLICT,
+            "todos_not_kept",
+            "This scan only kept the oldest TODO, rescan with every TODO kept",
+        )
+    })?;
+
+    // Serialize one TODO at a time as the client reads, rather than the whole body up front
+    let email_display = state.config.author_email_display;
+    let lines = futures::stream::iter(0..todos.len()).map(move |i| {
+        // The export is as public as the pages, so it masks emails the same way
+        let mut todo = todos[i].clone();
+        email_display.apply_to(&mut todo);
+        let mut line = serde_json::to_vec(&todo)?;
+        line.push(b'\n');
+        Ok::<_, serde_json::Error>(line)
+    });
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    ))
+}
+
+// RSS feed of the top of the global leaderboard
+async fn leaderboard_feed_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let board = state.leaderboard().ok_or_else(leaderboard_disabled)?;
+    let site_url = site_url(&headers, &state.config);
+    let now = state.clock.now();
+    let feed = board
+        .with_items(|items| leaderboard_feed(&site_url, items.take(LEADERBOARD_FEED_SIZE), now))
+        .await;
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")],
+        feed,
+    ))
+}
+
+const LEADERBOARD_DISABLED_MESSAGE: &str = "The leaderboard is turned off on this site";
+
+/// Answer for the leaderboard's routes when it's turned off
+fn leaderboard_disabled() -> ApiError {
+    ApiError::new(
+        StatusCode::GONE,
+        "leaderboard_disabled",
+        LEADERBOARD_DISABLED_MESSAGE,
+    )
+}
+
+/// URL of the site for links read outside it. Any client can send `Host` and
+/// `X-Forwarded-Proto`, so they're only used behind a trusted proxy. With neither
+/// configured the links are relative to the site's root
+fn site_url(headers: &HeaderMap, config: &Config) -> String {
+    if let Some(public_url) = &config.public_url {
+        return format!("{}{}", public_url, config.base_path);
+    }
+    if !config.trust_proxy_headers {
+        return config.base_path.clone();
+    }
+    let header_value = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    let host = header_value(header::HOST.as_str()).unwrap_or("localhost");
+    // Set by the reverse proxy when it terminates TLS
+    let scheme = header_value("x-forwarded-proto").unwrap_or("http");
+    format!("{}://{}{}", scheme, host, config.base_path)
+}
+
+// Site-wide numbers for the landing page
+async fn stats_handler(State(state): State<AppState>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "repositories_scanned": state.scan_counter.total().await,
+    }))
+}
+
+// Aggregate metrics over the leaderboard, computed without cloning the items
+async fn leaderboard_stats_handler(
+    State(state): State<AppState>,
+) -> Result<Json<LeaderboardStats>, ApiError> {
+    let board = state.leaderboard().ok_or_else(leaderboard_disabled)?;
+    let now = state.clock.now();
+    let stats = board
+        .with_items(|items| LeaderboardStats::from_items(items, now))
+        .await;
+
+    Ok(Json(stats))
+}
+
+#[derive(Deserialize)]
+struct EvictQuery {
+    // Only evict the entry in this file, when a repo has several on the same line number
+    file: Option<String>,
+}
+
+// Admin: pull an entry off the leaderboard, e.g. a deleted repo or a false positive.
+// The repo URL is percent-encoded into a single path segment
+async fn evict_leaderboard_entry_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    Path((repo_url, line)): Path<(String, u32)>,
+    Query(query): Query<EvictQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let matches = |todo: &blame_finder::TodoItem| {
+        same_repo(&todo.source_repo_url, &repo_url)
+            && todo.line_number == line
+            && query
+                .file
+                .as_deref()
+                .is_none_or(|file| file == todo.file_path)
+    };
+
+    if state.leaderboards.is_none() {
+        return Err(leaderboard_disabled());
+    }
+    let removed = state.remove_from_leaderboards(matches).await;
+
+    if removed.is_empty() {
+        return Err(ApiError::not_found("No matching leaderboard entry"));
+    }
+
+    for todo in &removed {
+        info!(
+            "Evicted {}:{} of {} from the leaderboard",
+            todo.file_path, todo.line_number, todo.source_repo_url
+        );
+    }
+
+    Ok(Json(serde_json::json!({ "removed": removed.len() })))
 }
 
-async fn leaderboard_handler(State(state): State<AppState>) -> impl IntoResponse {
-    // Fetch the top TODOs from the leaderboard
-    let items = state.leaderboard.get_items().await;
+// Admin: every clone kept on disk with its size and when it was last used, to see
+// what the cache holds between cleanups
+async fn cached_repos_handler(
+    _admin: AdminAuth,
+) -> Result<Json<Vec<blame_finder::CachedRepoInfo>>, ApiError> {
+    Ok(Json(blame_finder::list_cached_repos().await?))
+}
 
-    leaderboard_page(items)
+/// Whether two repo URLs point at the same repo, ignoring a trailing slash or `.git`
+fn same_repo(a: &str, b: &str) -> bool {
+    let normalize = |url: &str| {
+        url.trim_end_matches('/')
+            .trim_end_matches(".git")
+            .to_string()
+    };
+    normalize(a) == normalize(b)
 }
 
 // Handler for retrieving results by request ID
 async fn results_handler(
-    Path(request_id): Path<String>,
+    RequestId(request_id): RequestId,
     State(state): State<AppState>,
 ) -> impl IntoResponse {
     match state.get_result(&request_id).await {
         Some(result) => {
             if result.completed {
                 match result.todo_item {
-                    Some(todo) => result_page(todo),
+                    Some(todo) => result_page(
+                        state.templates.as_ref(),
+                        &state.config.base_path,
+                        todo,
+                        ResultDetails {
+                            leaderboard_note: result.leaderboard_note,
+                            leaderboard_rank: result.leaderboard_rank,
+                            timings: result.timings,
+                            head_sha: result.head_sha,
+                            primary_language: result.primary_language,
+                            todo_count: result.todo_count,
+                            extension_counts: result.extension_counts,
+                            email_display: state.config.author_email_display,
+                            all_todos_url: result.all_todos.is_some().then(|| {
+                                format!(
+                                    "{}/api/scan/{}/todos.ndjson",
+                                    state.config.base_path, request_id
+                                )
+                            }),
+                        },
+                        state.clock.as_ref(),
+                    )
+                    .into_response(),
                     None => {
                         let error_message = result.error.unwrap_or_else(|| {
                             "No TODO comments found in this repository".to_string()
                         });
-                        error_page(&error_message)
+                        error_page(
+                            state.templates.as_ref(),
+                            &state.config.base_path,
+                            &error_message,
+                        )
+                        .into_response()
                     }
                 }
             } else {
                 // Still processing
-                index_page() // Maybe redirect to a "still processing" page instead
+                index_page(
+                    state.templates.as_ref(),
+                    &state.config.base_path,
+                    state.scan_counter.total().await,
+                )
+                .into_response() // Maybe redirect to a "still processing" page instead
             }
         }
-        None => error_page("Invalid or expired request ID"),
+        None => (
+            StatusCode::NOT_FOUND,
+            error_page(
+                state.templates.as_ref(),
+                &state.config.base_path,
+                "Invalid or expired request ID",
+            ),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::FromRequest;
+    use axum::http::Request;
+    use blame_finder::{SystemClock, TodoItem};
+    use tempfile::tempdir;
+    use test_utils::{test_state, test_state_with_config, todo_aged};
+    use todo_templates::EmailDisplay;
+    use tower::ServiceExt;
+
+    async fn get_status(app: Router, uri: &str) -> StatusCode {
+        app.oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_socket_handler_exits_when_client_closes() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let request_id = "socket-close-test";
+        state.register_request(request_id).await;
+
+        // In-memory halves standing in for the split socket
+        let (sent_tx, mut sent_rx) = futures::channel::mpsc::unbounded::<Message>();
+        let (client_tx, client_rx) = futures::channel::mpsc::unbounded();
+        let handler = tokio::spawn(stream_statuses(
+            sent_tx,
+            client_rx,
+            request_id.to_string(),
+            state.clone(),
+        ));
+
+        // The scan never finishes, so only the client leaving can end the handler
+        state
+            .send_status(
+                request_id,
+                StatusUpdate {
+                    message: "working".to_string(),
+                    stage: state::Stage::Scan,
+                    percentage: None,
+                    error: None,
+                    redirect_url: None,
+                    queue_position: None,
+                    leaderboard_rank: None,
+                },
+            )
+            .await;
+        assert!(matches!(sent_rx.next().await, Some(Message::Text(_))));
+        assert!(!handler.is_finished());
+
+        client_tx.unbounded_send(Ok(Message::Close(None))).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), handler)
+            .await
+            .expect("handler should exit once the client closes")
+            .unwrap();
+
+        // Its status subscription went with it
+        let channels = state.status_channels.lock().await;
+        assert_eq!(channels[request_id].receiver_count(), 0);
+    }
+
+    fn working_status() -> StatusUpdate {
+        StatusUpdate {
+            message: "working".to_string(),
+            stage: state::Stage::Scan,
+            percentage: None,
+            error: None,
+            redirect_url: None,
+            queue_position: None,
+            leaderboard_rank: None,
+        }
+    }
+
+    fn sent_json(message: Message) -> serde_json::Value {
+        match message {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            other => panic!("expected a text message, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_after_completion_gets_final_status() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let request_id = "socket-reconnect-test";
+        state.register_request(request_id).await;
+        state.send_status(request_id, working_status()).await;
+        state
+            .store_result(request_id, Some(todo_aged(400)), None, None)
+            .await;
+        // The client dropped before the final update, and the channel went since
+        state.status_channels.lock().await.remove(request_id);
+
+        let (sent_tx, sent_rx) = futures::channel::mpsc::unbounded::<Message>();
+        let (_client_tx, client_rx) = futures::channel::mpsc::unbounded();
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            stream_statuses(sent_tx, client_rx, request_id.to_string(), state.clone()),
+        )
+        .await
+        .expect("handler should finish once it has caught the client up");
+
+        let sent: Vec<_> = sent_rx.map(sent_json).collect().await;
+        let stages: Vec<_> = sent.iter().map(|status| &status["stage"]).collect();
+        assert_eq!(stages, ["scan", "complete"]);
+        assert_eq!(sent[1]["redirect_url"], state.results_url(request_id));
+    }
+
+    #[tokio::test]
+    async fn test_closed_channel_still_ends_on_final_status() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let request_id = "socket-closed-channel-test";
+        state.register_request(request_id).await;
+
+        let (sent_tx, mut sent_rx) = futures::channel::mpsc::unbounded::<Message>();
+        let (_client_tx, client_rx) = futures::channel::mpsc::unbounded();
+        let handler = tokio::spawn(stream_statuses(
+            sent_tx,
+            client_rx,
+            request_id.to_string(),
+            state.clone(),
+        ));
+        state.send_status(request_id, working_status()).await;
+        assert_eq!(sent_json(sent_rx.next().await.unwrap())["stage"], "scan");
+
+        // The scan finishes but its channel closes before saying so
+        state
+            .store_result(request_id, None, Some("boom".to_string()), None)
+            .await;
+        state.status_channels.lock().await.remove(request_id);
+
+        let status = sent_json(sent_rx.next().await.unwrap());
+        assert_eq!(status["stage"], "error");
+        assert_eq!(status["error"], "boom");
+        tokio::time::timeout(Duration::from_secs(5), handler)
+            .await
+            .expect("handler should exit once the channel closes")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_results_rejects_malformed_request_id() {
+        let dir = tempdir().unwrap();
+        let app = app(test_state(&dir).await);
+
+        let status = get_status(app, "/results/not-a-uuid").await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_results_unknown_request_id_is_not_found() {
+        let dir = tempdir().unwrap();
+        let app = app(test_state(&dir).await);
+
+        let uri = format!("/results/{}", Uuid::new_v4());
+        let status = get_status(app, &uri).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    async fn get_body(app: Router, uri: &str) -> String {
+        let response = app
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    /// Themes the index page and wraps every other page in a banner
+    struct BannerTheme;
+
+    impl todo_templates::TemplateProvider for BannerTheme {
+        fn index(&self, page: todo_templates::IndexContent<'_>) -> String {
+            format!(
+                "<h1>Custom index, {} scanned</h1>",
+                page.repositories_scanned
+            )
+        }
+
+        fn leaderboard(&self, page: todo_templates::LeaderboardTemplate<'_>) -> String {
+            let inner = todo_templates::AskamaTemplates.leaderboard(page);
+            format!("<header>internal banner</header>{}", inner)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handlers_use_injected_templates() {
+        let dir = tempdir().unwrap();
+        let mut state = leaderboard_state(&dir).await;
+        state.templates = std::sync::Arc::new(BannerTheme);
+        let app = app(state);
+
+        let index = get_body(app.clone(), "/").await;
+        assert_eq!(index, "<h1>Custom index, 0 scanned</h1>");
+
+        let board = get_body(app, "/leaderboard").await;
+        assert!(board.starts_with("<header>internal banner</header>"));
+        assert!(board.contains("todo1.rs"));
+    }
+
+    /// State whose leaderboard holds five TODOs, `todo1.rs` being the oldest
+    async fn leaderboard_state(dir: &tempfile::TempDir) -> AppState {
+        let state = test_state(dir).await;
+        for i in 1..=5 {
+            let mut todo = todo_aged(1000 - i * 100);
+            todo.file_path = format!("todo{}.rs", i);
+            state.leaderboard().unwrap().try_add(todo).await;
+        }
+        state
+    }
+
+    #[tokio::test]
+    async fn test_leaderboard_slices_requested_page() {
+        let dir = tempdir().unwrap();
+        let app = app(leaderboard_state(&dir).await);
+
+        let body = get_body(app, "/leaderboard?page=2&per_page=2").await;
+
+        assert!(!body.contains("todo2.rs"));
+        assert!(body.contains("todo3.rs"));
+        assert!(body.contains("todo4.rs"));
+        assert!(!body.contains("todo5.rs"));
+        assert!(body.contains("/leaderboard?page=1&per_page=2&sort=age"));
+        assert!(body.contains("/leaderboard?page=3&per_page=2&sort=age"));
+    }
+
+    #[tokio::test]
+    async fn test_leaderboard_ranks_by_neglect_when_asked() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        for (file_path, age, later_commits) in [("quiet.rs", 500, 0), ("busy.rs", 200, 10)] {
+            let mut todo = todo_aged(age);
+            todo.file_path = file_path.to_string();
+            todo.blame_info.as_mut().unwrap().later_commits = Some(later_commits);
+            state.leaderboard().unwrap().try_add(todo).await;
+        }
+        let app = app(state);
+
+        let position = |body: &str, file_path| body.find(file_path).unwrap();
+        let by_age = get_body(app.clone(), "/leaderboard").await;
+        assert!(position(&by_age, "quiet.rs") < position(&by_age, "busy.rs"));
+
+        // Ten later commits at 100 days each put busy.rs well ahead
+        let by_neglect = get_body(app, "/leaderboard?neglect_days_per_commit=100").await;
+        assert!(position(&by_neglect, "busy.rs") < position(&by_neglect, "quiet.rs"));
+        assert!(by_neglect.contains("sort=repo&per_page=100&neglect_days_per_commit=100"));
+    }
+
+    #[tokio::test]
+    async fn test_leaderboard_out_of_range_page_is_empty() {
+        let dir = tempdir().unwrap();
+        let app = app(leaderboard_state(&dir).await);
+
+        let body = get_body(app, "/leaderboard?page=10&per_page=2").await;
+
+        assert!((1..=5).all(|i| !body.contains(&format!("todo{}.rs", i))));
+        assert!(body.contains("NOTHING ON THIS PAGE"));
+        // Prev links back to the last page that has items
+        assert!(body.contains("/leaderboard?page=3&per_page=2&sort=age"));
+        assert!(!body.contains("NEXT"));
+    }
+
+    #[tokio::test]
+    async fn test_leaderboard_stats() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let seeds = [
+            ("https://github.com/o/a.git", "alice@example.com", 100),
+            ("https://github.com/o/b.git", "bob@Example.com", 200),
+            ("https://gitlab.com/o/c.git", "carol@corp.dev", 600),
+        ];
+        for (url, email, days) in seeds {
+            let mut todo = todo_aged(days);
+            todo.source_repo_url = url.to_string();
+            todo.blame_info.as_mut().unwrap().author_email = email.to_string();
+            state.leaderboard().unwrap().try_add(todo).await;
+        }
+
+        let body = get_body(app(state), "/api/leaderboard/stats").await;
+        let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(stats["total_entries"], 3);
+        assert_eq!(stats["oldest_age_days"], 600);
+        assert_eq!(stats["newest_age_days"], 100);
+        assert_eq!(stats["average_age_days"], 300.0);
+        assert_eq!(stats["count_by_host"]["github.com"], 2);
+        assert_eq!(stats["count_by_host"]["gitlab.com"], 1);
+        assert_eq!(stats["count_by_author_domain"]["example.com"], 2);
+        assert_eq!(stats["count_by_author_domain"]["corp.dev"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_todos_ndjson_streams_every_kept_todo() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let request_id = Uuid::new_v4().to_string();
+        state.register_request(&request_id).await;
+        let todos: Vec<TodoItem> = [300, 200, 100].into_iter().map(todo_aged).collect();
+        state.record_all_todos(&request_id, todos).await;
+        state
+            .store_result(&request_id, Some(todo_aged(300)), None, None)
+            .await;
+
+        let uri = format!("/api/scan/{}/todos.ndjson", request_id);
+        let response = app(state).oneshot(Request::get(&uri).body(Body::empty()).unwrap());
+        let response = response.await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/x-ndjson"
+        );
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let streamed: Vec<TodoItem> = String::from_utf8(bytes.to_vec())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(streamed.len(), 3);
+        assert!(streamed[0].age_in_days(&SystemClock) > streamed[2].age_in_days(&SystemClock));
+    }
+
+    #[tokio::test]
+    async fn test_todos_ndjson_never_leaks_masked_emails() {
+        for email_display in [EmailDisplay::Hashed, EmailDisplay::Hidden] {
+            let dir = tempdir().unwrap();
+            let config = Config {
+                author_email_display: email_display,
+                ..Config::default()
+            };
+            let state = test_state_with_config(&dir, config).await;
+            let request_id = Uuid::new_v4().to_string();
+            state.register_request(&request_id).await;
+            state
+                .record_all_todos(&request_id, vec![todo_aged(300), todo_aged(100)])
+                .await;
+            state
+                .store_result(&request_id, Some(todo_aged(300)), None, None)
+                .await;
+
+            let uri = format!("/api/scan/{}/todos.ndjson", request_id);
+            let response = app(state).oneshot(Request::get(&uri).body(Body::empty()).unwrap());
+            let bytes = axum::body::to_bytes(response.await.unwrap().into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body = String::from_utf8(bytes.to_vec()).unwrap();
+            assert_eq!(body.lines().count(), 2);
+            assert!(!body.contains("someone@example.com"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_todos_ndjson_needs_a_finished_keep_all_scan() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let request_id = Uuid::new_v4().to_string();
+        state.register_request(&request_id).await;
+        let uri = format!("/api/scan/{}/todos.ndjson", request_id);
+
+        assert_eq!(
+            get_status(app(state.clone()), &uri).await,
+            StatusCode::CONFLICT
+        );
+
+        // Finished, but only the oldest TODO was kept
+        state
+            .store_result(&request_id, Some(todo_aged(300)), None, None)
+            .await;
+        assert_eq!(
+            get_status(app(state.clone()), &uri).await,
+            StatusCode::CONFLICT
+        );
+
+        let unknown = format!("/api/scan/{}/todos.ndjson", Uuid::new_v4());
+        assert_eq!(
+            get_status(app(state), &unknown).await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    /// Tags balance and every `&` starts an entity, enough to catch broken escaping
+    fn assert_well_formed_xml(xml: &str) {
+        let mut open_tags = Vec::new();
+        let mut rest = xml;
+        while let Some(start) = rest.find('<') {
+            let end = rest[start..].find('>').expect("unclosed tag") + start;
+            let tag = &rest[start + 1..end];
+            rest = &rest[end + 1..];
+            if tag.starts_with('?') || tag.ends_with('/') {
+                continue;
+            }
+            let name = tag.split_whitespace().next().unwrap();
+            match name.strip_prefix('/') {
+                Some(closing) => assert_eq!(open_tags.pop(), Some(closing.to_string())),
+                None => open_tags.push(name.to_string()),
+            }
+        }
+        assert!(open_tags.is_empty(), "unclosed tags: {:?}", open_tags);
+
+        for (i, _) in xml.match_indices('&') {
+            let entity = &xml[i..xml[i..].find(';').map_or(xml.len(), |end| i + end + 1)];
+            assert!(
+                entity.len() <= 8
+                    && entity[1..entity.len() - 1]
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '#'),
+                "bare ampersand near {:?}",
+                &xml[i..(i + 20).min(xml.len())]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_leaderboard_feed() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            public_url: Some("https://todos.example.com".to_string()),
+            ..Config::default()
+        };
+        let state = test_state_with_config(&dir, config).await;
+        for (repo, days) in [("a", 300), ("b", 200), ("c", 100)] {
+            let mut todo = todo_aged(days);
+            todo.source_repo_url = format!("https://github.com/o/{}.git", repo);
+            todo.todo_text = "// TODO: fix <this> & that".to_string();
+            state.leaderboard().unwrap().try_add(todo).await;
+        }
+
+        let response = app(state)
+            .oneshot(
+                Request::get("/leaderboard.rss")
+                    .header(header::HOST, "evil.example.com")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(
+            response.headers()[header::CONTENT_TYPE]
+                .to_str()
+                .unwrap()
+                .starts_with("application/rss+xml")
+        );
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let feed = String::from_utf8(bytes.to_vec()).unwrap();
+
+        assert_well_formed_xml(&feed);
+        assert!(feed.contains("<link>https://todos.example.com/leaderboard</link>"));
+        assert_eq!(feed.matches("<item>").count(), 3);
+        // Oldest first, each linking to its TODO
+        let first = feed
+            .find("https://github.com/o/a/blob/abc123/src/main.rs#L1")
+            .unwrap();
+        let last = feed
+            .find("https://github.com/o/c/blob/abc123/src/main.rs#L1")
+            .unwrap();
+        assert!(first < last);
+        assert!(feed.contains("o/a: a 300 day old TODO"));
+    }
+
+    #[test]
+    fn test_site_url_trusts_headers_only_behind_a_proxy() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, "proxied.example.com".parse().unwrap());
+        headers.insert("x-forwarded-proto", "https".parse().unwrap());
+        let config = Config {
+            base_path: "/todos".to_string(),
+            ..Config::default()
+        };
+        assert_eq!(site_url(&headers, &config), "/todos");
+
+        let config = Config {
+            trust_proxy_headers: true,
+            ..config
+        };
+        assert_eq!(
+            site_url(&headers, &config),
+            "https://proxied.example.com/todos"
+        );
+
+        let config = Config {
+            public_url: Some("https://todos.example.com".to_string()),
+            ..config
+        };
+        assert_eq!(
+            site_url(&headers, &config),
+            "https://todos.example.com/todos"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scan_count_on_api_and_index() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        assert!(
+            !get_body(app(state.clone()), "/")
+                .await
+                .contains("REPOSITORIES SCANNED")
+        );
+
+        state.scan_counter.increment().await;
+        state.scan_counter.increment().await;
+
+        let body = get_body(app(state.clone()), "/api/stats").await;
+        let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(stats["repositories_scanned"], 2);
+        assert!(
+            get_body(app(state), "/")
+                .await
+                .contains("2 REPOSITORIES SCANNED SO FAR")
+        );
+    }
+
+    async fn post_scan(app: Router, uri: &str) -> axum::response::Response {
+        app.oneshot(
+            Request::post(uri)
+                .header("content-type", "application/x-www-form-urlencoded")
+                .body(Body::from("repo_url=not-a-url"))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_invalid_path_filter_is_rejected_before_queueing() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+
+        let response = app(state.clone())
+            .oneshot(
+                Request::post("/find-oldest-todo")
+                    .header("content-type", "application/x-www-form-urlencoded")
+                    .body(Body::from(
+                        "repo_url=https://github.com/o/r&path_filter=src/%5B",
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&bytes).contains("invalid_pattern"));
+        assert!(state.scan_queue.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_completion_reports_leaderboard_rank() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let completion = async |request_id: &str, todo: TodoItem| {
+            state.register_request(request_id).await;
+            complete_scan(&state, request_id, todo).await;
+            let history = state.get_status_history(request_id).await;
+            serde_json::to_value(history.last().unwrap()).unwrap()
+        };
+
+        let record_id = Uuid::new_v4().to_string();
+        let record = completion(&record_id, todo_aged(400)).await;
+        assert_eq!(record["stage"], "complete");
+        assert_eq!(
+            record["leaderboard_rank"],
+            serde_json::json!({ "rank": 1, "record": true })
+        );
+        let body = get_body(app(state.clone()), &format!("/results/{}", record_id)).await;
+        assert!(body.contains("#1 OLDEST TODO ON THE SITE! NEW RECORD!"));
+
+        let runner_up = TodoItem {
+            file_path: "src/lib.rs".to_string(),
+            ..todo_aged(300)
+        };
+        let runner_up = completion(&Uuid::new_v4().to_string(), runner_up).await;
+        assert_eq!(
+            runner_up["leaderboard_rank"],
+            serde_json::json!({ "rank": 2, "record": false })
+        );
+
+        // Too young to rank, so there's nothing to celebrate
+        let young = completion(&Uuid::new_v4().to_string(), todo_aged(1)).await;
+        assert!(young.get("leaderboard_rank").is_none());
+    }
+
+    /// How a form body posted to the scan endpoint would be read
+    async fn scan_request(body: impl Into<String>) -> Result<ScanRequest, ApiError> {
+        let request = Request::post("/find-oldest-todo")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(body.into()))
+            .unwrap();
+        let Form(form) = Form::<RepoForm>::from_request(request, &()).await.unwrap();
+        ScanRequest::from_form(&Config::default(), form)
+    }
+
+    #[tokio::test]
+    async fn test_form_scan_options_reach_the_scan() {
+        let repos_dir = tempdir().unwrap();
+
+        let scan = scan_request(
+            "repo_url=https://github.com/o/r&keywords=FIXME&branch=develop&extensions=rs,+.py&quick=on&neglect_days_per_commit=30",
+        )
+        .await
+        .unwrap();
+        assert!(scan.options.quick);
+        assert_eq!(scan.options.neglect_days_per_commit, 30);
+        let repo = scan
+            .builder
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(repo.custom_pattern(), Some("FIXME"));
+        assert_eq!(repo.branch(), Some("develop"));
+        assert_eq!(repo.extensions(), ["rs", "py"]);
+
+        // Blank fields scan for TODOs in every file, as if they weren't sent
+        let scan = scan_request(
+            "repo_url=https://github.com/o/r&keywords=+&branch=&extensions=&neglect_days_per_commit=",
+        )
+        .await
+        .unwrap();
+        assert!(!scan.options.quick);
+        assert_eq!(scan.options.neglect_days_per_commit, 0);
+        let repo = scan
+            .builder
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(repo.custom_pattern(), None);
+        assert_eq!(repo.branch(), None);
+        assert!(repo.extensions().is_empty());
+
+        let too_many = format!(
+            "repo_url=https://github.com/o/r&keywords={}",
+            "A,".repeat(11)
+        );
+        assert_eq!(
+            scan_request(too_many).await.err().unwrap().code,
+            "invalid_pattern"
+        );
+        let not_days = "repo_url=https://github.com/o/r&neglect_days_per_commit=lots";
+        assert_eq!(
+            scan_request(not_days).await.err().unwrap().code,
+            "invalid_option"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_full_scan_queue_returns_503_with_retry_after() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            max_concurrent_scans: 1,
+            max_queued_scans: 1,
+            scan_queue_retry_after_secs: 15,
+            ..Config::default()
+        };
+        let state = test_state_with_config(&dir, config).await;
+
+        // Hold the only scan slot so submissions pile up in the queue
+        let _held = state.scan_permits.clone().acquire_owned().await.unwrap();
+
+        let queued = post_scan(app(state.clone()), "/find-oldest-todo").await;
+        assert_eq!(queued.status(), StatusCode::OK);
+
+        let overflow = post_scan(app(state.clone()), "/find-oldest-todo").await;
+        assert_eq!(overflow.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(overflow.headers()[header::RETRY_AFTER], "15");
+        let bytes = axum::body::to_bytes(overflow.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["code"], "busy");
+        assert_eq!(state.scan_queue.lock().await.len(), 1);
+    }
+
+    /// Poll a request's status history until `done` is satisfied, or give up after a second
+    async fn wait_for_history(
+        state: &AppState,
+        request_id: &str,
+        done: impl Fn(&[StatusUpdate]) -> bool,
+    ) -> Vec<StatusUpdate> {
+        let mut history = Vec::new();
+        for _ in 0..50 {
+            history = state.get_status_history(request_id).await;
+            if done(&history) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        history
+    }
+
+    #[tokio::test]
+    async fn test_queued_scans_hear_their_position_move_up() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            max_concurrent_scans: 1,
+            max_queued_scans: 3,
+            ..Config::default()
+        };
+        let state = test_state_with_config(&dir, config).await;
+        let held = state.scan_permits.clone().acquire_owned().await.unwrap();
+
+        let mut request_ids = Vec::new();
+        for _ in 0..3 {
+            let response = post_scan(app(state.clone()), "/find-oldest-todo").await;
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            request_ids.push(json["request_id"].as_str().unwrap().to_string());
+        }
+        for request_id in &request_ids {
+            wait_for_history(&state, request_id, |history| {
+                history
+                    .iter()
+                    .any(|status| status.stage == state::Stage::Queued)
+            })
+            .await;
+        }
+
+        // Each scan fails straight away on its bad URL, letting the next one in
+        drop(held);
+        let history = wait_for_history(&state, &request_ids[2], |history| {
+            history
+                .iter()
+                .any(|status| status.stage == state::Stage::Error)
+        })
+        .await;
+
+        let positions: Vec<usize> = history
+            .iter()
+            .filter_map(|status| status.queue_position)
+            .collect();
+        assert_eq!(positions, vec![3, 2, 1]);
+        assert!(state.scan_queue.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_repo_query_starts_nothing_on_get() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+
+        // The page's script submits the form, the GET itself just serves the page
+        let response = app(state.clone())
+            .oneshot(
+                Request::get("/?repo=https%3A%2F%2Fgithub.com%2Fo%2Fr&quick=on")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(state.status_channels.lock().await.is_empty());
+        assert!(state.scan_queue.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_branches_lists_remote_heads_or_explains_why_not() {
+        // Every github.com/o/ repository is served from here instead
+        let remotes = tempdir().unwrap();
+        let upstream = remotes.path().join("r.git");
+        std::fs::create_dir(&upstream).unwrap();
+        test_utils::git(&upstream, &["init", "-q", "-b", "main"]);
+        test_utils::git(
+            &upstream,
+            &[
+                "-c",
+                "user.name=Test Author",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "-q",
+                "--allow-empty",
+                "-m",
+                "Initial commit",
+            ],
+        );
+        test_utils::git(&upstream, &["branch", "develop"]);
+        let rewrite = blame_finder::UrlRewrite::new(
+            "^https://github.com/o/",
+            &format!("{}/", remotes.path().display()),
+        )
+        .unwrap();
+        let dir = tempdir().unwrap();
+        let config = Config {
+            url_rewrite: Some(rewrite),
+            ..Config::default()
+        };
+        let state = test_state_with_config(&dir, config).await;
+
+        let body = get_body(
+            app(state.clone()),
+            "/api/branches?repo=https://github.com/o/r",
+        )
+        .await;
+        let listed: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(listed["branches"], serde_json::json!(["develop", "main"]));
+        assert!(listed.get("note").is_none());
+
+        // No such repository, which looks just like a private one without credentials
+        let body = get_body(
+            app(state.clone()),
+            "/api/branches?repo=https://github.com/o/missing",
+        )
+        .await;
+        let unlisted: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(unlisted["branches"], serde_json::json!([]));
+        assert_eq!(unlisted["note"], BRANCHES_UNAVAILABLE_NOTE);
+
+        assert_eq!(
+            get_status(app(state), "/api/branches?repo=not-a-url").await,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn test_base_path_prefixes_routes_and_redirects() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            base_path: "/todo-finder".to_string(),
+            ..Config::default()
+        };
+        let state = test_state_with_config(&dir, config).await;
+
+        // Pages are only served under the prefix, and link to prefixed assets
+        let body = get_body(app(state.clone()), "/todo-finder/leaderboard").await;
+        assert!(body.contains("href=\"/todo-finder/static/css/styles.css\""));
+        assert_eq!(
+            get_status(app(state.clone()), "/leaderboard").await,
+            StatusCode::NOT_FOUND
+        );
+
+        // A failed scan still sends the user to a prefixed results page
+        let response = post_scan(app(state.clone()), "/todo-finder/find-oldest-todo").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let request_id = json["request_id"].as_str().unwrap().to_string();
+
+        let mut redirect = None;
+        for _ in 0..50 {
+            redirect = state
+                .get_status_history(&request_id)
+                .await
+                .into_iter()
+                .find_map(|status| status.redirect_url);
+            if redirect.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(
+            redirect.unwrap(),
+            format!("/todo-finder/results/{}", request_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_result_lands_on_global_and_language_boards() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+
+        // todo_aged lives in src/main.rs
+        assert!(
+            state
+                .admit_to_leaderboard(&todo_aged(400), None)
+                .await
+                .note
+                .is_none()
+        );
+
+        assert_eq!(state.leaderboard().unwrap().get_items().await.len(), 1);
+        let rust = state.category_leaderboard("rust").unwrap();
+        assert_eq!(rust.get_items().await.len(), 1);
+        let python = state.category_leaderboard("python").unwrap();
+        assert!(python.get_items().await.is_empty());
+
+        let body = get_body(app(state.clone()), "/leaderboard/rust").await;
+        assert!(body.contains("src/main.rs:1"));
+        assert!(body.contains("IN RUST"));
+        let body = get_body(app(state.clone()), "/leaderboard/python").await;
+        assert!(!body.contains("src/main.rs:1"));
+        assert_eq!(
+            get_status(app(state), "/leaderboard/klingon").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_disabled_leaderboard_is_gone_but_scans_still_show_results() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            leaderboard_enabled: false,
+            admin_token: Some("s3cret".to_string()),
+            ..Config::default()
+        };
+        let state = test_state_with_config(&dir, config).await;
+
+        for uri in [
+            "/leaderboard",
+            "/leaderboard/rust",
+            "/leaderboard.rss",
+            "/api/leaderboard/stats",
+        ] {
+            assert_eq!(
+                get_status(app(state.clone()), uri).await,
+                StatusCode::GONE,
+                "{}",
+                uri
+            );
+        }
+        assert_eq!(
+            delete_with_token(app(state.clone()), EVICT_URI, Some("s3cret")).await,
+            StatusCode::GONE
+        );
+
+        let fixture = test_utils::init_repo();
+        test_utils::commit_file(
+            fixture.path(),
+            "lib.rs",
+            "// TODO: kept to myself\n",
+            "Add lib.rs",
+        );
+        let repo = Repository::from_local_path(fixture.path()).unwrap();
+        let request_id = Uuid::new_v4().to_string();
+        state.register_request(&request_id).await;
+        let result = todo_entrypoint::find_oldest_todo(
+            &repo,
+            &state,
+            &request_id,
+            repo.url(),
+            &ScanOptions::default(),
+            false,
+        )
+        .await
+        .unwrap();
+        let ScanResult::Found(todo) = result else {
+            panic!("expected a TODO, got {:?}", result);
+        };
+
+        // Old enough to qualify, but there's no board to admit it to
+        let mut old = todo.clone();
+        old.blame_info = todo_aged(400).blame_info;
+        assert!(state.admit_to_leaderboard(&old, None).await.note.is_none());
+        assert!(!dir.path().join("leaderboard.json").exists());
+
+        state
+            .store_result(&request_id, Some(todo), None, None)
+            .await;
+        let body = get_body(app(state), &format!("/results/{}", request_id)).await;
+        assert!(body.contains("lib.rs"));
+    }
+
+    const EVICT_URI: &str = "/api/leaderboard/https%3A%2F%2Fgithub.com%2Fo%2Fr/1";
+
+    async fn delete_with_token(app: Router, uri: &str, token: Option<&str>) -> StatusCode {
+        let mut request = Request::delete(uri);
+        if let Some(token) = token {
+            request = request.header("x-admin-token", token);
+        }
+        app.oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_admin_can_evict_leaderboard_entry() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            admin_token: Some("s3cret".to_string()),
+            ..Config::default()
+        };
+        let state = test_state_with_config(&dir, config).await;
+        state.leaderboard().unwrap().try_add(todo_aged(400)).await;
+
+        let status = delete_with_token(app(state.clone()), EVICT_URI, Some("s3cret")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(state.leaderboard().unwrap().get_items().await.is_empty());
+
+        // The removal is persisted, not just dropped from memory
+        let path = dir.path().join("leaderboard.json");
+        let reloaded = leaderboard::SharedLeaderboard::<blame_finder::TodoItem>::new(
+            path.to_str().unwrap().to_string(),
+            10,
+        )
+        .await
+        .unwrap();
+        assert!(reloaded.get_items().await.is_empty());
+
+        // Nothing left to evict
+        let status = delete_with_token(app(state), EVICT_URI, Some("s3cret")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_evict_requires_admin_token() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            admin_token: Some("s3cret".to_string()),
+            ..Config::default()
+        };
+        let state = test_state_with_config(&dir, config).await;
+        state.leaderboard().unwrap().try_add(todo_aged(400)).await;
+
+        let missing = delete_with_token(app(state.clone()), EVICT_URI, None).await;
+        let wrong = delete_with_token(app(state.clone()), EVICT_URI, Some("guess")).await;
+        assert_eq!(missing, StatusCode::UNAUTHORIZED);
+        assert_eq!(wrong, StatusCode::UNAUTHORIZED);
+        assert_eq!(state.leaderboard().unwrap().get_items().await.len(), 1);
+
+        // With no token configured the admin API is off entirely
+        let unconfigured = test_state(&tempdir().unwrap()).await;
+        let status = delete_with_token(app(unconfigured), EVICT_URI, Some("")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_cache_listing_is_admin_only() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            admin_token: Some("s3cret".to_string()),
+            ..Config::default()
+        };
+        let state = test_state_with_config(&dir, config).await;
+        let list = |token: Option<&'static str>| {
+            let mut request = Request::get("/api/cache");
+            if let Some(token) = token {
+                request = request.header("x-admin-token", token);
+            }
+            app(state.clone()).oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        assert_eq!(list(None).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        let response = list(Some("s3cret")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let repos: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(repos.is_array());
+    }
+
+    #[tokio::test]
+    async fn test_ws_rejects_malformed_request_id() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let app = app(state.clone());
+
+        let status = get_status(app, "/ws/scan-status/not-a-uuid").await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(state.status_channels.lock().await.is_empty());
     }
 }
@@ -0,0 +1,221 @@
+use blame_finder::{Repository, ScanOptions, ScanResult};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::sync::Mutex;
+
+use crate::state::TodoCounts;
+
+/// Results of past scans, reused while the content they were computed from is unchanged.
+/// Holds at most `capacity` results, dropping the least recently used beyond that
+#[derive(Debug)]
+pub struct ResultCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    scans: HashMap<CacheKey, CachedScan>,
+    /// Ticks on every hit and insert, so the least recently used scan has the lowest `last_used`
+    tick: u64,
+}
+
+#[derive(Debug)]
+struct CachedScan {
+    /// `HEAD^{tree}` the result was computed from
+    tree_hash: String,
+    result: ScanResult,
+    /// How many TODOs the scan found, before blame
+    counts: TodoCounts,
+    last_used: u64,
+}
+
+/// One checkout scanned with one set of options, as each can give a different answer
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    repo_path: PathBuf,
+    subpath: Option<String>,
+    path_filter: Option<String>,
+    custom_pattern: Option<String>,
+    extensions: Vec<String>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    quick: bool,
+    exclude_authors: Vec<String>,
+    commit_body_limit: Option<usize>,
+    test_file_penalty_days: u32,
+    exclude_generated: bool,
+    generated_globs: Vec<String>,
+    neglect_days_per_commit: u32,
+}
+
+impl CacheKey {
+    pub fn new(repo: &Repository, options: &ScanOptions) -> Self {
+        CacheKey {
+            repo_path: repo.path().to_path_buf(),
+            subpath: repo.subpath().map(str::to_string),
+            path_filter: repo.path_filter().map(str::to_string),
+            custom_pattern: repo.custom_pattern().map(str::to_string),
+            extensions: repo.extensions().to_vec(),
+            since: options.since,
+            until: options.until,
+            quick: options.quick,
+            exclude_authors: options.exclude_authors.clone(),
+            commit_body_limit: options.commit_body_limit,
+            test_file_penalty_days: options.test_file_penalty_days,
+            exclude_generated: options.exclude_generated,
+            generated_globs: options.generated_globs.clone(),
+            neglect_days_per_commit: options.neglect_days_per_commit,
+        }
+    }
+}
+
+impl ResultCache {
+    /// A cache of at most `capacity` results, 0 caches nothing
+    pub fn new(capacity: usize) -> Self {
+        ResultCache {
+            capacity,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// The cached result and TODO counts for `key`, if computed from this exact tree
+    pub async fn get(&self, key: &CacheKey, tree_hash: &str) -> Option<(ScanResult, TodoCounts)> {
+        let mut entries = self.entries.lock().await;
+        entries.tick += 1;
+        let tick = entries.tick;
+        let cached = entries
+            .scans
+            .get_mut(key)
+            .filter(|cached| cached.tree_hash == tree_hash)?;
+        cached.last_used = tick;
+        Some((cached.result.clone(), cached.counts.clone()))
+    }
+
+    /// Remember a result, replacing whatever was cached for `key` before and making
+    /// room by dropping the least recently used result when full
+    pub async fn insert(
+        &self,
+        key: CacheKey,
+        tree_hash: String,
+        result: ScanResult,
+        counts: TodoCounts,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().await;
+        entries.tick += 1;
+        let last_used = entries.tick;
+        entries.scans.insert(
+            key,
+            CachedScan {
+                tree_hash,
+                result,
+                counts,
+                last_used,
+            },
+        );
+
+        if entries.scans.len() > self.capacity {
+            let least_recent = entries
+                .scans
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(key) = least_recent {
+                entries.scans.remove(&key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    async fn key(repos_dir: &Path, name: &str) -> CacheKey {
+        let repo = Repository::builder(&format!("https://github.com/o/{}", name))
+            .repos_dir(repos_dir)
+            .build()
+            .await
+            .unwrap();
+        CacheKey::new(&repo, &ScanOptions::default())
+    }
+
+    #[tokio::test]
+    async fn test_drops_least_recently_used_past_capacity() {
+        let repos_dir = tempdir().unwrap();
+        let cache = ResultCache::new(2);
+        let (a, b, c) = (
+            key(repos_dir.path(), "a").await,
+            key(repos_dir.path(), "b").await,
+            key(repos_dir.path(), "c").await,
+        );
+        let counts = TodoCounts::default();
+        cache
+            .insert(
+                a.clone(),
+                "tree".into(),
+                ScanResult::NoTodos,
+                counts.clone(),
+            )
+            .await;
+        cache
+            .insert(
+                b.clone(),
+                "tree".into(),
+                ScanResult::NoTodos,
+                counts.clone(),
+            )
+            .await;
+        // Using `a` leaves `b` the least recently used
+        assert!(cache.get(&a, "tree").await.is_some());
+        cache
+            .insert(
+                c.clone(),
+                "tree".into(),
+                ScanResult::NoTodos,
+                counts.clone(),
+            )
+            .await;
+
+        assert!(cache.get(&a, "tree").await.is_some());
+        assert!(cache.get(&b, "tree").await.is_none());
+        assert!(cache.get(&c, "tree").await.is_some());
+        assert!(cache.get(&c, "other tree").await.is_none());
+
+        let disabled = ResultCache::new(0);
+        disabled
+            .insert(a.clone(), "tree".into(), ScanResult::NoTodos, counts)
+            .await;
+        assert!(disabled.get(&a, "tree").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_key_tells_options_apart() {
+        let repos_dir = tempdir().unwrap();
+        let repo = Repository::builder("https://github.com/o/r")
+            .repos_dir(repos_dir.path())
+            .build()
+            .await
+            .unwrap();
+        let quick = ScanOptions {
+            quick: true,
+            ..ScanOptions::default()
+        };
+
+        assert_eq!(
+            CacheKey::new(&repo, &ScanOptions::default()),
+            CacheKey::new(&repo, &ScanOptions::default())
+        );
+        assert_ne!(
+            CacheKey::new(&repo, &ScanOptions::default()),
+            CacheKey::new(&repo, &quick)
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::sync::Mutex;
+
+/// What's persisted to the stats file
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredStats {
+    repositories_scanned: u64,
+}
+
+/// Running total of completed scans, persisted across restarts
+#[derive(Debug)]
+pub struct ScanCounter {
+    path: PathBuf,
+    // Held while writing the file too, so concurrent increments persist in order
+    total: Mutex<u64>,
+}
+
+impl ScanCounter {
+    /// Load the total from `path`, starting from zero if the file is missing or unreadable
+    pub async fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let total = match tokio::fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice::<StoredStats>(&bytes)
+                .map(|stats| stats.repositories_scanned)
+                .unwrap_or_else(|e| {
+                    error!("Ignoring unreadable stats file {}: {}", path.display(), e);
+                    0
+                }),
+            Err(_) => 0,
+        };
+
+        ScanCounter {
+            path,
+            total: Mutex::new(total),
+        }
+    }
+
+    /// Count one more completed scan and persist the new total, returning it
+    pub async fn increment(&self) -> u64 {
+        let mut total = self.total.lock().await;
+        *total += 1;
+
+        if let Err(e) = self.save(*total).await {
+            error!("Failed to save stats to {}: {}", self.path.display(), e);
+        }
+        *total
+    }
+
+    pub async fn total(&self) -> u64 {
+        *self.total.lock().await
+    }
+
+    async fn save(&self, total: u64) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let json = serde_json::to_vec_pretty(&StoredStats {
+            repositories_scanned: total,
+        })?;
+
+        // Write then rename, so a crash mid-write never loses the running total
+        let tmp_path = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, json).await?;
+        tokio::fs::rename(&tmp_path, &self.path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_increments_are_all_persisted() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("stats.json");
+        let counter = Arc::new(ScanCounter::load(&path).await);
+        assert_eq!(counter.total().await, 0);
+
+        let tasks: Vec<_> = (0..50)
+            .map(|_| {
+                let counter = counter.clone();
+                tokio::spawn(async move { counter.increment().await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(counter.total().await, 50);
+        let reloaded = ScanCounter::load(&path).await;
+        assert_eq!(reloaded.total().await, 50);
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_stats_start_from_zero() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("stats.json");
+        std::fs::write(&path, "not json").unwrap();
+
+        let counter = ScanCounter::load(&path).await;
+        assert_eq!(counter.total().await, 0);
+        assert_eq!(counter.increment().await, 1);
+    }
+}
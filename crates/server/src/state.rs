@@ -1,19 +1,44 @@
-use blame_finder::TodoItem;
-use leaderboard::SharedLeaderboard;
+use blame_finder::{Clock, ScanTimings, SystemClock, TodoItem, todo};
+use leaderboard::{SharedLeaderboard, TryAddResult};
+use log::{info, warn};
 use serde::Serialize;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     path::PathBuf,
     sync::Arc,
 };
 use strum_macros::{Display, EnumString};
-use tokio::sync::{Mutex, broadcast};
+use todo_templates::{AskamaTemplates, TemplateProvider};
+use tokio::sync::{Mutex, Semaphore, broadcast};
+
+use crate::categories::{GLOBAL_CATEGORY, LanguageMap};
+use crate::config::Config;
+use crate::result_cache::ResultCache;
+use crate::scan_counter::ScanCounter;
+
+/// Wait for the next status update, skipping ahead if this receiver fell behind
+///
+/// Returns `None` once the scan's channel is closed.
+pub async fn next_status(rx: &mut broadcast::Receiver<StatusUpdate>) -> Option<StatusUpdate> {
+    loop {
+        match rx.recv().await {
+            Ok(status) => return Some(status),
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                // The receiver now points at the oldest update still buffered
+                warn!("Status receiver lagged, skipped {} updates", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
 
 #[derive(Display, EnumString, Serialize, Clone, Debug, PartialEq)]
 #[strum(serialize_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 pub enum Stage {
     Init,
+    // Waiting for a scan slot, see `StatusUpdate::queue_position`
+    Queued,
     Clone,
     Scan,
     Error,
@@ -33,6 +58,46 @@ pub struct StatusUpdate {
     pub error: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub redirect_url: Option<String>,
+    // Place in the scan queue while `Queued`, 1 being next to run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_position: Option<usize>,
+    // Where the scan's TODO landed on the leaderboard, once it's `Complete`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leaderboard_rank: Option<LeaderboardRank>,
+}
+
+/// Where a scan's TODO landed on the global leaderboard
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct LeaderboardRank {
+    /// 1 being the oldest TODO on the site
+    pub rank: usize,
+    /// It took the top spot, so it's the new oldest TODO on the site
+    pub record: bool,
+}
+
+/// How a scan's TODO fared when offered to the leaderboard
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Admission {
+    /// Where it landed, if it made the global board
+    pub rank: Option<LeaderboardRank>,
+    /// Why it didn't, when there's something to say
+    pub note: Option<String>,
+}
+
+/// Tell a waiting scan where it is in the queue
+pub fn queued_status(position: usize) -> StatusUpdate {
+    StatusUpdate {
+        message: format!(
+            "Queued, number {} in line, waiting for another scan to finish...",
+            position
+        ),
+        stage: Stage::Queued,
+        percentage: Some(0),
+        error: None,
+        redirect_url: None,
+        queue_position: Some(position),
+        leaderboard_rank: None,
+    }
 }
 
 // Define a structure to store processing results
@@ -41,13 +106,63 @@ pub struct ProcessingResult {
     pub todo_item: Option<TodoItem>,
     pub error: Option<String>,
     pub completed: bool,
+    // Why the result didn't make it onto the leaderboard, if it didn't
+    pub leaderboard_note: Option<String>,
+    // Where the result landed on the leaderboard, if it made it
+    pub leaderboard_rank: Option<LeaderboardRank>,
+    // How long each phase of the scan took
+    pub timings: Option<ScanTimings>,
+    // The commit that was scanned
+    pub head_sha: Option<String>,
+    // Every blamed TODO, oldest first, when the scan was asked to keep them all
+    pub all_todos: Option<Arc<Vec<TodoItem>>>,
+    // How many TODOs the scan found, before any were blamed
+    pub todo_count: Option<usize>,
+    // How many of those were in each kind of file, most first
+    pub extension_counts: Vec<(String, usize)>,
+    // Display name of the language most of the repo is written in, if we know it
+    pub primary_language: Option<String>,
+}
+
+/// How many TODOs a scan found before blame, in all and by kind of file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TodoCounts {
+    pub total: usize,
+    /// See [`count_by_extension`](blame_finder::todo::count_by_extension)
+    pub by_extension: Vec<(String, usize)>,
+}
+
+impl TodoCounts {
+    pub fn of(todos: &[TodoItem]) -> Self {
+        TodoCounts {
+            total: todos.len(),
+            by_extension: todo::count_by_extension(todos),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct AppState {
     pub numb_active_jobs: Arc<Mutex<u32>>,
+    // Request ids of scans accepted but still waiting for a permit, in arrival order
+    pub scan_queue: Arc<Mutex<VecDeque<String>>>,
+    // Bounds how many scans run at once
+    pub scan_permits: Arc<Semaphore>,
     pub active_repo_paths: Arc<Mutex<HashSet<PathBuf>>>,
-    pub leaderboard: SharedLeaderboard<TodoItem>,
+    // One board per category, always including the global one. None when the
+    // leaderboard is turned off
+    pub leaderboards: Option<Arc<HashMap<String, SharedLeaderboard<TodoItem>>>>,
+    pub config: Arc<Config>,
+    // Which language board each file belongs on, with the configured overrides
+    pub languages: Arc<LanguageMap>,
+    // Source of "now" for TODO ages, pinned in tests
+    pub clock: Arc<dyn Clock>,
+    // Renders each page, the bundled askama templates unless a deployment themes them
+    pub templates: Arc<dyn TemplateProvider>,
+    // How many scans have ever completed, for the landing page
+    pub scan_counter: Arc<ScanCounter>,
+    // Past results, reused when a repo's content hasn't changed since
+    pub result_cache: Arc<ResultCache>,
 
     pub status_channels: Arc<Mutex<HashMap<String, broadcast::Sender<StatusUpdate>>>>,
     // Store results of processing for later retrieval by request ID
@@ -59,23 +174,97 @@ pub struct AppState {
 }
 
 impl AppState {
-    pub fn new(leaderboard: SharedLeaderboard<TodoItem>) -> Self {
+    pub fn new(
+        leaderboards: Option<HashMap<String, SharedLeaderboard<TodoItem>>>,
+        scan_counter: ScanCounter,
+        config: Config,
+    ) -> Self {
+        assert!(
+            leaderboards
+                .as_ref()
+                .is_none_or(|boards| boards.contains_key(GLOBAL_CATEGORY)),
+            "the global leaderboard is required"
+        );
+        let result_cache_capacity = config.result_cache_capacity;
         AppState {
             numb_active_jobs: Arc::new(Mutex::new(0)),
+            scan_queue: Arc::new(Mutex::new(VecDeque::new())),
+            scan_permits: Arc::new(Semaphore::new(config.max_concurrent_scans)),
             active_repo_paths: Arc::new(Mutex::new(HashSet::new())),
-            leaderboard,
+            leaderboards: leaderboards.map(Arc::new),
+            languages: Arc::new(LanguageMap::new(&config.language_overrides)),
+            config: Arc::new(config),
+            clock: Arc::new(SystemClock),
+            templates: Arc::new(AskamaTemplates),
+            scan_counter: Arc::new(scan_counter),
+            result_cache: Arc::new(ResultCache::new(result_cache_capacity)),
             status_channels: Arc::new(Mutex::new(HashMap::new())),
             results: Arc::new(Mutex::new(HashMap::new())),
             status_history: Arc::new(Mutex::new(HashMap::new())),
             result_timestamps: Arc::new(Mutex::new(HashMap::new())),
         }
     }
+
+    /// The global leaderboard, which every TODO competes on, unless it's turned off
+    pub fn leaderboard(&self) -> Option<&SharedLeaderboard<TodoItem>> {
+        self.category_leaderboard(GLOBAL_CATEGORY)
+    }
+
+    /// The leaderboard for a category, e.g. `rust`, if there is one
+    pub fn category_leaderboard(&self, category: &str) -> Option<&SharedLeaderboard<TodoItem>> {
+        self.leaderboards.as_ref()?.get(category)
+    }
+
+    /// Use a fixed clock for TODO ages in tests
+    #[cfg(test)]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
 }
 
 impl AppState {
+    /// Reserve a place at the back of the scan queue, or return false if it's already full
+    pub async fn try_enqueue_scan(&self, request_id: &str) -> bool {
+        let mut queue = self.scan_queue.lock().await;
+        if queue.len() >= self.config.max_queued_scans as usize {
+            return false;
+        }
+        queue.push_back(request_id.to_string());
+        true
+    }
+
+    /// Place of a scan in the queue, 1 being next to run, `None` once it's left
+    pub async fn queue_position(&self, request_id: &str) -> Option<usize> {
+        let queue = self.scan_queue.lock().await;
+        queue
+            .iter()
+            .position(|queued| queued == request_id)
+            .map(|index| index + 1)
+    }
+
+    /// Give back a place in the scan queue once the scan has a permit to run, telling
+    /// every scan still waiting that it moved up
+    pub async fn dequeue_scan(&self, request_id: &str) {
+        let waiting: Vec<String> = {
+            let mut queue = self.scan_queue.lock().await;
+            queue.retain(|queued| queued != request_id);
+            queue.iter().cloned().collect()
+        };
+        // With slots still free, whoever's behind is about to run rather than waiting
+        if self.scan_permits.available_permits() > 0 {
+            return;
+        }
+
+        for (index, waiting_id) in waiting.iter().enumerate() {
+            self.send_status(waiting_id, queued_status(index + 1)).await;
+        }
+    }
+
     pub async fn register_request(&self, request_id: &str) -> broadcast::Receiver<StatusUpdate> {
         let mut channels = self.status_channels.lock().await;
-        let (tx, rx) = broadcast::channel(100); // Buffer size of 100 messages
+        // broadcast channels panic on a zero capacity
+        let (tx, rx) = broadcast::channel(self.config.status_channel_capacity.max(1));
         channels.insert(request_id.to_string(), tx);
 
         // Initialize results entry
@@ -86,6 +275,14 @@ impl AppState {
                 todo_item: None,
                 error: None,
                 completed: false,
+                leaderboard_note: None,
+                leaderboard_rank: None,
+                timings: None,
+                head_sha: None,
+                all_todos: None,
+                todo_count: None,
+                extension_counts: Vec::new(),
+                primary_language: None,
             },
         );
 
@@ -95,7 +292,7 @@ impl AppState {
 
         // Set timestamp for this request
         let mut timestamps = self.result_timestamps.lock().await;
-        timestamps.insert(request_id.to_string(), chrono::Utc::now());
+        timestamps.insert(request_id.to_string(), self.clock.now());
 
         rx
     }
@@ -137,22 +334,174 @@ impl AppState {
         request_id: &str,
         todo_item: Option<TodoItem>,
         error: Option<String>,
+        leaderboard_note: Option<String>,
     ) {
         let mut results = self.results.lock().await;
         if let Some(result) = results.get_mut(request_id) {
             result.todo_item = todo_item;
             result.error = error;
             result.completed = true;
+            result.leaderboard_note = leaderboard_note;
+        }
+    }
+
+    pub async fn record_timings(&self, request_id: &str, timings: ScanTimings) {
+        let mut results = self.results.lock().await;
+        if let Some(result) = results.get_mut(request_id) {
+            result.timings = Some(timings);
+        }
+    }
+
+    pub async fn record_leaderboard_rank(&self, request_id: &str, rank: LeaderboardRank) {
+        let mut results = self.results.lock().await;
+        if let Some(result) = results.get_mut(request_id) {
+            result.leaderboard_rank = Some(rank);
+        }
+    }
+
+    pub async fn record_all_todos(&self, request_id: &str, todos: Vec<TodoItem>) {
+        let mut results = self.results.lock().await;
+        if let Some(result) = results.get_mut(request_id) {
+            result.all_todos = Some(Arc::new(todos));
+        }
+    }
+
+    pub async fn record_todo_counts(&self, request_id: &str, counts: TodoCounts) {
+        let mut results = self.results.lock().await;
+        if let Some(result) = results.get_mut(request_id) {
+            result.todo_count = Some(counts.total);
+            result.extension_counts = counts.by_extension;
+        }
+    }
+
+    pub async fn record_head_sha(&self, request_id: &str, head_sha: String) {
+        let mut results = self.results.lock().await;
+        if let Some(result) = results.get_mut(request_id) {
+            result.head_sha = Some(head_sha);
+        }
+    }
+
+    pub async fn record_primary_language(&self, request_id: &str, language: &str) {
+        let mut results = self.results.lock().await;
+        if let Some(result) = results.get_mut(request_id) {
+            result.primary_language = Some(language.to_string());
+        }
+    }
+
+    /// Offer a scan result to the global leaderboard and its language's board,
+    /// reporting where on the global board it landed, or a note for the user when the
+    /// TODO is too young to qualify, its repo has too few TODOs (`todo_count`, when
+    /// known), or the global board is full of older ones. Nothing to report when the
+    /// leaderboard is turned off
+    pub async fn admit_to_leaderboard(
+        &self,
+        todo: &TodoItem,
+        todo_count: Option<usize>,
+    ) -> Admission {
+        let Some(board) = self.leaderboard() else {
+            return Admission::default();
+        };
+        let rejected = |note: String| Admission {
+            rank: None,
+            note: Some(note),
+        };
+
+        let min_total_todos = self.config.min_total_todos;
+        if let Some(todo_count) = todo_count
+            && todo_count < min_total_todos
+        {
+            return rejected(format!(
+                "This repository has too few TODOs to qualify for the leaderboard, it has {} but needs at least {}",
+                todo_count, min_total_todos
+            ));
+        }
+
+        let age_in_days = todo.age_in_days(self.clock.as_ref()).unwrap_or(0);
+
+        let min_age_days = self.config.min_leaderboard_age_days;
+        if age_in_days < min_age_days {
+            return rejected(format!(
+                "This TODO is only {} days old, it must be at least {} days old to enter the leaderboard",
+                age_in_days, min_age_days
+            ));
+        }
+
+        if let Some(language_board) = self
+            .languages
+            .language_for_todo(todo)
+            .and_then(|lang| self.category_leaderboard(lang))
+        {
+            language_board.try_add(todo.clone()).await;
+        }
+
+        let ranked = |rank, record| Admission {
+            rank: Some(LeaderboardRank { rank, record }),
+            note: None,
+        };
+        match board.try_add_ranked(todo.clone()).await {
+            TryAddResult::AddedTop => {
+                info!(
+                    "New oldest TODO on the leaderboard: {}",
+                    todo.get_permalink_url()
+                );
+                return ranked(1, true);
+            }
+            TryAddResult::Added { rank } => return ranked(rank, false),
+            TryAddResult::NotAdded => {}
+        }
+
+        // Not admitted: either it's already on the board, or the board is full of older TODOs
+        let listed_at = board
+            .with_items(|mut items| items.position(|item| item == todo))
+            .await;
+        if let Some(index) = listed_at {
+            return ranked(index + 1, false);
+        }
+
+        let worst_age = board
+            .peek_worst()
+            .await
+            .and_then(|worst| worst.age_in_days(self.clock.as_ref()));
+        match worst_age {
+            Some(worst_age) => rejected(format!(
+                "The leaderboard is full, you need a TODO older than {} days to make the board",
+                worst_age
+            )),
+            None => Admission::default(),
         }
     }
 
+    /// Remove every TODO matching `matches` from every board, returning those taken
+    /// off the global board
+    pub async fn remove_from_leaderboards(
+        &self,
+        matches: impl Fn(&TodoItem) -> bool,
+    ) -> Vec<TodoItem> {
+        let Some(boards) = &self.leaderboards else {
+            return Vec::new();
+        };
+
+        // Language boards may hold the entry too, but the global board is the one we report on
+        for (category, board) in boards.iter() {
+            if category != GLOBAL_CATEGORY {
+                board.remove(&matches).await;
+            }
+        }
+        boards[GLOBAL_CATEGORY].remove(&matches).await
+    }
+
+    /// Where the results page for a request lives, including any base path
+    pub fn results_url(&self, request_id: &str) -> String {
+        format!("{}/results/{}", self.config.base_path, request_id)
+    }
+
     pub async fn get_result(&self, request_id: &str) -> Option<ProcessingResult> {
         let results = self.results.lock().await;
         results.get(request_id).cloned()
     }
 
     pub async fn cleanup_old_requests(&self, max_age_hours: i64) {
-        let now = chrono::Utc::now();
+        let now = self.clock.now();
         let cutoff = now - chrono::Duration::hours(max_age_hours);
 
         let mut timestamps = self.result_timestamps.lock().await;
@@ -176,3 +525,256 @@ impl AppState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Stage, StatusUpdate, next_status};
+    use crate::config::Config;
+    use crate::test_utils::{test_state, test_state_with_config, todo_aged, todo_committed_at};
+    use blame_finder::FixedClock;
+    use chrono::{Duration, TimeZone, Utc};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    fn status(message: &str, stage: Stage) -> StatusUpdate {
+        StatusUpdate {
+            message: message.to_string(),
+            stage,
+            percentage: None,
+            error: None,
+            redirect_url: None,
+            queue_position: None,
+            leaderboard_rank: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lagging_receiver_still_gets_final_status() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            status_channel_capacity: 4,
+            ..Config::default()
+        };
+        let state = test_state_with_config(&dir, config).await;
+        let mut rx = state.register_request("lagging").await;
+
+        // Far more updates than the channel holds, before the receiver reads any
+        for i in 0..20 {
+            state
+                .send_status("lagging", status(&format!("step {}", i), Stage::Scan))
+                .await;
+        }
+        state
+            .send_status("lagging", status("done", Stage::Complete))
+            .await;
+
+        let mut received = Vec::new();
+        while let Some(update) = next_status(&mut rx).await {
+            let complete = update.stage == Stage::Complete;
+            received.push(update);
+            if complete {
+                break;
+            }
+        }
+
+        assert_eq!(received.len(), 4);
+        assert_eq!(received.last().unwrap().message, "done");
+    }
+
+    #[tokio::test]
+    async fn test_young_todo_not_admitted_to_leaderboard() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+
+        let note = state.admit_to_leaderboard(&todo_aged(10), None).await.note;
+
+        assert!(note.is_some());
+        assert!(state.leaderboard().unwrap().get_items().await.is_empty());
+
+        // The user still gets to see their result, along with the note
+        let details = crate::templates::ResultDetails {
+            leaderboard_note: note,
+            ..Default::default()
+        };
+        let html = crate::templates::result_page(
+            &todo_templates::AskamaTemplates,
+            "",
+            todo_aged(10),
+            details,
+            state.clock.as_ref(),
+        )
+        .0;
+        assert!(html.contains("only 10 days old"));
+    }
+
+    #[tokio::test]
+    async fn test_min_total_todos_gates_the_leaderboard() {
+        let dir = tempdir().unwrap();
+        let config = Config {
+            min_total_todos: 3,
+            ..Config::default()
+        };
+        let state = test_state_with_config(&dir, config).await;
+
+        let note = state
+            .admit_to_leaderboard(&todo_aged(400), Some(2))
+            .await
+            .note;
+        assert!(note.unwrap().contains("has 2 but needs at least 3"));
+        assert!(state.leaderboard().unwrap().get_items().await.is_empty());
+
+        // Enough TODOs, or an unknown count, leaves only the usual checks
+        assert!(
+            state
+                .admit_to_leaderboard(&todo_aged(400), Some(3))
+                .await
+                .note
+                .is_none()
+        );
+        assert!(
+            state
+                .admit_to_leaderboard(&todo_aged(500), None)
+                .await
+                .note
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_leaderboard_entries_keep_their_request_id() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let mut todo = todo_aged(400);
+        todo.request_id = Some("request-1".to_string());
+
+        assert!(state.admit_to_leaderboard(&todo, None).await.note.is_none());
+        let entries = state.leaderboard().unwrap().get_items().await;
+        assert_eq!(entries[0].request_id.as_deref(), Some("request-1"));
+
+        // Provenance isn't identity: the same TODO from another scan is a duplicate
+        let mut rescanned = todo.clone();
+        rescanned.request_id = Some("request-2".to_string());
+        assert_eq!(rescanned, todo);
+        assert!(
+            state
+                .admit_to_leaderboard(&rescanned, None)
+                .await
+                .note
+                .is_none()
+        );
+        assert_eq!(state.leaderboard().unwrap().get_items().await.len(), 1);
+
+        // And it survives a save and reload
+        let reloaded = test_state(&dir).await;
+        let entries = reloaded.leaderboard().unwrap().get_items().await;
+        assert_eq!(entries[0].request_id.as_deref(), Some("request-1"));
+    }
+
+    #[tokio::test]
+    async fn test_old_todo_admitted_to_leaderboard() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+
+        let note = state.admit_to_leaderboard(&todo_aged(400), None).await.note;
+
+        assert!(note.is_none());
+        assert_eq!(state.leaderboard().unwrap().get_items().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_full_leaderboard_tells_user_the_age_to_beat() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        // The test leaderboard holds 10 items
+        for i in 0..10 {
+            let mut todo = todo_aged(500 + i * 10);
+            todo.file_path = format!("src/{}.rs", i);
+            assert!(state.admit_to_leaderboard(&todo, None).await.note.is_none());
+        }
+
+        let note = state
+            .admit_to_leaderboard(&todo_aged(400), None)
+            .await
+            .note
+            .unwrap();
+        assert!(note.contains("older than 500 days"));
+
+        // Re-submitting a TODO that's already on the board isn't a failure
+        let mut listed = todo_aged(500);
+        listed.file_path = "src/0.rs".to_string();
+        listed.blame_info = state
+            .leaderboard()
+            .unwrap()
+            .peek_worst()
+            .await
+            .unwrap()
+            .blame_info;
+        assert!(
+            state
+                .admit_to_leaderboard(&listed, None)
+                .await
+                .note
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_admission_ages_use_the_state_clock() {
+        let dir = tempdir().unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let state = test_state(&dir).await.with_clock(Arc::new(FixedClock(now)));
+
+        // A second short of the 30 day minimum is still only 29 days old
+        let almost = todo_committed_at(now - Duration::days(30) + Duration::seconds(1));
+        let note = state
+            .admit_to_leaderboard(&almost, None)
+            .await
+            .note
+            .unwrap();
+        assert!(note.contains("only 29 days old"));
+
+        let old_enough = todo_committed_at(now - Duration::days(30));
+        assert!(
+            state
+                .admit_to_leaderboard(&old_enough, None)
+                .await
+                .note
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_leaderboard_order_and_ages_are_stable_under_fixed_clock() {
+        let dir = tempdir().unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let clock = FixedClock(now);
+        let state = test_state(&dir).await.with_clock(Arc::new(clock));
+
+        for (path, days) in [("b.rs", 100), ("c.rs", 365), ("a.rs", 100), ("d.rs", 31)] {
+            let mut todo = todo_committed_at(now - Duration::days(days));
+            todo.file_path = path.to_string();
+            assert!(state.admit_to_leaderboard(&todo, None).await.note.is_none());
+        }
+
+        let ranked: Vec<(String, i64)> = state
+            .leaderboard()
+            .unwrap()
+            .get_items()
+            .await
+            .into_iter()
+            .map(|todo| {
+                let age = todo.age_in_days(&clock).unwrap();
+                (todo.file_path, age)
+            })
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![
+                ("c.rs".to_string(), 365),
+                ("b.rs".to_string(), 100),
+                ("a.rs".to_string(), 100),
+                ("d.rs".to_string(), 31),
+            ]
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use blame_finder::TodoItem;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Aggregate metrics over the leaderboard, served as JSON
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct LeaderboardStats {
+    pub total_entries: usize,
+    pub oldest_age_days: Option<i64>,
+    pub newest_age_days: Option<i64>,
+    pub average_age_days: Option<f64>,
+    pub count_by_host: BTreeMap<String, usize>,
+    pub count_by_author_domain: BTreeMap<String, usize>,
+}
+
+impl LeaderboardStats {
+    /// Compute stats over leaderboard items, with ages measured at `now`
+    pub fn from_items<'a>(items: impl Iterator<Item = &'a TodoItem>, now: DateTime<Utc>) -> Self {
+        let mut stats = LeaderboardStats::default();
+        let mut total_age = 0;
+
+        for item in items {
+            stats.total_entries += 1;
+
+            let host = item
+                .get_repo_host()
+                .unwrap_or_else(|| "unknown".to_string());
+            *stats.count_by_host.entry(host).or_default() += 1;
+
+            // Leaderboard entries always carry blame info, but don't count ages without it
+            let Some(blame) = item.blame_info.as_ref() else {
+                continue;
+            };
+
+            let domain = blame
+                .author_email
+                .rsplit_once('@')
+                .map(|(_, domain)| domain.to_lowercase())
+                .unwrap_or_else(|| "unknown".to_string());
+            *stats.count_by_author_domain.entry(domain).or_default() += 1;
+
+            let age = blame.age_in_days_at(now);
+            total_age += age;
+            stats.oldest_age_days = Some(stats.oldest_age_days.map_or(age, |a| a.max(age)));
+            stats.newest_age_days = Some(stats.newest_age_days.map_or(age, |a| a.min(age)));
+        }
+
+        let aged_entries = stats.count_by_author_domain.values().sum::<usize>();
+        if aged_entries > 0 {
+            stats.average_age_days = Some(total_age as f64 / aged_entries as f64);
+        }
+
+        stats
+    }
+}
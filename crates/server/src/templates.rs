@@ -1,28 +1,364 @@
 use axum::response::Html;
-use blame_finder::TodoItem;
+use blame_finder::{Clock, ScanTimings, TodoItem};
+use chrono::{DateTime, Utc};
+
+use crate::categories::{GLOBAL_CATEGORY, categories};
+use crate::leaderboard_query::LeaderboardWindow;
+use crate::state::LeaderboardRank;
+use todo_templates::templates::TodoItemDisplay;
 use todo_templates::{
-    ErrorContent, IndexContent, LeaderboardTemplate, ResultContent, render_template,
+    BoardLink, EmailDisplay, ErrorContent, FeedItem, IndexContent, LeaderboardFeed,
+    LeaderboardTemplate, ResultContent, TemplateProvider, render_template,
 };
 
 // Public handler functions
-pub fn index_page() -> Html<String> {
-    Html(render_template(IndexContent))
+pub fn index_page(
+    templates: &dyn TemplateProvider,
+    base_path: &str,
+    repositories_scanned: u64,
+) -> Html<String> {
+    Html(templates.index(IndexContent {
+        base_path,
+        repositories_scanned,
+    }))
 }
 
-pub fn result_page(todo_item: TodoItem) -> Html<String> {
-    Html(render_template(ResultContent { result: todo_item }))
+/// What a finished scan learned besides the oldest TODO itself
+#[derive(Debug, Default)]
+pub struct ResultDetails {
+    /// Why the TODO didn't make it onto the leaderboard, if it didn't
+    pub leaderboard_note: Option<String>,
+    /// Where the TODO landed on the leaderboard, if it made it
+    pub leaderboard_rank: Option<LeaderboardRank>,
+    pub timings: Option<ScanTimings>,
+    /// Full sha of the commit that was scanned
+    pub head_sha: Option<String>,
+    /// Display name of the language most of the repo is written in
+    pub primary_language: Option<String>,
+    /// How many TODOs the scan found in all
+    pub todo_count: Option<usize>,
+    /// How many of them were in each kind of file, most first
+    pub extension_counts: Vec<(String, usize)>,
+    /// Where to download every TODO the scan kept, if it kept them all
+    pub all_todos_url: Option<String>,
+    /// How much of the author's email to show
+    pub email_display: EmailDisplay,
 }
 
-pub fn error_page(error_message: &str) -> Html<String> {
-    Html(render_template(ErrorContent {
-        error: error_message,
+pub fn result_page(
+    templates: &dyn TemplateProvider,
+    base_path: &str,
+    mut todo_item: TodoItem,
+    details: ResultDetails,
+    clock: &dyn Clock,
+) -> Html<String> {
+    // Only the page's copy is masked, the stored result keeps the full address
+    details.email_display.apply_to(&mut todo_item);
+    Html(templates.result(ResultContent {
+        base_path,
+        age_in_days: todo_item.age_in_days(clock).unwrap_or(0),
+        result: todo_item,
+        leaderboard_note: details.leaderboard_note,
+        leaderboard_rank: details.leaderboard_rank.map(|ranked| ranked.rank),
+        new_record: details.leaderboard_rank.is_some_and(|ranked| ranked.record),
+        timings: details.timings,
+        scanned_commit: details.head_sha.map(|sha| short_sha(&sha)),
+        primary_language: details.primary_language,
+        todo_count: details.todo_count,
+        extension_counts: details.extension_counts,
+        all_todos_url: details.all_todos_url,
     }))
 }
 
-pub fn leaderboard_page(todos: Vec<TodoItem>) -> Html<String> {
-    let todos_length = todos.len();
-    Html(render_template(LeaderboardTemplate {
-        todos: todos.into_iter().map(|item| item.into()).collect(),
-        todos_length,
+pub fn error_page(
+    templates: &dyn TemplateProvider,
+    base_path: &str,
+    error_message: &str,
+) -> Html<String> {
+    Html(templates.error(ErrorContent {
+        base_path,
+        error: error_message,
     }))
 }
+
+pub fn leaderboard_page(
+    templates: &dyn TemplateProvider,
+    base_path: &str,
+    category: &str,
+    window: LeaderboardWindow,
+    clock: &dyn Clock,
+) -> Html<String> {
+    let rank_offset = window.rank_offset();
+    let todos_length = window.items.len();
+    let boards = categories()
+        .map(|name| BoardLink {
+            name,
+            path: board_path(name),
+            active: name == category,
+        })
+        .collect();
+    Html(
+        templates.leaderboard(LeaderboardTemplate {
+            base_path,
+            category,
+            board_path: board_path(category),
+            boards,
+            todos: window
+                .items
+                .into_iter()
+                // The board credits authors by name only
+                .map(|item| TodoItemDisplay::new(item, EmailDisplay::Hidden, clock))
+                .collect(),
+            todos_length,
+            total: window.total,
+            rank_offset,
+            per_page: window.per_page,
+            sort: window.sort.as_str(),
+            neglect_days_per_commit: window.neglect_days_per_commit,
+            prev_page: window.prev_page,
+            next_page: window.next_page,
+        }),
+    )
+}
+
+/// RSS feed of leaderboard entries, in the order given
+pub fn leaderboard_feed<'a>(
+    site_url: &str,
+    todos: impl Iterator<Item = &'a TodoItem>,
+    now: DateTime<Utc>,
+) -> String {
+    let items = todos
+        .map(|todo| {
+            let age_in_days = todo
+                .blame_info
+                .as_ref()
+                .map_or(0, |blame| blame.age_in_days_at(now));
+            FeedItem {
+                title: format!(
+                    "{}: a {} day old TODO",
+                    todo.get_repo_display_name(),
+                    age_in_days
+                ),
+                link: todo.get_permalink_url(),
+                pub_date: todo.discovered_at.map(|at| at.to_rfc2822()),
+                description: todo.todo_text.clone(),
+            }
+        })
+        .collect();
+    render_template(LeaderboardFeed { site_url, items })
+}
+
+/// Route of a category's board, the global board living at `/leaderboard`
+fn board_path(category: &str) -> String {
+    if category == GLOBAL_CATEGORY {
+        "/leaderboard".to_string()
+    } else {
+        format!("/leaderboard/{}", category)
+    }
+}
+
+/// The familiar 7 character abbreviation of a commit sha
+fn short_sha(sha: &str) -> String {
+    sha.chars().take(7).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leaderboard_query::LeaderboardQuery;
+    use crate::state::TodoCounts;
+    use crate::test_utils::{todo_aged, todo_committed_at};
+    use blame_finder::{FixedClock, SystemClock};
+    use chrono::{Duration, TimeZone};
+    use todo_templates::AskamaTemplates;
+
+    #[test]
+    fn test_result_page_shows_scanned_commit() {
+        let sha = "0123456789abcdef0123456789abcdef01234567".to_string();
+        let details = ResultDetails {
+            head_sha: Some(sha),
+            ..ResultDetails::default()
+        };
+        let html = result_page(&AskamaTemplates, "", todo_aged(10), details, &SystemClock).0;
+        assert!(html.contains("SCANNED AT COMMIT 0123456"));
+        assert!(!html.contains("SCANNED AT COMMIT 01234567"));
+    }
+
+    #[test]
+    fn test_pages_age_todos_by_the_clock() {
+        let committed = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let clock = FixedClock(committed + Duration::days(1234));
+
+        let html = result_page(
+            &AskamaTemplates,
+            "",
+            todo_committed_at(committed),
+            ResultDetails::default(),
+            &clock,
+        )
+        .0;
+        assert!(html.contains(r#"<span class="age-number">1234</span>"#));
+
+        let window = LeaderboardQuery::default().apply(&[todo_committed_at(committed)]);
+        let board = leaderboard_page(&AskamaTemplates, "", GLOBAL_CATEGORY, window, &clock).0;
+        assert!(board.contains("1234"));
+    }
+
+    #[test]
+    fn test_result_page_shows_primary_language() {
+        let details = ResultDetails {
+            primary_language: Some("Rust".to_string()),
+            ..ResultDetails::default()
+        };
+        let html = result_page(&AskamaTemplates, "", todo_aged(10), details, &SystemClock).0;
+        assert!(html.contains("LANGUAGE: <strong>Rust</strong>"));
+
+        let html = result_page(
+            &AskamaTemplates,
+            "",
+            todo_aged(10),
+            ResultDetails::default(),
+            &SystemClock,
+        )
+        .0;
+        assert!(!html.contains("LANGUAGE:"));
+    }
+
+    #[test]
+    fn test_result_page_shows_commit_body() {
+        let html = result_page(
+            &AskamaTemplates,
+            "",
+            todo_aged(10),
+            ResultDetails::default(),
+            &SystemClock,
+        )
+        .0;
+        assert!(!html.contains("commit-body"));
+
+        let mut todo = todo_aged(10);
+        todo.blame_info.as_mut().unwrap().body = Some("Because <reasons>".to_string());
+        let html = result_page(
+            &AskamaTemplates,
+            "",
+            todo,
+            ResultDetails::default(),
+            &SystemClock,
+        )
+        .0;
+        assert!(html.contains("<summary>WHY?</summary>"));
+        assert!(html.contains("Because &lt;reasons&gt;"));
+    }
+
+    #[test]
+    fn test_result_page_breaks_down_todos_by_extension() {
+        let todos: Vec<_> = [
+            "src/main.rs",
+            "src/lib.rs",
+            "README.md",
+            "Cargo.toml",
+            "src/util.rs",
+        ]
+        .into_iter()
+        .map(|file| TodoItem {
+            file_path: file.to_string(),
+            ..todo_aged(10)
+        })
+        .collect();
+        let counts = TodoCounts::of(&todos);
+
+        let details = ResultDetails {
+            todo_count: Some(counts.total),
+            extension_counts: counts.by_extension,
+            ..ResultDetails::default()
+        };
+        let html = result_page(&AskamaTemplates, "", todo_aged(10), details, &SystemClock).0;
+        assert!(html.contains("5 TODOS TOTAL: 3 IN .rs, 1 IN .md, 1 IN .toml</p>"));
+
+        let html = result_page(
+            &AskamaTemplates,
+            "",
+            todo_aged(10),
+            ResultDetails::default(),
+            &SystemClock,
+        )
+        .0;
+        assert!(!html.contains("TODOS TOTAL"));
+    }
+
+    #[test]
+    fn test_result_page_links_commit() {
+        let html = result_page(
+            &AskamaTemplates,
+            "",
+            todo_aged(10),
+            ResultDetails::default(),
+            &SystemClock,
+        )
+        .0;
+        assert!(html.contains(r#"href="https://github.com/o/r/commit/abc123""#));
+    }
+
+    #[test]
+    fn test_author_email_display() {
+        // The leaderboard shows names only
+        let window = LeaderboardQuery::default().apply(&[todo_aged(400)]);
+        let board = leaderboard_page(&AskamaTemplates, "", GLOBAL_CATEGORY, window, &SystemClock).0;
+        assert!(!board.contains("example.com"));
+        assert!(
+            EmailDisplay::Hashed
+                .apply("someone@example.com")
+                .starts_with("anon-")
+        );
+        assert_eq!(EmailDisplay::Hidden.apply("someone@example.com"), "");
+        // The same author always gets the same pseudonym
+        assert_eq!(
+            EmailDisplay::Hashed.apply("Someone@Example.com"),
+            EmailDisplay::Hashed.apply("someone@example.com")
+        );
+
+        let details = ResultDetails {
+            email_display: EmailDisplay::Masked,
+            ..ResultDetails::default()
+        };
+        let html = result_page(&AskamaTemplates, "", todo_aged(10), details, &SystemClock).0;
+        assert!(html.contains("s***@example.com"));
+        assert!(!html.contains("someone@"));
+        let details = ResultDetails {
+            email_display: EmailDisplay::Hidden,
+            ..ResultDetails::default()
+        };
+        let html = result_page(&AskamaTemplates, "", todo_aged(10), details, &SystemClock).0;
+        assert!(!html.contains("example.com"));
+        assert!(!html.contains("&lt;&gt;"));
+        let html = result_page(
+            &AskamaTemplates,
+            "",
+            todo_aged(10),
+            ResultDetails::default(),
+            &SystemClock,
+        )
+        .0;
+        assert!(html.contains("someone@example.com"));
+    }
+
+    #[test]
+    fn test_result_page_escapes_todo_text_and_context() {
+        let mut todo = todo_aged(10);
+        todo.todo_text = "// TODO: <script>alert(1)</script>".to_string();
+        todo.context_code = "<img src=x onerror=alert(1)>".to_string();
+
+        let html = result_page(
+            &AskamaTemplates,
+            "",
+            todo,
+            ResultDetails::default(),
+            &SystemClock,
+        )
+        .0;
+
+        assert!(!html.contains("<script>alert"));
+        assert!(!html.contains("<img src=x"));
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+    }
+}
@@ -0,0 +1,65 @@
+use blame_finder::{BlameInfo, SystemClock, TodoItem};
+use chrono::{DateTime, Duration, Utc};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+use crate::categories::open_leaderboards;
+use crate::config::Config;
+use crate::scan_counter::ScanCounter;
+use crate::state::AppState;
+
+pub use blame_finder::test_utils::{commit_file, git, init_repo};
+
+/// A TODO whose blame date is `days` days in the past
+pub fn todo_aged(days: i64) -> TodoItem {
+    todo_committed_at(Utc::now() - Duration::days(days))
+}
+
+/// A TODO blamed to a commit made at `date`
+pub fn todo_committed_at(date: DateTime<Utc>) -> TodoItem {
+    TodoItem {
+        file_path: "src/main.rs".to_string(),
+        line_number: 1,
+        column: None,
+        todo_text: "// TODO: something".to_string(),
+        context_code: "// TODO: something".to_string(),
+        blame_info: Some(BlameInfo {
+            commit_hash: "abc123".to_string(),
+            author: "Someone".to_string(),
+            author_email: "someone@example.com".to_string(),
+            date,
+            summary: "Add something".to_string(),
+            body: None,
+            boundary: false,
+            approximate: false,
+            later_commits: None,
+        }),
+        source_repo_url: "https://github.com/o/r.git".to_string(),
+        discovered_at: None,
+        untracked: false,
+        request_id: None,
+        context_truncated: false,
+    }
+}
+
+/// App state backed by a leaderboard file inside `dir`
+pub async fn test_state(dir: &TempDir) -> AppState {
+    test_state_with_config(dir, Config::default()).await
+}
+
+pub async fn test_state_with_config(dir: &TempDir, config: Config) -> AppState {
+    let path = dir.path().join("leaderboard.json");
+    let leaderboards = if config.leaderboard_enabled {
+        let boards = open_leaderboards(
+            path.to_str().unwrap(),
+            10,
+            config.leaderboard_snapshots,
+            Arc::new(SystemClock),
+        );
+        Some(boards.await.unwrap())
+    } else {
+        None
+    };
+    let scan_counter = ScanCounter::load(dir.path().join("stats.json")).await;
+    AppState::new(leaderboards, scan_counter, config)
+}
@@ -1,19 +1,92 @@
 use blame_finder::{
-    BlameError, Repository, TodoItem,
+    BlameError, Repository, ScanOptions, ScanResult, ScanTimings,
     blame::{self, get_git_depth},
+    timings::timed,
     todo,
 };
-use log::debug;
+use log::{debug, warn};
+use std::time::Duration;
 
-use crate::state::{self, AppState, StatusUpdate};
+use crate::result_cache::CacheKey;
+use crate::state::{self, AppState, StatusUpdate, TodoCounts};
 
 /// Main entry point for finding the oldest TODO in a git repository
+///
+/// With `keep_all`, every blamed TODO is stored with the result too, for download.
 pub async fn find_oldest_todo(
     repo: &Repository,
     app_state: &AppState,
     request_id: &str,
     repo_url: &str,
-) -> Result<Option<TodoItem>, BlameError> {
+    options: &ScanOptions,
+    keep_all: bool,
+) -> Result<ScanResult, BlameError> {
+    let threshold = Duration::from_secs(app_state.config.slow_scan_warning_secs);
+    let pipeline = scan_pipeline(repo, app_state, request_id, repo_url, options, keep_all);
+
+    with_slow_scan_warning(app_state, request_id, threshold, pipeline).await
+}
+
+const SLOW_SCAN_MESSAGE: &str = "Still working, this is a large repo, hang tight...";
+
+fn rate_limited_message(backoff: Duration) -> String {
+    format!(
+        "Rate limited by the git host, waiting {}s before retrying...",
+        backoff.as_secs()
+    )
+}
+
+fn quick_mode_message(limit: u32) -> String {
+    format!(
+        "History is over {} commits, switching to quick mode: each TODO is dated by its line's history...",
+        limit
+    )
+}
+
+const CACHED_RESULT_MESSAGE: &str =
+    "Nothing has changed since the last scan, reusing its result...";
+
+/// Await `pipeline`, telling the user we're still working if it runs past `threshold`
+async fn with_slow_scan_warning<F: Future>(
+    app_state: &AppState,
+    request_id: &str,
+    threshold: Duration,
+    pipeline: F,
+) -> F::Output {
+    tokio::pin!(pipeline);
+
+    tokio::select! {
+        output = &mut pipeline => return output,
+        _ = tokio::time::sleep(threshold) => {
+            app_state
+                .send_status(
+                    request_id,
+                    StatusUpdate {
+                        message: SLOW_SCAN_MESSAGE.to_string(),
+                        stage: state::Stage::Scan,
+                        percentage: None,
+                        error: None,
+                        redirect_url: None,
+                        queue_position: None,
+                        leaderboard_rank: None,
+                    },
+                )
+                .await;
+        }
+    }
+
+    pipeline.await
+}
+
+/// Clone, scan and blame, reporting progress as each stage starts
+async fn scan_pipeline(
+    repo: &Repository,
+    app_state: &AppState,
+    request_id: &str,
+    repo_url: &str,
+    options: &ScanOptions,
+    keep_all: bool,
+) -> Result<ScanResult, BlameError> {
     // Clone or fetch the repository
     debug!("getting repo");
     app_state
@@ -25,63 +98,641 @@ pub async fn find_oldest_todo(
                 percentage: Some(10),
                 error: None,
                 redirect_url: None,
+                queue_position: None,
+                leaderboard_rank: None,
             },
         )
         .await;
-    repo.prepare().await?;
+    let mut timings = ScanTimings::default();
+    // Let the user know why nothing is happening while we wait out a rate limit
+    let on_rate_limit = |backoff: Duration| {
+        let app_state = app_state.clone();
+        let request_id = request_id.to_string();
+        tokio::spawn(async move {
+            app_state
+                .send_status(
+                    &request_id,
+                    StatusUpdate {
+                        message: rate_limited_message(backoff),
+                        stage: state::Stage::Clone,
+                        percentage: Some(10),
+                        error: None,
+                        redirect_url: None,
+                        queue_position: None,
+                        leaderboard_rank: None,
+                    },
+                )
+                .await;
+        });
+    };
+    let (prepared, clone_ms) = timed(repo.prepare_with_rate_limit_hook(&on_rate_limit)).await;
+    timings.clone_ms = clone_ms;
+    let reused = prepared?;
     debug!("done preparing");
 
+    // Remember exactly which snapshot is being scanned
+    match repo.head_sha().await {
+        Ok(head_sha) => app_state.record_head_sha(request_id, head_sha).await,
+        Err(e) => warn!("Couldn't read HEAD of {}: {}", repo_url, e),
+    }
+
+    // The clone skipped deepening a history this long, and line blame through it
+    // would be slow anyway
+    let quick_options;
+    let options = if !options.quick && repo.skipped_deepen() {
+        app_state
+            .send_status(
+                request_id,
+                StatusUpdate {
+                    message: quick_mode_message(app_state.config.quick_mode_history_limit),
+                    stage: state::Stage::Scan,
+                    percentage: Some(20),
+                    error: None,
+                    redirect_url: None,
+                    queue_position: None,
+                    leaderboard_rank: None,
+                },
+            )
+            .await;
+        quick_options = ScanOptions {
+            quick: true,
+            ..options.clone()
+        };
+        &quick_options
+    } else {
+        options
+    };
+
+    // Only reads the index, so it's cheap enough to do on every scan, cached or not
+    match repo.tracked_files().await {
+        Ok(files) => {
+            if let Some(language) = app_state
+                .languages
+                .primary_language(files.iter().map(String::as_str))
+            {
+                app_state
+                    .record_primary_language(request_id, language)
+                    .await;
+            }
+        }
+        Err(e) => warn!("Couldn't list the files of {}: {}", repo_url, e),
+    }
+
+    // Nothing to do if the content is exactly what we scanned last time. The cache
+    // only holds the oldest TODO, so it's no use when every TODO is wanted
+    let key = CacheKey::new(repo, options);
+    let tree_hash = if keep_all {
+        None
+    } else {
+        repo.tree_hash()
+            .await
+            .inspect_err(|e| warn!("Couldn't read the tree of {}: {}", repo_url, e))
+            .ok()
+    };
+    if let Some(tree_hash) = &tree_hash
+        && let Some((cached, counts)) = app_state.result_cache.get(&key, tree_hash).await
+    {
+        debug!("tree {} unchanged, reusing the cached result", tree_hash);
+        app_state
+            .send_status(
+                request_id,
+                StatusUpdate {
+                    message: CACHED_RESULT_MESSAGE.to_string(),
+                    stage: state::Stage::Scan,
+                    percentage: Some(90),
+                    error: None,
+                    redirect_url: None,
+                    queue_position: None,
+                    leaderboard_rank: None,
+                },
+            )
+            .await;
+        app_state.record_timings(request_id, timings).await;
+        app_state.record_todo_counts(request_id, counts).await;
+        return Ok(cached);
+    }
+
+    let (result, counts) = scan_and_blame(
+        repo, app_state, request_id, options, keep_all, reused, timings,
+    )
+    .await?;
+    app_state
+        .record_todo_counts(request_id, counts.clone())
+        .await;
+
+    // Blame failures may be transient, so only remember definite answers
+    if let Some(tree_hash) = tree_hash
+        && let ScanResult::Found(_) | ScanResult::NoTodos = &result
+    {
+        app_state
+            .result_cache
+            .insert(key, tree_hash, result.clone(), counts)
+            .await;
+    }
+    Ok(result)
+}
+
+/// Scan a prepared checkout for TODOs and blame them to find the oldest, along with
+/// how many TODOs of each kind of file were found before blame
+async fn scan_and_blame(
+    repo: &Repository,
+    app_state: &AppState,
+    request_id: &str,
+    options: &ScanOptions,
+    keep_all: bool,
+    reused: bool,
+    mut timings: ScanTimings,
+) -> Result<(ScanResult, TodoCounts), BlameError> {
     // Find all TODO comments
+    let message = if reused {
+        "Repository already up to date. Starting TODO scan..."
+    } else {
+        "Repository cloned successfully. Starting TODO scan..."
+    };
     app_state
         .send_status(
             request_id,
             StatusUpdate {
-                message: "Repository cloned successfully. Starting TODO scan...".to_string(),
+                message: message.to_string(),
                 stage: state::Stage::Scan,
                 percentage: Some(30),
                 error: None,
                 redirect_url: None,
+                queue_position: None,
+                leaderboard_rank: None,
             },
         )
         .await;
-    let todos = todo::find_todos(repo).await?;
+    let (todos, scan_ms) = timed(todo::find_todos(repo, app_state.clock.as_ref())).await;
+    timings.scan_ms = scan_ms;
+    let todos = todos?;
 
     if todos.is_empty() {
-        return Ok(None);
+        app_state.record_timings(request_id, timings).await;
+        if keep_all {
+            app_state.record_all_todos(request_id, Vec::new()).await;
+        }
+        return Ok((ScanResult::NoTodos, TodoCounts::default()));
     }
+    let counts = TodoCounts::of(&todos);
 
     // Find the oldest TODO by analyzing git blame for each
     app_state
         .send_status(
             request_id,
             StatusUpdate {
-                message: format!("Found {} TODOs, Git Blaming each one...", todos.len()),
+                message: if options.quick {
+                    format!(
+                        "Found {} TODOs, quick mode: dating each by its line's history...",
+                        todos.len()
+                    )
+                } else {
+                    format!("Found {} TODOs, Git Blaming each one...", todos.len())
+                },
                 stage: state::Stage::Scan,
                 percentage: Some(30),
                 error: None,
                 redirect_url: None,
+                queue_position: None,
+                leaderboard_rank: None,
             },
         )
         .await;
-    let git_depth = get_git_depth(repo).await;
-    if git_depth.is_ok() && *git_depth.as_ref().unwrap() > 500 {
+    // Quick mode never walks the history, so its depth doesn't matter
+    if !options.quick
+        && let Ok(git_depth) = get_git_depth(repo).await
+        && git_depth > 500
+    {
         app_state
             .send_status(
                 request_id,
                 StatusUpdate {
-                    message: format!(
-                        "Git Depth of {}, this could take a while...",
-                        git_depth.unwrap()
-                    ),
+                    message: format!("Git Depth of {}, this could take a while...", git_depth),
                     stage: state::Stage::Scan,
                     percentage: Some(30),
                     error: None,
                     redirect_url: None,
+                    queue_position: None,
+                    leaderboard_rank: None,
                 },
             )
             .await;
     }
-    let oldest = blame::find_oldest_todo(repo, todos).await?;
+    let (blamed, blame_ms) = timed(blame::blame_matching_todos(
+        repo,
+        todos,
+        options,
+        app_state.clock.as_ref(),
+    ))
+    .await;
+    timings.blame_ms = blame_ms;
+    app_state.record_timings(request_id, timings).await;
+    let mut blamed = match blamed {
+        Ok(blamed) => blamed,
+        Err(BlameError::AllBlameFailed { attempted }) => {
+            return Ok((ScanResult::AllBlameFailed { attempted }, counts));
+        }
+        Err(e) => return Err(e),
+    };
 
-    Ok(Some(oldest))
+    let mut oldest = blame::pick_oldest_in(repo, &blamed, options)?
+        .cloned()
+        .ok_or(BlameError::NoTodosInDateRange)?;
+    blame::attach_commit_body(repo, &mut oldest, options).await;
+    if keep_all {
+        // Oldest first, ties broken by path and line
+        blamed.sort_by(|a, b| b.cmp(a));
+        todo::apply_context_budget(&mut blamed, app_state.config.all_todos_context_budget);
+        app_state.record_all_todos(request_id, blamed).await;
+    }
+
+    if oldest.blame_info.as_ref().is_some_and(|b| b.boundary) {
+        app_state
+            .send_status(
+                request_id,
+                StatusUpdate {
+                    message: "Oldest TODO hit the shallow history boundary, it may be older than reported".to_string(),
+                    stage: state::Stage::Scan,
+                    percentage: Some(90),
+                    error: None,
+                    redirect_url: None,
+                    queue_position: None,
+                    leaderboard_rank: None,
+                },
+            )
+            .await;
+    }
+
+    Ok((ScanResult::Found(oldest), counts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_utils::{commit_file, git, init_repo, test_state, test_state_with_config};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_scan_records_timings() {
+        let fixture = init_repo();
+        commit_file(fixture.path(), "lib.rs", "// TODO: time me\n", "Add lib.rs");
+        let repo = Repository::from_local_path(fixture.path()).unwrap();
+
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let request_id = "timings-test";
+        state.register_request(request_id).await;
+
+        let oldest = find_oldest_todo(
+            &repo,
+            &state,
+            request_id,
+            repo.url(),
+            &ScanOptions::default(),
+            false,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(oldest, ScanResult::Found(_)));
+
+        let timings = state.get_result(request_id).await.unwrap().timings.unwrap();
+        // Every stage ran, so every stage was timed
+        assert!(timings.clone_ms > 0);
+        assert!(timings.scan_ms > 0);
+        assert!(timings.blame_ms > 0);
+    }
+
+    async fn scan(state: &AppState, repo: &Repository, request_id: &str) -> (ScanResult, bool) {
+        state.register_request(request_id).await;
+        let result = find_oldest_todo(
+            repo,
+            state,
+            request_id,
+            repo.url(),
+            &ScanOptions::default(),
+            false,
+        )
+        .await
+        .unwrap();
+        let cache_hit = state
+            .get_status_history(request_id)
+            .await
+            .iter()
+            .any(|status| status.message == CACHED_RESULT_MESSAGE);
+        (result, cache_hit)
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_tree_reuses_result() {
+        let fixture = init_repo();
+        commit_file(fixture.path(), "lib.rs", "// TODO: first\n", "Add lib.rs");
+        let repo = Repository::from_local_path(fixture.path()).unwrap();
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+
+        let (_, cache_hit) = scan(&state, &repo, "first").await;
+        assert!(!cache_hit);
+
+        // A new commit with identical content is still a hit
+        git(
+            fixture.path(),
+            &["commit", "--allow-empty", "-q", "-m", "Empty"],
+        );
+        let (result, cache_hit) = scan(&state, &repo, "second").await;
+        assert!(cache_hit);
+        assert_eq!(result.into_todo().unwrap().todo_text, "// TODO: first");
+
+        // Changed content forces a real rescan
+        commit_file(fixture.path(), "lib.rs", "// TODO: second\n", "Edit lib.rs");
+        let (result, cache_hit) = scan(&state, &repo, "third").await;
+        assert!(!cache_hit);
+        assert_eq!(result.into_todo().unwrap().todo_text, "// TODO: second");
+    }
+
+    #[tokio::test]
+    async fn test_keep_all_records_every_todo_oldest_first() {
+        let fixture = init_repo();
+        commit_file(fixture.path(), "a.rs", "// TODO: older\n", "Add a.rs");
+        commit_file(fixture.path(), "b.rs", "// TODO: newer\n", "Add b.rs");
+        let repo = Repository::from_local_path(fixture.path()).unwrap();
+
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let request_id = "keep-all-test";
+        state.register_request(request_id).await;
+
+        find_oldest_todo(
+            &repo,
+            &state,
+            request_id,
+            repo.url(),
+            &ScanOptions::default(),
+            true,
+        )
+        .await
+        .unwrap();
+
+        let all_todos = state
+            .get_result(request_id)
+            .await
+            .unwrap()
+            .all_todos
+            .unwrap();
+        assert_eq!(all_todos.len(), 2);
+        assert!(all_todos[0] >= all_todos[1]);
+    }
+
+    #[tokio::test]
+    async fn test_keep_all_context_is_cut_to_budget() {
+        let fixture = init_repo();
+        commit_file(fixture.path(), "a.rs", "// TODO: older\n", "Add a.rs");
+        commit_file(fixture.path(), "b.rs", "// TODO: newer\n", "Add b.rs");
+        let repo = Repository::from_local_path(fixture.path()).unwrap();
+
+        let dir = tempdir().unwrap();
+        let config = Config {
+            all_todos_context_budget: "// TODO: older".len(),
+            ..Config::default()
+        };
+        let state = test_state_with_config(&dir, config).await;
+        let request_id = "budget-test";
+        state.register_request(request_id).await;
+
+        find_oldest_todo(
+            &repo,
+            &state,
+            request_id,
+            repo.url(),
+            &ScanOptions::default(),
+            true,
+        )
+        .await
+        .unwrap();
+
+        let all_todos = state
+            .get_result(request_id)
+            .await
+            .unwrap()
+            .all_todos
+            .unwrap();
+        // Both commits can land in the same second, so either may come first
+        assert_eq!(all_todos[0].context_code, all_todos[0].todo_text);
+        assert!(!all_todos[0].context_truncated);
+        assert_eq!(all_todos[1].context_code, "");
+        assert!(all_todos[1].context_truncated);
+    }
+
+    #[tokio::test]
+    async fn test_single_todo_repo_is_shown_but_not_admitted() {
+        let fixture = init_repo();
+        commit_file(
+            fixture.path(),
+            "lib.rs",
+            "// TODO: only one\n",
+            "Add lib.rs",
+        );
+        git(
+            fixture.path(),
+            &[
+                "commit",
+                "--amend",
+                "-q",
+                "--no-edit",
+                "--date",
+                "2001-01-01T00:00:00Z",
+            ],
+        );
+        let repo = Repository::from_local_path(fixture.path()).unwrap();
+
+        let dir = tempdir().unwrap();
+        let config = Config {
+            min_total_todos: 2,
+            ..Config::default()
+        };
+        let state = test_state_with_config(&dir, config).await;
+
+        // Scanned twice so the cached result is judged the same way
+        for request_id in ["first", "second"] {
+            let (result, _) = scan(&state, &repo, request_id).await;
+            let todo = result.into_todo().unwrap();
+            let stored = state.get_result(request_id).await.unwrap();
+            let todo_count = stored.todo_count;
+            assert_eq!(todo_count, Some(1));
+            assert_eq!(stored.extension_counts, [(".rs".to_string(), 1)]);
+
+            let note = state.admit_to_leaderboard(&todo, todo_count).await.note;
+            assert!(note.as_deref().unwrap().contains("too few TODOs"));
+            assert!(state.leaderboard().unwrap().get_items().await.is_empty());
+
+            // The user still sees their TODO, with the reason it wasn't ranked
+            let details = crate::templates::ResultDetails {
+                leaderboard_note: note,
+                ..Default::default()
+            };
+            let html = crate::templates::result_page(
+                &todo_templates::AskamaTemplates,
+                "",
+                todo,
+                details,
+                state.clock.as_ref(),
+            )
+            .0;
+            assert!(html.contains("// TODO: only one"));
+            assert!(html.contains("too few TODOs to qualify"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scan_records_head_sha() {
+        let fixture = init_repo();
+        commit_file(fixture.path(), "lib.rs", "// TODO: pin me\n", "Add lib.rs");
+        let repo = Repository::from_local_path(fixture.path()).unwrap();
+
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let request_id = "head-sha-test";
+        state.register_request(request_id).await;
+
+        find_oldest_todo(
+            &repo,
+            &state,
+            request_id,
+            repo.url(),
+            &ScanOptions::default(),
+            false,
+        )
+        .await
+        .unwrap();
+
+        let result = state.get_result(request_id).await.unwrap();
+        assert_eq!(result.head_sha, Some(repo.head_sha().await.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_scan_records_primary_language() {
+        let fixture = init_repo();
+        commit_file(fixture.path(), "main.rs", "// TODO: rust\n", "Add main.rs");
+        commit_file(fixture.path(), "lib.rs", "pub fn lib() {}\n", "Add lib.rs");
+        commit_file(
+            fixture.path(),
+            "util.rs",
+            "pub fn util() {}\n",
+            "Add util.rs",
+        );
+        commit_file(
+            fixture.path(),
+            "build.py",
+            "# TODO: python\n",
+            "Add build.py",
+        );
+        let repo = Repository::from_local_path(fixture.path()).unwrap();
+
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let request_id = "language-test";
+        state.register_request(request_id).await;
+
+        find_oldest_todo(
+            &repo,
+            &state,
+            request_id,
+            repo.url(),
+            &ScanOptions::default(),
+            false,
+        )
+        .await
+        .unwrap();
+
+        let result = state.get_result(request_id).await.unwrap();
+        assert_eq!(result.primary_language.as_deref(), Some("Rust"));
+    }
+
+    #[tokio::test]
+    async fn test_slow_scan_warns_before_completion() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let request_id = "slow-scan-test";
+        state.register_request(request_id).await;
+
+        let state_clone = state.clone();
+        let pipeline = async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            // The warning must already be out by the time the scan finishes
+            state_clone.get_status_history(request_id).await.len()
+        };
+        let seen_at_completion =
+            with_slow_scan_warning(&state, request_id, Duration::from_millis(20), pipeline).await;
+
+        let history = state.get_status_history(request_id).await;
+        assert_eq!(seen_at_completion, 1);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].message, SLOW_SCAN_MESSAGE);
+    }
+
+    #[tokio::test]
+    async fn test_long_history_downgrades_to_quick_mode() {
+        let fixture = init_repo();
+        for commit in 1..=12 {
+            commit_file(
+                fixture.path(),
+                "lib.rs",
+                &format!("// TODO: revision {}\n", commit),
+                &format!("Commit {}", commit),
+            );
+        }
+        let dir = tempdir().unwrap();
+        let config = Config {
+            quick_mode_history_limit: 5,
+            url_rewrite: Some(
+                blame_finder::UrlRewrite::new(
+                    ".*",
+                    &format!("file://{}", fixture.path().display()),
+                )
+                .unwrap(),
+            ),
+            ..Config::default()
+        };
+        let repos_dir = tempdir().unwrap();
+        let repo = crate::repo_builder(&config, "https://github.com/o/r")
+            .repos_dir(repos_dir.path())
+            .depth(8)
+            .build()
+            .await
+            .unwrap();
+        let state = test_state_with_config(&dir, config).await;
+        let request_id = "long-history-test";
+        state.register_request(request_id).await;
+
+        let result = find_oldest_todo(
+            &repo,
+            &state,
+            request_id,
+            repo.url(),
+            &ScanOptions::default(),
+            false,
+        )
+        .await;
+
+        let history = state.get_status_history(request_id).await;
+        assert!(history.iter().any(|s| s.message == quick_mode_message(5)));
+        // The deepen step was skipped, and the TODO dated by line history rather than blamed
+        assert!(repo.skipped_deepen());
+        let todo = result.unwrap().into_todo().unwrap();
+        assert!(todo.blame_info.unwrap().approximate);
+    }
+
+    #[tokio::test]
+    async fn test_fast_scan_has_no_warning() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir).await;
+        let request_id = "fast-scan-test";
+        state.register_request(request_id).await;
+
+        let output =
+            with_slow_scan_warning(&state, request_id, Duration::from_secs(60), async { 42 }).await;
+
+        assert_eq!(output, 42);
+        assert!(state.get_status_history(request_id).await.is_empty());
+    }
 }
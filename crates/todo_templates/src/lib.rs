@@ -1,6 +1,12 @@
+mod provider;
 pub mod templates;
 
-pub use templates::{ErrorContent, IndexContent, LeaderboardTemplate, ResultContent};
+pub use provider::{AskamaTemplates, TemplateProvider};
+
+pub use templates::{
+    BoardLink, EmailDisplay, ErrorContent, FeedItem, IndexContent, LeaderboardFeed,
+    LeaderboardTemplate, ResultContent,
+};
 
 pub fn render_template<T: askama::Template>(template: T) -> String {
     match template.render() {
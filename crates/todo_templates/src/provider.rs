@@ -0,0 +1,30 @@
+use crate::render_template;
+use crate::templates::{ErrorContent, IndexContent, LeaderboardTemplate, ResultContent};
+
+/// Renders each page of the site to HTML, so a deployment can theme it without forking
+///
+/// Every method defaults to the bundled askama templates, so a theme only overrides
+/// the pages it changes, and can wrap [`AskamaTemplates`] to add a header or footer.
+pub trait TemplateProvider: Send + Sync {
+    fn index(&self, page: IndexContent<'_>) -> String {
+        render_template(page)
+    }
+
+    fn result(&self, page: ResultContent<'_>) -> String {
+        render_template(page)
+    }
+
+    fn error(&self, page: ErrorContent<'_>) -> String {
+        render_template(page)
+    }
+
+    fn leaderboard(&self, page: LeaderboardTemplate<'_>) -> String {
+        render_template(page)
+    }
+}
+
+/// The bundled askama templates
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AskamaTemplates;
+
+impl TemplateProvider for AskamaTemplates {}
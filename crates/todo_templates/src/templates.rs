@@ -1,20 +1,46 @@
 use askama::Template;
-use blame_finder::{BlameInfo, TodoItem};
+use blame_finder::{BlameInfo, Clock, ScanTimings, TodoItem};
+use std::str::FromStr;
 
 // Specific content templates
 #[derive(Template)]
 #[template(path = "index_content.html")]
-pub struct IndexContent;
+pub struct IndexContent<'a> {
+    /// Prefix for every server route, empty unless running behind a reverse proxy
+    pub base_path: &'a str,
+    /// Total scans completed since the site went up
+    pub repositories_scanned: u64,
+}
 
 #[derive(Template)]
 #[template(path = "result_content.html")]
-pub struct ResultContent {
+pub struct ResultContent<'a> {
+    pub base_path: &'a str,
     pub result: TodoItem,
+    /// Age of the TODO's commit in whole days, as of when the page is rendered
+    pub age_in_days: i64,
+    pub leaderboard_note: Option<String>,
+    /// Where the TODO landed on the leaderboard, 1 being the oldest on the site
+    pub leaderboard_rank: Option<usize>,
+    /// It's the new oldest TODO on the site
+    pub new_record: bool,
+    pub timings: Option<ScanTimings>,
+    /// Abbreviated sha of the commit that was scanned
+    pub scanned_commit: Option<String>,
+    /// Language most of the repo is written in, e.g. `Rust`
+    pub primary_language: Option<String>,
+    /// How many TODOs the scan found in all
+    pub todo_count: Option<usize>,
+    /// How many of them were in each kind of file, e.g. `(".rs", 30)`, most first
+    pub extension_counts: Vec<(String, usize)>,
+    /// Where to download every TODO the scan kept, if it kept them all
+    pub all_todos_url: Option<String>,
 }
 
 #[derive(Template)]
 #[template(path = "error_content.html")]
 pub struct ErrorContent<'a> {
+    pub base_path: &'a str,
     pub error: &'a str,
 }
 
@@ -36,35 +62,158 @@ pub struct TodoItemDisplay {
     /// Information about the commit that introduced this TODO
     pub blame_info: BlameInfo,
 
+    /// Age of that commit in whole days, as of when the TODO was prepared for display
+    pub age_in_days: i64,
+
     /// The source repo url, copied here for easy displaying
     pub source_repo_url: String,
 
     pub permalink_url: String,
 
+    /// Link to the commit that introduced the TODO, on hosts we know
+    pub commit_url: Option<String>,
+
     pub display_repo_name: String,
 }
 
-impl From<TodoItem> for TodoItemDisplay {
-    fn from(value: TodoItem) -> Self {
+/// How much of an author's email public pages and exports show. The stored
+/// `TodoItem` always keeps the full address
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmailDisplay {
+    /// The address as blamed
+    #[default]
+    Full,
+    /// Only the first letter and the domain, e.g. `a***@example.com`
+    Masked,
+    /// A stable pseudonym, e.g. `anon-3f2a9c1e`, so one author's entries can still be
+    /// told apart from another's
+    Hashed,
+    /// Nothing at all
+    Hidden,
+}
+
+impl EmailDisplay {
+    pub fn apply(self, email: &str) -> String {
+        match self {
+            EmailDisplay::Full => email.to_string(),
+            EmailDisplay::Masked => match email.rsplit_once('@') {
+                Some((local, domain)) => {
+                    let first: String = local.chars().take(1).collect();
+                    format!("{}***@{}", first, domain)
+                }
+                None => "***".to_string(),
+            },
+            EmailDisplay::Hashed => {
+                // FNV-1a, so the pseudonym stays the same across builds and restarts
+                let hash = email
+                    .trim()
+                    .to_lowercase()
+                    .bytes()
+                    .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+                    });
+                format!("anon-{:08x}", hash >> 32)
+            }
+            EmailDisplay::Hidden => String::new(),
+        }
+    }
+
+    /// Show `todo`'s author email as this says, in place. For copies headed to a page
+    /// or export, never the stored TODO
+    pub fn apply_to(self, todo: &mut TodoItem) {
+        if let Some(blame_info) = todo.blame_info.as_mut() {
+            blame_info.author_email = self.apply(&blame_info.author_email);
+        }
+    }
+}
+
+impl FromStr for EmailDisplay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "full" => Ok(EmailDisplay::Full),
+            "mask" | "masked" => Ok(EmailDisplay::Masked),
+            "hash" | "hashed" => Ok(EmailDisplay::Hashed),
+            "hide" | "hidden" => Ok(EmailDisplay::Hidden),
+            other => Err(format!("Unknown email display {:?}", other)),
+        }
+    }
+}
+
+impl TodoItemDisplay {
+    /// Prepare a TODO for display, aged according to `clock` and showing its
+    /// author's email as `email_display` says
+    pub fn new(value: TodoItem, email_display: EmailDisplay, clock: &dyn Clock) -> Self {
+        let mut blame_info = value
+            .blame_info
+            .clone()
+            .expect("Should never try and display todo info without blame info");
+        blame_info.author_email = email_display.apply(&blame_info.author_email);
+        let age_in_days = blame_info.age_in_days(clock);
+
         TodoItemDisplay {
             file_path: value.file_path.clone(),
             line_number: value.line_number,
             todo_text: value.todo_text.clone(),
             context_code: value.context_code.clone(),
-            blame_info: value
-                .blame_info
-                .clone()
-                .expect("Should never try and display todo info without blame info"),
+            blame_info,
+            age_in_days,
             permalink_url: value.get_permalink_url(),
+            commit_url: value.get_commit_url(),
             display_repo_name: value.get_repo_display_name(),
             source_repo_url: value.source_repo_url,
         }
     }
 }
 
+/// A link to one leaderboard category
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoardLink {
+    pub name: &'static str,
+    pub path: String,
+    pub active: bool,
+}
+
 #[derive(Template)]
 #[template(path = "leaderboard_content.html")]
-pub struct LeaderboardTemplate {
+pub struct LeaderboardTemplate<'a> {
+    pub base_path: &'a str,
+    /// Category shown, e.g. `rust`, or `all` for the global board
+    pub category: &'a str,
+    /// Route of this board, e.g. `/leaderboard/rust`, for sort and page links
+    pub board_path: String,
+    /// Every board, for switching between them
+    pub boards: Vec<BoardLink>,
     pub todos: Vec<TodoItemDisplay>,
     pub todos_length: usize,
+    /// Number of TODOs across all pages
+    pub total: usize,
+    /// Rank of the first TODO on this page, minus one
+    pub rank_offset: usize,
+    pub per_page: usize,
+    pub sort: &'static str,
+    /// Carried through sort and page links, 0 when ranked by age alone
+    pub neglect_days_per_commit: u32,
+    pub prev_page: Option<usize>,
+    pub next_page: Option<usize>,
+}
+
+/// One leaderboard entry in the RSS feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedItem {
+    pub title: String,
+    /// Permalink to the TODO's line
+    pub link: String,
+    /// RFC 2822 date the TODO was discovered, if known
+    pub pub_date: Option<String>,
+    pub description: String,
+}
+
+#[derive(Template)]
+#[template(path = "leaderboard_feed.xml", escape = "html")]
+pub struct LeaderboardFeed<'a> {
+    /// Absolute URL of the site, including any base path
+    pub site_url: &'a str,
+    pub items: Vec<FeedItem>,
 }